@@ -0,0 +1,297 @@
+use anyhow::Result;
+use std::future::Future;
+
+use crate::spotify::{
+    Album, Artist, AudioFeatures, CurrentlyPlaying, Device, Playlist, Queue, RequestRecord, Seeds,
+    Show, SpotifyClient, Track, TrackPage,
+};
+
+/// Everything the app asks of Spotify. The app is generic over it so its logic can be tested
+/// against a mock instead of the real API
+pub trait SpotifyApi: Clone + Send + Sync + 'static {
+    // Authentication
+    fn refresh_access_token(&self) -> impl Future<Output = Result<()>> + Send;
+    /// Signs in with the saved refresh token when there is one, through the browser otherwise
+    fn authenticate(&self) -> impl Future<Output = Result<()>> + Send;
+    /// The current access token, for handing to librespot
+    fn access_token(&self) -> impl Future<Output = Option<String>> + Send;
+    /// The latest requests sent, newest first, for the debug overlay
+    fn recent_requests(&self) -> Vec<RequestRecord>;
+
+    // Library
+    fn get_playlists(&self) -> impl Future<Output = Result<Vec<Playlist>>> + Send;
+    fn get_saved_albums(&self) -> impl Future<Output = Result<Vec<Album>>> + Send;
+    fn get_followed_artists(&self) -> impl Future<Output = Result<Vec<Artist>>> + Send;
+    fn get_saved_shows(&self) -> impl Future<Output = Result<Vec<Show>>> + Send;
+    fn get_show_episodes(&self, show: &Show) -> impl Future<Output = Result<Vec<Track>>> + Send;
+    fn get_playlist_followers(&self, playlist_id: &str)
+        -> impl Future<Output = Result<u32>> + Send;
+    fn get_playlist_tracks(
+        &self,
+        playlist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send;
+    fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send;
+    fn get_album_tracks(&self, album: &Album) -> impl Future<Output = Result<Vec<Track>>> + Send;
+    fn get_artist_top_tracks(
+        &self,
+        artist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send;
+    fn get_recommendations(
+        &self,
+        seeds: &Seeds,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send;
+    fn get_genre_seeds(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+    fn get_audio_features(
+        &self,
+        track_ids: &[String],
+    ) -> impl Future<Output = Result<Vec<AudioFeatures>>> + Send;
+
+    // Search
+    fn search_tracks(
+        &self,
+        query: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send;
+    fn search_playlists(&self, query: &str) -> impl Future<Output = Result<Vec<Playlist>>> + Send;
+
+    // Playback
+    fn play_tracks(&self, track_uris: &[&str]) -> impl Future<Output = Result<()>> + Send;
+    fn play_context(&self, context_uri: &str) -> impl Future<Output = Result<()>> + Send;
+    fn pause_playback(&self) -> impl Future<Output = Result<()>> + Send;
+    fn resume_playback(&self) -> impl Future<Output = Result<()>> + Send;
+    fn next_track(&self) -> impl Future<Output = Result<()>> + Send;
+    fn previous_track(&self) -> impl Future<Output = Result<()>> + Send;
+    fn seek(&self, position_ms: u64) -> impl Future<Output = Result<()>> + Send;
+    fn set_volume(&self, volume_percent: u32) -> impl Future<Output = Result<()>> + Send;
+    fn get_devices(&self) -> impl Future<Output = Result<Vec<Device>>> + Send;
+    fn set_target_device(&self, device_id: &str) -> impl Future<Output = Result<()>> + Send;
+    fn get_currently_playing(
+        &self,
+    ) -> impl Future<Output = Result<Option<CurrentlyPlaying>>> + Send;
+    fn get_queue(&self) -> impl Future<Output = Result<Option<Queue>>> + Send;
+
+    // Queue and playlist changes
+    fn add_to_queue(&self, track_uri: &str) -> impl Future<Output = Result<()>> + Send;
+    fn add_tracks_to_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+        position: Option<usize>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn remove_tracks_from_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn create_playlist(
+        &self,
+        name: &str,
+        description: &str,
+    ) -> impl Future<Output = Result<Playlist>> + Send;
+    fn unfollow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send;
+    fn follow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send;
+    fn save_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send;
+    fn remove_saved_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl SpotifyApi for SpotifyClient {
+    fn refresh_access_token(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::refresh_access_token(self)
+    }
+
+    fn authenticate(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::sign_in(self)
+    }
+
+    fn access_token(&self) -> impl Future<Output = Option<String>> + Send {
+        SpotifyClient::access_token(self)
+    }
+
+    fn recent_requests(&self) -> Vec<RequestRecord> {
+        SpotifyClient::recent_requests(self)
+    }
+
+    fn get_playlists(&self) -> impl Future<Output = Result<Vec<Playlist>>> + Send {
+        SpotifyClient::get_playlists(self)
+    }
+
+    fn get_saved_albums(&self) -> impl Future<Output = Result<Vec<Album>>> + Send {
+        SpotifyClient::get_saved_albums(self)
+    }
+
+    fn get_followed_artists(&self) -> impl Future<Output = Result<Vec<Artist>>> + Send {
+        SpotifyClient::get_followed_artists(self)
+    }
+
+    fn get_saved_shows(&self) -> impl Future<Output = Result<Vec<Show>>> + Send {
+        SpotifyClient::get_saved_shows(self)
+    }
+
+    fn get_show_episodes(&self, show: &Show) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        SpotifyClient::get_show_episodes(self, show)
+    }
+
+    fn get_playlist_followers(
+        &self,
+        playlist_id: &str,
+    ) -> impl Future<Output = Result<u32>> + Send {
+        SpotifyClient::get_playlist_followers(self, playlist_id)
+    }
+
+    fn get_playlist_tracks(
+        &self,
+        playlist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        SpotifyClient::get_playlist_tracks(self, playlist_id)
+    }
+
+    fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send {
+        SpotifyClient::get_playlist_tracks_page(self, playlist_id, offset)
+    }
+
+    fn get_album_tracks(&self, album: &Album) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        SpotifyClient::get_album_tracks(self, album)
+    }
+
+    fn get_artist_top_tracks(
+        &self,
+        artist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        SpotifyClient::get_artist_top_tracks(self, artist_id)
+    }
+
+    fn get_recommendations(
+        &self,
+        seeds: &Seeds,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        SpotifyClient::get_recommendations(self, seeds, limit)
+    }
+
+    fn get_genre_seeds(&self) -> impl Future<Output = Result<Vec<String>>> + Send {
+        SpotifyClient::get_genre_seeds(self)
+    }
+
+    fn get_audio_features(
+        &self,
+        track_ids: &[String],
+    ) -> impl Future<Output = Result<Vec<AudioFeatures>>> + Send {
+        SpotifyClient::get_audio_features(self, track_ids)
+    }
+
+    fn search_tracks(
+        &self,
+        query: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send {
+        SpotifyClient::search_tracks(self, query, offset)
+    }
+
+    fn search_playlists(&self, query: &str) -> impl Future<Output = Result<Vec<Playlist>>> + Send {
+        SpotifyClient::search_playlists(self, query)
+    }
+
+    fn play_tracks(&self, track_uris: &[&str]) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::play_tracks(self, track_uris)
+    }
+
+    fn play_context(&self, context_uri: &str) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::play_context(self, context_uri)
+    }
+
+    fn pause_playback(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::pause_playback(self)
+    }
+
+    fn resume_playback(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::resume_playback(self)
+    }
+
+    fn next_track(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::next_track(self)
+    }
+
+    fn previous_track(&self) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::previous_track(self)
+    }
+
+    fn seek(&self, position_ms: u64) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::seek(self, position_ms)
+    }
+
+    fn set_volume(&self, volume_percent: u32) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::set_volume(self, volume_percent)
+    }
+
+    fn get_devices(&self) -> impl Future<Output = Result<Vec<Device>>> + Send {
+        SpotifyClient::get_devices(self)
+    }
+
+    fn set_target_device(&self, device_id: &str) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::set_target_device(self, device_id)
+    }
+
+    fn get_currently_playing(
+        &self,
+    ) -> impl Future<Output = Result<Option<CurrentlyPlaying>>> + Send {
+        SpotifyClient::get_currently_playing(self)
+    }
+
+    fn get_queue(&self) -> impl Future<Output = Result<Option<Queue>>> + Send {
+        SpotifyClient::get_queue(self)
+    }
+
+    fn add_to_queue(&self, track_uri: &str) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::add_to_queue(self, track_uri)
+    }
+
+    fn add_tracks_to_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+        position: Option<usize>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::add_tracks_to_playlist(self, playlist_id, track_uris, position)
+    }
+
+    fn remove_tracks_from_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+    ) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::remove_tracks_from_playlist(self, playlist_id, track_uris)
+    }
+
+    fn create_playlist(
+        &self,
+        name: &str,
+        description: &str,
+    ) -> impl Future<Output = Result<Playlist>> + Send {
+        SpotifyClient::create_playlist(self, name, description)
+    }
+
+    fn unfollow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::unfollow_playlist(self, playlist_id)
+    }
+
+    fn follow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::follow_playlist(self, playlist_id)
+    }
+
+    fn save_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::save_tracks(self, track_ids)
+    }
+
+    fn remove_saved_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send {
+        SpotifyClient::remove_saved_tracks(self, track_ids)
+    }
+}
@@ -0,0 +1,12 @@
+//! Spotify's Web API as spotitui uses it: the client and its sign-in, the models it returns,
+//! the SpotifyApi trait the app is written against, the worker that runs playback commands
+//! off the UI thread, the app state that doesn't depend on the terminal and the library changes
+//! made of several requests, like merges and undo. Status bar scripts and other frontends can
+//! build on it the same way
+
+pub mod api;
+pub mod library;
+mod ratelimit;
+pub mod spotify;
+pub mod state;
+pub mod worker;
@@ -0,0 +1,117 @@
+//! Changes to the user's library that take more than one request, and taking them back. The
+//! app runs these in the background and shows what they return as notifications
+
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::api::SpotifyApi;
+use crate::spotify::Playlist;
+
+/// A change to the user's library that can be taken back with u
+#[derive(Debug, Clone)]
+pub enum Undoable {
+    /// Ids of tracks saved to Liked Songs
+    Liked(Vec<String>),
+    AddedToPlaylist {
+        playlist_id: String,
+        playlist_name: String,
+        uris: Vec<String>,
+    },
+    /// Tracks removed from a playlist, with the positions they were at in ascending order
+    RemovedFromPlaylist {
+        playlist_id: String,
+        playlist_name: String,
+        removed: Vec<(usize, String)>,
+    },
+    /// A deleted playlist and where it was in playlists
+    DeletedPlaylist { index: usize, playlist: Playlist },
+}
+
+/// Takes back a change to the library, returning what was undone
+pub async fn undo(client: &impl SpotifyApi, action: &Undoable) -> Result<String> {
+    match action {
+        Undoable::Liked(ids) => client
+            .remove_saved_tracks(ids)
+            .await
+            .map(|_| format!("Removed {} from Liked Songs", describe_count(ids.len()))),
+        Undoable::AddedToPlaylist {
+            playlist_id,
+            playlist_name,
+            uris,
+        } => client
+            .remove_tracks_from_playlist(playlist_id, uris)
+            .await
+            .map(|_| {
+                format!(
+                    "Removed {} from {}",
+                    describe_count(uris.len()),
+                    playlist_name
+                )
+            }),
+        Undoable::RemovedFromPlaylist {
+            playlist_id,
+            playlist_name,
+            removed,
+        } => restore_tracks(client, playlist_id, removed).await.map(|_| {
+            format!(
+                "Put {} back in {}",
+                describe_count(removed.len()),
+                playlist_name
+            )
+        }),
+        Undoable::DeletedPlaylist { playlist, .. } => client
+            .follow_playlist(&playlist.id)
+            .await
+            .map(|_| format!("Restored the playlist {}", playlist.name)),
+    }
+}
+
+/// Puts removed tracks back where they were, one request per run of adjacent positions.
+/// Going in ascending order means each run lands where it was before
+pub async fn restore_tracks(
+    client: &impl SpotifyApi,
+    playlist_id: &str,
+    removed: &[(usize, String)],
+) -> Result<()> {
+    for run in removed.chunk_by(|a, b| b.0 == a.0 + 1) {
+        let uris: Vec<String> = run.iter().map(|(_, uri)| uri.clone()).collect();
+        client
+            .add_tracks_to_playlist(playlist_id, &uris, Some(run[0].0))
+            .await?;
+    }
+    Ok(())
+}
+
+/// URIs of the source playlist's tracks to add to the destination, leaving out the ones the
+/// destination already has and repeats when skipping duplicates
+pub async fn merge_uris(
+    client: &impl SpotifyApi,
+    source_id: &str,
+    destination_id: &str,
+    skip_duplicates: bool,
+) -> Result<Vec<String>> {
+    let uris = client
+        .get_playlist_tracks(source_id)
+        .await?
+        .into_iter()
+        .map(|track| track.uri);
+    if !skip_duplicates {
+        return Ok(uris.collect());
+    }
+    let mut seen: HashSet<String> = client
+        .get_playlist_tracks(destination_id)
+        .await?
+        .into_iter()
+        .map(|track| track.uri)
+        .collect();
+    Ok(uris.filter(|uri| seen.insert(uri.clone())).collect())
+}
+
+/// "1 track" or "N tracks"
+pub fn describe_count(count: usize) -> String {
+    if count == 1 {
+        "1 track".to_string()
+    } else {
+        format!("{} tracks", count)
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A token bucket holding up to a second's worth of requests, refilled at `per_second`.
+/// Bursts like opening a few playlists go straight through, while long runs like queueing a
+/// whole playlist are spread out before Spotify starts answering 429 with long waits
+pub struct RateLimiter {
+    per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Below zero when requests are waiting their turn
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: per_second.max(1.0),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tracing::debug!(?wait, "Waiting for the rate limit");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token, returning how long to wait for it when there are none left. Taking it
+    /// ahead of time keeps waiting requests in the order they came
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.per_second.max(1.0));
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.per_second)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_past_a_burst_are_spread_out() {
+        let limiter = RateLimiter::new(2.0);
+        let start = Instant::now();
+        let waits: Vec<Duration> = (0..4).map(|_| limiter.reserve(start)).collect();
+        assert_eq!(
+            waits,
+            [
+                Duration::ZERO,
+                Duration::ZERO,
+                Duration::from_millis(500),
+                Duration::from_secs(1)
+            ]
+        );
+
+        // A second and a half on, the two waiting have gone and there's a token again
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert_eq!(limiter.reserve(later), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,2317 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rand::Rng;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::{Client, ClientBuilder, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener as AsyncTcpListener;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::{timeout, Duration, Instant};
+use url::Url;
+
+use crate::ratelimit::RateLimiter;
+
+const API_URL: &str = "https://api.spotify.com/v1";
+const ACCOUNTS_URL: &str = "https://accounts.spotify.com";
+/// Most pages fetched for one listing, 5000 Liked Songs at 50 a page
+const MAX_PAGES: usize = 100;
+/// Requests kept for the debug overlay
+const REQUEST_LOG_SIZE: usize = 100;
+/// How many times a rate limited GET is sent again before giving up
+const RATE_LIMIT_RETRIES: u32 = 2;
+/// The longest Retry-After a GET waits out itself, longer ones are left to the caller
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(5);
+/// Responses kept for conditional GETs, enough for every page of a 5000 song library and then
+/// some
+const ETAG_CACHE_SIZE: usize = 200;
+/// Listings whose responses are kept for conditional GETs. They're fetched again and again
+/// but rarely change, unlike search results or the player
+const REVALIDATED_PATHS: &[&str] = &[
+    "/me/playlists",
+    "/me/tracks",
+    "/me/albums",
+    "/me/following",
+    "/me/shows",
+    "/playlists/",
+];
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("spotitui-core needs a TLS backend, enable the native-tls or rustls feature");
+
+/// The TLS library requests go through, picked with the native-tls and rustls features.
+/// rustls wins when both are on, it's the one asked for on top of the defaults
+#[cfg(feature = "rustls")]
+pub const TLS_BACKEND: &str = "rustls";
+#[cfg(not(feature = "rustls"))]
+pub const TLS_BACKEND: &str = "native-tls";
+
+/// An HTTP client builder using TLS_BACKEND
+#[cfg(feature = "rustls")]
+pub fn client_builder() -> ClientBuilder {
+    Client::builder().use_rustls_tls()
+}
+
+/// An HTTP client builder using TLS_BACKEND
+#[cfg(not(feature = "rustls"))]
+pub fn client_builder() -> ClientBuilder {
+    Client::builder()
+}
+
+/// Failures worth telling apart, carried inside the anyhow errors the client returns so callers
+/// can downcast to them. Anything else comes back as a plain message
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SpotifyError {
+    /// Not signed in, or the sign-in was turned down or has lapsed
+    #[error("{0}")]
+    Auth(String),
+    /// Over the rate limit, the request can be retried after the wait
+    #[error("Rate limited by Spotify, retry in {}s", retry_after.as_secs())]
+    RateLimited { retry_after: Duration },
+    #[error(
+        "No active device found. Please start Spotify on your phone, computer, or web browser."
+    )]
+    NoDevice,
+    /// Spotify only allows controlling the player on Premium accounts, for the action named
+    #[error("Spotify Premium is required for {0}.")]
+    PremiumRequired(&'static str),
+    /// Spotify couldn't be reached, or the connection dropped
+    #[error("Couldn't reach Spotify: {0}")]
+    Network(String),
+    /// A response didn't have the shape expected of it
+    #[error("Unexpected response from Spotify: {0}")]
+    Deserialize(String),
+}
+
+impl SpotifyError {
+    /// A stable name for the category, for scripts reading --json errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SpotifyError::Auth(_) => "auth",
+            SpotifyError::RateLimited { .. } => "rate_limited",
+            SpotifyError::NoDevice => "no_device",
+            SpotifyError::PremiumRequired(_) => "premium_required",
+            SpotifyError::Network(_) => "network",
+            SpotifyError::Deserialize(_) => "deserialize",
+        }
+    }
+}
+
+impl From<reqwest::Error> for SpotifyError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            SpotifyError::Deserialize(e.to_string())
+        } else {
+            SpotifyError::Network(e.to_string())
+        }
+    }
+}
+
+/// A request sent to the Web API, as the debug overlay lists it
+#[derive(Debug, Clone)]
+pub struct RequestRecord {
+    pub method: Method,
+    /// Relative to the API root, with the query
+    pub path: String,
+    /// None when no answer came, with the error in its place
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub latency: Duration,
+    /// 0 for the first try, counting up each time it's sent again, after refreshing the access
+    /// token or waiting out a rate limit
+    pub retry: u32,
+    pub sent_at: Instant,
+}
+
+/// A GET's status and body, handed to every caller that asked for it while it was in flight
+type SharedResponse = Result<(StatusCode, String), Arc<anyhow::Error>>;
+
+/// One page of a listing, `next` linking to the page after it
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub id: String,
+    pub name: String,
+    pub artists: Vec<Artist>,
+    pub album: Album,
+    pub duration_ms: u32,
+    pub uri: String,
+    /// When the track was added to the playlist or library it was loaded from
+    #[serde(default)]
+    pub added_at: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    /// How much the track is played lately, from 0 to 100
+    #[serde(default)]
+    pub popularity: Option<u32>,
+}
+
+/// One page of a longer list of tracks
+#[derive(Debug, Clone, Default)]
+pub struct TrackPage {
+    pub tracks: Vec<Track>,
+    /// Where the next page starts, if there is one
+    pub next: Option<usize>,
+    /// How many tracks there are in all
+    pub total: usize,
+}
+
+impl From<Vec<Track>> for TrackPage {
+    /// The whole list as a single page
+    fn from(tracks: Vec<Track>) -> Self {
+        TrackPage {
+            total: tracks.len(),
+            tracks,
+            next: None,
+        }
+    }
+}
+
+/// What recommendations are grown from, Spotify takes up to five seeds between the three
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Seeds {
+    pub track_ids: Vec<String>,
+    pub artist_ids: Vec<String>,
+    pub genres: Vec<String>,
+}
+
+impl Seeds {
+    pub fn track(id: &str) -> Self {
+        Seeds {
+            track_ids: vec![id.to_string()],
+            ..Seeds::default()
+        }
+    }
+
+    pub fn artist(id: &str) -> Self {
+        Seeds {
+            artist_ids: vec![id.to_string()],
+            ..Seeds::default()
+        }
+    }
+
+    /// The seeds as the query /recommendations takes them
+    fn query(&self) -> Vec<(&'static str, String)> {
+        [
+            ("seed_tracks", &self.track_ids),
+            ("seed_artists", &self.artist_ids),
+            ("seed_genres", &self.genres),
+        ]
+        .into_iter()
+        .filter(|(_, seeds)| !seeds.is_empty())
+        .map(|(name, seeds)| (name, seeds.join(",")))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artist {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Album {
+    pub id: String,
+    pub name: String,
+    pub images: Vec<Image>,
+    #[serde(default)]
+    pub artists: Vec<Artist>,
+    /// Like "1997", "1997-06" or "1997-06-16", as precisely as it's known
+    #[serde(default)]
+    pub release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Image {
+    pub height: Option<u32>,
+    pub url: String,
+    pub width: Option<u32>,
+}
+
+/// A podcast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Show {
+    pub id: String,
+    pub name: String,
+    pub publisher: String,
+    pub images: Vec<Image>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub owner: Option<PlaylistOwner>,
+    pub tracks: PlaylistTracks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistOwner {
+    pub id: String,
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistTracks {
+    pub total: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u32,
+    refresh_token: Option<String>,
+    scope: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlaylistFollowersResponse {
+    followers: Followers,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Followers {
+    total: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlaylistTrackItem {
+    added_at: Option<String>,
+    track: Track,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchResponse {
+    tracks: TracksResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TracksResponse {
+    items: Vec<Track>,
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlaylistSearchResponse {
+    playlists: PlaylistSearchResults,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlaylistSearchResults {
+    // Spotify pads playlist results with nulls
+    items: Vec<Option<Playlist>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AlbumTrack {
+    id: String,
+    name: String,
+    artists: Vec<Artist>,
+    duration_ms: u32,
+    uri: String,
+    #[serde(default)]
+    explicit: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArtistTopTracksResponse {
+    tracks: Vec<Track>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecommendationsResponse {
+    tracks: Vec<Track>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GenreSeedsResponse {
+    genres: Vec<String>,
+}
+
+/// What Spotify's analysis made of a track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioFeatures {
+    pub id: String,
+    /// Beats per minute
+    pub tempo: f32,
+    /// From 0 to 1, like the rest
+    pub energy: f32,
+    pub danceability: f32,
+    pub valence: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AudioFeaturesResponse {
+    // Tracks that haven't been analysed come back as null
+    audio_features: Vec<Option<AudioFeatures>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedAlbum {
+    album: Album,
+}
+
+#[derive(Debug, Deserialize)]
+struct FollowedArtistsResponse {
+    artists: Page<Artist>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedShow {
+    show: Show,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Episode {
+    id: String,
+    name: String,
+    duration_ms: u32,
+    uri: String,
+    release_date: Option<String>,
+    #[serde(default)]
+    explicit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub is_active: bool,
+    pub volume_percent: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DevicesResponse {
+    devices: Vec<Device>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentlyPlaying {
+    pub item: Option<Track>,
+    pub is_playing: bool,
+    pub progress_ms: Option<u64>,
+    pub device: Option<Device>,
+    pub shuffle_state: bool,
+    /// "off", "track" or "context"
+    pub repeat_state: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CurrentlyPlayingResponse {
+    item: Option<Track>,
+    is_playing: bool,
+    progress_ms: Option<u64>,
+    device: Option<Device>,
+    #[serde(default)]
+    shuffle_state: bool,
+    #[serde(default)]
+    repeat_state: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Queue {
+    pub currently_playing: Option<Track>,
+    pub queue: Vec<Track>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueueResponse {
+    currently_playing: Option<Track>,
+    queue: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRefreshResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+/// Bodies of earlier GETs by path with the ETag Spotify sent along, dropping the least
+/// recently used past ETAG_CACHE_SIZE
+#[derive(Debug, Default)]
+struct EtagCache {
+    entries: HashMap<String, (String, String)>,
+    /// Paths from least to most recently used
+    order: VecDeque<String>,
+}
+
+impl EtagCache {
+    fn get(&mut self, path: &str) -> Option<(String, String)> {
+        let entry = self.entries.get(path).cloned()?;
+        self.touch(path);
+        Some(entry)
+    }
+
+    fn insert(&mut self, path: &str, etag: String, body: String) {
+        self.entries.insert(path.to_string(), (etag, body));
+        self.touch(path);
+        while self.entries.len() > ETAG_CACHE_SIZE {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, path: &str) {
+        if self.entries.remove(path).is_some() {
+            self.order.retain(|known| known != path);
+        }
+    }
+
+    fn touch(&mut self, path: &str) {
+        self.order.retain(|known| known != path);
+        self.order.push_back(path.to_string());
+    }
+}
+
+#[derive(Clone)]
+pub struct SpotifyClient {
+    client: Client,
+    access_token: Arc<Mutex<Option<String>>>,
+    refresh_token: Arc<Mutex<Option<String>>>,
+    /// Device picked in the device picker, which player calls are sent to
+    target_device: Arc<Mutex<Option<String>>>,
+    client_id: String,
+    /// Roots of the Web API and the accounts service, pointed at a local server in tests
+    api_url: String,
+    accounts_url: String,
+    /// Where the refresh token is saved, so later runs can sign in without the browser
+    token_file: Option<PathBuf>,
+    /// Paces Web API requests, shared by every clone
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// How long a playback change can take, see with_playback_timeout
+    playback_timeout: Option<Duration>,
+    /// GETs in flight by path, see get_shared
+    in_flight: Arc<std::sync::Mutex<HashMap<String, Arc<OnceCell<SharedResponse>>>>>,
+    /// Bodies of earlier library GETs, see get_text
+    etags: Arc<std::sync::Mutex<EtagCache>>,
+    /// The latest requests, newest last
+    request_log: Arc<std::sync::Mutex<VecDeque<RequestRecord>>>,
+}
+
+impl SpotifyClient {
+    pub fn new(client_id: String, _client_secret: String) -> Self {
+        Self {
+            client: client_builder()
+                .build()
+                .expect("the TLS backend couldn't be set up"),
+            access_token: Arc::new(Mutex::new(None)),
+            refresh_token: Arc::new(Mutex::new(None)),
+            target_device: Arc::new(Mutex::new(None)),
+            client_id,
+            api_url: API_URL.to_string(),
+            accounts_url: ACCOUNTS_URL.to_string(),
+            token_file: None,
+            rate_limiter: None,
+            playback_timeout: None,
+            in_flight: Arc::default(),
+            etags: Arc::default(),
+            request_log: Arc::default(),
+        }
+    }
+
+    /// Keeps the refresh token in `path`, starting from the one saved there by an earlier run
+    pub fn with_token_file(mut self, path: PathBuf) -> Self {
+        let saved = fs::read_to_string(&path)
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        self.refresh_token = Arc::new(Mutex::new(saved));
+        self.token_file = Some(path);
+        self
+    }
+
+    /// Sends requests through `client`, one set up with a proxy or extra certificates
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sends at most `per_second` Web API requests a second, with no limit for 0
+    pub fn with_rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limiter = (per_second > 0.0).then(|| Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    /// Gives up on requests that change playback after `timeout`, on top of the HTTP client's
+    /// own timeouts. A pause or skip that lands much later is no use
+    pub fn with_playback_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.playback_timeout = timeout;
+        self
+    }
+
+    /// Signs in with the saved refresh token while it still works, through the browser otherwise
+    pub async fn sign_in(&self) -> Result<()> {
+        let saved = self.refresh_token.lock().await.is_some();
+        if saved {
+            match self.refresh_access_token().await {
+                Ok(_) => {
+                    tracing::info!("Signed in with the saved refresh token");
+                    return Ok(());
+                }
+                // The browser can't get through either, and the token may well work later
+                Err(e) if matches!(e.downcast_ref(), Some(SpotifyError::Network(_))) => {
+                    return Err(e)
+                }
+                Err(_) => {}
+            }
+        }
+        tracing::info!("Signing in through the browser");
+        self.authenticate().await
+    }
+
+    /// Failing to save only means signing in through the browser again, so it isn't reported
+    fn save_refresh_token(&self, refresh_token: Option<&str>) {
+        if let (Some(path), Some(token)) = (&self.token_file, refresh_token) {
+            if let Err(e) = write_private(path, token) {
+                tracing::warn!(
+                    "Failed to save the refresh token to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// The latest requests, newest first
+    pub fn recent_requests(&self) -> Vec<RequestRecord> {
+        self.request_log
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn log_request(&self, record: RequestRecord) {
+        let mut log = self.request_log.lock().unwrap();
+        if log.len() == REQUEST_LOG_SIZE {
+            log.pop_front();
+        }
+        log.push_back(record);
+    }
+
+    pub async fn access_token(&self) -> Option<String> {
+        self.access_token.lock().await.clone()
+    }
+
+    pub async fn refresh_access_token(&self) -> Result<()> {
+        let mut refresh_token = self.refresh_token.lock().await;
+        let refresh_token_value = refresh_token
+            .clone()
+            .ok_or_else(|| SpotifyError::Auth("Not signed in to Spotify".to_string()))?;
+
+        let params = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token_value.as_str()),
+            ("client_id", self.client_id.as_str()),
+        ];
+
+        let response = self
+            .client
+            .post(format!("{}/api/token", self.accounts_url))
+            .form(&params)
+            .send()
+            .await
+            .map_err(SpotifyError::from)?;
+
+        let status = response.status();
+        if status.is_server_error() {
+            tracing::warn!(status = status.as_u16(), "Token refresh failed");
+            return Err(SpotifyError::Network(format!("Spotify answered {}", status)).into());
+        }
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            tracing::warn!(
+                status = status.as_u16(),
+                "Token refresh failed: {}",
+                error_text
+            );
+            return Err(SpotifyError::Auth(format!(
+                "Token refresh failed with status {}: {}",
+                status, error_text
+            ))
+            .into());
+        }
+
+        let token_response: TokenRefreshResponse = read_json(response).await?;
+
+        tracing::debug!(
+            rotated = token_response.refresh_token.is_some(),
+            "Refreshed the access token"
+        );
+        let mut access_token = self.access_token.lock().await;
+        *access_token = Some(token_response.access_token);
+        // Spotify only sends a new refresh token when it rotates the old one
+        if token_response.refresh_token.is_some() {
+            *refresh_token = token_response.refresh_token;
+            self.save_refresh_token(refresh_token.as_deref());
+        }
+        Ok(())
+    }
+
+    pub async fn authenticate(&self) -> Result<()> {
+        let port = env::var("PORT").unwrap_or_else(|_| 8888.to_string());
+        let redirect_host = format!("127.0.0.1:{}", port);
+        let redirect_uri = format!("http://{}/callback", redirect_host);
+        let scope = "user-read-private user-read-email playlist-read-private playlist-read-collaborative user-modify-playback-state user-read-playback-state user-read-currently-playing user-read-playback-position user-library-read user-library-modify playlist-modify-public playlist-modify-private user-follow-read streaming";
+
+        let code_verifier = self.generate_code_verifier();
+        let code_challenge = self.generate_code_challenge(&code_verifier);
+        let state = self.generate_state();
+
+        let auth_url = format!(
+            "{}/authorize?client_id={}&response_type=code&redirect_uri={}&code_challenge_method=S256&code_challenge={}&state={}&scope={}",
+            self.accounts_url,
+            self.client_id,
+            urlencoding::encode(redirect_uri.as_str()),
+            code_challenge,
+            state,
+            urlencoding::encode(scope)
+        );
+
+        webbrowser::open(&auth_url)?;
+
+        let auth_code = match self
+            .start_callback_server_with_timeout(redirect_host.clone())
+            .await
+        {
+            Ok(code) => code,
+            Err(e) => {
+                // Fallback to manual entry - this will be handled by the UI layer
+                return Err(SpotifyError::Auth(format!(
+                    "Authentication callback failed - manual entry required: {e}"
+                ))
+                .into());
+            }
+        };
+
+        let token = self
+            .exchange_code_for_token(&auth_code, &code_verifier, redirect_uri.as_str())
+            .await?;
+
+        let mut access_token = self.access_token.lock().await;
+        *access_token = Some(token.access_token);
+
+        let mut refresh_token = self.refresh_token.lock().await;
+        *refresh_token = token.refresh_token;
+        self.save_refresh_token(refresh_token.as_deref());
+
+        Ok(())
+    }
+
+    async fn start_callback_server_with_timeout(&self, bind_addr: String) -> Result<String> {
+        timeout(
+            Duration::from_secs(60),
+            self.start_callback_server(bind_addr),
+        )
+        .await?
+    }
+
+    async fn start_callback_server(&self, bind_addr: String) -> Result<String> {
+        let listener = AsyncTcpListener::bind(bind_addr.clone()).await?;
+
+        loop {
+            match listener.accept().await {
+                Ok((mut stream, _)) => {
+                    let mut buffer = vec![0; 2048];
+
+                    // Give the client time to send the request
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+
+                    match stream.try_read(&mut buffer) {
+                        Ok(n) => {
+                            let request = String::from_utf8_lossy(&buffer[..n]);
+
+                            if let Some(code) =
+                                self.extract_code_from_request(&request, bind_addr.clone())
+                            {
+                                self.send_async_response(&mut stream).await?;
+                                return Ok(code);
+                            }
+                        }
+                        Err(_) => {
+                            // Try again with a blocking read
+                            let mut buffer = vec![0; 2048];
+                            match stream.readable().await {
+                                Ok(_) => match stream.try_read(&mut buffer) {
+                                    Ok(n) => {
+                                        let request = String::from_utf8_lossy(&buffer[..n]);
+
+                                        if let Some(code) = self
+                                            .extract_code_from_request(&request, bind_addr.clone())
+                                        {
+                                            self.send_async_response(&mut stream).await?;
+                                            return Ok(code);
+                                        }
+                                    }
+                                    Err(_) => continue,
+                                },
+                                Err(_) => continue,
+                            }
+                        }
+                    }
+                }
+                Err(_) => continue, // Don't log connection errors
+            }
+        }
+    }
+
+    fn extract_code_from_request(&self, request: &str, callback_host: String) -> Option<String> {
+        // Look for both /callback and / endpoints
+        let patterns = ["GET /callback?", "GET /?"];
+
+        for pattern in &patterns {
+            if let Some(query_start) = request.find(pattern) {
+                let query_part = &request[query_start + pattern.len()..];
+                if let Some(query_end) = query_part.find(' ') {
+                    let query = &query_part[..query_end];
+                    let url = format!("http://{}?{}", callback_host, query);
+                    if let Ok(parsed_url) = Url::parse(&url) {
+                        for (key, value) in parsed_url.query_pairs() {
+                            if key == "code" {
+                                return Some(value.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    async fn send_async_response(&self, stream: &mut tokio::net::TcpStream) -> Result<()> {
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n<html><body><h1>Authentication successful!</h1><p>You can close this window and return to the terminal.</p></body></html>";
+        stream.try_write(response.as_bytes())?;
+        Ok(())
+    }
+
+    async fn exchange_code_for_token(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+    ) -> Result<TokenResponse> {
+        let mut params = HashMap::new();
+        params.insert("grant_type", "authorization_code");
+        params.insert("code", code);
+        params.insert("redirect_uri", redirect_uri);
+        params.insert("client_id", &self.client_id);
+        params.insert("code_verifier", code_verifier);
+
+        let response = self
+            .client
+            .post(format!("{}/api/token", self.accounts_url))
+            .form(&params)
+            .send()
+            .await
+            .map_err(SpotifyError::from)?;
+
+        let token: TokenResponse = read_json(response).await?;
+        Ok(token)
+    }
+
+    /// GETs `path` like send, except that a caller asking for a path that's already in flight
+    /// shares that response instead of sending another request. Polls overlap like that when
+    /// Spotify is slow to answer
+    async fn get_shared(&self, path: &str) -> Result<(StatusCode, String)> {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default()
+            .clone();
+        // Should the caller sending it go away, one of those waiting sends it instead
+        let shared = cell
+            .get_or_init(|| async {
+                let result = self.get_text(path).await.map_err(Arc::new);
+                // Only overlapping calls share, the next one asks again
+                let mut in_flight = self.in_flight.lock().unwrap();
+                if in_flight.get(path).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                    in_flight.remove(path);
+                }
+                result
+            })
+            .await;
+
+        shared
+            .clone()
+            .map_err(|e| match e.downcast_ref::<SpotifyError>() {
+                Some(e) => e.clone().into(),
+                None => anyhow!("{:#}", e),
+            })
+    }
+
+    /// GETs `path`, asking Spotify to answer 304 Not Modified when what it sent with an ETag
+    /// last time hasn't changed, in which case that body is used again. Only the library's
+    /// listings are kept for that, see REVALIDATED_PATHS
+    async fn get_text(&self, path: &str) -> Result<(StatusCode, String)> {
+        let revalidated = self.is_revalidated(path);
+        let known = if revalidated {
+            self.etags.lock().unwrap().get(path)
+        } else {
+            None
+        };
+        let response = self
+            .send(Method::GET, path, |request| match &known {
+                Some((etag, _)) => request.header(IF_NONE_MATCH, etag),
+                None => request,
+            })
+            .await?;
+
+        let status = response.status();
+        if let (StatusCode::NOT_MODIFIED, Some((_, body))) = (status, &known) {
+            tracing::trace!(path, "Not modified");
+            return Ok((StatusCode::OK, body.clone()));
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let text = response.text().await.map_err(SpotifyError::from)?;
+        if revalidated && status.is_success() {
+            let mut etags = self.etags.lock().unwrap();
+            match etag {
+                Some(etag) => etags.insert(path, etag, text.clone()),
+                None => etags.remove(path),
+            }
+        }
+        Ok((status, text))
+    }
+
+    /// Whether a GET of `path`, relative to the API root or a full URL, is kept for
+    /// conditional requests
+    fn is_revalidated(&self, path: &str) -> bool {
+        let path = path.strip_prefix(&self.api_url).unwrap_or(path);
+        REVALIDATED_PATHS
+            .iter()
+            .any(|revalidated| path.starts_with(revalidated))
+    }
+
+    /// Sends a request to the Web API, `path` being relative to the API root or a full URL
+    /// like a page's `next` link. An expired access token is refreshed and the request sent
+    /// again, and running into the rate limit comes back as a RateLimited error
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response> {
+        let url = if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.api_url, path)
+        };
+
+        let logged_path = url.strip_prefix(&self.api_url).unwrap_or(&url).to_string();
+        let mut refreshed = false;
+        let mut retry = 0;
+        let mut rate_limit_retries = 0;
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            let token = self
+                .access_token
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| SpotifyError::Auth("Not signed in to Spotify".to_string()))?;
+            let mut request = self.client.request(method.clone(), &url).bearer_auth(token);
+            if let Some(timeout) = self.playback_timeout {
+                if method != Method::GET && logged_path.starts_with("/me/player") {
+                    request = request.timeout(timeout);
+                }
+            }
+            let started = Instant::now();
+            let result = build(request).send().await;
+            self.log_request(RequestRecord {
+                method: method.clone(),
+                path: logged_path.clone(),
+                status: result.as_ref().ok().map(|r| r.status().as_u16()),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                latency: started.elapsed(),
+                retry,
+                sent_at: started,
+            });
+            let response = result.map_err(|e| {
+                tracing::warn!(%method, %url, "Request failed: {}", e);
+                SpotifyError::from(e)
+            })?;
+            tracing::debug!(
+                %method,
+                %url,
+                status = response.status().as_u16(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "Spotify request"
+            );
+
+            match response.status() {
+                StatusCode::UNAUTHORIZED
+                    if !refreshed && self.refresh_token.lock().await.is_some() =>
+                {
+                    tracing::debug!("Access token expired, refreshing it");
+                    self.refresh_access_token().await?;
+                    refreshed = true;
+                    retry += 1;
+                }
+                // Turned down even with a fresh token, or there's no way to get one
+                StatusCode::UNAUTHORIZED => {
+                    tracing::warn!(%url, refreshed, "Unauthorized");
+                    return Err(SpotifyError::Auth(
+                        "Spotify turned down the sign-in, sign in again".to_string(),
+                    )
+                    .into());
+                }
+                StatusCode::TOO_MANY_REQUESTS => {
+                    // Retry-After is in seconds, assume a short wait if it's missing
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(1);
+                    let retry_after = Duration::from_secs(retry_after);
+                    // Reads are safe to send again, once the wait is over
+                    if method == Method::GET
+                        && rate_limit_retries < RATE_LIMIT_RETRIES
+                        && retry_after <= MAX_RETRY_WAIT
+                    {
+                        tracing::debug!(%url, ?retry_after, "Rate limited, retrying");
+                        tokio::time::sleep(retry_after).await;
+                        rate_limit_retries += 1;
+                        retry += 1;
+                        continue;
+                    }
+                    tracing::warn!(%url, ?retry_after, "Rate limited");
+                    return Err(SpotifyError::RateLimited { retry_after }.into());
+                }
+                // Spotify itself is down rather than the request being wrong
+                status @ (StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT) => {
+                    tracing::warn!(%url, status = status.as_u16(), "Spotify unavailable");
+                    return Err(
+                        SpotifyError::Network(format!("Spotify answered {}", status)).into(),
+                    );
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    /// Every item of a paged listing, following the `next` links from the first page
+    async fn get_pages<T: DeserializeOwned>(&self, path: &str, what: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = Some(path.to_string());
+        // Stop somewhere, so a huge library can't keep the view loading for long
+        for _ in 0..MAX_PAGES {
+            let Some(url) = next else {
+                break;
+            };
+            let (status, text) = self.get_text(&url).await?;
+            check_status(status, &format!("load {}", what))?;
+            let page: Page<T> =
+                parse_json(&text).with_context(|| format!("Failed to read {}", what))?;
+            items.extend(page.items);
+            next = page.next;
+        }
+        Ok(items)
+    }
+
+    pub async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.get_pages("/me/playlists?limit=50", "playlists").await
+    }
+
+    pub async fn get_saved_albums(&self) -> Result<Vec<Album>> {
+        let albums: Vec<SavedAlbum> = self.get_pages("/me/albums?limit=50", "albums").await?;
+        Ok(albums.into_iter().map(|item| item.album).collect())
+    }
+
+    /// Every followed artist. These page by cursor and come wrapped in "artists", so they
+    /// can't go through `get_pages`, but the `next` links are followed the same way
+    pub async fn get_followed_artists(&self) -> Result<Vec<Artist>> {
+        let mut artists = Vec::new();
+        let mut next = Some("/me/following?type=artist&limit=50".to_string());
+        for _ in 0..MAX_PAGES {
+            let Some(url) = next else {
+                break;
+            };
+            let (status, text) = self.get_text(&url).await?;
+            check_status(status, "load artists")?;
+            let page: FollowedArtistsResponse =
+                parse_json(&text).context("Failed to read artists")?;
+            artists.extend(page.artists.items);
+            next = page.artists.next;
+        }
+        Ok(artists)
+    }
+
+    pub async fn get_saved_shows(&self) -> Result<Vec<Show>> {
+        let shows: Vec<SavedShow> = self.get_pages("/me/shows?limit=50", "podcasts").await?;
+        Ok(shows.into_iter().map(|item| item.show).collect())
+    }
+
+    /// The show's latest episodes as tracks, with the show standing in for the album and the
+    /// publisher for the artist so they fit the tracks pane
+    pub async fn get_show_episodes(&self, show: &Show) -> Result<Vec<Track>> {
+        let response = self
+            .send(
+                Method::GET,
+                &format!("/shows/{}/episodes", show.id),
+                |request| request.query(&[("limit", "50"), ("market", "from_token")]),
+            )
+            .await?;
+
+        check_status(response.status(), "load episodes")?;
+
+        // Episodes that are no longer available come back as null
+        let episodes: Page<Option<Episode>> = read_json(response).await?;
+        Ok(episodes
+            .items
+            .into_iter()
+            .flatten()
+            .map(|episode| Track {
+                id: episode.id,
+                name: episode.name,
+                // Publishers aren't artists, so leave the id empty
+                artists: vec![Artist {
+                    id: String::new(),
+                    name: show.publisher.clone(),
+                }],
+                album: Album {
+                    id: show.id.clone(),
+                    name: show.name.clone(),
+                    images: show.images.clone(),
+                    artists: Vec::new(),
+                    release_date: None,
+                },
+                duration_ms: episode.duration_ms,
+                uri: episode.uri,
+                added_at: episode.release_date,
+                explicit: episode.explicit,
+                popularity: None,
+            })
+            .collect())
+    }
+
+    /// Follower count of a playlist, which the playlist listing leaves out
+    pub async fn get_playlist_followers(&self, playlist_id: &str) -> Result<u32> {
+        let response = self
+            .send(
+                Method::GET,
+                &format!("/playlists/{}", playlist_id),
+                |request| request.query(&[("fields", "followers.total")]),
+            )
+            .await?;
+
+        check_status(response.status(), "load playlist details")?;
+
+        let playlist: PlaylistFollowersResponse = read_json(response).await?;
+        Ok(playlist.followers.total)
+    }
+
+    /// The playlist's tracks starting at `offset`, a page at a time for showing long lists as
+    /// they're scrolled through
+    pub async fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &str,
+        offset: usize,
+    ) -> Result<TrackPage> {
+        let (path, what) = match playlist_id {
+            "liked" => (
+                format!("/me/tracks?limit=50&offset={}", offset),
+                "Liked Songs",
+            ),
+            _ => (
+                format!(
+                    "/playlists/{}/tracks?limit=100&offset={}",
+                    playlist_id, offset
+                ),
+                "playlist",
+            ),
+        };
+        let (status, text) = self.get_text(&path).await?;
+        check_status(status, &format!("load {}", what))?;
+        let page: Page<PlaylistTrackItem> =
+            parse_json(&text).with_context(|| format!("Failed to read {}", what))?;
+        let tracks: Vec<Track> = page
+            .items
+            .into_iter()
+            .map(|item| Track {
+                added_at: item.added_at,
+                ..item.track
+            })
+            .collect();
+        Ok(TrackPage {
+            next: page.next.map(|_| offset + tracks.len()),
+            total: page.total,
+            tracks,
+        })
+    }
+
+    /// A playlist's tracks with the dates they were added, "liked" meaning Liked Songs
+    pub async fn get_playlist_tracks(&self, playlist_id: &str) -> Result<Vec<Track>> {
+        let items: Vec<PlaylistTrackItem> = match playlist_id {
+            "liked" => self.get_pages("/me/tracks?limit=50", "Liked Songs").await?,
+            _ => {
+                let path = format!("/playlists/{}/tracks?limit=100", playlist_id);
+                self.get_pages(&path, "playlist").await?
+            }
+        };
+
+        Ok(items
+            .into_iter()
+            .map(|item| Track {
+                added_at: item.added_at,
+                ..item.track
+            })
+            .collect())
+    }
+
+    /// A page of tracks matching `query`, starting at `offset`
+    pub async fn search_tracks(&self, query: &str, offset: usize) -> Result<TrackPage> {
+        let response = self
+            .send(Method::GET, "/search", |request| {
+                request.query(&[
+                    ("q", query),
+                    ("type", "track"),
+                    ("limit", "50"),
+                    ("offset", &offset.to_string()),
+                ])
+            })
+            .await?;
+
+        check_status(response.status(), "search")?;
+
+        let search_response: SearchResponse = read_json(response).await?;
+        let tracks = search_response.tracks;
+        Ok(TrackPage {
+            next: tracks.next.map(|_| offset + tracks.items.len()),
+            total: tracks.total,
+            tracks: tracks.items,
+        })
+    }
+
+    pub async fn search_playlists(&self, query: &str) -> Result<Vec<Playlist>> {
+        let response = self
+            .send(Method::GET, "/search", |request| {
+                request.query(&[("q", query), ("type", "playlist"), ("limit", "20")])
+            })
+            .await?;
+
+        check_status(response.status(), "search")?;
+
+        let search_response: PlaylistSearchResponse = read_json(response).await?;
+        Ok(search_response
+            .playlists
+            .items
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Replaces playback with the given tracks, playing the first one
+    pub async fn play_tracks(&self, track_uris: &[&str]) -> Result<()> {
+        // First, check if there are any available devices
+        let devices = self.get_devices().await?;
+        if devices.is_empty() {
+            return Err(SpotifyError::NoDevice.into());
+        }
+
+        let mut body = HashMap::new();
+        body.insert("uris", track_uris);
+
+        let device = self.device_query().await;
+        let response = self
+            .send(Method::PUT, "/me/player/play", |request| {
+                request.query(&device).json(&body)
+            })
+            .await?;
+
+        player_result(response.status(), "play track")
+    }
+
+    /// Starts playing an album, playlist, artist or show from its first track
+    pub async fn play_context(&self, context_uri: &str) -> Result<()> {
+        let body = serde_json::json!({ "context_uri": context_uri });
+        let device = self.device_query().await;
+        let response = self
+            .send(Method::PUT, "/me/player/play", |request| {
+                request.query(&device).json(&body)
+            })
+            .await?;
+
+        player_result(response.status(), "start playing")
+    }
+
+    /// Devices playback can be sent to, from the Spotify apps the user has open
+    pub async fn get_devices(&self) -> Result<Vec<Device>> {
+        let response = self
+            .send(Method::GET, "/me/player/devices", |request| request)
+            .await?;
+
+        if response.status().is_success() {
+            let devices_response: DevicesResponse = read_json(response).await?;
+            Ok(devices_response.devices)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Moves playback to a device and sends later play, queue and playback control calls to it
+    pub async fn set_target_device(&self, device_id: &str) -> Result<()> {
+        let mut body = HashMap::new();
+        body.insert("device_ids", [device_id]);
+
+        let response = self
+            .send(Method::PUT, "/me/player", |request| request.json(&body))
+            .await?;
+
+        if response.status().is_success() {
+            *self.target_device.lock().await = Some(device_id.to_string());
+            Ok(())
+        } else {
+            let status = response.status();
+            match status.as_u16() {
+                404 => Err(anyhow!("That device is no longer available.")),
+                403 => Err(SpotifyError::PremiumRequired("playback control").into()),
+                _ => Err(status_error(status, "switch device")),
+            }
+        }
+    }
+
+    /// The device_id parameter for player calls, once a device has been picked
+    async fn device_query(&self) -> Vec<(&'static str, String)> {
+        self.target_device
+            .lock()
+            .await
+            .clone()
+            .map(|id| ("device_id", id))
+            .into_iter()
+            .collect()
+    }
+
+    pub async fn get_currently_playing(&self) -> Result<Option<CurrentlyPlaying>> {
+        // The full player state, which unlike currently-playing includes the device,
+        // shuffle and repeat
+        let (status, response_text) = self.get_shared("/me/player").await?;
+        check_status(status, "get player state")?;
+
+        // No content means nothing is currently playing
+        if response_text.is_empty() {
+            return Ok(None);
+        }
+        let currently_playing_response: CurrentlyPlayingResponse = parse_json(&response_text)?;
+        Ok(Some(CurrentlyPlaying {
+            item: currently_playing_response.item,
+            is_playing: currently_playing_response.is_playing,
+            progress_ms: currently_playing_response.progress_ms,
+            device: currently_playing_response.device,
+            shuffle_state: currently_playing_response.shuffle_state,
+            repeat_state: currently_playing_response
+                .repeat_state
+                .unwrap_or_else(|| "off".to_string()),
+        }))
+    }
+
+    pub async fn get_queue(&self) -> Result<Option<Queue>> {
+        let (status, text) = self.get_shared("/me/player/queue").await?;
+
+        if status.is_success() {
+            let queue_response: QueueResponse = parse_json(&text)?;
+            Ok(Some(Queue {
+                currently_playing: queue_response.currently_playing,
+                queue: queue_response.queue,
+            }))
+        } else {
+            Ok(None) // Don't error on other status codes, just return None
+        }
+    }
+
+    pub async fn add_to_queue(&self, track_uri: &str) -> Result<()> {
+        let device = self.device_query().await;
+        let response = self
+            .send(Method::POST, "/me/player/queue", |request| {
+                request
+                    .query(&[("uri", track_uri)])
+                    .query(&device)
+                    .header("Content-Length", "0")
+            })
+            .await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let status = response.status();
+            match status.as_u16() {
+                404 => Err(SpotifyError::NoDevice.into()),
+                403 => Err(SpotifyError::PremiumRequired("queue control").into()),
+                _ => Err(status_error(status, "add to queue")),
+            }
+        }
+    }
+
+    /// Adds tracks to a playlist, at `position` or at the end if there's none
+    pub async fn add_tracks_to_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+        position: Option<usize>,
+    ) -> Result<()> {
+        let path = format!("/playlists/{}/tracks", playlist_id);
+        // The endpoint accepts at most 100 tracks per request
+        for (i, chunk) in track_uris.chunks(100).enumerate() {
+            let mut body = HashMap::new();
+            body.insert("uris", serde_json::json!(chunk));
+            if let Some(position) = position {
+                body.insert("position", serde_json::json!(position + i * 100));
+            }
+
+            let response = self
+                .send(Method::POST, &path, |request| request.json(&body))
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                return match status.as_u16() {
+                    403 => Err(anyhow!(
+                        "You can only add tracks to playlists you own or collaborate on."
+                    )),
+                    _ => Err(status_error(status, "add tracks to playlist")),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes every occurrence of the tracks from a playlist
+    pub async fn remove_tracks_from_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+    ) -> Result<()> {
+        let path = format!("/playlists/{}/tracks", playlist_id);
+        // The endpoint accepts at most 100 tracks per request
+        for chunk in track_uris.chunks(100) {
+            let tracks: Vec<HashMap<&str, &str>> = chunk
+                .iter()
+                .map(|uri| HashMap::from([("uri", uri.as_str())]))
+                .collect();
+            let mut body = HashMap::new();
+            body.insert("tracks", tracks);
+
+            let response = self
+                .send(Method::DELETE, &path, |request| request.json(&body))
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                return match status.as_u16() {
+                    403 => Err(anyhow!(
+                        "You can only remove tracks from playlists you own or collaborate on."
+                    )),
+                    _ => Err(status_error(status, "remove tracks from playlist")),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates a private playlist in the user's library
+    pub async fn create_playlist(&self, name: &str, description: &str) -> Result<Playlist> {
+        let body = serde_json::json!({
+            "name": name,
+            "description": description,
+            "public": false,
+        });
+        let response = self
+            .send(Method::POST, "/me/playlists", |request| request.json(&body))
+            .await?;
+
+        check_status(response.status(), "create playlist")?;
+        Ok(read_json(response).await?)
+    }
+
+    /// Removes a playlist from the user's library, which deletes it if they own it
+    pub async fn unfollow_playlist(&self, playlist_id: &str) -> Result<()> {
+        let path = format!("/playlists/{}/followers", playlist_id);
+        let response = self.send(Method::DELETE, &path, |request| request).await?;
+
+        check_status(response.status(), "delete playlist")
+    }
+
+    /// Adds a playlist back to the user's library, which also restores one they deleted
+    pub async fn follow_playlist(&self, playlist_id: &str) -> Result<()> {
+        let path = format!("/playlists/{}/followers", playlist_id);
+        let response = self
+            .send(Method::PUT, &path, |request| {
+                request.header("Content-Length", "0")
+            })
+            .await?;
+
+        check_status(response.status(), "restore playlist")
+    }
+
+    /// Adds tracks to the user's Liked Songs
+    pub async fn save_tracks(&self, track_ids: &[String]) -> Result<()> {
+        // The endpoint accepts at most 50 ids per request
+        for chunk in track_ids.chunks(50) {
+            let ids = chunk.join(",");
+            let response = self
+                .send(Method::PUT, "/me/tracks", |request| {
+                    request
+                        .query(&[("ids", &ids)])
+                        .header("Content-Length", "0")
+                })
+                .await?;
+
+            check_status(response.status(), "like tracks")?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes tracks from the user's Liked Songs
+    pub async fn remove_saved_tracks(&self, track_ids: &[String]) -> Result<()> {
+        // The endpoint accepts at most 50 ids per request
+        for chunk in track_ids.chunks(50) {
+            let ids = chunk.join(",");
+            let response = self
+                .send(Method::DELETE, "/me/tracks", |request| {
+                    request.query(&[("ids", &ids)])
+                })
+                .await?;
+
+            check_status(response.status(), "unlike tracks")?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_album_tracks(&self, album: &Album) -> Result<Vec<Track>> {
+        let path = format!("/albums/{}/tracks?limit=50", album.id);
+        let album_tracks: Vec<AlbumTrack> = self.get_pages(&path, "album").await?;
+
+        // Album tracks come back without their album, so fill it back in
+        Ok(album_tracks
+            .into_iter()
+            .map(|track| Track {
+                id: track.id,
+                name: track.name,
+                artists: track.artists,
+                album: album.clone(),
+                duration_ms: track.duration_ms,
+                uri: track.uri,
+                added_at: None,
+                explicit: track.explicit,
+                popularity: None,
+            })
+            .collect())
+    }
+
+    pub async fn get_artist_top_tracks(&self, artist_id: &str) -> Result<Vec<Track>> {
+        let path = format!("/artists/{}/top-tracks", artist_id);
+        let response = self
+            .send(Method::GET, &path, |request| {
+                request.query(&[("market", "from_token")])
+            })
+            .await?;
+
+        check_status(response.status(), "load artist")?;
+
+        let top_tracks: ArtistTopTracksResponse = read_json(response).await?;
+        Ok(top_tracks.tracks)
+    }
+
+    /// Up to `limit` tracks like the seeds, a different set each time
+    pub async fn get_recommendations(&self, seeds: &Seeds, limit: usize) -> Result<Vec<Track>> {
+        let limit = limit.to_string();
+        let response = self
+            .send(Method::GET, "/recommendations", |request| {
+                request
+                    .query(&seeds.query())
+                    .query(&[("limit", limit.as_str()), ("market", "from_token")])
+            })
+            .await?;
+
+        check_status(response.status(), "load recommendations")?;
+
+        let recommendations: RecommendationsResponse = read_json(response).await?;
+        Ok(recommendations.tracks)
+    }
+
+    /// The genres recommendations can be seeded with
+    pub async fn get_genre_seeds(&self) -> Result<Vec<String>> {
+        let response = self
+            .send(
+                Method::GET,
+                "/recommendations/available-genre-seeds",
+                |request| request,
+            )
+            .await?;
+
+        check_status(response.status(), "load genres")?;
+
+        let seeds: GenreSeedsResponse = read_json(response).await?;
+        Ok(seeds.genres)
+    }
+
+    /// The features of the tracks with these ids, leaving out those Spotify hasn't analysed
+    pub async fn get_audio_features(&self, track_ids: &[String]) -> Result<Vec<AudioFeatures>> {
+        let mut features = Vec::new();
+        // Spotify takes up to 100 ids at a time
+        for chunk in track_ids.chunks(100) {
+            let ids = chunk.join(",");
+            let response = self
+                .send(Method::GET, "/audio-features", |request| {
+                    request.query(&[("ids", ids.as_str())])
+                })
+                .await?;
+
+            check_status(response.status(), "load audio features")?;
+
+            let chunk: AudioFeaturesResponse = read_json(response).await?;
+            features.extend(chunk.audio_features.into_iter().flatten());
+        }
+        Ok(features)
+    }
+
+    /// Sends a playback control with no body to the picked device
+    async fn player_command(&self, method: Method, path: &str, action: &str) -> Result<()> {
+        let device = self.device_query().await;
+        let response = self
+            .send(method, path, |request| {
+                request.query(&device).header("Content-Length", "0")
+            })
+            .await?;
+
+        player_result(response.status(), action)
+    }
+
+    pub async fn pause_playback(&self) -> Result<()> {
+        self.player_command(Method::PUT, "/me/player/pause", "pause playback")
+            .await
+    }
+
+    pub async fn resume_playback(&self) -> Result<()> {
+        self.player_command(Method::PUT, "/me/player/play", "resume playback")
+            .await
+    }
+
+    pub async fn next_track(&self) -> Result<()> {
+        self.player_command(Method::POST, "/me/player/next", "skip to next track")
+            .await
+    }
+
+    pub async fn previous_track(&self) -> Result<()> {
+        self.player_command(
+            Method::POST,
+            "/me/player/previous",
+            "skip to previous track",
+        )
+        .await
+    }
+
+    /// Jumps to `position_ms` into the playing track
+    pub async fn seek(&self, position_ms: u64) -> Result<()> {
+        let mut query = self.device_query().await;
+        query.push(("position_ms", position_ms.to_string()));
+        let response = self
+            .send(Method::PUT, "/me/player/seek", |request| {
+                request.query(&query).header("Content-Length", "0")
+            })
+            .await?;
+
+        player_result(response.status(), "seek")
+    }
+
+    /// Sets the playing device's volume, from 0 to 100
+    pub async fn set_volume(&self, volume_percent: u32) -> Result<()> {
+        let mut query = self.device_query().await;
+        query.push(("volume_percent", volume_percent.min(100).to_string()));
+        let response = self
+            .send(Method::PUT, "/me/player/volume", |request| {
+                request.query(&query).header("Content-Length", "0")
+            })
+            .await?;
+
+        player_result(response.status(), "set the volume")
+    }
+
+    fn generate_code_verifier(&self) -> String {
+        let mut rng = rand::rng();
+        let code_verifier: String = (0..128)
+            .map(|_| {
+                let chars = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+                chars[rng.random_range(0..chars.len())] as char
+            })
+            .collect();
+        code_verifier
+    }
+
+    fn generate_code_challenge(&self, code_verifier: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(code_verifier.as_bytes());
+        let digest = hasher.finalize();
+        general_purpose::URL_SAFE_NO_PAD.encode(digest)
+    }
+
+    fn generate_state(&self) -> String {
+        let mut rng = rand::rng();
+        (0..16)
+            .map(|_| rng.random_range(0..16))
+            .map(|n| format!("{:x}", n))
+            .collect()
+    }
+}
+
+/// Reads a response body, telling a body that isn't what was expected apart from a dropped
+/// connection
+async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T, SpotifyError> {
+    Ok(response.json().await?)
+}
+
+/// A body already read, like one used again after a 304
+fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, SpotifyError> {
+    serde_json::from_str(text).map_err(|e| SpotifyError::Deserialize(e.to_string()))
+}
+
+/// Writes a file only the current user can read, as it holds credentials
+fn write_private(path: &PathBuf, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+}
+
+/// Fails with the error an unsuccessful `status` stands for, `action` saying what was being
+/// done like "load playlists"
+fn check_status(status: StatusCode, action: &str) -> Result<()> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(status_error(status, action))
+    }
+}
+
+/// The error an unsuccessful `status` stands for. Statuses without a category of their own come
+/// back as a plain message, send has already turned Spotify being down into a network error
+fn status_error(status: StatusCode, action: &str) -> anyhow::Error {
+    match status {
+        StatusCode::UNAUTHORIZED => SpotifyError::Auth(format!(
+            "Spotify turned down the sign-in while trying to {}, sign in again",
+            action
+        ))
+        .into(),
+        _ => anyhow!("Failed to {}: {}", action, status),
+    }
+}
+
+/// The outcome of a playback control, with the failures Spotify's player commonly returns
+/// spelled out
+fn player_result(status: StatusCode, action: &str) -> Result<()> {
+    match status.as_u16() {
+        _ if status.is_success() => Ok(()),
+        404 => Err(SpotifyError::NoDevice.into()),
+        403 => Err(SpotifyError::PremiumRequired("playback control").into()),
+        _ => Err(status_error(status, action)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A recorded response from tests/fixtures, with its links pointed at the mock server
+    fn fixture(server: &MockServer, name: &str) -> ResponseTemplate {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        let body = std::fs::read_to_string(file)
+            .unwrap()
+            .replace("{server}", &server.uri());
+        ResponseTemplate::new(200).set_body_raw(body, "application/json")
+    }
+
+    /// A client talking to the mock server, signed in with the access token "stale"
+    async fn client(server: &MockServer) -> SpotifyClient {
+        let mut client = SpotifyClient::new("client-id".to_string(), String::new());
+        client.api_url = format!("{}/v1", server.uri());
+        client.accounts_url = server.uri();
+        *client.access_token.lock().await = Some("stale".to_string());
+        *client.refresh_token.lock().await = Some("refresh-token".to_string());
+        client
+    }
+
+    #[tokio::test]
+    async fn overlapping_polls_share_one_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/queue"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"currently_playing": null, "queue": []}"#)
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let (first, second) = tokio::join!(client.get_queue(), client.get_queue());
+        assert!(first.unwrap().is_some());
+        assert!(second.unwrap().is_some());
+
+        // Once it's answered, the next poll is a request of its own
+        client.get_queue().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unchanged_listings_are_read_from_the_last_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/following"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/following"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_string(r#"{"artists": {"items": [{"id": "a", "name": "Artist"}]}}"#),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let first = client.get_followed_artists().await.unwrap();
+        let second = client.get_followed_artists().await.unwrap();
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(second[0].name, "Artist");
+    }
+
+    #[tokio::test]
+    async fn only_library_listings_are_kept_for_conditional_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/queue"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_string(r#"{"currently_playing": null, "queue": []}"#),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        client.get_queue().await.unwrap();
+        client.get_queue().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| !request.headers.contains_key("If-None-Match")));
+    }
+
+    #[test]
+    fn the_least_recently_used_response_is_dropped_when_full() {
+        let mut cache = EtagCache::default();
+        for page in 0..ETAG_CACHE_SIZE {
+            cache.insert(&page.to_string(), "etag".to_string(), String::new());
+        }
+        assert!(cache.get("0").is_some());
+
+        cache.insert("new", "etag".to_string(), String::new());
+
+        assert!(cache.get("0").is_some());
+        assert!(cache.get("1").is_none());
+        assert_eq!(cache.entries.len(), ETAG_CACHE_SIZE);
+    }
+
+    #[tokio::test]
+    async fn listings_follow_next_links() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .and(query_param("offset", "2"))
+            .respond_with(fixture(&server, "playlists_page2.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .and(query_param("limit", "50"))
+            .respond_with(fixture(&server, "playlists_page1.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let playlists = client(&server).await.get_playlists().await.unwrap();
+
+        let names: Vec<&str> = playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Commute", "Discover Weekly", "Road Trip"]);
+        assert_eq!(playlists[1].owner.as_ref().unwrap().id, "spotify");
+        assert_eq!(playlists[0].tracks.total, 42);
+    }
+
+    #[tokio::test]
+    async fn followed_artists_follow_the_cursor() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/following"))
+            .and(query_param("after", "a1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"artists": {"items": [{"id": "a2", "name": "Second"}], "next": null}}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/following"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                r#"{{"artists": {{"items": [{{"id": "a1", "name": "First"}}], "next": "{}/v1/me/following?type=artist&after=a1&limit=50"}}}}"#,
+                server.uri()
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let artists = client(&server).await.get_followed_artists().await.unwrap();
+
+        let names: Vec<&str> = artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["First", "Second"]);
+    }
+
+    #[tokio::test]
+    async fn liked_songs_keep_the_date_they_were_added() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/tracks"))
+            .respond_with(fixture(&server, "liked_tracks.json"))
+            .mount(&server)
+            .await;
+
+        let tracks = client(&server)
+            .await
+            .get_playlist_tracks("liked")
+            .await
+            .unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].name, "Life on Mars? - 2015 Remaster");
+        assert_eq!(tracks[0].album.name, "Hunky Dory");
+        assert_eq!(tracks[0].added_at.as_deref(), Some("2024-06-03T08:12:45Z"));
+    }
+
+    #[tokio::test]
+    async fn track_pages_start_at_the_offset() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/tracks"))
+            .and(query_param("offset", "50"))
+            .respond_with(fixture(&server, "liked_tracks.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let page = client(&server)
+            .await
+            .get_playlist_tracks_page("liked", 50)
+            .await
+            .unwrap();
+
+        assert_eq!(page.tracks.len(), 1);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.next, None);
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_refreshed_and_the_request_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/devices"))
+            .and(header("authorization", "Bearer stale"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/token"))
+            .and(body_string_contains("refresh_token=refresh-token"))
+            .respond_with(fixture(&server, "token_refresh.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/devices"))
+            .and(header("authorization", "Bearer fresh-access-token"))
+            .respond_with(fixture(&server, "devices.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client(&server).await;
+        let devices = client.get_devices().await.unwrap();
+
+        assert_eq!(devices[0].name, "Living Room");
+        let log: Vec<_> = client
+            .recent_requests()
+            .into_iter()
+            .map(|r| (r.path, r.status, r.retry))
+            .collect();
+        assert_eq!(
+            log,
+            [
+                ("/me/player/devices".to_string(), Some(200), 1),
+                ("/me/player/devices".to_string(), Some(401), 0)
+            ]
+        );
+        // The response didn't rotate the refresh token, so the old one still works
+        assert_eq!(
+            client.refresh_token.lock().await.as_deref(),
+            Some("refresh-token")
+        );
+    }
+
+    #[tokio::test]
+    async fn token_is_only_refreshed_once_per_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/token"))
+            .respond_with(fixture(&server, "token_refresh.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .await
+            .get_currently_playing()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(SpotifyError::Auth(_))));
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_asks_to_sign_in_again() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_grant"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .await
+            .get_currently_playing()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(SpotifyError::Auth(_))));
+    }
+
+    #[tokio::test]
+    async fn unauthorized_without_a_refresh_token_asks_to_sign_in_again() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        *client.refresh_token.lock().await = None;
+
+        let error = client.get_playlists().await.unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(SpotifyError::Auth(_))));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_reports_how_long_to_wait() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/me/player/queue"))
+            .and(query_param("uri", "spotify:track:7gy6MM0zbVlRrHhmmbAMjR"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "7"))
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .await
+            .add_to_queue("spotify:track:7gy6MM0zbVlRrHhmmbAMjR")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SpotifyError::RateLimited { retry_after }) if *retry_after == Duration::from_secs(7)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_applies_to_every_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+
+        let error = client(&server).await.get_playlists().await.unwrap_err();
+
+        // Without a Retry-After header, a short wait is assumed
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SpotifyError::RateLimited { retry_after }) if *retry_after == Duration::from_secs(1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_reads_are_sent_again() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/playlists"))
+            .respond_with(fixture(&server, "playlists_page2.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        client.get_playlists().await.unwrap();
+
+        let retries: Vec<_> = client
+            .recent_requests()
+            .into_iter()
+            .map(|r| (r.status, r.retry))
+            .collect();
+        assert_eq!(retries, [(Some(200), 1), (Some(429), 0)]);
+    }
+
+    #[tokio::test]
+    async fn player_failures_are_explained() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player/pause"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/me/player/next"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/me/player/previous"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player/play"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let error = client.pause_playback().await.unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(SpotifyError::NoDevice)));
+        let error = client.next_track().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SpotifyError::PremiumRequired(_))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Spotify Premium is required for playback control."
+        );
+        let error = client.previous_track().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to skip to previous track: 500 Internal Server Error"
+        );
+        // Spotify being down is told apart, for going offline
+        let error = client.resume_playback().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SpotifyError::Network(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn slow_playback_commands_time_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player/pause"))
+            .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+        let client = client(&server)
+            .await
+            .with_playback_timeout(Some(Duration::from_millis(100)));
+
+        let error = client.pause_playback().await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SpotifyError::Network(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn playing_without_devices_fails_before_sending_the_tracks() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/devices"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"devices":[]}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player/play"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .await
+            .play_tracks(&["spotify:track:7gy6MM0zbVlRrHhmmbAMjR"])
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(SpotifyError::NoDevice)));
+    }
+
+    #[tokio::test]
+    async fn picked_device_receives_playback() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player/devices"))
+            .respond_with(fixture(&server, "devices.json"))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/me/player/play"))
+            .and(query_param("device_id", "c1b3bd3d1a8e4d6fa1f2a0b1c2d3e4f5"))
+            .and(body_string_contains("spotify:track:7gy6MM0zbVlRrHhmmbAMjR"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        client
+            .set_target_device("c1b3bd3d1a8e4d6fa1f2a0b1c2d3e4f5")
+            .await
+            .unwrap();
+        client
+            .play_tracks(&["spotify:track:7gy6MM0zbVlRrHhmmbAMjR"])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn player_state_is_read() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player"))
+            .respond_with(fixture(&server, "player.json"))
+            .mount(&server)
+            .await;
+
+        let playing = client(&server)
+            .await
+            .get_currently_playing()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(playing.is_playing);
+        assert!(playing.shuffle_state);
+        assert_eq!(playing.repeat_state, "context");
+        assert_eq!(playing.progress_ms, Some(61234));
+        assert_eq!(playing.device.unwrap().name, "Living Room");
+    }
+
+    #[tokio::test]
+    async fn nothing_playing_is_not_an_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/me/player"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+
+        let playing = client(&server).await.get_currently_playing().await.unwrap();
+
+        assert!(playing.is_none());
+    }
+}
@@ -0,0 +1,284 @@
+//! The parts of the app's state that don't depend on how it's drawn: whether it's signed in,
+//! whether Spotify can be reached, and the queue additions and merges sent a request at a time.
+//! What's selected, scrolled or still loading stays with the panes in the binary
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::spotify::Track;
+
+/// How long after going offline signing in is first retried, the wait doubling after each
+/// failed attempt up to MAX_RECONNECT_INTERVAL
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(120);
+/// Player polls in a row that can't reach Spotify before going offline, one can be a blip
+const OFFLINE_AFTER_FAILED_POLLS: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub enum AppState {
+    Authenticating,
+    Ready,
+    Error(String),
+}
+
+/// Whether Spotify can be reached, and when signing in is next retried if it can't
+#[derive(Debug)]
+pub struct Connection {
+    /// Set while Spotify can't be reached, leaving only the cached library to browse
+    offline: bool,
+    reconnecting: bool,
+    /// When signing in is next retried while offline, and how many tries have failed so far
+    reconnect_at: Instant,
+    reconnect_attempts: u32,
+    /// Player polls in a row that couldn't reach Spotify
+    failed_polls: u32,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            reconnecting: false,
+            reconnect_at: Instant::now(),
+            reconnect_attempts: 0,
+            failed_polls: 0,
+        }
+    }
+}
+
+impl Connection {
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnecting
+    }
+
+    /// Starts the wait before the first reconnect, returning false if it was offline already
+    pub fn go_offline(&mut self) -> bool {
+        if self.offline {
+            return false;
+        }
+        self.offline = true;
+        self.reconnect_attempts = 0;
+        self.reconnect_at = Instant::now() + RECONNECT_INTERVAL;
+        true
+    }
+
+    /// How long until signing in is retried while offline, None while it's being tried
+    pub fn reconnect_in(&self) -> Option<Duration> {
+        (self.offline && !self.reconnecting)
+            .then(|| self.reconnect_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether it's offline and time to try signing in again, marking the try as started
+    pub fn start_reconnect(&mut self) -> bool {
+        if !self.offline || self.reconnecting || Instant::now() < self.reconnect_at {
+            return false;
+        }
+        self.reconnecting = true;
+        true
+    }
+
+    /// Marks a reconnect as started straight away, whether or not it was due
+    pub fn reconnect_now(&mut self) {
+        self.reconnecting = true;
+    }
+
+    /// Back online after signing in again
+    pub fn reconnected(&mut self) {
+        self.reconnecting = false;
+        self.offline = false;
+        self.failed_polls = 0;
+    }
+
+    /// Still unreachable, so the next try waits longer. Returns the wait
+    pub fn reconnect_failed(&mut self) -> Duration {
+        self.reconnecting = false;
+        self.reconnect_attempts += 1;
+        let wait = RECONNECT_INTERVAL
+            .saturating_mul(1 << self.reconnect_attempts.min(8))
+            .min(MAX_RECONNECT_INTERVAL);
+        self.reconnect_at = Instant::now() + wait;
+        wait
+    }
+
+    /// Reachable but signed out, which waiting to reconnect can't fix
+    pub fn signed_out(&mut self) {
+        self.reconnecting = false;
+        self.offline = false;
+    }
+
+    /// A player poll got through, or found nothing changed
+    pub fn poll_succeeded(&mut self) {
+        self.failed_polls = 0;
+    }
+
+    /// A player poll couldn't reach Spotify. Returns whether enough have in a row to go offline
+    pub fn poll_failed(&mut self) -> bool {
+        self.failed_polls += 1;
+        self.failed_polls >= OFFLINE_AFTER_FAILED_POLLS
+    }
+}
+
+/// Something done while offline, carried out once Spotify can be reached again
+#[derive(Debug)]
+pub enum OfflineAction {
+    /// Track ids to add to Liked Songs
+    Like(Vec<String>),
+    Queue(Vec<Track>),
+}
+
+/// Tracks being added to the queue one request per tick, so the UI can show progress
+#[derive(Debug, Default)]
+pub struct QueueBatch {
+    pending: VecDeque<Track>,
+    pub total: usize,
+    /// Set while waiting out Spotify's rate limit
+    resume_at: Option<Instant>,
+    /// Set while a track's request is out, tracks are added one at a time to keep their order
+    sending: bool,
+}
+
+impl QueueBatch {
+    pub fn add(&mut self, tracks: Vec<Track>) {
+        self.total += tracks.len();
+        self.pending.extend(tracks);
+    }
+
+    /// The next track to send, unless one is out already or it's waiting out the rate limit
+    pub fn take_next(&mut self) -> Option<Track> {
+        if self.sending || self.paused_for().is_some() {
+            return None;
+        }
+        let track = self.pending.pop_front()?;
+        self.sending = true;
+        Some(track)
+    }
+
+    /// The last track sent has been answered, whether or not it was added
+    pub fn answered(&mut self) {
+        self.sending = false;
+    }
+
+    /// Puts a rate limited track back to be sent again once `retry_after` has passed
+    pub fn retry(&mut self, track: Track, retry_after: Duration) {
+        self.pending.push_front(track);
+        self.resume_at = Some(Instant::now() + retry_after);
+    }
+
+    pub fn pending(&self) -> &VecDeque<Track> {
+        &self.pending
+    }
+
+    /// The tracks not sent yet, for when the batch can't carry on
+    pub fn into_pending(self) -> Vec<Track> {
+        self.pending.into()
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.sending
+    }
+
+    pub fn done(&self) -> usize {
+        self.total - self.pending.len()
+    }
+
+    /// How long until the batch carries on after being rate limited
+    pub fn paused_for(&self) -> Option<Duration> {
+        self.resume_at
+            .map(|resume_at| resume_at.saturating_duration_since(Instant::now()))
+            .filter(|wait| !wait.is_zero())
+    }
+}
+
+/// A playlist's tracks being added to another, a request per tick so the UI can show progress
+#[derive(Debug)]
+pub struct MergeBatch {
+    pub playlist_id: String,
+    pub playlist_name: String,
+    pending: VecDeque<String>,
+    /// URIs added so far, kept so the merge can be undone
+    pub added: Vec<String>,
+    pub total: usize,
+    /// Set while a chunk's request is out
+    sending: bool,
+}
+
+impl MergeBatch {
+    pub fn new(playlist_id: String, playlist_name: String, uris: Vec<String>) -> Self {
+        Self {
+            playlist_id,
+            playlist_name,
+            total: uris.len(),
+            pending: uris.into(),
+            added: Vec::new(),
+            sending: false,
+        }
+    }
+
+    /// The next `size` URIs to send, unless a chunk is out already
+    pub fn next_chunk(&mut self, size: usize) -> Option<Vec<String>> {
+        if self.sending || self.pending.is_empty() {
+            return None;
+        }
+        let count = self.pending.len().min(size);
+        self.sending = true;
+        Some(self.pending.drain(..count).collect())
+    }
+
+    /// The last chunk sent has been answered, `added` if it worked
+    pub fn answered(&mut self, chunk: Vec<String>, added: bool) {
+        self.sending = false;
+        if added {
+            self.added.extend(chunk);
+        }
+    }
+
+    pub fn pending(&self) -> &VecDeque<String> {
+        &self.pending
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty() && !self.sending
+    }
+
+    pub fn done(&self) -> usize {
+        self.added.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_waits_longer_after_each_failure() {
+        let mut connection = Connection::default();
+        assert!(connection.go_offline());
+        assert!(!connection.go_offline());
+        assert!(!connection.start_reconnect());
+
+        let waits: Vec<Duration> = (0..6)
+            .map(|_| {
+                connection.reconnect_now();
+                connection.reconnect_failed()
+            })
+            .collect();
+        assert_eq!(waits, [10, 20, 40, 80, 120, 120].map(Duration::from_secs));
+
+        connection.reconnected();
+        assert!(!connection.is_offline());
+        assert_eq!(connection.reconnect_in(), None);
+    }
+
+    #[test]
+    fn polls_go_offline_after_failing_in_a_row() {
+        let mut connection = Connection::default();
+        assert!(!connection.poll_failed());
+        connection.poll_succeeded();
+        assert!(!connection.poll_failed());
+        assert!(connection.poll_failed());
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use std::time::Instant;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::api::SpotifyApi;
+use crate::spotify::{CurrentlyPlaying, Device, Queue};
+
+/// Requests the UI hands to the worker task, so drawing never waits on Spotify
+#[derive(Debug)]
+pub enum Command {
+    /// Fetches the player state, and the queue along with it when `queue` is set and the
+    /// track has changed
+    RefreshPlayer {
+        queue: bool,
+    },
+    /// Fetches the queue, after something was added to it
+    RefreshQueue,
+    /// Starts playing the tracks with these URIs
+    Play(Vec<String>),
+    /// Starts playing an album, playlist, artist or show by its URI
+    PlayContext(String),
+    Pause,
+    Resume,
+    Next,
+    Previous,
+    /// Jumps to a position in the playing track, in milliseconds
+    Seek(u64),
+    /// Sets the playing device's volume, in percent
+    SetVolume(u32),
+    ListDevices,
+    TransferPlayback {
+        device_id: String,
+        name: String,
+    },
+}
+
+/// What the worker sends back as the requests for a command finish
+#[derive(Debug)]
+pub enum WorkerEvent {
+    Player(Result<Option<CurrentlyPlaying>>),
+    /// A poll found the player where the last one sent left it
+    PlayerUnchanged,
+    Queue(Result<Option<Queue>>),
+    /// A playback command failed, successful ones are followed by a Player event instead
+    PlaybackFailed(anyhow::Error),
+    Devices(Result<Vec<Device>>),
+    Transferred {
+        name: String,
+        result: Result<()>,
+    },
+}
+
+/// Starts the worker task and returns the channels to talk to it. Commands run one after
+/// another, so playback changes reach Spotify in the order they were asked for
+pub fn spawn(
+    client: impl SpotifyApi,
+) -> (UnboundedSender<Command>, UnboundedReceiver<WorkerEvent>) {
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel();
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut polls = Polls::default();
+        while let Some(command) = command_rx.recv().await {
+            tracing::debug!(?command, "Worker command");
+            for event in run(&client, &mut polls, command).await {
+                // The receiver only goes away when the app is shutting down
+                if event_tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    (command_tx, event_rx)
+}
+
+/// How far the player can be from where the last state sent puts it by now before it counts as
+/// seeked, polls take a moment to come back
+const SEEK_TOLERANCE_MS: u64 = 2000;
+
+/// What polls of the player are compared against
+#[derive(Default)]
+struct Polls {
+    /// The last player state sent to the app, and when it was fetched
+    last: Option<(Option<CurrentlyPlaying>, Instant)>,
+    /// Set once the queue has been fetched for the track playing
+    queue_fetched: bool,
+}
+
+impl Polls {
+    /// Whether `player` is anything but the last state sent carrying on as it was
+    fn changed(&self, player: &Option<CurrentlyPlaying>) -> bool {
+        let Some((last, at)) = &self.last else {
+            return true;
+        };
+        let (Some(last), Some(player)) = (last, player) else {
+            return last.is_some() != player.is_some();
+        };
+        let device = |playing: &CurrentlyPlaying| {
+            playing
+                .device
+                .as_ref()
+                .map(|device| (device.id.clone(), device.volume_percent))
+        };
+        let mut expected = last.progress_ms.unwrap_or(0);
+        if last.is_playing {
+            expected += at.elapsed().as_millis() as u64;
+        }
+        track_uri(last) != track_uri(player)
+            || last.is_playing != player.is_playing
+            || device(last) != device(player)
+            || last.shuffle_state != player.shuffle_state
+            || last.repeat_state != player.repeat_state
+            || player.progress_ms.unwrap_or(0).abs_diff(expected) > SEEK_TOLERANCE_MS
+    }
+
+    /// Fetches the player state, sending it on only when it's changed since the last one sent
+    /// unless `always` is set. The queue comes along when `queue` is set and it hasn't been
+    /// fetched since the track changed
+    async fn poll(
+        &mut self,
+        client: &impl SpotifyApi,
+        queue: bool,
+        always: bool,
+    ) -> Vec<WorkerEvent> {
+        let at = Instant::now();
+        let player = match client.get_currently_playing().await {
+            Ok(player) => player,
+            Err(e) => return vec![WorkerEvent::Player(Err(e))],
+        };
+
+        let mut events = Vec::new();
+        if always || self.changed(&player) {
+            let last_track = self
+                .last
+                .as_ref()
+                .and_then(|(last, _)| last.as_ref().and_then(track_uri));
+            if last_track != player.as_ref().and_then(track_uri) {
+                self.queue_fetched = false;
+            }
+            self.last = Some((player.clone(), at));
+            events.push(WorkerEvent::Player(Ok(player)));
+        } else {
+            events.push(WorkerEvent::PlayerUnchanged);
+        }
+        if queue && !self.queue_fetched {
+            self.queue_fetched = true;
+            events.push(WorkerEvent::Queue(client.get_queue().await));
+        }
+        events
+    }
+}
+
+fn track_uri(playing: &CurrentlyPlaying) -> Option<&str> {
+    playing.item.as_ref().map(|track| track.uri.as_str())
+}
+
+async fn run(client: &impl SpotifyApi, polls: &mut Polls, command: Command) -> Vec<WorkerEvent> {
+    let playback = match command {
+        Command::RefreshPlayer { queue } => return polls.poll(client, queue, false).await,
+        Command::RefreshQueue => return vec![WorkerEvent::Queue(client.get_queue().await)],
+        Command::ListDevices => return vec![WorkerEvent::Devices(client.get_devices().await)],
+        Command::TransferPlayback { device_id, name } => {
+            let result = client.set_target_device(&device_id).await;
+            let succeeded = result.is_ok();
+            let mut events = vec![WorkerEvent::Transferred { name, result }];
+            if succeeded {
+                events.extend(polls.poll(client, false, true).await);
+            }
+            return events;
+        }
+        Command::Play(uris) => {
+            let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+            client.play_tracks(&uris).await
+        }
+        Command::PlayContext(uri) => client.play_context(&uri).await,
+        Command::Pause => client.pause_playback().await,
+        Command::Resume => client.resume_playback().await,
+        Command::Next => client.next_track().await,
+        Command::Previous => client.previous_track().await,
+        Command::Seek(position_ms) => client.seek(position_ms).await,
+        Command::SetVolume(volume_percent) => client.set_volume(volume_percent).await,
+    };
+
+    match playback {
+        // Show the change right away rather than at the next poll
+        Ok(_) => polls.poll(client, false, true).await,
+        Err(e) => vec![WorkerEvent::PlaybackFailed(e)],
+    }
+}
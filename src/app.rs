@@ -1,85 +1,1116 @@
-use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
-use ratatui::{widgets::ListState, Terminal};
-use std::time::Duration;
+use anyhow::{anyhow, Result};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::{
+    layout::{Position, Rect},
+    widgets::{ListState, TableState},
+    Terminal,
+};
+use spotitui_core::api::SpotifyApi;
+use spotitui_core::library::{self, describe_count, merge_uris, Undoable};
+use spotitui_core::spotify::{
+    Album, Artist, AudioFeatures, CurrentlyPlaying, Device, Playlist, Queue, Seeds, Show,
+    SpotifyClient, SpotifyError, Track, TrackPage,
+};
+use spotitui_core::state::{AppState, Connection, MergeBatch, OfflineAction, QueueBatch};
+use spotitui_core::worker::{self, Command, WorkerEvent};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 
-use crate::spotify::{CurrentlyPlaying, Playlist, Queue, SpotifyClient, Track};
+use crate::cache::{self, LibraryCache};
+use crate::cli;
+use crate::clipboard::{self, Copied};
+use crate::config::{Config, ExplicitMode};
+use crate::export::{self, Backup};
+use crate::fuzzy;
+use crate::hooks::{self, Hooks};
+use crate::import::{self, Entry};
+use crate::input::TextInput;
+use crate::keymap::{Action, Context, Keymap, Remaps, SEEK_STEP_MS, VOLUME_STEP};
+use crate::launcher;
+#[cfg(feature = "librespot")]
+use crate::librespot::LocalDevice;
+use crate::notifications::Notifications;
+use crate::scripting::{CommandContext, ScriptAction, Scripts};
+use crate::shuffle;
+use crate::shutdown::Shutdown;
+use crate::state::{self, LocalState, OpenView, PlaylistSort, Session};
+use crate::stats::PlaylistStats;
+use crate::theme::Theme;
 use crate::ui;
 
+const RESIZE_STEP: u16 = 5;
+const PLAYBACK_CONTROLS_COUNT: usize = 4;
+const SCROLL_STEP: usize = 3;
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// Most tracks sent with "Play from here", to keep the request body reasonable
+const PLAY_FROM_HERE_LIMIT: usize = 100;
+/// Most actions kept for undo, older ones are forgotten
+const UNDO_LIMIT: usize = 20;
+/// Largest count that can be typed before a motion
+const MAX_COUNT: usize = 9999;
+/// How long type-ahead find waits for the next letter before keys go back to their usual jobs
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Tracks added per request while merging playlists, the most Spotify takes at once
+const MERGE_CHUNK: usize = 100;
+/// Search results offered for each entry of an imported file that needs a pick
+const IMPORT_CANDIDATES: usize = 5;
+/// Most tracks listed in Recently Added
+const RECENTLY_ADDED_LIMIT: usize = 500;
+/// How close the cursor gets to the end of a partly loaded list before the next page is fetched
+const LOAD_MORE_MARGIN: usize = 20;
+/// Recommendations asked for each time a radio starts or is topped up
+const RADIO_BATCH: usize = 20;
+/// Tracks left to play before a radio is topped up
+const RADIO_TOP_UP_MARGIN: usize = 3;
+/// Most genres a radio can be seeded with, the most seeds Spotify takes
+const MAX_GENRES: usize = 5;
+/// Times the device list is checked for librespot, half a second apart
+#[cfg(feature = "librespot")]
+const LOCAL_DEVICE_ATTEMPTS: usize = 20;
+/// How long the volume keys wait for another press before setting the volume
+const VOLUME_DEBOUNCE: Duration = Duration::from_millis(300);
+/// How many times over its step a volume key goes when pressed in quick succession, each
+/// press within VOLUME_DEBOUNCE of the last adding another step
+const MAX_VOLUME_ACCELERATION: i32 = 3;
+/// Names of the playlists Spotify makes for each listener, listed under For You in this order.
+/// Daily Mixes are numbered, so names are matched by prefix
+const FOR_YOU_PLAYLISTS: [&str; 7] = [
+    "Discover Weekly",
+    "Release Radar",
+    "Daily Mix",
+    "On Repeat",
+    "Repeat Rewind",
+    "Your Time Capsule",
+    "daylist",
+];
+/// The For You playlists looked up by search when they aren't in the library, every Daily Mix
+/// that turns up for the one search
+const FOR_YOU_SEARCHED: [&str; 4] = [
+    "Discover Weekly",
+    "Release Radar",
+    "Daily Mix",
+    "Your Time Capsule",
+];
+
 #[derive(Debug, Clone, Copy)]
 pub enum FocusedPane {
-    Playlists,
+    Library,
     Tracks,
     SearchInput,
+    /// The second tracks pane of the split view
+    Split,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortColumn {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    DateAdded,
+    /// Beats per minute, from the tracks' audio features
+    Tempo,
+}
+
+impl SortColumn {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortColumn::Title => "Title",
+            SortColumn::Artist => "Artist",
+            SortColumn::Album => "Album",
+            SortColumn::Duration => "Duration",
+            SortColumn::DateAdded => "Date Added",
+            SortColumn::Tempo => "Tempo",
+        }
+    }
+
+    /// The next column in the sort cycle, or None to go back to playlist order
+    fn next(self) -> Option<SortColumn> {
+        match self {
+            SortColumn::Title => Some(SortColumn::Artist),
+            SortColumn::Artist => Some(SortColumn::Album),
+            SortColumn::Album => Some(SortColumn::Duration),
+            SortColumn::Duration => Some(SortColumn::DateAdded),
+            SortColumn::DateAdded => Some(SortColumn::Tempo),
+            SortColumn::Tempo => None,
+        }
+    }
+
+    fn compare(&self, a: &Track, b: &Track, tempos: &HashMap<String, f32>) -> Ordering {
+        match self {
+            SortColumn::Title => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortColumn::Artist => {
+                let first_artist = |t: &Track| {
+                    t.artists
+                        .first()
+                        .map(|artist| artist.name.to_lowercase())
+                        .unwrap_or_default()
+                };
+                first_artist(a).cmp(&first_artist(b))
+            }
+            SortColumn::Album => a
+                .album
+                .name
+                .to_lowercase()
+                .cmp(&b.album.name.to_lowercase()),
+            SortColumn::Duration => a.duration_ms.cmp(&b.duration_ms),
+            // added_at is an ISO 8601 timestamp, so it sorts lexicographically
+            SortColumn::DateAdded => a.added_at.cmp(&b.added_at),
+            // Tracks without a tempo yet go after the rest
+            SortColumn::Tempo => match (tempos.get(&a.id), tempos.get(&b.id)) {
+                (Some(a), Some(b)) => a.total_cmp(b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            },
+        }
+    }
+}
+
+/// The collections the library pane can switch between
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryTab {
+    Playlists,
+    /// The playlists Spotify makes for the listener, found in Playlists or by search
+    ForYou,
+    Albums,
+    Artists,
+    Podcasts,
+    Liked,
+}
+
+impl LibraryTab {
+    pub const ALL: [LibraryTab; 6] = [
+        LibraryTab::Playlists,
+        LibraryTab::ForYou,
+        LibraryTab::Albums,
+        LibraryTab::Artists,
+        LibraryTab::Podcasts,
+        LibraryTab::Liked,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LibraryTab::Playlists => "Playlists",
+            LibraryTab::ForYou => "For You",
+            LibraryTab::Albums => "Albums",
+            LibraryTab::Artists => "Artists",
+            LibraryTab::Podcasts => "Podcasts",
+            LibraryTab::Liked => "Liked",
+        }
+    }
+
+    /// Label for narrow library columns
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            LibraryTab::Playlists => "Lists",
+            LibraryTab::ForYou => "Mixes",
+            LibraryTab::Podcasts => "Shows",
+            _ => self.label(),
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    fn cycle(self, forward: bool) -> LibraryTab {
+        let len = Self::ALL.len();
+        let index = if forward {
+            (self.index() + 1) % len
+        } else {
+            (self.index() + len - 1) % len
+        };
+        Self::ALL[index]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterTarget {
+    Library,
+    Tracks,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrackSort {
+    pub column: SortColumn,
+    pub ascending: bool,
+}
+
+/// A cursor movement within a list pane
+#[derive(Debug, Clone, Copy)]
+pub enum Motion {
+    Up(usize),
+    Down(usize),
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    /// A row by position, for a count before G
+    To(usize),
+}
+
+impl Motion {
+    /// The new selection for a list of `len` rows showing `viewport` rows at a time
+    fn apply(self, selected: Option<usize>, len: usize, viewport: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+
+        let last = len - 1;
+        let half_page = (viewport / 2).max(1);
+        let Some(selected) = selected else {
+            // Nothing selected yet, so any movement lands on the first row
+            return Some(0);
+        };
+
+        let position = match self {
+            Motion::Up(n) => selected.saturating_sub(n),
+            Motion::Down(n) => selected.saturating_add(n),
+            Motion::HalfPageUp => selected.saturating_sub(half_page),
+            Motion::HalfPageDown => selected.saturating_add(half_page),
+            Motion::PageUp => selected.saturating_sub(viewport.max(1)),
+            Motion::PageDown => selected.saturating_add(viewport.max(1)),
+            Motion::Top => 0,
+            Motion::Bottom => last,
+            Motion::To(position) => position,
+        };
+        Some(position.min(last))
+    }
+}
+
+/// Where the tracks in the tracks pane were loaded from
+#[derive(Debug, Clone)]
+pub enum TrackSource {
+    /// The playlist with this id. Playlists are kept by id rather than position, the library
+    /// can be fetched again in another order while one is open
+    Playlist(String),
+    Album(Album),
+    /// The artist's top tracks
+    Artist(Artist),
+    /// A podcast's latest episodes
+    Show(Show),
+    Liked,
+    /// Liked Songs and the playlists fetched so far, newest addition first
+    RecentlyAdded,
+}
+
+impl TrackSource {
+    /// Label for the breadcrumbs above the tracks pane, `root` for the view opened from the
+    /// library, which the tab name already describes
+    pub fn breadcrumb(&self, playlists: &[Playlist], root: bool) -> String {
+        let (kind, name) = match self {
+            TrackSource::Playlist(id) => (
+                "Playlist",
+                playlists
+                    .iter()
+                    .find(|playlist| playlist.id == *id)
+                    .map(|playlist| playlist.name.as_str())
+                    .unwrap_or_default(),
+            ),
+            TrackSource::Album(album) => ("Album", album.name.as_str()),
+            TrackSource::Artist(artist) => ("Artist", artist.name.as_str()),
+            TrackSource::Show(show) => ("Podcast", show.name.as_str()),
+            TrackSource::Liked => return "Liked Songs".to_string(),
+            TrackSource::RecentlyAdded => return "Recently Added".to_string(),
+        };
+        if root {
+            name.to_string()
+        } else {
+            format!("{}: {}", kind, name)
+        }
+    }
+
+    /// The library tab this kind of view is opened from
+    pub fn library_tab(&self) -> LibraryTab {
+        match self {
+            TrackSource::Playlist(_) => LibraryTab::Playlists,
+            TrackSource::Album(_) => LibraryTab::Albums,
+            TrackSource::Artist(_) => LibraryTab::Artists,
+            TrackSource::Show(_) => LibraryTab::Podcasts,
+            TrackSource::Liked | TrackSource::RecentlyAdded => LibraryTab::Liked,
+        }
+    }
+}
+
+/// What the letter typed after m or ' does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkAction {
+    Set,
+    Jump,
+}
+
+/// Letters typed after f, jumping to the first entry that starts with them
+#[derive(Debug, Clone)]
+pub struct TypeAhead {
+    pub text: String,
+    last_key: Instant,
+}
+
+impl TypeAhead {
+    pub fn expired(&self) -> bool {
+        self.last_key.elapsed() >= TYPE_AHEAD_TIMEOUT
+    }
+}
+
+/// A view the tracks pane showed before going to an album or artist, so Backspace can return
+#[derive(Debug, Clone)]
+pub struct NavEntry {
+    pub source: TrackSource,
+    selected: Option<usize>,
+}
+
+/// Actions in the per-track context menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackMenuItem {
+    Play,
+    PlayFromHere,
+    AddToQueue,
+    QueueRestOfAlbum,
+    Radio,
+    ArtistRadio,
+    AddToPlaylist,
+    RemoveFromPlaylist,
+    Like,
+    GoToAlbum,
+    GoToArtist,
+    CopyLink,
+}
+
+impl TrackMenuItem {
+    pub const ALL: [TrackMenuItem; 12] = [
+        TrackMenuItem::Play,
+        TrackMenuItem::PlayFromHere,
+        TrackMenuItem::AddToQueue,
+        TrackMenuItem::QueueRestOfAlbum,
+        TrackMenuItem::Radio,
+        TrackMenuItem::ArtistRadio,
+        TrackMenuItem::AddToPlaylist,
+        TrackMenuItem::RemoveFromPlaylist,
+        TrackMenuItem::Like,
+        TrackMenuItem::GoToAlbum,
+        TrackMenuItem::GoToArtist,
+        TrackMenuItem::CopyLink,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrackMenuItem::Play => "▶ Play",
+            TrackMenuItem::PlayFromHere => "▶ Play from here",
+            TrackMenuItem::AddToQueue => "+ Add to queue",
+            TrackMenuItem::QueueRestOfAlbum => "+ Queue rest of album",
+            TrackMenuItem::Radio => "◎ Start radio",
+            TrackMenuItem::ArtistRadio => "◎ Start artist radio",
+            TrackMenuItem::AddToPlaylist => "+ Add to playlist...",
+            TrackMenuItem::RemoveFromPlaylist => "✕ Remove from playlist",
+            TrackMenuItem::Like => "♥ Like",
+            TrackMenuItem::GoToAlbum => "→ Go to album",
+            TrackMenuItem::GoToArtist => "→ Go to artist",
+            TrackMenuItem::CopyLink => "⧉ Copy link",
+        }
+    }
+}
+
+/// Actions that can't be taken back, run once the user confirms them
+#[derive(Debug, Clone)]
+pub enum Destructive {
+    /// Removes the tracks from the playlist with this id. Playlists are kept by id rather than
+    /// position, the library can be fetched again in another order while the popup is open
+    RemoveTracks {
+        playlist_id: String,
+        tracks: Vec<Track>,
+    },
+    /// Unfollows the playlist with this id, which deletes it if the user owns it
+    DeletePlaylist(String),
+}
+
+/// What picking a playlist in the playlist picker does
+#[derive(Debug, Clone)]
+pub enum PickerPurpose {
+    /// Adds the tracks with these URIs to the picked playlist
+    AddTracks(Vec<String>),
+    /// Opens the picked playlist in the split view
+    SplitView,
+    /// Merges the source playlist's tracks into the picked one
+    Merge {
+        source_id: String,
+        source_name: String,
+        skip_duplicates: bool,
+    },
+    /// Compares the source playlist with the picked one
+    Compare {
+        source_id: String,
+        source_name: String,
+    },
+}
+
+/// A second tracks pane next to the main one, showing the playlist tracks are copied to
+#[derive(Debug)]
+pub struct SplitView {
+    pub playlist_id: String,
+    pub playlist_name: String,
+    /// None while the playlist's tracks are being fetched
+    pub tracks: Option<Vec<Track>>,
+    pub state: TableState,
+    /// Visible rows, updated on every draw
+    pub viewport: usize,
+}
+
+impl SplitView {
+    fn selected_track(&self) -> Option<&Track> {
+        self.tracks.as_ref()?.get(self.state.selected()?)
+    }
 }
 
+/// A destructive action waiting on a yes or no from the confirmation popup
 #[derive(Debug, Clone)]
-pub enum AppState {
-    Authenticating,
-    Loading,
-    Ready,
-    Error(String),
+pub struct Confirmation {
+    pub message: String,
+    action: Destructive,
+}
+
+/// What else to bring up to date once tracks have been added to a playlist
+#[derive(Debug)]
+enum AfterAdding {
+    Nothing,
+    /// Copied from the tracks pane, to show in the split view
+    SplitView(Vec<Track>),
+    /// Copied from the split view, to show in the tracks pane
+    TracksPane(Vec<Track>),
+    /// Copied over from one side of the comparison
+    Comparison {
+        left_to_right: bool,
+    },
+}
+
+/// Recommendations grown from a few seeds, topped up as they're played through
+#[derive(Debug)]
+pub struct Radio {
+    /// What it was started from, like a track's name
+    pub name: String,
+    seeds: Seeds,
+    /// URIs of every track handed to Spotify so far, in the order they were
+    uris: Vec<String>,
+    /// Set once one of its tracks has played, until then others are what played before it
+    started: bool,
+    topping_up: bool,
+}
+
+/// Which tracks two playlists share and which only one of them has, for syncing them
+#[derive(Debug)]
+pub struct Comparison {
+    left_id: String,
+    pub left_name: String,
+    right_id: String,
+    pub right_name: String,
+    pub only_left: Vec<Track>,
+    pub only_right: Vec<Track>,
+    pub shared: Vec<Track>,
+    /// First line shown in the popup
+    pub scroll: usize,
+    /// Visible lines, updated on every draw
+    pub viewport: usize,
 }
 
-pub struct App {
-    pub spotify_client: SpotifyClient,
+impl Comparison {
+    /// Sorts the tracks of both playlists by which of them has each, listing repeats once
+    fn new(left: (String, String, Vec<Track>), right: (String, String, Vec<Track>)) -> Self {
+        let (left_id, left_name, left_tracks) = left;
+        let (right_id, right_name, right_tracks) = right;
+        let left_uris: HashSet<&str> = left_tracks.iter().map(|t| t.uri.as_str()).collect();
+        let right_uris: HashSet<&str> = right_tracks.iter().map(|t| t.uri.as_str()).collect();
+
+        let mut seen = HashSet::new();
+        let (mut only_left, mut only_right, mut shared) = (Vec::new(), Vec::new(), Vec::new());
+        for track in left_tracks.iter().chain(&right_tracks) {
+            if !seen.insert(track.uri.as_str()) {
+                continue;
+            }
+            match (
+                left_uris.contains(track.uri.as_str()),
+                right_uris.contains(track.uri.as_str()),
+            ) {
+                (true, true) => shared.push(track.clone()),
+                (true, false) => only_left.push(track.clone()),
+                _ => only_right.push(track.clone()),
+            }
+        }
+
+        Self {
+            left_id,
+            left_name,
+            right_id,
+            right_name,
+            only_left,
+            only_right,
+            shared,
+            scroll: 0,
+            viewport: 0,
+        }
+    }
+
+    /// Lines in the popup: a heading per group plus its tracks
+    pub fn len(&self) -> usize {
+        3 + self.only_left.len() + self.only_right.len() + self.shared.len()
+    }
+}
+
+/// An entry of an imported file and the tracks search found for it
+#[derive(Debug)]
+pub struct ImportMatch {
+    pub entry: Entry,
+    pub candidates: Vec<Track>,
+    /// The track it's imported as, from the file itself, a sure search result or the review
+    pub uri: Option<String>,
+}
+
+/// A file on its way into a playlist, waiting on picks for the entries search wasn't sure of
+#[derive(Debug)]
+pub struct ImportReview {
+    /// The playlist it goes into, made if there isn't one by this name
+    pub name: String,
+    pub matches: Vec<ImportMatch>,
+    /// Positions in `matches` left to review, the first is in the popup
+    pub pending: VecDeque<usize>,
+    /// The cursor among the first's candidates, with skipping it below them
+    pub state: ListState,
+}
+
+impl ImportReview {
+    /// The entry in the popup
+    pub fn current(&self) -> Option<&ImportMatch> {
+        self.matches.get(*self.pending.front()?)
+    }
+}
+
+/// Screen areas of the clickable parts of the UI, recorded on every draw
+#[derive(Debug, Clone, Default)]
+pub struct PaneAreas {
+    pub library: Rect,
+    /// One area per library tab, in LibraryTab::ALL order
+    pub library_tabs: Vec<Rect>,
+    pub tracks: Rect,
+    pub search_bar: Rect,
+    pub playback_controls: Rect,
+    pub track_menu: Rect,
+    pub playlist_picker: Rect,
+    pub device_picker: Rect,
+    pub genre_picker: Rect,
+    pub import_review: Rect,
+    pub confirmation: Rect,
+    pub split: Rect,
+    pub comparison: Rect,
+    pub stats: Rect,
+}
+
+/// Results of fetches run in the background, handed back to the run loop
+enum Loaded {
+    /// Tracks for the tracks pane, tagged with the request they answer. A copy from the disk
+    /// cache is followed by the fetched one
+    Tracks {
+        request: u64,
+        source: TrackSource,
+        result: Result<TrackPage>,
+        cached: bool,
+    },
+    /// The next page of the playlist or Liked Songs in the tracks pane, from `offset`
+    MoreTracks {
+        request: u64,
+        list_id: String,
+        offset: usize,
+        result: Result<TrackPage>,
+    },
+    /// Tracks found for a query, tagged with the search they answer
+    SearchResults {
+        generation: u64,
+        query: String,
+        result: Result<TrackPage>,
+    },
+    /// The next page of search results, from `offset`
+    MoreSearchResults {
+        query: String,
+        offset: usize,
+        result: Result<TrackPage>,
+    },
+    Playlists {
+        result: Result<Vec<Playlist>>,
+        cached: bool,
+    },
+    /// For You playlists found by search, missing from the library
+    ForYou(Result<Vec<Playlist>>),
+    /// Signing in again while offline
+    Reconnected(Result<()>),
+    /// The access token refreshed ahead of expiring
+    TokenRefreshed(Result<()>),
+    /// librespot showing up among the devices after being started
+    #[cfg(feature = "librespot")]
+    LocalDevice(Result<Device>),
+    /// The playlist this week's Discover Weekly was copied into
+    Archived {
+        week: String,
+        result: Result<Playlist>,
+    },
+    Albums {
+        result: Result<Vec<Album>>,
+        cached: bool,
+    },
+    Artists {
+        result: Result<Vec<Artist>>,
+        cached: bool,
+    },
+    Shows(Result<Vec<Show>>),
+    PlaylistFollowers {
+        playlist_id: String,
+        result: Result<u32>,
+    },
+    /// Tracks for the split view
+    SplitTracks {
+        playlist_id: String,
+        result: Result<Vec<Track>>,
+    },
+    /// Recommendations to start a radio with, `first` playing ahead of them
+    Radio {
+        generation: u64,
+        name: String,
+        seeds: Seeds,
+        first: Option<Track>,
+        result: Result<Vec<Track>>,
+    },
+    /// More recommendations for the radio that's playing
+    RadioTopUp {
+        generation: u64,
+        result: Result<Vec<Track>>,
+    },
+    /// Every track of a library entry, to add to the queue
+    QueueTracks {
+        name: String,
+        result: Result<Vec<Track>>,
+    },
+    Comparison(Result<Comparison>),
+    Stats(Result<PlaylistStats>),
+    /// A playlist made from the queue
+    QueueSaved(Result<Playlist>),
+    Tempos(Result<Vec<AudioFeatures>>),
+    /// The tracks found for each entry of a file being imported
+    ImportMatches {
+        name: String,
+        result: Result<Vec<ImportMatch>>,
+    },
+    /// The playlist an import goes into, made if it's new, and the URIs it doesn't have yet
+    ImportTarget {
+        name: String,
+        not_found: usize,
+        result: Result<(Option<Playlist>, String, Vec<String>)>,
+    },
+    Backup(Result<Backup>),
+    /// The genres recommendations can be seeded with, for the genre picker
+    Genres(Result<Vec<String>>),
+    /// URIs of the tracks to merge into a playlist
+    MergeTracks {
+        playlist_id: String,
+        playlist_name: String,
+        source_name: String,
+        result: Result<Vec<String>>,
+    },
+    /// Tracks of a playlist next to the selected one, fetched before they're asked for
+    Prefetched {
+        playlist_id: String,
+        result: Result<Vec<Track>>,
+    },
+    /// A track of the batch queue addition, added to the queue
+    Queued {
+        track: Track,
+        result: Result<()>,
+    },
+    /// URIs a script asked to queue
+    UrisQueued(Result<()>),
+    /// One chunk of the running merge, added to its playlist
+    Merged {
+        chunk: Vec<String>,
+        result: Result<()>,
+    },
+    /// Tracks added to Liked Songs, `replayed` from while offline
+    Liked {
+        ids: Vec<String>,
+        replayed: bool,
+        result: Result<()>,
+    },
+    PlaylistDeleted {
+        playlist_id: String,
+        playlist_name: String,
+        result: Result<()>,
+    },
+    /// Tracks with these URIs added to the end of a playlist
+    AddedToPlaylist {
+        playlist_id: String,
+        playlist_name: String,
+        uris: Vec<String>,
+        then: AfterAdding,
+        result: Result<()>,
+    },
+    /// Tracks with these URIs removed from a playlist
+    TracksRemoved {
+        playlist_id: String,
+        playlist_name: String,
+        uris: Vec<String>,
+        result: Result<()>,
+    },
+    /// An action taken back, with the message saying what was undone
+    Undone {
+        action: Undoable,
+        result: Result<String>,
+    },
+}
+
+pub struct App<C: SpotifyApi = SpotifyClient> {
+    pub config: Config,
+    /// Colors from the config's theme
+    pub theme: Theme,
+    /// Keys the config makes act like other keys
+    pub remaps: Remaps,
+    /// The action each key stands for, once remapped
+    keymap: Keymap,
+    pub local_state: LocalState,
+    pub spotify_client: C,
     pub playlists: Vec<Playlist>,
+    /// Follower counts by playlist id, fetched when a playlist is first opened
+    pub playlist_followers: HashMap<String, u32>,
+    /// Tracks of every playlist fetched this session by playlist id, which Recently Added is
+    /// put together from. Opening one of these shows them while they're fetched again
+    pub track_cache: HashMap<String, Vec<Track>>,
+    /// Playlists whose tracks are being prefetched
+    prefetching: HashSet<String>,
+    /// Playlists and tracks kept between runs
+    pub library_cache: LibraryCache,
+    /// The newest addition to each playlist in the library cache, read when playlists are
+    /// first sorted by their latest change
+    last_added: Option<HashMap<String, String>>,
+    /// URIs of the tracks pane's contents while they're a cached copy waiting on a fresh one
+    revalidating: Option<Vec<String>>,
+    /// The other library tabs, None until the tab is first opened
+    pub saved_albums: Option<Vec<Album>>,
+    pub followed_artists: Option<Vec<Artist>>,
+    pub saved_shows: Option<Vec<Show>>,
+    pub library_tab: LibraryTab,
+    /// Display order of the library pane as indices into playlists
+    pub library_order: Vec<usize>,
+    /// Library tabs whose entries are being fetched
+    pub loading_tabs: Vec<LibraryTab>,
+    /// Where the tracks in current_tracks came from
+    pub track_source: Option<TrackSource>,
+    /// Where the tracks being fetched for the tracks pane come from
+    pub loading_tracks: Option<TrackSource>,
+    /// Views left for an album or artist since the last one opened from the library, oldest first
+    pub nav_history: Vec<NavEntry>,
+    /// Row to select once the tracks being fetched arrive, when going back
+    restore_selection: Option<usize>,
+    /// Scroll position to put the tracks pane at once they arrive, when picking up a session
+    restore_offset: Option<usize>,
+    /// Where the last run left off, picked up once the library tab it was on has loaded
+    pending_session: Option<Session>,
+    /// Counts track fetches, so only the latest one is shown when several overlap
+    tracks_request: u64,
+    loaded_tx: UnboundedSender<Loaded>,
+    loaded_rx: UnboundedReceiver<Loaded>,
+    /// Playback, polling and search go through the worker task, see worker.rs
+    commands: UnboundedSender<Command>,
+    worker_events: UnboundedReceiver<WorkerEvent>,
+    /// Set while a poll of the player is in flight, so slow responses don't pile up more
+    player_refresh_pending: bool,
+    /// Hands each polled player state on to whatever follows it outside the interface
+    player_updates: watch::Sender<Option<CurrentlyPlaying>>,
     pub current_tracks: Vec<Track>,
     pub search_results: Vec<Track>,
+    /// Where the next page of the tracks pane starts, while there's more to load
+    tracks_next: Option<usize>,
+    /// The query and offset of the next page of search results, while there's more to load
+    search_next: Option<(String, usize)>,
+    /// Set while a next page is being fetched
+    loading_more: bool,
+    /// Counts the searches started, so only the latest one's results are shown
+    search_generation: u64,
+    /// The search in flight, abandoned when the query changes
+    search_task: Option<JoinHandle<()>>,
+    pub track_sort: Option<TrackSort>,
+    /// Puts a header above each run of tracks from the same album
+    pub group_by_album: bool,
+    /// Display order of the tracks pane as indices into get_display_tracks()
+    pub track_order: Vec<usize>,
+    /// Tracks marked for batch actions, as indices into get_display_tracks()
+    pub marked_tracks: BTreeSet<usize>,
+    /// Track the visual selection started from and the marks from before it
+    visual_anchor: Option<(usize, BTreeSet<usize>)>,
+    pub queue_batch: Option<QueueBatch>,
+    /// The radio playing, if any
+    pub radio: Option<Radio>,
+    /// Counts the radios started, so recommendations for an older one are dropped
+    radio_generation: u64,
+    pub merge_batch: Option<MergeBatch>,
+    /// Two playlists being compared, shown in a popup
+    pub comparison: Option<Comparison>,
+    /// What the selected playlist is made of, shown in a popup
+    pub stats: Option<PlaylistStats>,
+    /// Beats per minute of the tracks whose audio features have been fetched, by track id
+    pub tempos: HashMap<String, f32>,
+    tempos_requested: HashSet<String>,
+    /// An import waiting on picks, shown in a popup
+    pub import_review: Option<ImportReview>,
+    /// Whether a backup of the library is being written
+    backing_up: bool,
     pub currently_playing: Option<CurrentlyPlaying>,
+    /// When `currently_playing` was fetched, to advance its progress between polls
+    player_fetched_at: Instant,
+    /// A volume picked with the keys and not sent yet, with when it last changed. Presses in
+    /// quick succession go to Spotify as one request
+    pending_volume: Option<(u32, Instant)>,
+    /// How far the last volume key press went, grown by the next one in quick succession
+    volume_step: i32,
     pub queue: Option<Queue>,
-    pub playlists_state: ListState,
-    pub tracks_state: ListState,
-    pub search_state: ListState,
+    /// Selection in each library tab, indexed by LibraryTab::index
+    library_states: [ListState; 6],
+    pub tracks_state: TableState,
+    pub search_state: TableState,
+    /// Visible rows in the playlists and tracks panes, updated on every draw
+    pub library_viewport: usize,
+    pub tracks_viewport: usize,
+    /// Whether the terminal is too narrow for both columns, so only the focused one is drawn
+    pub narrow: bool,
+    pub pane_areas: PaneAreas,
+    last_track_click: Option<(Instant, usize)>,
     pub focused_pane: FocusedPane,
     pub show_search: bool,
-    pub search_input: String,
+    pub search_input: TextInput,
+    /// The list whose filter prompt is currently open
+    pub editing_filter: Option<FilterTarget>,
+    pub type_ahead: Option<TypeAhead>,
+    /// Count typed before a motion, like the 15 in 15j
+    pub pending_count: Option<usize>,
+    /// Set after m or ', waiting for the mark's letter
+    pub pending_mark: Option<MarkAction>,
+    /// Row of the tracks pane each playlist was left on, by playlist id, so jumping to a mark
+    /// picks up where it left off
+    playlist_selection: HashMap<String, usize>,
+    pub library_filter: TextInput,
+    pub track_filter: TextInput,
     pub show_playback_controls: bool,
     pub playback_controls_state: ListState,
+    pub show_track_menu: bool,
+    pub track_menu_state: ListState,
+    pub show_playlist_picker: bool,
+    pub playlist_picker_state: ListState,
+    pub playlist_picker_purpose: PickerPurpose,
+    pub split: Option<SplitView>,
+    pub show_device_picker: bool,
+    pub device_picker_state: ListState,
+    /// Devices listed in the device picker, fetched when it opens
+    pub devices: Vec<Device>,
+    pub show_genre_picker: bool,
+    pub genre_picker_state: ListState,
+    /// Genres to seed a radio with, fetched the first time the genre picker opens
+    pub genres: Option<Vec<String>>,
+    /// Genres picked in the genre picker, as indices into genres
+    pub picked_genres: BTreeSet<usize>,
+    /// The action the confirmation popup is asking about, shown while set
+    pub confirmation: Option<Confirmation>,
+    /// Recent changes to the library, most recent last
+    undo_stack: Vec<Undoable>,
     pub show_help: bool,
+    /// The recent requests overlay, for seeing what's slow or failing
+    pub show_debug: bool,
+    /// First line of the help popup in view
+    pub help_scroll: usize,
+    pub help_filter: TextInput,
+    pub editing_help_filter: bool,
+    /// Lines in the help popup and how many of them fit, updated on every draw
+    pub help_len: usize,
+    pub help_viewport: usize,
     pub state: AppState,
+    pub notifications: Notifications,
     pub should_quit: bool,
     pub last_search_time: Option<std::time::Instant>,
     pub search_debounce_ms: u64,
+    /// A spotify: URI to start playing once signed in, from --play
+    pub play_on_start: Option<String>,
+    /// A file to import into a playlist once signed in, from --import
+    pub import_on_start: Option<PathBuf>,
+    /// Whether Spotify can be reached, while it can't only the cached library can be browsed
+    pub connection: Connection,
+    /// Likes and queued tracks waiting for the connection to come back
+    pub offline_actions: Vec<OfflineAction>,
+    /// librespot, while it's running as this app's own device
+    #[cfg(feature = "librespot")]
+    local_device: Option<LocalDevice>,
+    /// For queue_added, the player's hooks are run from main
+    hooks: Hooks,
+    /// init.lua's commands, key bindings and track formatting
+    pub scripts: Scripts,
+    /// Quitting from outside, like on SIGTERM, and the background tasks to stop
+    pub shutdown: Shutdown,
 }
 
 impl App {
-    pub async fn new() -> Result<Self> {
-        let client_id = std::env::var("SPOTIFY_CLIENT_ID")
-            .expect("SPOTIFY_CLIENT_ID environment variable not set");
-        let client_secret = std::env::var("SPOTIFY_CLIENT_SECRET")
-            .expect("SPOTIFY_CLIENT_SECRET environment variable not set");
+    /// An app with the state saved for the config's profile
+    pub fn new(config: Config, spotify_client: SpotifyClient) -> Self {
+        let local_state = LocalState::load(config.profile.as_deref());
+        let library_cache = match cache::path(config.profile.as_deref()) {
+            Some(path) if config.cache.enabled => LibraryCache::open(&path).unwrap_or_else(|e| {
+                tracing::warn!("Failed to open the library cache: {}", e);
+                LibraryCache::default()
+            }),
+            _ => LibraryCache::default(),
+        };
+        let mut app = App::with_client(config, spotify_client, local_state);
+        app.library_cache = library_cache;
+        app
+    }
+}
 
-        let spotify_client = SpotifyClient::new(client_id, client_secret);
+impl<C: SpotifyApi> App<C> {
+    /// An app talking to Spotify through `spotify_client`, which tests swap for a mock
+    pub fn with_client(config: Config, spotify_client: C, local_state: LocalState) -> Self {
+        let (loaded_tx, loaded_rx) = mpsc::unbounded_channel();
+        let (commands, worker_events) = worker::spawn(spotify_client.clone());
 
+        let group_by_album = config.display.group_by_album;
+        // Config::load has already reported any problems with these
+        let theme = config.theme.resolve().unwrap_or_default();
+        let remaps = Remaps::parse(&config.keys).unwrap_or_default();
+        let pending_session = local_state.session.clone();
+        let hooks = Hooks::new(config.hooks.clone());
         let mut app = Self {
+            config,
+            theme,
+            remaps,
+            keymap: Keymap::default(),
+            local_state,
             spotify_client,
             playlists: Vec::new(),
+            playlist_followers: HashMap::new(),
+            track_cache: HashMap::new(),
+            prefetching: HashSet::new(),
+            library_cache: LibraryCache::default(),
+            last_added: None,
+            revalidating: None,
+            saved_albums: None,
+            followed_artists: None,
+            saved_shows: None,
+            library_tab: LibraryTab::Playlists,
+            library_order: Vec::new(),
+            loading_tabs: Vec::new(),
+            track_source: None,
+            loading_tracks: None,
+            nav_history: Vec::new(),
+            restore_selection: None,
+            restore_offset: None,
+            pending_session,
+            tracks_request: 0,
+            loaded_tx,
+            loaded_rx,
+            commands,
+            worker_events,
+            player_refresh_pending: false,
+            player_updates: watch::channel(None).0,
             current_tracks: Vec::new(),
             search_results: Vec::new(),
+            tracks_next: None,
+            search_next: None,
+            loading_more: false,
+            search_generation: 0,
+            search_task: None,
+            track_sort: None,
+            group_by_album,
+            track_order: Vec::new(),
+            marked_tracks: BTreeSet::new(),
+            visual_anchor: None,
+            queue_batch: None,
+            radio: None,
+            radio_generation: 0,
+            merge_batch: None,
+            comparison: None,
+            stats: None,
+            tempos: HashMap::new(),
+            tempos_requested: HashSet::new(),
+            import_review: None,
+            backing_up: false,
             currently_playing: None,
+            player_fetched_at: Instant::now(),
+            pending_volume: None,
+            volume_step: 0,
             queue: None,
-            playlists_state: ListState::default(),
-            tracks_state: ListState::default(),
-            search_state: ListState::default(),
-            focused_pane: FocusedPane::Playlists,
+            library_states: Default::default(),
+            tracks_state: TableState::default(),
+            search_state: TableState::default(),
+            library_viewport: 0,
+            tracks_viewport: 0,
+            narrow: false,
+            pane_areas: PaneAreas::default(),
+            last_track_click: None,
+            focused_pane: FocusedPane::Library,
             show_search: false,
-            search_input: String::new(),
+            search_input: TextInput::default(),
+            editing_filter: None,
+            type_ahead: None,
+            pending_count: None,
+            pending_mark: None,
+            playlist_selection: HashMap::new(),
+            library_filter: TextInput::default(),
+            track_filter: TextInput::default(),
             show_playback_controls: false,
             playback_controls_state: ListState::default(),
+            show_track_menu: false,
+            track_menu_state: ListState::default(),
+            show_playlist_picker: false,
+            playlist_picker_state: ListState::default(),
+            playlist_picker_purpose: PickerPurpose::AddTracks(Vec::new()),
+            split: None,
+            show_device_picker: false,
+            device_picker_state: ListState::default(),
+            show_genre_picker: false,
+            genre_picker_state: ListState::default(),
+            genres: None,
+            picked_genres: BTreeSet::new(),
+            devices: Vec::new(),
+            confirmation: None,
+            undo_stack: Vec::new(),
             show_help: false,
+            show_debug: false,
+            help_scroll: 0,
+            help_filter: TextInput::default(),
+            editing_help_filter: false,
+            help_len: 0,
+            help_viewport: 0,
             state: AppState::Authenticating,
+            notifications: Notifications::default(),
             should_quit: false,
             last_search_time: None,
             search_debounce_ms: 500, // 300ms debounce
+            play_on_start: None,
+            import_on_start: None,
+            connection: Connection::default(),
+            offline_actions: Vec::new(),
+            #[cfg(feature = "librespot")]
+            local_device: None,
+            hooks,
+            scripts: Scripts::default(),
+            shutdown: Shutdown::default(),
         };
 
-        app.playlists_state.select(Some(0));
+        for state in &mut app.library_states {
+            state.select(Some(0));
+        }
         app.tracks_state.select(Some(0));
         app.search_state.select(Some(0));
         app.playback_controls_state.select(Some(0));
 
-        Ok(app)
+        app
     }
 
     pub async fn run(
@@ -87,552 +1118,6371 @@ impl App {
         terminal: &mut Terminal<impl ratatui::backend::Backend>,
     ) -> Result<()> {
         self.authenticate().await?;
-        self.load_playlists().await?;
+        self.fetch_library_tab(LibraryTab::Playlists);
+        #[cfg(feature = "librespot")]
+        self.start_local_device().await;
+        #[cfg(not(feature = "librespot"))]
+        if self.config.device.enabled {
+            self.notifications
+                .error("spotitui was built without the librespot feature, device.enabled needs it");
+        }
+        if let Some(uri) = self.play_on_start.take() {
+            self.send(if cli::is_context(&uri) {
+                Command::PlayContext(uri)
+            } else {
+                Command::Play(vec![uri])
+            });
+        }
+        if let Some(path) = self.import_on_start.take() {
+            self.start_import(&path);
+        }
 
         let mut last_update = std::time::Instant::now();
         let mut last_refreshed = std::time::Instant::now();
 
         loop {
+            self.notifications.expire();
             terminal.draw(|f| ui::draw(f, self))?;
 
-            if self.should_quit {
+            if self.should_quit || self.shutdown.is_shutting_down() {
                 break;
             }
 
-            // Update currently playing and queue every 2 seconds
-            if last_update.elapsed() >= Duration::from_secs(2) {
-                self.update_currently_playing().await;
-                self.update_queue().await;
+            if self.connection.start_reconnect() {
+                self.reconnect();
+            }
+
+            // Update currently playing and queue at the configured interval
+            if last_update.elapsed() >= self.config.polling.player_interval()
+                && !self.player_refresh_pending
+                && !self.connection.is_offline()
+            {
+                // Low power mode only fetches the queue when it's on screen
+                let queue = !self.config.polling.low_power || self.config.layout.show_queue;
+                self.refresh_player(queue);
                 last_update = std::time::Instant::now();
             }
 
             // Update the refresh token every 10 mins
-            if last_refreshed.elapsed() >= Duration::from_secs(600) {
-                self.refresh_access_token().await?;
+            if last_refreshed.elapsed() >= Duration::from_secs(600) && !self.connection.is_offline()
+            {
+                self.refresh_access_token();
                 last_refreshed = std::time::Instant::now();
+                // Catches the Monday refresh when the app is left running
+                self.archive_discover_weekly();
             }
 
+            self.receive_loaded();
+            self.receive_worker_events();
+            self.load_more_near_end();
+
             // Check for pending search
-            self.check_pending_search().await;
+            self.check_pending_search();
+            self.check_pending_volume();
+            self.replay_offline_actions();
+            self.process_queue_batch();
+            self.process_merge_batch();
 
-            if crossterm::event::poll(Duration::from_millis(50))? {
-                if let Event::Key(key) = event::read()? {
-                    self.handle_key_event(key).await?;
+            if crossterm::event::poll(self.config.polling.event_poll())? {
+                let result = match event::read()? {
+                    Event::Key(key) => self.handle_key_event(key).await,
+                    Event::Mouse(mouse) => self.handle_mouse_event(mouse).await,
+                    // The terminal may have reflowed what was on screen, so the next frame is
+                    // drawn in full rather than as changes to the last one
+                    Event::Resize(..) => terminal.clear().map_err(Into::into),
+                    _ => Ok(()),
+                };
+                // Failed requests aren't fatal, report them and carry on
+                if let Err(e) = result {
+                    self.report(e);
                 }
+                self.update_visual_marks();
             }
         }
 
+        self.save_session();
         Ok(())
     }
 
+    fn set_state(&mut self, state: AppState) {
+        tracing::info!(?state, "App state");
+        self.state = state;
+    }
+
     async fn authenticate(&mut self) -> Result<()> {
-        self.state = AppState::Authenticating;
+        self.set_state(AppState::Authenticating);
         match self.spotify_client.authenticate().await {
             Ok(_) => {
-                self.state = AppState::Ready;
+                self.set_state(AppState::Ready);
+                Ok(())
+            }
+            // The cached library can still be browsed, and signing in is retried meanwhile
+            Err(e) if is_network_error(&e) => {
+                self.set_state(AppState::Ready);
+                self.go_offline(e);
                 Ok(())
             }
             Err(e) => {
-                self.state = AppState::Error(format!("Authentication failed: {}", e));
+                self.set_state(AppState::Error(format!("Authentication failed: {}", e)));
                 Err(e)
             }
         }
     }
 
-    async fn refresh_access_token(&mut self) -> Result<()> {
-        match self.spotify_client.refresh_access_token().await {
-            Ok(_) => {
-                self.state = AppState::Ready;
-                Ok(())
-            }
+    /// Refreshes the access token in the background, ahead of it expiring
+    fn refresh_access_token(&mut self) {
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move { Loaded::TokenRefreshed(client.refresh_access_token().await) });
+    }
+
+    /// Starts librespot when the config asks for a device of its own, and moves playback to it
+    /// once it shows up
+    #[cfg(feature = "librespot")]
+    async fn start_local_device(&mut self) {
+        if !self.config.device.enabled || self.connection.is_offline() {
+            return;
+        }
+        let cache_dir = cache::dir(self.config.profile.as_deref()).map(|dir| dir.join("librespot"));
+        // Once librespot has saved credentials it signs in with those, so the token only has to
+        // be handed over on its first run
+        let access_token = match &cache_dir {
+            Some(dir) if LocalDevice::has_credentials(dir) => None,
+            _ => self.spotify_client.access_token().await,
+        };
+        let proxy = self.config.network.proxy.as_deref();
+        match LocalDevice::start(
+            &self.config.device,
+            access_token.as_deref(),
+            cache_dir,
+            proxy,
+        ) {
+            Ok(device) => self.local_device = Some(device),
             Err(e) => {
-                self.state = AppState::Error(format!("Authentication failed: {}", e));
-                Err(e)
+                self.notifications.error(e);
+                return;
             }
         }
-    }
 
-    async fn load_playlists(&mut self) -> Result<()> {
-        self.state = AppState::Loading;
-        match self.spotify_client.get_playlists().await {
-            Ok(playlists) => {
-                self.playlists = playlists;
-                if !self.playlists.is_empty() {
-                    self.load_playlist_tracks(0).await?;
+        let client = self.spotify_client.clone();
+        let name = self.config.device.name.clone();
+        self.spawn_load(async move {
+            // librespot takes a few seconds to sign in and register
+            for _ in 0..LOCAL_DEVICE_ATTEMPTS {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let devices = client.get_devices().await.unwrap_or_default();
+                if let Some(device) = devices.into_iter().find(|d| d.name == name) {
+                    return Loaded::LocalDevice(Ok(device));
                 }
-                self.state = AppState::Ready;
-                Ok(())
             }
-            Err(e) => {
-                self.state = AppState::Error(format!("Failed to load playlists: {}", e));
-                Err(e)
+            Loaded::LocalDevice(Err(anyhow!(
+                "librespot didn't show up as \"{}\", run with --debug to see its log",
+                name
+            )))
+        });
+    }
+
+    /// Switches to browsing the cached library until Spotify can be reached again
+    fn go_offline(&mut self, error: anyhow::Error) {
+        if self.connection.go_offline() {
+            tracing::warn!("Going offline: {:#}", error);
+        }
+    }
+
+    /// Tries signing in again in the background while offline
+    fn reconnect(&mut self) {
+        self.connection.reconnect_now();
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move { Loaded::Reconnected(client.refresh_access_token().await) });
+    }
+
+    /// Carries out what was done while offline, once back online. Likes are sent straight
+    /// away and queued tracks join the batch queue addition
+    fn replay_offline_actions(&mut self) {
+        if self.connection.is_offline() || self.offline_actions.is_empty() {
+            return;
+        }
+        for action in std::mem::take(&mut self.offline_actions) {
+            match action {
+                OfflineAction::Like(ids) => self.like_tracks(ids, true),
+                OfflineAction::Queue(tracks) => self.enqueue_batch(tracks),
             }
         }
     }
 
-    async fn load_playlist_tracks(&mut self, playlist_index: usize) -> Result<()> {
-        if playlist_index < self.playlists.len() {
-            let playlist_id = &self.playlists[playlist_index].id;
-            self.current_tracks = self.spotify_client.get_playlist_tracks(playlist_id).await?;
-            self.tracks_state.select(Some(0));
+    /// Adds tracks to Liked Songs in the background. `replayed` likes were made offline, and
+    /// wait for the next reconnect if the connection drops again
+    fn like_tracks(&mut self, ids: Vec<String>, replayed: bool) {
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client.save_tracks(&ids).await;
+            Loaded::Liked {
+                ids,
+                replayed,
+                result,
+            }
+        });
+    }
+
+    /// Keeps a like or queue addition for when the connection comes back
+    fn defer_offline(&mut self, action: OfflineAction) {
+        let message = match &action {
+            OfflineAction::Like(ids) => format!(
+                "Offline, {} will be liked once back online",
+                describe_count(ids.len())
+            ),
+            OfflineAction::Queue(tracks) => format!(
+                "Offline, {} will be queued once back online",
+                describe_count(tracks.len())
+            ),
+        };
+        self.offline_actions.push(action);
+        self.clear_marks();
+        self.notifications.info(message);
+    }
+
+    /// Runs a fetch in the background, its result is picked up by receive_loaded
+    fn spawn_load(&self, fetch: impl Future<Output = Loaded> + Send + 'static) -> JoinHandle<()> {
+        let sender = self.loaded_tx.clone();
+        self.shutdown.spawn(async move {
+            // The receiver only goes away when the app is shutting down
+            let _ = sender.send(fetch.await);
+        })
+    }
+
+    /// Runs a fetch in the background like spawn_load, first handing over the library cache's
+    /// copy from `read` if it has one, to show while the fetch runs. The fetched result is
+    /// stored in its place with `write`
+    fn spawn_cached<T>(
+        &self,
+        read: impl FnOnce(&LibraryCache) -> Option<T> + Send + 'static,
+        write: impl FnOnce(&LibraryCache, &T) + Send + 'static,
+        fetch: impl Future<Output = Result<T>> + Send + 'static,
+        loaded: impl Fn(Result<T>, bool) -> Loaded + Send + 'static,
+    ) where
+        T: Clone + Send + 'static,
+    {
+        let sender = self.loaded_tx.clone();
+        let library_cache = self.library_cache.clone();
+        let offline = self.connection.is_offline();
+        let cancelled = self.shutdown.cancelled();
+        // Tracked so a write that's begun is finished before quitting
+        self.shutdown.track(async move {
+            // SQLite blocks, and writing out a large library takes a moment
+            let reader = library_cache.clone();
+            if let Ok(Some(items)) = tokio::task::spawn_blocking(move || read(&reader)).await {
+                let _ = sender.send(loaded(Ok(items), true));
+            } else if offline {
+                let _ = sender.send(loaded(Err(anyhow!("Not in the cached library")), false));
+            }
+            // Only the cache can answer while offline
+            if offline {
+                return;
+            }
+            let result = tokio::select! {
+                result = fetch => result,
+                _ = cancelled => return,
+            };
+            if let Ok(items) = &result {
+                let items = items.clone();
+                let _ = tokio::task::spawn_blocking(move || write(&library_cache, &items)).await;
+            }
+            // The receiver only goes away when the app is shutting down
+            let _ = sender.send(loaded(result, false));
+        });
+    }
+
+    /// How old a cached list can be and still be shown. Offline there's nothing to replace it
+    /// with, so any copy will do
+    fn cache_ttl(&self, ttl: Duration) -> Duration {
+        if self.connection.is_offline() {
+            Duration::MAX
+        } else {
+            ttl
         }
-        Ok(())
     }
 
-    async fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
-        // Handle error state - any key dismisses the error
-        if matches!(self.state, AppState::Error(_)) {
-            self.state = AppState::Ready;
-            return Ok(());
+    /// Fetches the entries of a library tab in the background
+    fn fetch_library_tab(&mut self, tab: LibraryTab) {
+        if tab == LibraryTab::Liked || self.loading_tabs.contains(&tab) {
+            return;
         }
+        self.loading_tabs.push(tab);
 
-        if self.show_help {
-            if matches!(key.code, KeyCode::Esc | KeyCode::Char('?')) {
-                self.show_help = false;
+        let client = self.spotify_client.clone();
+        let ttl = self.cache_ttl(self.config.cache.playlists_ttl());
+        match tab {
+            LibraryTab::Playlists => self.spawn_cached(
+                move |cache| cache.playlists(ttl),
+                |cache, playlists| cache.save_playlists(playlists),
+                async move { client.get_playlists().await },
+                |result, cached| Loaded::Playlists { result, cached },
+            ),
+            LibraryTab::Albums => self.spawn_cached(
+                move |cache| cache.albums(ttl),
+                |cache, albums| cache.save_albums(albums),
+                async move { client.get_saved_albums().await },
+                |result, cached| Loaded::Albums { result, cached },
+            ),
+            LibraryTab::Artists => self.spawn_cached(
+                move |cache| cache.artists(ttl),
+                |cache, artists| cache.save_artists(artists),
+                async move { client.get_followed_artists().await },
+                |result, cached| Loaded::Artists { result, cached },
+            ),
+            LibraryTab::Podcasts => {
+                self.spawn_load(async move { Loaded::Shows(client.get_saved_shows().await) });
             }
-            return Ok(());
-        } else if self.show_playback_controls {
-            return self.handle_playback_controls_key(key).await;
-        } else if self.show_search {
-            match key.code {
-                KeyCode::Esc => {
-                    self.show_search = false;
-                    self.search_input.clear();
-                    self.search_results.clear();
-                    self.focused_pane = FocusedPane::Playlists;
-                    self.last_search_time = None;
-                }
-                KeyCode::Enter => {
-                    // Enter while in search mode should focus the tracks pane
-                    if !self.search_results.is_empty() {
-                        self.focused_pane = FocusedPane::Tracks;
+            LibraryTab::ForYou | LibraryTab::Liked => {}
+        }
+    }
+
+    /// Fetches tracks for the tracks pane in the background, replacing any fetch still running
+    fn load_tracks(&mut self, source: TrackSource) {
+        self.tracks_request += 1;
+        let request = self.tracks_request;
+        self.loading_tracks = Some(source.clone());
+        self.restore_selection = None;
+        self.restore_offset = None;
+        self.remember_playlist_selection();
+
+        let client = self.spotify_client.clone();
+        let playlist_id = match &source {
+            TrackSource::Playlist(id) => Some(id.clone()),
+            _ => None,
+        };
+        let cached = self.cached_tracks_for(&source);
+        // A playlist fetched before, or prefetched, shows straight away and the fetch below
+        // brings it up to date. Offline, the disk cache has the last word
+        let remembered = playlist_id
+            .as_ref()
+            .filter(|_| !self.connection.is_offline())
+            .and_then(|id| self.track_cache.get(id))
+            .cloned();
+        let from_memory = remembered.is_some();
+        if let Some(tracks) = remembered {
+            // The receiver only goes away when the app is shutting down
+            let _ = self.loaded_tx.send(Loaded::Tracks {
+                request,
+                source: source.clone(),
+                result: Ok(tracks.into()),
+                cached: true,
+            });
+        }
+        if let Some(ref id) = playlist_id {
+            if !self.playlist_followers.contains_key(id) {
+                let client = client.clone();
+                let playlist_id = id.clone();
+                self.spawn_load(async move {
+                    let result = client.get_playlist_followers(&playlist_id).await;
+                    Loaded::PlaylistFollowers {
+                        playlist_id,
+                        result,
                     }
+                });
+            }
+        }
+        // Playlists and Liked Songs are big and slow to page through, so they're kept on disk
+        let list_id = match &source {
+            TrackSource::Playlist(_) => playlist_id.clone(),
+            TrackSource::Liked => Some("liked".to_string()),
+            _ => None,
+        };
+        self.revalidating = None;
+        let fetched_source = source.clone();
+        let ttl = self.cache_ttl(self.config.cache.tracks_ttl());
+        let written_id = list_id.clone();
+        let recent = matches!(source, TrackSource::RecentlyAdded);
+        let shown = cached.clone();
+        let library_cache = self.library_cache.clone();
+        self.spawn_cached(
+            move |cache| {
+                if from_memory {
+                    None
+                } else if recent {
+                    let stored = cache.recently_added(RECENTLY_ADDED_LIMIT);
+                    Some(recently_added(shown.into_iter().chain(stored)).into())
+                } else {
+                    cache.tracks(list_id.as_deref()?, ttl).map(TrackPage::from)
                 }
-                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    // Ctrl+P - Previous (same as Up)
-                    if matches!(self.focused_pane, FocusedPane::Tracks)
-                        && !self.search_results.is_empty()
-                    {
-                        let selected = self.search_state.selected().unwrap_or(0);
-                        if selected > 0 {
-                            self.search_state.select(Some(selected - 1));
+            },
+            // Lists loaded a page at a time are kept once scrolled to the end
+            move |cache, page| {
+                if let (Some(id), None) = (written_id, page.next) {
+                    cache.save_tracks(&id, &page.tracks);
+                }
+            },
+            async move {
+                let mut cached = cached;
+                if recent {
+                    let stored = tokio::task::spawn_blocking(move || {
+                        library_cache.recently_added(RECENTLY_ADDED_LIMIT)
+                    });
+                    cached.extend(stored.await.unwrap_or_default());
+                }
+                fetch_first_page(&client, &fetched_source, cached).await
+            },
+            move |result, cached| Loaded::Tracks {
+                request,
+                source: source.clone(),
+                result,
+                cached,
+            },
+        );
+    }
+
+    /// Sends commands to the worker from outside the interface, like media keys over MPRIS
+    #[cfg(all(target_os = "linux", feature = "mpris"))]
+    pub fn command_sender(&self) -> UnboundedSender<Command> {
+        self.commands.clone()
+    }
+
+    /// Follows the player state as it's polled
+    pub fn watch_player(&self) -> watch::Receiver<Option<CurrentlyPlaying>> {
+        self.player_updates.subscribe()
+    }
+
+    /// Hands a command to the worker task
+    fn send(&self, command: Command) {
+        // The worker only stops when the app is shutting down
+        let _ = self.commands.send(command);
+    }
+
+    /// Shows a failed request, and for failures with a remedy, offers it
+    fn report(&mut self, error: anyhow::Error) {
+        // Nothing but browsing works offline, whatever went wrong
+        if self.connection.is_offline() {
+            tracing::debug!("Failed while offline: {:#}", error);
+            self.notifications
+                .info("Offline, only the cached library can be browsed");
+            return;
+        }
+        match error.downcast_ref::<SpotifyError>() {
+            Some(SpotifyError::Network(_)) => self.go_offline(error),
+            // Usually the device went to sleep, so the others are offered instead
+            Some(SpotifyError::NoDevice) => {
+                self.notifications.error(error);
+                self.open_device_picker();
+            }
+            // Nothing else will work until the user signs in again
+            Some(SpotifyError::Auth(_)) => {
+                self.set_state(AppState::Error(format!("Signed out of Spotify: {}", error)))
+            }
+            _ => self.notifications.error(error),
+        }
+    }
+
+    /// Fetches the player state in the background, and the queue with it when `queue` is set
+    /// and the track has changed
+    fn refresh_player(&mut self, queue: bool) {
+        self.player_refresh_pending = true;
+        self.send(Command::RefreshPlayer { queue });
+    }
+
+    /// Fetches the queue in the background, once something has been added to it
+    fn refresh_queue(&self) {
+        self.send(Command::RefreshQueue);
+    }
+
+    /// Applies what the worker task has sent back since the last tick
+    fn receive_worker_events(&mut self) {
+        while let Ok(event) = self.worker_events.try_recv() {
+            match event {
+                WorkerEvent::Player(result) => {
+                    self.player_refresh_pending = false;
+                    match result {
+                        Ok(currently_playing) => {
+                            self.connection.poll_succeeded();
+                            self.player_updates.send_replace(currently_playing.clone());
+                            self.currently_playing = currently_playing;
+                            self.player_fetched_at = Instant::now();
+                            // A poll from before the last press would undo it on screen
+                            self.show_pending_volume();
+                            self.follow_radio();
+                        }
+                        // Polls fail quietly until it's clear the connection is gone
+                        Err(e) if is_network_error(&e) => {
+                            if self.connection.poll_failed() {
+                                self.go_offline(e);
+                            } else {
+                                self.notifications.record_error(e);
+                            }
                         }
+                        Err(e) => self.notifications.record_error(e),
                     }
                 }
-                KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    // Ctrl+N - Next (same as Down)
-                    if matches!(self.focused_pane, FocusedPane::Tracks)
-                        && !self.search_results.is_empty()
-                    {
-                        let selected = self.search_state.selected().unwrap_or(0);
-                        if selected < self.search_results.len() - 1 {
-                            self.search_state.select(Some(selected + 1));
-                        }
+                // Where playback is now still follows from the last state
+                WorkerEvent::PlayerUnchanged => {
+                    self.player_refresh_pending = false;
+                    self.connection.poll_succeeded();
+                }
+                WorkerEvent::Queue(result) => {
+                    if let Ok(queue) = result {
+                        self.queue = queue;
                     }
                 }
-                KeyCode::Char('+') => {
-                    if matches!(self.focused_pane, FocusedPane::Tracks) {
-                        if let Err(e) = self.add_current_track_to_queue().await {
-                            self.state = AppState::Error(e.to_string());
+                WorkerEvent::PlaybackFailed(e) => self.report(e),
+                WorkerEvent::Devices(result) => match result {
+                    Ok(devices) if devices.is_empty() => self.notifications.error(
+                        "No Spotify devices found. Open Spotify on your phone, computer, or web browser.",
+                    ),
+                    Ok(devices) => {
+                        let active = devices.iter().position(|device| device.is_active);
+                        self.devices = devices;
+                        self.device_picker_state.select(Some(active.unwrap_or(0)));
+                        self.show_device_picker = true;
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                WorkerEvent::Transferred { name, result } => match result {
+                    Ok(_) => self.notifications.success(format!("Playing on {}", name)),
+                    Err(e) => self.notifications.error(e),
+                },
+            }
+        }
+    }
+
+    /// The already fetched tracks a source is built from, only Recently Added has any. The
+    /// library cache has playlists from earlier runs too, read off the interface's thread
+    fn cached_tracks_for(&self, source: &TrackSource) -> Vec<Track> {
+        match source {
+            TrackSource::RecentlyAdded => self.track_cache.values().flatten().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Applies the results of background fetches that have finished
+    fn receive_loaded(&mut self) {
+        while let Ok(loaded) = self.loaded_rx.try_recv() {
+            match loaded {
+                Loaded::Tracks {
+                    request,
+                    source,
+                    result,
+                    cached,
+                } => {
+                    // A newer fetch has replaced this one
+                    if request != self.tracks_request {
+                        continue;
+                    }
+                    self.loading_tracks = None;
+                    match result {
+                        Ok(page) => {
+                            if let (TrackSource::Playlist(id), None) = (&source, page.next) {
+                                self.track_cache.insert(id.clone(), page.tracks.clone());
+                            }
+                            let uris: Vec<String> =
+                                page.tracks.iter().map(|track| track.uri.clone()).collect();
+                            if cached {
+                                self.revalidating = Some(uris);
+                            } else if let Some(shown) = self.revalidating.take() {
+                                // The cached copy stays unless something changed, and the
+                                // cursor stays where it was either way. A first page can only
+                                // be checked against the start of it
+                                if shown.len() == page.total && shown.starts_with(&uris) {
+                                    continue;
+                                }
+                                self.restore_selection = self.tracks_state.selected();
+                            }
+                            self.set_current_tracks(source, page.tracks);
+                            self.tracks_next = page.next;
+                            if let Some(selected) = self.restore_selection.take() {
+                                let last = self.track_order.len().saturating_sub(1);
+                                self.tracks_state.select(Some(selected.min(last)));
+                            }
+                            if let Some(offset) = self.restore_offset.take() {
+                                *self.tracks_state.offset_mut() = offset;
+                            }
+                        }
+                        Err(e) => {
+                            self.revalidating = None;
+                            self.notifications.error(e);
                         }
                     }
                 }
-                KeyCode::Char(c) => {
-                    if matches!(self.focused_pane, FocusedPane::SearchInput) {
-                        self.search_input.push(c);
-                        // Start debounce timer
-                        self.last_search_time = Some(std::time::Instant::now());
+                Loaded::MoreTracks {
+                    request,
+                    list_id,
+                    offset,
+                    result,
+                } => {
+                    self.loading_more = false;
+                    // The pane shows something else now, or already has this page
+                    if request != self.tracks_request || self.tracks_next != Some(offset) {
+                        continue;
                     }
-                }
-                KeyCode::Backspace => {
-                    if matches!(self.focused_pane, FocusedPane::SearchInput) {
-                        self.search_input.pop();
-                        if self.search_input.is_empty() {
-                            // Clear results immediately if search input is empty
-                            self.search_results.clear();
-                            self.last_search_time = None;
-                        } else {
-                            // Start debounce timer
-                            self.last_search_time = Some(std::time::Instant::now());
+                    match result {
+                        Ok(page) => {
+                            self.tracks_next = page.next;
+                            self.append_tracks(false, page.tracks);
+                            if self.tracks_next.is_none() {
+                                self.remember_tracks(list_id);
+                            }
+                        }
+                        Err(e) => {
+                            self.tracks_next = None;
+                            self.report(e);
                         }
                     }
                 }
-                KeyCode::Up => {
-                    if matches!(self.focused_pane, FocusedPane::Tracks)
-                        && !self.search_results.is_empty()
-                    {
-                        let selected = self.search_state.selected().unwrap_or(0);
-                        if selected > 0 {
-                            self.search_state.select(Some(selected - 1));
-                        }
+                Loaded::SearchResults {
+                    generation,
+                    query,
+                    result,
+                } => {
+                    // The query has changed since, its own search is on the way
+                    if generation != self.search_generation || !self.show_search {
+                        continue;
+                    }
+                    self.search_task = None;
+                    if let Ok(page) = result {
+                        self.search_results = page.tracks;
+                        self.search_next = page.next.map(|next| (query, next));
+                        self.clear_marks();
+                        self.update_track_order();
+                        // Don't auto-select first result, let user navigate first
+                        self.search_state.select(None);
                     }
                 }
-                KeyCode::Down => {
-                    if matches!(self.focused_pane, FocusedPane::Tracks)
-                        && !self.search_results.is_empty()
-                    {
-                        let selected = self.search_state.selected().unwrap_or(0);
-                        if selected < self.search_results.len() - 1 {
-                            self.search_state.select(Some(selected + 1));
+                Loaded::MoreSearchResults {
+                    query,
+                    offset,
+                    result,
+                } => {
+                    self.loading_more = false;
+                    let expected = self
+                        .search_next
+                        .as_ref()
+                        .is_some_and(|(next_query, next)| *next_query == query && *next == offset);
+                    if !expected || query != self.search_input.text() {
+                        continue;
+                    }
+                    match result {
+                        Ok(page) => {
+                            self.search_next = page.next.map(|next| (query, next));
+                            self.append_tracks(true, page.tracks);
+                        }
+                        Err(e) => {
+                            self.search_next = None;
+                            self.report(e);
                         }
                     }
                 }
-                _ => {}
-            }
-        } else {
-            match key.code {
-                KeyCode::Char('q') => {
-                    self.should_quit = true;
-                }
-                KeyCode::Char('s') => {
-                    self.show_search = true;
-                    self.search_input.clear();
-                    self.search_results.clear();
-                    self.focused_pane = FocusedPane::SearchInput;
-                }
-                KeyCode::Char(' ') => {
-                    self.show_playback_controls = true;
-                    self.playback_controls_state.select(Some(0));
-                }
-                KeyCode::Char('?') => {
-                    self.show_help = true;
-                }
-                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    // Ctrl+P - Previous (same as Up)
-                    match self.focused_pane {
-                        FocusedPane::Playlists => {
-                            if !self.playlists.is_empty() {
-                                let selected = self.playlists_state.selected().unwrap_or(0);
-                                if selected > 0 {
-                                    self.playlists_state.select(Some(selected - 1));
-                                    self.load_playlist_tracks(selected - 1).await?;
-                                }
+                Loaded::Playlists { result, cached } => {
+                    let changed = match result {
+                        Ok(playlists) => {
+                            let changed = !same_playlists(&self.playlists, &playlists);
+                            if changed {
+                                self.playlists = playlists;
                             }
-                        }
-                        FocusedPane::Tracks => {
-                            if self.show_search && !self.search_results.is_empty() {
-                                if let Some(selected) = self.search_state.selected() {
-                                    if selected > 0 {
-                                        self.search_state.select(Some(selected - 1));
-                                    }
-                                }
-                            } else if !self.current_tracks.is_empty() {
-                                let selected = self.tracks_state.selected().unwrap_or(0);
-                                if selected > 0 {
-                                    self.tracks_state.select(Some(selected - 1));
+                            // Only worth doing with an up to date list
+                            if !cached {
+                                if self.config.features.for_you {
+                                    self.search_for_you_playlists();
                                 }
+                                self.archive_discover_weekly();
                             }
+                            changed
+                        }
+                        Err(e) if self.playlists.is_empty() && !self.connection.is_offline() => {
+                            self.set_state(AppState::Error(format!(
+                                "Failed to load playlists: {}",
+                                e
+                            )));
+                            false
                         }
-                        FocusedPane::SearchInput => {
-                            // No action for search input pane
+                        // The cached list is still up
+                        Err(e) => {
+                            self.notifications.error(e);
+                            false
                         }
+                    };
+                    if changed || self.loading_tabs.contains(&LibraryTab::Playlists) {
+                        self.library_tab_loaded(LibraryTab::Playlists);
                     }
                 }
-                KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    // Ctrl+N - Next (same as Down)
-                    match self.focused_pane {
-                        FocusedPane::Playlists => {
-                            if !self.playlists.is_empty() {
-                                let selected = self.playlists_state.selected().unwrap_or(0);
-                                if selected < self.playlists.len() - 1 {
-                                    self.playlists_state.select(Some(selected + 1));
-                                    self.load_playlist_tracks(selected + 1).await?;
-                                }
-                            }
-                        }
-                        FocusedPane::Tracks => {
-                            if self.show_search && !self.search_results.is_empty() {
-                                if let Some(selected) = self.search_state.selected() {
-                                    if selected < self.search_results.len() - 1 {
-                                        self.search_state.select(Some(selected + 1));
-                                    }
-                                }
-                            } else if !self.current_tracks.is_empty() {
-                                let selected = self.tracks_state.selected().unwrap_or(0);
-                                if selected < self.current_tracks.len() - 1 {
-                                    self.tracks_state.select(Some(selected + 1));
-                                }
-                            }
+                Loaded::TokenRefreshed(result) => {
+                    if let Err(e) = result {
+                        self.report(e);
+                    }
+                }
+                Loaded::Reconnected(result) => {
+                    match result {
+                        Ok(_) => {
+                            self.connection.reconnected();
+                            self.notifications.success("Back online");
+                            self.fetch_library_tab(LibraryTab::Playlists);
+                            self.refresh_player(true);
+                            self.refresh_queue();
+                        }
+                        // Still unreachable, so the next try waits longer
+                        Err(e) if is_network_error(&e) => {
+                            let wait = self.connection.reconnect_failed();
+                            tracing::debug!(?wait, "Still offline: {:#}", e);
                         }
-                        FocusedPane::SearchInput => {
-                            // No action for search input pane
+                        // Reachable but signed out, which offline browsing can't fix
+                        Err(e) => {
+                            self.connection.signed_out();
+                            self.set_state(AppState::Error(format!(
+                                "Signed out of Spotify: {}",
+                                e
+                            )));
                         }
                     }
                 }
-                KeyCode::Tab => {
-                    self.focused_pane = match self.focused_pane {
-                        FocusedPane::Playlists => FocusedPane::Tracks,
-                        FocusedPane::Tracks => {
-                            if self.show_search {
-                                FocusedPane::SearchInput
-                            } else {
-                                FocusedPane::Playlists
+                #[cfg(feature = "librespot")]
+                Loaded::LocalDevice(result) => match result {
+                    // Something's playing elsewhere already, which is left alone
+                    Ok(_)
+                        if self
+                            .currently_playing
+                            .as_ref()
+                            .is_some_and(|p| p.is_playing) => {}
+                    Ok(Device {
+                        id: Some(device_id),
+                        name,
+                        ..
+                    }) => self.send(Command::TransferPlayback { device_id, name }),
+                    Ok(_) => {}
+                    Err(e) => self.notifications.error(e),
+                },
+                // Only a shortcut, so a failure isn't worth a toast
+                Loaded::ForYou(result) => match result {
+                    Ok(found) => {
+                        let selected = self.selected_library_index();
+                        for playlist in found {
+                            if !self.playlists.iter().any(|p| p.id == playlist.id) {
+                                self.playlists.push(playlist);
                             }
                         }
-                        FocusedPane::SearchInput => FocusedPane::Playlists,
-                    };
-                }
-                KeyCode::Up => {
-                    match self.focused_pane {
-                        FocusedPane::Playlists => {
-                            if !self.playlists.is_empty() {
-                                let selected = self.playlists_state.selected().unwrap_or(0);
-                                if selected > 0 {
-                                    self.playlists_state.select(Some(selected - 1));
-                                    self.load_playlist_tracks(selected - 1).await?;
-                                }
+                        self.archive_discover_weekly();
+                        self.update_library_order();
+                        // Keep the cursor on the playlist it was on as the list reshuffles
+                        if self.library_tab == LibraryTab::Playlists {
+                            if let Some(selected) = selected {
+                                let position =
+                                    self.library_order.iter().position(|&i| i == selected);
+                                self.library_state_mut().select(position);
                             }
                         }
-                        FocusedPane::Tracks => {
-                            if self.show_search && !self.search_results.is_empty() {
-                                if let Some(selected) = self.search_state.selected() {
-                                    if selected > 0 {
-                                        self.search_state.select(Some(selected - 1));
-                                    }
-                                }
-                            } else if !self.current_tracks.is_empty() {
-                                let selected = self.tracks_state.selected().unwrap_or(0);
-                                if selected > 0 {
-                                    self.tracks_state.select(Some(selected - 1));
-                                }
+                    }
+                    Err(e) => self.notifications.record_error(e),
+                },
+                Loaded::Archived { week, result } => match result {
+                    Ok(playlist) => {
+                        self.notifications
+                            .success(format!("Archived Discover Weekly to {}", playlist.name));
+                        self.playlists.push(playlist);
+                        self.update_library_order();
+                    }
+                    Err(e) => {
+                        // Try again next time round
+                        if self.local_state.last_archived_week.as_ref() == Some(&week) {
+                            self.local_state.last_archived_week = None;
+                            if let Err(e) = self.local_state.save() {
+                                self.notifications.record_error(e);
                             }
                         }
-                        FocusedPane::SearchInput => {
-                            // No action for search input pane
-                        }
+                        self.notifications
+                            .error(format!("Failed to archive Discover Weekly: {}", e));
                     }
-                }
-                KeyCode::Down => {
-                    match self.focused_pane {
-                        FocusedPane::Playlists => {
-                            if !self.playlists.is_empty() {
-                                let selected = self.playlists_state.selected().unwrap_or(0);
-                                if selected < self.playlists.len() - 1 {
-                                    self.playlists_state.select(Some(selected + 1));
-                                    self.load_playlist_tracks(selected + 1).await?;
-                                }
+                },
+                Loaded::QueueSaved(result) => match result {
+                    Ok(playlist) => {
+                        self.notifications.success(format!(
+                            "Saved {} from the queue to {}",
+                            describe_count(playlist.tracks.total as usize),
+                            playlist.name
+                        ));
+                        self.playlists.push(playlist);
+                        self.update_library_order();
+                    }
+                    Err(e) => self
+                        .notifications
+                        .error(format!("Failed to save the queue: {}", e)),
+                },
+                Loaded::Albums { result, cached } => {
+                    let changed = match result {
+                        Ok(albums) => {
+                            let shown = self.saved_albums.iter().flatten().map(|a| &a.id);
+                            let changed = !shown.eq(albums.iter().map(|a| &a.id));
+                            if changed || self.saved_albums.is_none() {
+                                self.saved_albums = Some(albums);
                             }
+                            changed
                         }
-                        FocusedPane::Tracks => {
-                            if self.show_search && !self.search_results.is_empty() {
-                                if let Some(selected) = self.search_state.selected() {
-                                    if selected < self.search_results.len() - 1 {
-                                        self.search_state.select(Some(selected + 1));
-                                    }
-                                }
-                            } else if !self.current_tracks.is_empty() {
-                                let selected = self.tracks_state.selected().unwrap_or(0);
-                                if selected < self.current_tracks.len() - 1 {
-                                    self.tracks_state.select(Some(selected + 1));
-                                }
+                        Err(e) => {
+                            self.notifications.error(e);
+                            false
+                        }
+                    };
+                    // A cached list stays up until the fetched one differs from it
+                    if changed || !cached && self.loading_tabs.contains(&LibraryTab::Albums) {
+                        self.library_tab_loaded(LibraryTab::Albums);
+                    }
+                }
+                Loaded::Artists { result, cached } => {
+                    let changed = match result {
+                        Ok(artists) => {
+                            let shown = self.followed_artists.iter().flatten().map(|a| &a.id);
+                            let changed = !shown.eq(artists.iter().map(|a| &a.id));
+                            if changed || self.followed_artists.is_none() {
+                                self.followed_artists = Some(artists);
                             }
+                            changed
                         }
-                        FocusedPane::SearchInput => {
-                            // No action for search input pane
+                        Err(e) => {
+                            self.notifications.error(e);
+                            false
                         }
+                    };
+                    if changed || !cached && self.loading_tabs.contains(&LibraryTab::Artists) {
+                        self.library_tab_loaded(LibraryTab::Artists);
                     }
                 }
-                KeyCode::Enter => {
-                    match self.focused_pane {
-                        FocusedPane::Tracks => {
-                            if self.show_search {
-                                if let Some(selected) = self.search_state.selected() {
-                                    if selected < self.search_results.len() {
-                                        let track = &self.search_results[selected];
-                                        if let Err(e) =
-                                            self.spotify_client.play_track(&track.uri).await
-                                        {
-                                            self.state = AppState::Error(e.to_string());
-                                        }
-                                    }
-                                }
-                            } else if let Some(selected) = self.tracks_state.selected() {
-                                if selected < self.current_tracks.len() {
-                                    let track = &self.current_tracks[selected];
-                                    if let Err(e) = self.spotify_client.play_track(&track.uri).await
-                                    {
-                                        self.state = AppState::Error(e.to_string());
-                                    }
-                                }
-                            }
+                Loaded::Shows(result) => {
+                    match result {
+                        Ok(shows) => self.saved_shows = Some(shows),
+                        Err(e) => self.notifications.error(e),
+                    }
+                    self.library_tab_loaded(LibraryTab::Podcasts);
+                }
+                // Only shown in the playlist header, so a failure isn't worth a toast
+                Loaded::PlaylistFollowers {
+                    playlist_id,
+                    result,
+                } => match result {
+                    Ok(followers) => {
+                        self.playlist_followers.insert(playlist_id, followers);
+                    }
+                    Err(e) => self.notifications.record_error(e),
+                },
+                Loaded::SplitTracks {
+                    playlist_id,
+                    result,
+                } => {
+                    // The split view was closed or moved to another playlist meanwhile
+                    let Some(split) = self
+                        .split
+                        .as_mut()
+                        .filter(|split| split.playlist_id == playlist_id)
+                    else {
+                        continue;
+                    };
+                    match result {
+                        Ok(tracks) => {
+                            self.track_cache.insert(playlist_id, tracks.clone());
+                            split.tracks = Some(tracks);
                         }
-                        FocusedPane::SearchInput => {
-                            // Enter in search input focuses tracks pane
-                            if !self.search_results.is_empty() {
-                                self.focused_pane = FocusedPane::Tracks;
-                                // Select first result when focusing tracks pane
-                                self.search_state.select(Some(0));
+                        Err(e) => {
+                            split.tracks = Some(Vec::new());
+                            self.notifications.error(e);
+                        }
+                    }
+                }
+                Loaded::Radio {
+                    generation,
+                    name,
+                    seeds,
+                    first,
+                    result,
+                } => {
+                    if generation != self.radio_generation {
+                        continue;
+                    }
+                    match result {
+                        Ok(tracks) => self.play_radio(name, seeds, first, tracks),
+                        Err(e) => self.report(e),
+                    }
+                }
+                Loaded::RadioTopUp { generation, result } => {
+                    if generation != self.radio_generation {
+                        continue;
+                    }
+                    match result {
+                        Ok(tracks) => self.top_up_radio(tracks),
+                        // Tried again on the next poll
+                        Err(e) => {
+                            if let Some(radio) = &mut self.radio {
+                                radio.topping_up = false;
                             }
+                            self.notifications.record_error(e);
                         }
-                        _ => {}
                     }
                 }
-                KeyCode::Char('+') => {
-                    if matches!(self.focused_pane, FocusedPane::Tracks) {
-                        if let Err(e) = self.add_current_track_to_queue().await {
-                            self.state = AppState::Error(e.to_string());
+                Loaded::QueueTracks { name, result } => match result {
+                    Ok(tracks) if tracks.is_empty() => self
+                        .notifications
+                        .info(format!("Nothing to add from {}", name)),
+                    Ok(tracks) => {
+                        self.notifications.info(format!(
+                            "Adding {} from {} to queue",
+                            describe_count(tracks.len()),
+                            name
+                        ));
+                        self.enqueue_batch(tracks);
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::Genres(result) => match result {
+                    Ok(genres) => {
+                        self.genres = Some(genres);
+                        self.open_genre_picker();
+                    }
+                    Err(e) => self.report(e),
+                },
+                Loaded::Comparison(result) => match result {
+                    Ok(comparison) => self.comparison = Some(comparison),
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::Stats(result) => match result {
+                    Ok(stats) => self.stats = Some(stats),
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::Tempos(result) => match result {
+                    Ok(features) => {
+                        self.tempos.extend(
+                            features
+                                .into_iter()
+                                .map(|features| (features.id, features.tempo)),
+                        );
+                        self.resort_keeping_selection();
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                // Nobody is waiting on these, so a failure is only logged
+                Loaded::Prefetched {
+                    playlist_id,
+                    result,
+                } => {
+                    self.prefetching.remove(&playlist_id);
+                    match result {
+                        // Opening the playlist meanwhile will have fetched it fresher
+                        Ok(tracks) => {
+                            self.track_cache.entry(playlist_id).or_insert(tracks);
                         }
+                        Err(e) => tracing::debug!("Prefetching {} failed: {:#}", playlist_id, e),
                     }
                 }
-                _ => {}
+                Loaded::Backup(result) => {
+                    self.backing_up = false;
+                    match result {
+                        Ok(backup) => self.notifications.success(backup.describe()),
+                        Err(e) => self
+                            .notifications
+                            .error(format!("Failed to back up the library: {}", e)),
+                    }
+                }
+                Loaded::ImportMatches { name, result } => match result {
+                    Ok(matches) => self.review_import(name, matches),
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::ImportTarget {
+                    name,
+                    not_found,
+                    result,
+                } => match result {
+                    Ok((created, playlist_id, uris)) => {
+                        if let Some(playlist) = created {
+                            self.playlists.push(playlist);
+                            self.update_library_order();
+                        }
+                        if not_found > 0 {
+                            self.notifications.info(format!(
+                                "Couldn't find {} to import",
+                                describe_count(not_found)
+                            ));
+                        }
+                        if uris.is_empty() {
+                            self.notifications
+                                .info(format!("{} already has every track", name));
+                        } else {
+                            self.merge_batch = Some(MergeBatch::new(playlist_id, name, uris));
+                        }
+                    }
+                    Err(e) => self
+                        .notifications
+                        .error(format!("Failed to import into {}: {}", name, e)),
+                },
+                Loaded::MergeTracks {
+                    playlist_id,
+                    playlist_name,
+                    source_name,
+                    result,
+                } => match result {
+                    Ok(uris) if uris.is_empty() => self.notifications.info(format!(
+                        "{} has nothing {} doesn't already",
+                        source_name, playlist_name
+                    )),
+                    Ok(uris) => {
+                        self.merge_batch = Some(MergeBatch::new(playlist_id, playlist_name, uris));
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::Queued { track, result } => self.track_queued(track, result),
+                Loaded::UrisQueued(result) => match result {
+                    Ok(_) => self.refresh_queue(),
+                    Err(e) => self.report(e),
+                },
+                Loaded::Merged { chunk, result } => self.chunk_merged(chunk, result),
+                Loaded::Liked {
+                    ids,
+                    replayed,
+                    result,
+                } => match result {
+                    Ok(_) => {
+                        if !replayed {
+                            self.clear_marks();
+                            self.push_undo(Undoable::Liked(ids.clone()));
+                        }
+                        self.notifications.success(format!(
+                            "Added {} to Liked Songs",
+                            describe_count(ids.len())
+                        ));
+                    }
+                    Err(e) if replayed && is_network_error(&e) => {
+                        // Gone again, so this waits for the next reconnect
+                        self.offline_actions.push(OfflineAction::Like(ids));
+                        self.go_offline(e);
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::PlaylistDeleted {
+                    playlist_id,
+                    playlist_name,
+                    result,
+                } => match result {
+                    Ok(_) => {
+                        // The library may have been reloaded since it was asked for
+                        let index = self.playlists.iter().position(|p| p.id == playlist_id);
+                        if let Some(index) = index {
+                            let playlist = self.playlist_deleted(index);
+                            self.push_undo(Undoable::DeletedPlaylist { index, playlist });
+                        }
+                        self.notifications
+                            .success(format!("Deleted the playlist {}", playlist_name));
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::AddedToPlaylist {
+                    playlist_id,
+                    playlist_name,
+                    uris,
+                    then,
+                    result,
+                } => match result {
+                    Ok(_) => self.added_to_playlist(playlist_id, playlist_name, uris, then),
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::TracksRemoved {
+                    playlist_id,
+                    playlist_name,
+                    uris,
+                    result,
+                } => match result {
+                    Ok(_) => {
+                        let removed = self.tracks_removed(&playlist_id, &uris);
+                        self.notifications.success(format!(
+                            "Removed {} from {}",
+                            describe_count(uris.len()),
+                            playlist_name
+                        ));
+                        self.push_undo(Undoable::RemovedFromPlaylist {
+                            playlist_id,
+                            playlist_name,
+                            removed,
+                        });
+                    }
+                    Err(e) => self.notifications.error(e),
+                },
+                Loaded::Undone { action, result } => self.action_undone(action, result),
             }
         }
+    }
 
-        if self.show_search
-            && matches!(self.focused_pane, FocusedPane::Tracks)
-            && key.code == KeyCode::Enter
-        {
-            if let Some(selected) = self.search_state.selected() {
-                if selected < self.search_results.len() {
-                    let track = &self.search_results[selected];
-                    if let Err(e) = self.spotify_client.play_track(&track.uri).await {
-                        self.state = AppState::Error(e.to_string());
+    /// Looks up the For You playlists the library doesn't have, like a Discover Weekly that was
+    /// never followed
+    fn search_for_you_playlists(&mut self) {
+        let missing: Vec<&'static str> = FOR_YOU_SEARCHED
+            .into_iter()
+            .filter(|name| {
+                !self
+                    .playlists
+                    .iter()
+                    .any(|p| is_for_you(p) && p.name.starts_with(name))
+            })
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let search = async {
+                let mut found = Vec::new();
+                for name in missing {
+                    let playlists = client
+                        .search_playlists(name)
+                        .await?
+                        .into_iter()
+                        .filter(|playlist| is_for_you(playlist) && playlist.name.starts_with(name));
+                    for playlist in playlists {
+                        if !found.iter().any(|p: &Playlist| p.id == playlist.id) {
+                            found.push(playlist);
+                        }
                     }
                 }
+                Ok(found)
+            };
+            Loaded::ForYou(search.await)
+        });
+    }
+
+    /// Copies Discover Weekly into a playlist named after the week, like "DW 2024-06-03", once a
+    /// week when `archive_discover_weekly` is on
+    fn archive_discover_weekly(&mut self) {
+        if !self.config.behavior.archive_discover_weekly {
+            return;
+        }
+        let week = week_start();
+        if self.local_state.last_archived_week.as_ref() == Some(&week) {
+            return;
+        }
+        let Some(discover_weekly) = self
+            .playlists
+            .iter()
+            .find(|playlist| is_for_you(playlist) && playlist.name == "Discover Weekly")
+        else {
+            return;
+        };
+        let source_id = discover_weekly.id.clone();
+
+        // Claimed up front so a second trigger doesn't archive the week twice
+        self.local_state.last_archived_week = Some(week.clone());
+        if let Err(e) = self.local_state.save() {
+            self.notifications.record_error(e);
+        }
+        let name = format!("DW {}", week);
+        // Archived in an earlier run whose state was lost
+        if self.playlists.iter().any(|playlist| playlist.name == name) {
+            return;
+        }
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let archive = async {
+                let uris: Vec<String> = client
+                    .get_playlist_tracks(&source_id)
+                    .await?
+                    .into_iter()
+                    .map(|track| track.uri)
+                    .collect();
+                let description = format!("Discover Weekly for the week of {}", week);
+                let mut playlist = client.create_playlist(&name, &description).await?;
+                client
+                    .add_tracks_to_playlist(&playlist.id, &uris, None)
+                    .await?;
+                playlist.tracks.total = uris.len() as u32;
+                Ok(playlist)
+            };
+            Loaded::Archived {
+                result: archive.await,
+                week,
+            }
+        });
+    }
+
+    /// Saves what's playing and the tracks queued after it as a new playlist, as Spotify's
+    /// queue can't be kept otherwise
+    fn save_queue(&mut self) {
+        let name = queue_playlist_name();
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let save = async {
+                let queue = client
+                    .get_queue()
+                    .await?
+                    .ok_or_else(|| anyhow!("Nothing is playing"))?;
+                let uris: Vec<String> = queue
+                    .currently_playing
+                    .into_iter()
+                    .chain(queue.queue)
+                    .map(|track| track.uri)
+                    .collect();
+                if uris.is_empty() {
+                    return Err(anyhow!("The queue is empty"));
+                }
+                let mut playlist = client
+                    .create_playlist(&name, "Saved from the queue with spotitui")
+                    .await?;
+                client
+                    .add_tracks_to_playlist(&playlist.id, &uris, None)
+                    .await?;
+                playlist.tracks.total = uris.len() as u32;
+                Ok(playlist)
+            };
+            Loaded::QueueSaved(save.await)
+        });
+    }
+
+    /// Shows a library tab's freshly fetched entries, loading the selected one if the tab is open
+    fn library_tab_loaded(&mut self, tab: LibraryTab) {
+        self.loading_tabs.retain(|&loading| loading != tab);
+        // For You is made from the playlists
+        let shown = tab == self.library_tab
+            || (tab == LibraryTab::Playlists && self.library_tab == LibraryTab::ForYou);
+        if shown {
+            self.update_library_order();
+            if self.pending_session.is_some() {
+                self.restore_session();
+            } else if let Some(index) = self.selected_library_index() {
+                self.load_library_item(index);
             }
         }
+    }
 
-        Ok(())
+    /// Picks up where the last run left off, moving to the library tab it was on first if
+    /// that isn't the one that just loaded. Anything that's gone since is left at its default
+    fn restore_session(&mut self) {
+        let Some(session) = self.pending_session.take() else {
+            return;
+        };
+        let tab = LibraryTab::ALL
+            .into_iter()
+            .find(|tab| tab.label() == session.library_tab)
+            .unwrap_or(LibraryTab::Playlists);
+        if tab != self.library_tab {
+            self.library_tab = tab;
+            self.pending_session = Some(session);
+            // Liked Songs and For You have nothing to fetch to list
+            if matches!(tab, LibraryTab::ForYou | LibraryTab::Liked) {
+                self.library_tab_loaded(tab);
+            } else {
+                self.fetch_library_tab(tab);
+            }
+            return;
+        }
+
+        let position = session.library_item.as_deref().and_then(|id| {
+            self.library_order
+                .iter()
+                .position(|&index| self.library_item_id(index).as_deref() == Some(id))
+        });
+        if let Some(position) = position {
+            let state = self.library_state_mut();
+            state.select(Some(position));
+            *state.offset_mut() = session.library_offset;
+        }
+        let source = session
+            .open
+            .and_then(|view| self.source_of(view))
+            .or_else(|| {
+                let index = self.selected_library_index()?;
+                self.library_source(index)
+            });
+        if let Some(source) = source {
+            self.nav_history.clear();
+            self.load_tracks(source);
+            self.restore_selection = session.track_row;
+            self.restore_offset = Some(session.track_offset);
+        }
+        if session.tracks_focused {
+            self.focused_pane = FocusedPane::Tracks;
+        }
     }
 
-    pub fn get_display_tracks(&self) -> &Vec<Track> {
-        if self.show_search {
-            &self.search_results
-        } else {
-            &self.current_tracks
+    /// Notes where the app was left in the state file, for restore_session on the next run.
+    /// A session that was never picked up is kept as it was
+    fn save_session(&mut self) {
+        if self.pending_session.is_some() {
+            return;
+        }
+        let library_item = self
+            .selected_library_index()
+            .and_then(|index| self.library_item_id(index));
+        let open = self
+            .track_source
+            .as_ref()
+            .and_then(|source| self.open_view(source));
+        self.local_state.session = Some(Session {
+            library_tab: self.library_tab.label().to_string(),
+            library_item,
+            library_offset: self.library_state().offset(),
+            open,
+            track_row: self.tracks_state.selected(),
+            track_offset: self.tracks_state.offset(),
+            tracks_focused: matches!(self.focused_pane, FocusedPane::Tracks | FocusedPane::Split),
+        });
+        // The interface is on its way out, so there's nowhere to show this
+        if let Err(e) = self.local_state.save() {
+            tracing::warn!("Failed to save the session: {:#}", e);
         }
     }
 
-    async fn update_currently_playing(&mut self) {
-        if let Ok(currently_playing) = self.spotify_client.get_currently_playing().await {
-            self.currently_playing = currently_playing;
+    /// Id of an entry in the active library tab, which stays put as the library changes
+    fn library_item_id(&self, index: usize) -> Option<String> {
+        match self.library_tab {
+            LibraryTab::Playlists => self.playlists.get(index).map(|p| p.id.clone()),
+            LibraryTab::ForYou => self
+                .playlists
+                .get(self.for_you_playlist(index)?)
+                .map(|p| p.id.clone()),
+            LibraryTab::Albums => self.saved_albums.as_ref()?.get(index).map(|a| a.id.clone()),
+            LibraryTab::Artists => self
+                .followed_artists
+                .as_ref()?
+                .get(index)
+                .map(|a| a.id.clone()),
+            LibraryTab::Podcasts => self.saved_shows.as_ref()?.get(index).map(|s| s.id.clone()),
+            LibraryTab::Liked => ["liked", "recently-added"]
+                .get(index)
+                .map(|id| id.to_string()),
         }
     }
 
-    async fn update_queue(&mut self) {
-        if let Ok(queue) = self.spotify_client.get_queue().await {
-            self.queue = queue;
+    fn open_view(&self, source: &TrackSource) -> Option<OpenView> {
+        Some(match source {
+            TrackSource::Playlist(id) => OpenView::Playlist {
+                id: self.playlist(id)?.id.clone(),
+            },
+            TrackSource::Album(album) => OpenView::Album(album.clone()),
+            TrackSource::Artist(artist) => OpenView::Artist(artist.clone()),
+            TrackSource::Show(show) => OpenView::Show(show.clone()),
+            TrackSource::Liked => OpenView::Liked,
+            TrackSource::RecentlyAdded => OpenView::RecentlyAdded,
+        })
+    }
+
+    fn source_of(&self, view: OpenView) -> Option<TrackSource> {
+        Some(match view {
+            OpenView::Playlist { id } => TrackSource::Playlist(self.playlist(&id)?.id.clone()),
+            OpenView::Album(album) => TrackSource::Album(album),
+            OpenView::Artist(artist) => TrackSource::Artist(artist),
+            OpenView::Show(show) => TrackSource::Show(show),
+            OpenView::Liked => TrackSource::Liked,
+            OpenView::RecentlyAdded => TrackSource::RecentlyAdded,
+        })
+    }
+
+    /// Shows the tracks of an entry in the active library tab, by index into that tab's items
+    fn load_library_item(&mut self, index: usize) {
+        if let Some(source) = self.library_source(index) {
+            // Opening something from the library starts a new trail of breadcrumbs
+            self.nav_history.clear();
+            self.load_tracks(source);
         }
     }
 
-    async fn check_pending_search(&mut self) {
-        if let Some(last_search_time) = self.last_search_time {
-            if last_search_time.elapsed() >= Duration::from_millis(self.search_debounce_ms) {
-                self.last_search_time = None;
-                if !self.search_input.is_empty() {
-                    if let Ok(results) = self.spotify_client.search_tracks(&self.search_input).await
-                    {
-                        self.search_results = results;
-                        // Don't auto-select first result, let user navigate first
-                        self.search_state.select(None);
-                    }
+    /// Fetches the tracks of the playlists either side of the selected one in the background,
+    /// so moving onto them shows their tracks without waiting. Low power mode goes without
+    fn prefetch_neighbours(&mut self) {
+        if self.connection.is_offline()
+            || self.config.polling.low_power
+            || self.library_tab != LibraryTab::Playlists
+        {
+            return;
+        }
+        let Some(selected) = self.library_state().selected() else {
+            return;
+        };
+        for row in [selected.checked_sub(1), Some(selected + 1)]
+            .into_iter()
+            .flatten()
+        {
+            let Some(playlist) = self
+                .library_order
+                .get(row)
+                .and_then(|&index| self.playlists.get(index))
+            else {
+                continue;
+            };
+            let playlist_id = playlist.id.clone();
+            if self.track_cache.contains_key(&playlist_id)
+                || !self.prefetching.insert(playlist_id.clone())
+            {
+                continue;
+            }
+            let client = self.spotify_client.clone();
+            self.spawn_load(async move {
+                let result = client.get_playlist_tracks(&playlist_id).await;
+                Loaded::Prefetched {
+                    playlist_id,
+                    result,
                 }
+            });
+        }
+    }
+
+    /// Adds every track of the library entry under the cursor to the queue, fetching them in
+    /// the background first, shuffled with `shuffle_seed` when there is one
+    fn queue_selected_library_item(&mut self, shuffle_seed: Option<u64>) {
+        let Some(index) = self.selected_library_index() else {
+            return;
+        };
+        let Some(source) = self.library_source(index) else {
+            return;
+        };
+        let mut name = self.library_names().swap_remove(index);
+        if let Some(seed) = shuffle_seed {
+            name = format!("{} shuffled with seed {}", name, seed);
+        }
+        let cached = self.cached_tracks_for(&source);
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let mut result = fetch_tracks(&client, &source, cached).await;
+            if let (Ok(tracks), Some(seed)) = (&mut result, shuffle_seed) {
+                shuffle::shuffle(tracks, seed);
             }
+            Loaded::QueueTracks { name, result }
+        });
+    }
+
+    /// Adds the tracks that come after `track` on its album to the queue, fetching the album in
+    /// the background first
+    fn queue_rest_of_album(&mut self, track: Track) {
+        // Podcast episodes carry their show in place of an album
+        if track.uri.starts_with("spotify:episode:") {
+            self.notifications
+                .error("Podcast episodes aren't part of an album");
+            return;
         }
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client.get_album_tracks(&track.album).await.map(|tracks| {
+                tracks
+                    .into_iter()
+                    .skip_while(|album_track| album_track.id != track.id)
+                    .skip(1)
+                    .collect()
+            });
+            Loaded::QueueTracks {
+                name: track.album.name,
+                result,
+            }
+        });
     }
 
-    async fn handle_playback_controls_key(&mut self, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Esc => {
-                self.show_playback_controls = false;
+    /// Starts a radio of recommendations like `track`, which plays first
+    fn start_track_radio(&mut self, track: Track) {
+        if track.uri.starts_with("spotify:episode:") {
+            self.notifications
+                .error("Radio can only be started from a song");
+            return;
+        }
+        let seeds = Seeds::track(&track.id);
+        self.start_radio(track.name.clone(), seeds, Some(track));
+    }
+
+    /// Starts a radio of recommendations like the artist's music
+    fn start_artist_radio(&mut self, artist: Artist) {
+        // Podcast publishers stand in as artists without an id
+        if artist.id.is_empty() {
+            self.notifications
+                .error("Radio can only be started from an artist");
+            return;
+        }
+        let seeds = Seeds::artist(&artist.id);
+        self.start_radio(artist.name, seeds, None);
+    }
+
+    /// The artist under the cursor in the Artists tab, or the first artist of the selected
+    /// track in the tracks pane
+    fn selected_artist(&self) -> Option<Artist> {
+        match self.focused_pane {
+            FocusedPane::Library
+                if self.library_tab == LibraryTab::Artists && !self.show_search =>
+            {
+                let index = self.selected_library_index()?;
+                self.followed_artists.as_ref()?.get(index).cloned()
             }
-            KeyCode::Up => {
-                let selected = self.playback_controls_state.selected().unwrap_or(0);
-                if selected > 0 {
-                    self.playback_controls_state.select(Some(selected - 1));
-                }
+            FocusedPane::Tracks => self.selected_track()?.artists.first().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Fetches recommendations for the seeds in the background, then plays them as a radio
+    fn start_radio(&mut self, name: String, seeds: Seeds, first: Option<Track>) {
+        self.radio_generation += 1;
+        let generation = self.radio_generation;
+        self.notifications
+            .info(format!("Starting radio from {}", name));
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client.get_recommendations(&seeds, RADIO_BATCH).await;
+            Loaded::Radio {
+                generation,
+                name,
+                seeds,
+                first,
+                result,
             }
-            KeyCode::Down => {
-                let selected = self.playback_controls_state.selected().unwrap_or(0);
-                if selected < 3 {
-                    // 0: Play/Pause, 1: Previous, 2: Next, 3: Close
-                    self.playback_controls_state.select(Some(selected + 1));
-                }
+        });
+    }
+
+    fn play_radio(&mut self, name: String, seeds: Seeds, first: Option<Track>, tracks: Vec<Track>) {
+        if tracks.is_empty() {
+            self.notifications
+                .info(format!("No recommendations for {}", name));
+            return;
+        }
+        let mut uris: Vec<String> = first.into_iter().map(|track| track.uri).collect();
+        for track in tracks {
+            if !uris.contains(&track.uri) {
+                uris.push(track.uri);
             }
-            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                // Ctrl+P - Previous (same as Up)
-                let selected = self.playback_controls_state.selected().unwrap_or(0);
-                if selected > 0 {
-                    self.playback_controls_state.select(Some(selected - 1));
+        }
+        self.send(Command::Play(uris.clone()));
+        self.notifications
+            .success(format!("Playing radio from {}", name));
+        self.radio = Some(Radio {
+            name,
+            seeds,
+            uris,
+            started: false,
+            topping_up: false,
+        });
+    }
+
+    /// Ends the radio once something else plays, and tops it up when it's nearly played through
+    fn follow_radio(&mut self) {
+        let Some(radio) = &mut self.radio else {
+            return;
+        };
+        let playing = self
+            .currently_playing
+            .as_ref()
+            .and_then(|p| p.item.as_ref())
+            .map(|track| track.uri.as_str());
+        match radio
+            .uris
+            .iter()
+            .position(|uri| Some(uri.as_str()) == playing)
+        {
+            Some(position) => {
+                radio.started = true;
+                let left = radio.uris.len() - position - 1;
+                if left <= RADIO_TOP_UP_MARGIN && !radio.topping_up {
+                    radio.topping_up = true;
+                    let seeds = radio.seeds.clone();
+                    let generation = self.radio_generation;
+                    let client = self.spotify_client.clone();
+                    self.spawn_load(async move {
+                        let result = client.get_recommendations(&seeds, RADIO_BATCH).await;
+                        Loaded::RadioTopUp { generation, result }
+                    });
                 }
             }
-            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                // Ctrl+N - Next (same as Down)
-                let selected = self.playback_controls_state.selected().unwrap_or(0);
-                if selected < 3 {
-                    // 0: Play/Pause, 1: Previous, 2: Next, 3: Close
-                    self.playback_controls_state.select(Some(selected + 1));
-                }
+            None if radio.started && playing.is_some() => {
+                tracing::debug!(radio = radio.name, "Radio ended, something else is playing");
+                self.radio = None;
             }
-            KeyCode::Enter => {
-                if let Some(selected) = self.playback_controls_state.selected() {
-                    match selected {
-                        0 => {
-                            // Play/Pause
-                            if let Some(ref currently_playing) = self.currently_playing {
-                                if currently_playing.is_playing {
-                                    if let Err(e) = self.spotify_client.pause_playback().await {
-                                        self.state = AppState::Error(e.to_string());
-                                    }
-                                } else if let Err(e) = self.spotify_client.resume_playback().await {
-                                    self.state = AppState::Error(e.to_string());
-                                }
-                            } else if let Err(e) = self.spotify_client.resume_playback().await {
-                                self.state = AppState::Error(e.to_string());
-                            }
-                        }
-                        1 => {
-                            // Previous
-                            if let Err(e) = self.spotify_client.previous_track().await {
-                                self.state = AppState::Error(e.to_string());
-                            }
-                        }
-                        2 => {
-                            // Next
-                            if let Err(e) = self.spotify_client.next_track().await {
-                                self.state = AppState::Error(e.to_string());
-                            }
-                        }
-                        3 => {
-                            // Close
-                            self.show_playback_controls = false;
-                        }
-                        _ => {}
-                    }
-                }
+            None => {}
+        }
+    }
+
+    /// Queues the recommendations the radio hasn't played yet
+    fn top_up_radio(&mut self, tracks: Vec<Track>) {
+        let Some(radio) = &mut self.radio else {
+            return;
+        };
+        radio.topping_up = false;
+        let mut fresh = Vec::new();
+        for track in tracks {
+            if !radio.uris.contains(&track.uri) {
+                radio.uris.push(track.uri.clone());
+                fresh.push(track);
             }
-            _ => {}
         }
-        Ok(())
+        if fresh.is_empty() {
+            self.notifications.info(format!(
+                "Radio from {} ran out of recommendations",
+                radio.name
+            ));
+            self.radio = None;
+            return;
+        }
+        self.enqueue_batch(fresh);
     }
 
-    async fn add_current_track_to_queue(&mut self) -> Result<()> {
-        let tracks = self.get_display_tracks().clone();
-        let selected_index = if self.show_search {
-            self.search_state.selected()
+    /// What the tracks pane shows for an entry in the active library tab
+    fn library_source(&self, index: usize) -> Option<TrackSource> {
+        match self.library_tab {
+            LibraryTab::Playlists => self
+                .playlists
+                .get(index)
+                .filter(|_| !self.in_collapsed_folder(index))
+                .map(|playlist| TrackSource::Playlist(playlist.id.clone())),
+            LibraryTab::ForYou => self
+                .for_you_playlist(index)
+                .map(|index| TrackSource::Playlist(self.playlists[index].id.clone())),
+            LibraryTab::Albums => self
+                .saved_albums
+                .as_ref()
+                .and_then(|a| a.get(index))
+                .cloned()
+                .map(TrackSource::Album),
+            LibraryTab::Artists => self
+                .followed_artists
+                .as_ref()
+                .and_then(|a| a.get(index))
+                .cloned()
+                .map(TrackSource::Artist),
+            LibraryTab::Podcasts => self
+                .saved_shows
+                .as_ref()
+                .and_then(|s| s.get(index))
+                .cloned()
+                .map(TrackSource::Show),
+            LibraryTab::Liked => match index {
+                0 => Some(TrackSource::Liked),
+                _ => Some(TrackSource::RecentlyAdded),
+            },
+        }
+    }
+
+    /// Switches the library pane to another tab, fetching its contents the first time
+    fn switch_library_tab(&mut self, tab: LibraryTab) {
+        if tab == self.library_tab {
+            return;
+        }
+        if !self.library_filter.is_empty() {
+            self.clear_filter(FilterTarget::Library);
+        }
+        self.library_tab = tab;
+        self.focused_pane = FocusedPane::Library;
+
+        let fetched = match tab {
+            LibraryTab::Playlists | LibraryTab::ForYou | LibraryTab::Liked => true,
+            LibraryTab::Albums => self.saved_albums.is_some(),
+            LibraryTab::Artists => self.followed_artists.is_some(),
+            LibraryTab::Podcasts => self.saved_shows.is_some(),
+        };
+        if !fetched {
+            self.fetch_library_tab(tab);
+        }
+        self.update_library_order();
+
+        if let Some(index) = self.selected_library_index() {
+            self.load_library_item(index);
+        }
+    }
+
+    /// Names of the entries in the active library tab
+    pub fn library_names(&self) -> Vec<String> {
+        match self.library_tab {
+            LibraryTab::Playlists => self.playlists.iter().map(|p| p.name.clone()).collect(),
+            LibraryTab::ForYou => self
+                .for_you_playlists()
+                .into_iter()
+                .map(|index| self.playlists[index].name.clone())
+                .collect(),
+            LibraryTab::Albums => self
+                .saved_albums
+                .iter()
+                .flatten()
+                .map(|album| match album.artists.first() {
+                    Some(artist) => format!("{} — {}", album.name, artist.name),
+                    None => album.name.clone(),
+                })
+                .collect(),
+            LibraryTab::Artists => self
+                .followed_artists
+                .iter()
+                .flatten()
+                .map(|artist| artist.name.clone())
+                .collect(),
+            LibraryTab::Podcasts => self
+                .saved_shows
+                .iter()
+                .flatten()
+                .map(|show| show.name.clone())
+                .collect(),
+            LibraryTab::Liked => vec!["Liked Songs".to_string(), "Recently Added".to_string()],
+        }
+    }
+
+    pub fn library_state(&self) -> &ListState {
+        &self.library_states[self.library_tab.index()]
+    }
+
+    pub fn library_state_mut(&mut self) -> &mut ListState {
+        &mut self.library_states[self.library_tab.index()]
+    }
+
+    /// Whether the list shown in the tracks pane has more pages to load
+    pub fn has_more_tracks(&self) -> bool {
+        if self.show_search {
+            self.search_next.is_some()
         } else {
-            self.tracks_state.selected()
+            self.tracks_next.is_some()
+        }
+    }
+
+    /// Fetches the next page of the list shown in the tracks pane once the cursor nears the
+    /// end of what's loaded
+    fn load_more_near_end(&mut self) {
+        if self.loading_more || self.connection.is_offline() {
+            return;
+        }
+        let Some(selected) = self.selected_track_position() else {
+            return;
         };
+        // A sort covers the whole list, so every page of it is fetched while one is on
+        let sorted = self.track_sort.is_some() && !self.show_search;
+        if !sorted && selected + LOAD_MORE_MARGIN < self.track_order.len() {
+            return;
+        }
 
-        if let Some(index) = selected_index {
-            if index < tracks.len() {
-                let track = &tracks[index];
-                match self.spotify_client.add_to_queue(&track.uri).await {
-                    Ok(_) => {
-                        // Immediately update the queue to show the new addition
-                        self.update_queue().await;
-                        Ok(())
-                    }
-                    Err(e) => {
-                        self.state = AppState::Error(e.to_string());
-                        Err(e)
+        let client = self.spotify_client.clone();
+        if self.show_search {
+            let Some((query, offset)) = self.search_next.clone() else {
+                return;
+            };
+            self.loading_more = true;
+            self.spawn_load(async move {
+                let result = client.search_tracks(&query, offset).await;
+                Loaded::MoreSearchResults {
+                    query,
+                    offset,
+                    result,
+                }
+            });
+        } else {
+            let Some(offset) = self.tracks_next else {
+                return;
+            };
+            let list_id = match &self.track_source {
+                Some(TrackSource::Playlist(id)) => Some(id.clone()),
+                Some(TrackSource::Liked) => Some("liked".to_string()),
+                _ => None,
+            };
+            let Some(list_id) = list_id else {
+                return;
+            };
+            let request = self.tracks_request;
+            self.loading_more = true;
+            self.spawn_load(async move {
+                let result = client.get_playlist_tracks_page(&list_id, offset).await;
+                Loaded::MoreTracks {
+                    request,
+                    list_id,
+                    offset,
+                    result,
+                }
+            });
+        }
+    }
+
+    /// Adds a page to the end of the search results or the tracks pane, keeping the cursor on
+    /// the track it was on when that list is the one shown
+    fn append_tracks(&mut self, search: bool, tracks: Vec<Track>) {
+        let shown = search == self.show_search;
+        let selected = self
+            .selected_track_position()
+            .and_then(|row| self.track_order.get(row))
+            .copied();
+        let list = if search {
+            &mut self.search_results
+        } else {
+            &mut self.current_tracks
+        };
+        list.extend(tracks);
+        if !shown {
+            return;
+        }
+        self.update_track_order();
+        if let Some(index) = selected {
+            let row = self.track_order.iter().position(|&i| i == index);
+            self.tracks_state_mut().select(row);
+        }
+    }
+
+    /// Keeps a list that's been loaded to its end, to show straight away next time
+    fn remember_tracks(&mut self, list_id: String) {
+        let tracks = self.current_tracks.clone();
+        if list_id != "liked" {
+            self.track_cache.insert(list_id.clone(), tracks.clone());
+        }
+        let library_cache = self.library_cache.clone();
+        // Tracked so a write that's begun is finished before quitting
+        self.shutdown.track(async move {
+            let _ =
+                tokio::task::spawn_blocking(move || library_cache.save_tracks(&list_id, &tracks))
+                    .await;
+        });
+    }
+
+    /// Replaces the tracks pane contents, resetting its filter and selection
+    fn set_current_tracks(&mut self, source: TrackSource, tracks: Vec<Track>) {
+        self.current_tracks = tracks;
+        self.tracks_next = None;
+        self.track_source = Some(source);
+        self.clear_marks();
+        if self.editing_filter == Some(FilterTarget::Tracks) {
+            self.editing_filter = None;
+        }
+        self.track_filter.clear();
+        self.update_track_order();
+        self.tracks_state.select(Some(0));
+    }
+
+    async fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        // Handle error state - any key dismisses the error
+        if matches!(self.state, AppState::Error(_)) {
+            self.set_state(AppState::Ready);
+            return Ok(());
+        }
+        let key = if self.typing() {
+            key
+        } else {
+            self.remaps.apply(key)
+        };
+
+        if self.confirmation.is_some() {
+            self.handle_confirmation_key(key);
+            return Ok(());
+        } else if self.import_review.is_some() {
+            self.handle_import_review_key(key);
+            return Ok(());
+        } else if self.comparison.is_some() {
+            self.handle_comparison_key(key);
+            return Ok(());
+        } else if self.stats.is_some() {
+            self.handle_stats_key(key);
+            return Ok(());
+        } else if self.show_help {
+            self.handle_help_key(key);
+            return Ok(());
+        } else if self.show_playback_controls {
+            return self.handle_playback_controls_key(key).await;
+        } else if self.show_playlist_picker {
+            return self.handle_playlist_picker_key(key);
+        } else if self.show_device_picker {
+            return self.handle_device_picker_key(key).await;
+        } else if self.show_genre_picker {
+            self.handle_genre_picker_key(key);
+            return Ok(());
+        } else if self.show_track_menu {
+            return self.handle_track_menu_key(key).await;
+        } else if let Some(target) = self.editing_filter {
+            return self.handle_filter_key(target, key).await;
+        }
+
+        // Keys bound by scripts come before the built-in ones they shadow
+        if !self.typing() {
+            if let Some(command) = self.scripts.command_for(key).map(str::to_string) {
+                self.pending_count = None;
+                self.run_script_command(&command);
+                return Ok(());
+            }
+        }
+
+        if self.handle_type_ahead_key(key) || self.handle_count_key(key) {
+            return Ok(());
+        }
+        if let Some(action) = self.pending_mark.take() {
+            if let KeyCode::Char(c) = key.code {
+                if c.is_ascii_alphabetic() {
+                    match action {
+                        MarkAction::Set => self.set_mark(c),
+                        MarkAction::Jump => self.jump_to_mark(c),
                     }
                 }
-            } else {
-                Ok(())
             }
-        } else {
-            Ok(())
+            return Ok(());
+        }
+        let count = self.pending_count.take();
+        if self.handle_resize_key(key) {
+            return Ok(());
         }
+
+        if self.show_search
+            && matches!(self.focused_pane, FocusedPane::SearchInput)
+            && self.edit_search_input(key)
+        {
+            return Ok(());
+        }
+
+        let context = if self.show_search {
+            Context::Search
+        } else {
+            Context::Browse
+        };
+        if let Some(action) = self.keymap.resolve(context, key) {
+            self.dispatch(action, count).await?;
+        }
+        Ok(())
+    }
+
+    /// Carries out what a key stood for, in the focused pane. `count` is the number typed
+    /// before it, if any
+    async fn dispatch(&mut self, action: Action, count: Option<usize>) -> Result<()> {
+        // A count repeats a motion, or picks the row to go to for G
+        let repeat = count.unwrap_or(1);
+        let tracks = matches!(self.focused_pane, FocusedPane::Tracks);
+        let library = matches!(self.focused_pane, FocusedPane::Library);
+        // Search results are the only list the rest of these can act on while searching
+        let results = tracks || !self.show_search;
+
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::Search => {
+                self.show_search = true;
+                self.search_input.clear();
+                self.search_results.clear();
+                self.cancel_search();
+                self.clear_marks();
+                self.focused_pane = FocusedPane::SearchInput;
+                self.update_track_order();
+            }
+            Action::PlaybackControls => {
+                self.show_playback_controls = true;
+                self.playback_controls_state.select(Some(0));
+            }
+            Action::DevicePicker => self.open_device_picker(),
+            Action::GenrePicker => self.open_genre_picker(),
+            Action::ToggleDebug => self.show_debug = !self.show_debug,
+            Action::Help => {
+                self.show_help = true;
+                self.help_scroll = 0;
+                self.help_filter.clear();
+                self.editing_help_filter = false;
+            }
+            Action::Filter => {
+                let target = match self.focused_pane {
+                    FocusedPane::Library => FilterTarget::Library,
+                    _ => FilterTarget::Tracks,
+                };
+                self.editing_filter = Some(target);
+            }
+            Action::Escape => self.escape(),
+            Action::Up => self.move_focused(Motion::Up(repeat)).await?,
+            Action::Down => self.move_focused(Motion::Down(repeat)).await?,
+            Action::HalfPageUp => self.move_focused(Motion::HalfPageUp).await?,
+            Action::HalfPageDown => self.move_focused(Motion::HalfPageDown).await?,
+            Action::PageUp => self.move_focused(Motion::PageUp).await?,
+            Action::PageDown => self.move_focused(Motion::PageDown).await?,
+            Action::Top => self.move_focused(Motion::Top).await?,
+            Action::Bottom => {
+                let bottom = count.map_or(Motion::Bottom, |row| Motion::To(row - 1));
+                self.move_focused(bottom).await?;
+            }
+            Action::NextPane => {
+                self.focused_pane = match self.focused_pane {
+                    FocusedPane::Library => FocusedPane::Tracks,
+                    FocusedPane::Tracks if self.split.is_some() => FocusedPane::Split,
+                    FocusedPane::Tracks | FocusedPane::Split => {
+                        if self.show_search {
+                            FocusedPane::SearchInput
+                        } else {
+                            FocusedPane::Library
+                        }
+                    }
+                    FocusedPane::SearchInput => FocusedPane::Library,
+                };
+            }
+            Action::FocusLibrary => self.focused_pane = FocusedPane::Library,
+            Action::FocusTracks => self.focused_pane = FocusedPane::Tracks,
+            Action::PreviousTab => self.switch_library_tab(self.library_tab.cycle(false)),
+            Action::NextTab => self.switch_library_tab(self.library_tab.cycle(true)),
+            Action::Right if library && !self.show_search => self.set_folder_open(true),
+            Action::Left if library && !self.show_search => self.set_folder_open(false),
+            Action::Right => self.seek_by(SEEK_STEP_MS),
+            Action::Left => self.seek_by(-SEEK_STEP_MS),
+            Action::Seek(offset_ms) => self.seek_by(offset_ms),
+            Action::Volume(step) => self.step_volume(step),
+            Action::SetVolume(volume) => self.set_volume(volume),
+            Action::Select => self.select(),
+            Action::Queue => match self.focused_pane {
+                FocusedPane::Tracks => self.add_tracks_to_queue(),
+                FocusedPane::Library if !self.show_search => self.queue_selected_library_item(None),
+                _ => {}
+            },
+            Action::ShuffleQueue if library => {
+                let seed = self
+                    .config
+                    .behavior
+                    .shuffle_seed
+                    .unwrap_or_else(shuffle::fresh_seed);
+                self.queue_selected_library_item(Some(seed));
+            }
+            Action::TrackMenu if tracks => self.open_track_menu(),
+            Action::QueueRestOfAlbum if tracks => {
+                if let Some(track) = self.selected_track().cloned() {
+                    self.queue_rest_of_album(track);
+                }
+            }
+            Action::Radio if library => {
+                if let Some(artist) = self.selected_artist() {
+                    self.start_artist_radio(artist);
+                }
+            }
+            Action::Radio if tracks => {
+                if let Some(track) = self.selected_track().cloned() {
+                    self.start_track_radio(track);
+                }
+            }
+            Action::ArtistRadio if library || tracks => {
+                if let Some(artist) = self.selected_artist() {
+                    self.start_artist_radio(artist);
+                }
+            }
+            Action::ToggleMark if tracks => self.toggle_mark(),
+            Action::VisualMode if tracks => self.toggle_visual_mode(),
+            Action::CopyLink if tracks => self.copy_track_links(false)?,
+            Action::CopyUri if tracks => self.copy_track_links(true)?,
+            Action::OpenInBrowser if !matches!(self.focused_pane, FocusedPane::SearchInput) => {
+                self.open_selected(false)?
+            }
+            Action::OpenInApp if !matches!(self.focused_pane, FocusedPane::SearchInput) => {
+                self.open_selected(true)?
+            }
+            Action::CycleSort if library && !self.show_search => self.cycle_playlist_sort(),
+            Action::CycleSort if results => self.cycle_sort_column(),
+            Action::FlipSort if results => self.toggle_sort_direction(),
+            Action::GroupByAlbum if results => self.group_by_album = !self.group_by_album,
+            Action::TypeAhead if results => self.start_type_ahead(),
+            Action::Undo if results => self.undo(),
+            Action::TogglePin if library => self.toggle_pin(),
+            Action::Backup => self.start_backup(),
+            Action::SaveQueue => self.save_queue(),
+            Action::Merge if library => self.start_merge(),
+            Action::Compare if library => self.start_comparison(),
+            Action::Stats if library => self.start_stats(),
+            Action::SetMark => self.pending_mark = Some(MarkAction::Set),
+            Action::JumpToMark => self.pending_mark = Some(MarkAction::Jump),
+            Action::Delete => match self.focused_pane {
+                FocusedPane::Library => self.delete_selected_playlist(),
+                FocusedPane::Tracks => self.remove_tracks_from_playlist(),
+                FocusedPane::SearchInput | FocusedPane::Split => {}
+            },
+            Action::Back => self.go_back(),
+            Action::ToggleSplit => self.toggle_split(),
+            Action::CopyToSplit if tracks => self.copy_to_split(),
+            Action::CopyFromSplit if matches!(self.focused_pane, FocusedPane::Split) => {
+                self.copy_from_split()
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the selection in the focused pane, or in the search results while searching
+    async fn move_focused(&mut self, motion: Motion) -> Result<()> {
+        if self.show_search {
+            self.move_search_selection(motion);
+            Ok(())
+        } else {
+            self.move_selection(motion).await
+        }
+    }
+
+    /// Esc clears the marks first, then the focused pane's filter or the search
+    fn escape(&mut self) {
+        if self.show_search {
+            if self.has_marks() {
+                self.clear_marks();
+            } else {
+                self.leave_search();
+                self.focused_pane = FocusedPane::Library;
+                self.update_track_order();
+            }
+            return;
+        }
+        match self.focused_pane {
+            FocusedPane::Tracks if self.has_marks() => self.clear_marks(),
+            FocusedPane::Library if !self.library_filter.is_empty() => {
+                self.clear_filter(FilterTarget::Library);
+            }
+            FocusedPane::Tracks if !self.track_filter.is_empty() => {
+                self.clear_filter(FilterTarget::Tracks);
+            }
+            _ => {}
+        }
+    }
+
+    /// Enter plays the selected track, or opens the entry or search results it's on
+    fn select(&mut self) {
+        if self.show_search {
+            if !self.search_results.is_empty() {
+                self.focused_pane = FocusedPane::Tracks;
+            }
+            if matches!(self.focused_pane, FocusedPane::Tracks) {
+                self.play_selected_track();
+            }
+            return;
+        }
+        match self.focused_pane {
+            FocusedPane::Tracks => self.play_selected_track(),
+            FocusedPane::Split => {
+                let uri = self
+                    .split
+                    .as_ref()
+                    .and_then(|split| split.selected_track())
+                    .map(|track| track.uri.clone());
+                if let Some(uri) = uri {
+                    self.send(Command::Play(vec![uri]));
+                }
+            }
+            FocusedPane::Library if self.on_collapsed_folder() => self.set_folder_open(true),
+            // The tracks are off screen, so opening an entry shows them
+            FocusedPane::Library if self.narrow => self.focused_pane = FocusedPane::Tracks,
+            FocusedPane::Library | FocusedPane::SearchInput => {}
+        }
+    }
+
+    /// Whether keys are going into a text input or naming a mark, which remaps leave alone
+    fn typing(&self) -> bool {
+        (self.show_search && matches!(self.focused_pane, FocusedPane::SearchInput))
+            || self.editing_filter.is_some()
+            || (self.show_help && self.editing_help_filter)
+            || self.type_ahead.is_some()
+            || self.pending_mark.is_some()
+    }
+
+    /// Collects the digits of a count typed before a motion, returning whether the key was one
+    fn handle_count_key(&mut self, key: KeyEvent) -> bool {
+        if matches!(self.focused_pane, FocusedPane::SearchInput)
+            || key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return false;
+        }
+        let KeyCode::Char(c @ '0'..='9') = key.code else {
+            return false;
+        };
+        // Counts can't start with 0
+        if c == '0' && self.pending_count.is_none() {
+            return false;
+        }
+
+        let digit = c.to_digit(10).unwrap_or(0) as usize;
+        let count = self.pending_count.unwrap_or(0);
+        self.pending_count = Some((count * 10 + digit).min(MAX_COUNT));
+        true
+    }
+
+    /// Marks the playlist under the cursor in the library, or the one open in the tracks pane,
+    /// with a letter
+    fn set_mark(&mut self, mark: char) {
+        let playlist = match self.focused_pane {
+            FocusedPane::Library if self.library_tab == LibraryTab::Playlists => self
+                .selected_library_index()
+                .and_then(|index| self.playlists.get(index)),
+            _ => match &self.track_source {
+                Some(TrackSource::Playlist(id)) => self.playlist(id),
+                _ => None,
+            },
+        };
+        let Some(playlist) = playlist else {
+            self.notifications
+                .error("Marks can only be set on playlists");
+            return;
+        };
+
+        let message = format!("Marked {} as '{}", playlist.name, mark);
+        self.local_state
+            .marks
+            .insert(mark.to_string(), playlist.id.clone());
+        if let Err(e) = self.local_state.save() {
+            self.notifications.error(e);
+        }
+        self.notifications.info(message);
+    }
+
+    /// Opens the playlist marked with a letter, back on the row it was left on
+    fn jump_to_mark(&mut self, mark: char) {
+        let Some(playlist_id) = self.local_state.marks.get(&mark.to_string()).cloned() else {
+            self.notifications
+                .error(format!("Mark '{} isn't set", mark));
+            return;
+        };
+        let Some(index) = self.playlists.iter().position(|p| p.id == playlist_id) else {
+            self.notifications.error(format!(
+                "The playlist marked '{} is no longer in your library",
+                mark
+            ));
+            return;
+        };
+
+        self.leave_search();
+        self.switch_library_tab(LibraryTab::Playlists);
+        if !self.library_order.contains(&index) {
+            self.clear_filter(FilterTarget::Library);
+        }
+        if let Some((folder, _)) = self.library_folder(index) {
+            let folder = folder.to_string();
+            self.local_state.set_folder_open(&folder, true);
+            self.update_library_order();
+        }
+        let position = self.library_order.iter().position(|&i| i == index);
+        self.library_state_mut().select(position);
+        self.focused_pane = FocusedPane::Tracks;
+
+        let already_open =
+            matches!(&self.track_source, Some(TrackSource::Playlist(open)) if *open == playlist_id);
+        if !already_open {
+            self.nav_history.clear();
+            self.load_tracks(TrackSource::Playlist(playlist_id.clone()));
+            self.restore_selection = self.playlist_selection.get(&playlist_id).copied();
+        }
+    }
+
+    /// Notes the row the open playlist is on before the tracks pane moves on from it
+    fn remember_playlist_selection(&mut self) {
+        if let (Some(TrackSource::Playlist(id)), Some(selected)) =
+            (&self.track_source, self.tracks_state.selected())
+        {
+            self.playlist_selection.insert(id.clone(), selected);
+        }
+    }
+
+    fn start_type_ahead(&mut self) {
+        if !matches!(self.focused_pane, FocusedPane::SearchInput) {
+            self.type_ahead = Some(TypeAhead {
+                text: String::new(),
+                last_key: Instant::now(),
+            });
+        }
+    }
+
+    /// Feeds a key to type-ahead find while it's running, returning whether it was used up.
+    /// Keys that aren't letters end it and go on to do their usual job, so Enter plays or opens
+    /// the entry that was found
+    fn handle_type_ahead_key(&mut self, key: KeyEvent) -> bool {
+        let Some(type_ahead) = self.type_ahead.as_mut() else {
+            return false;
+        };
+        if type_ahead.expired() {
+            self.type_ahead = None;
+            return false;
+        }
+
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                type_ahead.text.push(c);
+            }
+            KeyCode::Backspace => {
+                type_ahead.text.pop();
+            }
+            KeyCode::Esc => {
+                self.type_ahead = None;
+                return true;
+            }
+            _ => {
+                self.type_ahead = None;
+                return false;
+            }
+        }
+        type_ahead.last_key = Instant::now();
+        let prefix = type_ahead.text.to_lowercase();
+        self.jump_to_prefix(&prefix);
+        true
+    }
+
+    /// Selects the first entry in the focused list whose name starts with `prefix`
+    fn jump_to_prefix(&mut self, prefix: &str) {
+        if prefix.is_empty() {
+            return;
+        }
+        let starts_with = |name: &str| name.to_lowercase().starts_with(prefix);
+
+        match self.focused_pane {
+            FocusedPane::Library => {
+                let names = self.library_names();
+                let position = self
+                    .library_order
+                    .iter()
+                    .position(|&index| names.get(index).is_some_and(|name| starts_with(name)));
+                if position.is_some() && position != self.library_state().selected() {
+                    self.library_state_mut().select(position);
+                    if let Some(index) = self.selected_library_index() {
+                        self.load_library_item(index);
+                    }
+                }
+            }
+            FocusedPane::Tracks => {
+                let tracks = self.get_display_tracks();
+                let position = self.track_order.iter().position(|&index| {
+                    tracks
+                        .get(index)
+                        .is_some_and(|track| starts_with(&track.name))
+                });
+                if position.is_some() {
+                    self.tracks_state_mut().select(position);
+                }
+            }
+            FocusedPane::SearchInput | FocusedPane::Split => {}
+        }
+    }
+
+    /// Scrolls the help popup, or edits its filter after /
+    fn handle_help_key(&mut self, key: KeyEvent) {
+        if self.editing_help_filter {
+            match key.code {
+                KeyCode::Esc => {
+                    self.help_filter.clear();
+                    self.editing_help_filter = false;
+                    self.help_scroll = 0;
+                }
+                KeyCode::Enter => {
+                    self.editing_help_filter = false;
+                }
+                KeyCode::Up => self.scroll_help(Motion::Up(1)),
+                KeyCode::Down => self.scroll_help(Motion::Down(1)),
+                KeyCode::PageUp => self.scroll_help(Motion::PageUp),
+                KeyCode::PageDown => self.scroll_help(Motion::PageDown),
+                _ => {
+                    if self.help_filter.handle_key(key) {
+                        self.help_scroll = 0;
+                    }
+                }
+            }
+            return;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc if !self.help_filter.is_empty() => {
+                self.help_filter.clear();
+                self.help_scroll = 0;
+            }
+            KeyCode::Esc | KeyCode::Char('?') => {
+                self.show_help = false;
+            }
+            KeyCode::Char('/') => {
+                self.editing_help_filter = true;
+            }
+            KeyCode::Char('u') if ctrl => self.scroll_help(Motion::HalfPageUp),
+            KeyCode::Char('d') if ctrl => self.scroll_help(Motion::HalfPageDown),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_help(Motion::Up(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_help(Motion::Down(1)),
+            KeyCode::PageUp => self.scroll_help(Motion::PageUp),
+            KeyCode::PageDown => self.scroll_help(Motion::PageDown),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_help(Motion::Top),
+            KeyCode::End | KeyCode::Char('G') => self.scroll_help(Motion::Bottom),
+            _ => {}
+        }
+    }
+
+    fn scroll_help(&mut self, motion: Motion) {
+        // Stop once the last line is at the bottom of the popup
+        let positions = self.help_len.saturating_sub(self.help_viewport) + 1;
+        self.help_scroll = motion
+            .apply(Some(self.help_scroll), positions, self.help_viewport)
+            .unwrap_or(0);
+    }
+
+    /// Applies an editing key to the search box, restarting the search debounce if the query
+    /// changed. Returns true if the key was handled
+    fn edit_search_input(&mut self, key: KeyEvent) -> bool {
+        let before = self.search_input.text().to_string();
+        if !self.search_input.handle_key(key) {
+            return false;
+        }
+        if self.search_input.text() == before {
+            return true;
+        }
+
+        if self.search_input.is_empty() {
+            // Clear results immediately if search input is empty
+            self.search_results.clear();
+            self.clear_marks();
+            self.last_search_time = None;
+            self.update_track_order();
+        } else {
+            // Start debounce timer
+            self.last_search_time = Some(std::time::Instant::now());
+        }
+        // Whatever was being searched for isn't wanted any more
+        self.cancel_search();
+        true
+    }
+
+    /// Ctrl+arrows resize the panes for the rest of the session. Returns true if the key was handled
+    fn handle_resize_key(&mut self, key: KeyEvent) -> bool {
+        if !key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+
+        let layout = &mut self.config.layout;
+        match key.code {
+            KeyCode::Left => layout.resize_library(false, RESIZE_STEP),
+            KeyCode::Right => layout.resize_library(true, RESIZE_STEP),
+            KeyCode::Up => layout.resize_playlists(false, RESIZE_STEP),
+            KeyCode::Down => layout.resize_playlists(true, RESIZE_STEP),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Moves the selection in the focused list pane, loading the playlist if it changed
+    async fn move_selection(&mut self, motion: Motion) -> Result<()> {
+        match self.focused_pane {
+            FocusedPane::Library => {
+                let selected = self.library_state().selected();
+                let position =
+                    motion.apply(selected, self.library_order.len(), self.library_viewport);
+                if position.is_some() && position != selected {
+                    self.library_state_mut().select(position);
+                    if let Some(index) = self.selected_library_index() {
+                        self.load_library_item(index);
+                        self.prefetch_neighbours();
+                    }
+                }
+            }
+            FocusedPane::Tracks => {
+                let len = self.track_order.len();
+                let state = if self.show_search {
+                    &mut self.search_state
+                } else {
+                    &mut self.tracks_state
+                };
+                if let Some(position) = motion.apply(state.selected(), len, self.tracks_viewport) {
+                    state.select(Some(position));
+                }
+            }
+            FocusedPane::Split => {
+                if let Some(split) = &mut self.split {
+                    let len = split.tracks.as_ref().map_or(0, Vec::len);
+                    if let Some(position) =
+                        motion.apply(split.state.selected(), len, split.viewport)
+                    {
+                        split.state.select(Some(position));
+                    }
+                }
+            }
+            FocusedPane::SearchInput => {
+                // No action for search input pane
+            }
+        }
+        Ok(())
+    }
+
+    fn move_search_selection(&mut self, motion: Motion) {
+        if matches!(self.focused_pane, FocusedPane::Tracks) {
+            if let Some(position) = motion.apply(
+                self.search_state.selected(),
+                self.track_order.len(),
+                self.tracks_viewport,
+            ) {
+                self.search_state.select(Some(position));
+            }
+        }
+    }
+
+    pub fn get_display_tracks(&self) -> &Vec<Track> {
+        if self.show_search {
+            &self.search_results
+        } else {
+            &self.current_tracks
+        }
+    }
+
+    /// Moves the playing track `offset_ms` forward or back, showing where it's got to straight
+    /// away rather than after the next poll
+    fn seek_by(&mut self, offset_ms: i64) {
+        let Some(progress) = self.playback_progress_ms() else {
+            return;
+        };
+        let Some(player) = self.currently_playing.as_mut() else {
+            return;
+        };
+        let duration = player
+            .item
+            .as_ref()
+            .map_or(0, |track| track.duration_ms as u64);
+        let position = progress.saturating_add_signed(offset_ms).min(duration);
+        player.progress_ms = Some(position);
+        self.player_fetched_at = Instant::now();
+        self.send(Command::Seek(position));
+    }
+
+    /// Turns the volume up, or down when `step` is negative, from where the last press left it.
+    /// Presses in quick succession the same way go further each time, so holding the key down
+    /// gets across the range quickly
+    fn step_volume(&mut self, step: i32) {
+        let quick = self
+            .pending_volume
+            .is_some_and(|(_, changed_at)| changed_at.elapsed() < VOLUME_DEBOUNCE);
+        let step = if quick && self.volume_step.signum() == step.signum() {
+            let grown = self.volume_step + step;
+            if grown.abs() <= step.abs() * MAX_VOLUME_ACCELERATION {
+                grown
+            } else {
+                self.volume_step
+            }
+        } else {
+            step
+        };
+        let volume = self.pending_volume.map(|(volume, _)| volume).or_else(|| {
+            self.currently_playing
+                .as_ref()?
+                .device
+                .as_ref()?
+                .volume_percent
+        });
+        if let Some(volume) = volume {
+            self.set_volume(volume.saturating_add_signed(step).min(100));
+            self.volume_step = step;
+        }
+    }
+
+    /// Shows the volume right away, it's sent once the keys have been left alone for a moment
+    fn set_volume(&mut self, volume: u32) {
+        self.pending_volume = Some((volume.min(100), Instant::now()));
+        self.volume_step = 0;
+        self.show_pending_volume();
+    }
+
+    fn show_pending_volume(&mut self) {
+        let device = self
+            .currently_playing
+            .as_mut()
+            .and_then(|player| player.device.as_mut());
+        if let (Some((volume, _)), Some(device)) = (self.pending_volume, device) {
+            device.volume_percent = Some(volume);
+        }
+    }
+
+    fn check_pending_volume(&mut self) {
+        if let Some((volume, changed_at)) = self.pending_volume {
+            if changed_at.elapsed() >= VOLUME_DEBOUNCE {
+                self.pending_volume = None;
+                self.send(Command::SetVolume(volume));
+            }
+        }
+    }
+
+    /// Progress of the playing track, advanced by the time since the last poll while playing so
+    /// the progress bar moves smoothly
+    pub fn playback_progress_ms(&self) -> Option<u64> {
+        let player = self.currently_playing.as_ref()?;
+        let progress = player.progress_ms?;
+        let duration = player.item.as_ref()?.duration_ms as u64;
+        let elapsed = if player.is_playing {
+            self.player_fetched_at.elapsed().as_millis() as u64
+        } else {
+            0
+        };
+        Some((progress + elapsed).min(duration))
+    }
+
+    fn check_pending_search(&mut self) {
+        if let Some(last_search_time) = self.last_search_time {
+            if last_search_time.elapsed() >= Duration::from_millis(self.search_debounce_ms) {
+                self.last_search_time = None;
+                if !self.search_input.is_empty() {
+                    self.start_search();
+                }
+            }
+        }
+    }
+
+    /// Searches for what's been typed in the background, in place of any search before it
+    fn start_search(&mut self) {
+        self.cancel_search();
+        let generation = self.search_generation;
+        let query = self.search_input.text().to_string();
+        let client = self.spotify_client.clone();
+        self.search_task = Some(self.spawn_load(async move {
+            let result = client.search_tracks(&query, 0).await;
+            Loaded::SearchResults {
+                generation,
+                query,
+                result,
+            }
+        }));
+    }
+
+    /// Abandons the search in flight, and the rest of the results of the last one
+    fn cancel_search(&mut self) {
+        if let Some(task) = self.search_task.take() {
+            task.abort();
+        }
+        // Results already on their way are dropped too
+        self.search_generation += 1;
+        self.search_next = None;
+    }
+
+    async fn handle_filter_key(&mut self, target: FilterTarget, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.clear_filter(target);
+            }
+            KeyCode::Enter => {
+                // Act on the highlighted match, leaving the filter applied
+                self.editing_filter = None;
+                match target {
+                    FilterTarget::Library => {
+                        if let Some(index) = self.selected_library_index() {
+                            self.load_library_item(index);
+                        }
+                    }
+                    FilterTarget::Tracks => self.play_selected_track(),
+                }
+            }
+            KeyCode::Up => {
+                self.move_filtered_selection(target, Motion::Up(1));
+            }
+            KeyCode::Down => {
+                self.move_filtered_selection(target, Motion::Down(1));
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.move_filtered_selection(target, Motion::Up(1));
+            }
+            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.move_filtered_selection(target, Motion::Down(1));
+            }
+            KeyCode::PageUp => {
+                self.move_filtered_selection(target, Motion::PageUp);
+            }
+            KeyCode::PageDown => {
+                self.move_filtered_selection(target, Motion::PageDown);
+            }
+            KeyCode::Home => {
+                self.move_filtered_selection(target, Motion::Top);
+            }
+            KeyCode::End => {
+                self.move_filtered_selection(target, Motion::Bottom);
+            }
+            _ => {
+                // Home and End are taken by the list above, Ctrl+A/E move the cursor instead
+                let input = self.filter_input_mut(target);
+                let before = input.text().to_string();
+                if input.handle_key(key) && input.text() != before {
+                    self.apply_filter(target);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn filter_input_mut(&mut self, target: FilterTarget) -> &mut TextInput {
+        match target {
+            FilterTarget::Library => &mut self.library_filter,
+            FilterTarget::Tracks => &mut self.track_filter,
+        }
+    }
+
+    fn update_order(&mut self, target: FilterTarget) {
+        match target {
+            FilterTarget::Library => self.update_library_order(),
+            FilterTarget::Tracks => self.update_track_order(),
+        }
+    }
+
+    /// The visible order and selection of a filterable list
+    fn filtered_list(&self, target: FilterTarget) -> (&Vec<usize>, Option<usize>) {
+        match target {
+            FilterTarget::Library => (&self.library_order, self.library_state().selected()),
+            FilterTarget::Tracks => (&self.track_order, self.tracks_state.selected()),
+        }
+    }
+
+    fn select_filtered(&mut self, target: FilterTarget, position: Option<usize>) {
+        match target {
+            FilterTarget::Library => self.library_state_mut().select(position),
+            FilterTarget::Tracks => self.tracks_state.select(position),
+        }
+    }
+
+    fn apply_filter(&mut self, target: FilterTarget) {
+        self.update_order(target);
+        // Jump to the best match as the filter changes
+        let (order, _) = self.filtered_list(target);
+        let position = if order.is_empty() { None } else { Some(0) };
+        self.select_filtered(target, position);
+    }
+
+    fn clear_filter(&mut self, target: FilterTarget) {
+        // Keep the highlighted entry selected once the full list comes back
+        let (order, selected) = self.filtered_list(target);
+        let selected_index = selected.and_then(|selected| order.get(selected).copied());
+
+        self.editing_filter = None;
+        self.filter_input_mut(target).clear();
+        self.update_order(target);
+
+        let (order, _) = self.filtered_list(target);
+        let position = selected_index
+            .and_then(|index| order.iter().position(|&i| i == index))
+            .or(Some(0));
+        self.select_filtered(target, position);
+    }
+
+    fn move_filtered_selection(&mut self, target: FilterTarget, motion: Motion) {
+        let (order, selected) = self.filtered_list(target);
+        let viewport = match target {
+            FilterTarget::Library => self.library_viewport,
+            FilterTarget::Tracks => self.tracks_viewport,
+        };
+        if let Some(position) = motion.apply(selected, order.len(), viewport) {
+            self.select_filtered(target, Some(position));
+        }
+    }
+
+    async fn handle_playback_controls_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.show_playback_controls = false;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.move_playback_controls_selection(Motion::Up(1));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.move_playback_controls_selection(Motion::Down(1));
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                // Ctrl+P - Previous (same as Up)
+                self.move_playback_controls_selection(Motion::Up(1));
+            }
+            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                // Ctrl+N - Next (same as Down)
+                self.move_playback_controls_selection(Motion::Down(1));
+            }
+            KeyCode::Home | KeyCode::PageUp => {
+                self.move_playback_controls_selection(Motion::Top);
+            }
+            KeyCode::End | KeyCode::PageDown => {
+                self.move_playback_controls_selection(Motion::Bottom);
+            }
+            KeyCode::Enter => {
+                if let Some(selected) = self.playback_controls_state.selected() {
+                    self.execute_playback_control(selected);
+                }
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => self.step_volume(VOLUME_STEP),
+            KeyCode::Char('-') => self.step_volume(-VOLUME_STEP),
+            KeyCode::Char(digit @ '0'..='9') => {
+                self.set_volume(digit.to_digit(10).unwrap_or(0) * 10);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn execute_playback_control(&mut self, selected: usize) {
+        match selected {
+            0 => {
+                // Play/Pause
+                let playing = self
+                    .currently_playing
+                    .as_ref()
+                    .is_some_and(|currently_playing| currently_playing.is_playing);
+                self.send(if playing {
+                    Command::Pause
+                } else {
+                    Command::Resume
+                });
+            }
+            1 => {
+                // Previous
+                self.send(Command::Previous);
+            }
+            2 => {
+                // Next
+                self.send(Command::Next);
+            }
+            3 => {
+                // Close
+                self.show_playback_controls = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn open_track_menu(&mut self) {
+        if self.selected_track().is_some() {
+            self.show_track_menu = true;
+            self.track_menu_state.select(Some(0));
+        }
+    }
+
+    async fn handle_track_menu_key(&mut self, key: KeyEvent) -> Result<()> {
+        let len = TrackMenuItem::ALL.len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('a') | KeyCode::Char('q') => {
+                self.show_track_menu = false;
+            }
+            KeyCode::Enter => {
+                let item = self
+                    .track_menu_state
+                    .selected()
+                    .and_then(|selected| TrackMenuItem::ALL.get(selected).copied());
+                if let Some(item) = item {
+                    self.execute_track_menu_item(item).await;
+                }
+            }
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    let selected = self.track_menu_state.selected();
+                    self.track_menu_state
+                        .select(motion.apply(selected, len, len));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a menu action on the marked tracks, or the selected track if none are marked.
+    /// Navigation and "Play from here" always use the selected track
+    async fn execute_track_menu_item(&mut self, item: TrackMenuItem) {
+        self.show_track_menu = false;
+        let Some(track) = self.selected_track().cloned() else {
+            return;
+        };
+        let tracks = self.action_tracks();
+
+        let result = match item {
+            TrackMenuItem::Play => {
+                let uris = tracks
+                    .into_iter()
+                    .take(PLAY_FROM_HERE_LIMIT)
+                    .map(|track| track.uri)
+                    .collect();
+                self.send(Command::Play(uris));
+                Ok(())
+            }
+            TrackMenuItem::PlayFromHere => {
+                self.play_from_selected_track();
+                Ok(())
+            }
+            TrackMenuItem::AddToQueue => {
+                self.add_tracks_to_queue();
+                Ok(())
+            }
+            TrackMenuItem::QueueRestOfAlbum => {
+                self.queue_rest_of_album(track);
+                Ok(())
+            }
+            TrackMenuItem::Radio => {
+                self.start_track_radio(track);
+                Ok(())
+            }
+            TrackMenuItem::ArtistRadio => {
+                if let Some(artist) = track.artists.into_iter().next() {
+                    self.start_artist_radio(artist);
+                }
+                Ok(())
+            }
+            TrackMenuItem::AddToPlaylist => {
+                let uris = tracks.into_iter().map(|track| track.uri).collect();
+                self.open_playlist_picker(PickerPurpose::AddTracks(uris));
+                Ok(())
+            }
+            TrackMenuItem::RemoveFromPlaylist => {
+                self.remove_tracks_from_playlist();
+                Ok(())
+            }
+            TrackMenuItem::Like => {
+                let ids: Vec<String> = tracks.into_iter().map(|track| track.id).collect();
+                if self.connection.is_offline() {
+                    self.defer_offline(OfflineAction::Like(ids));
+                    return;
+                }
+                self.like_tracks(ids, false);
+                Ok(())
+            }
+            TrackMenuItem::GoToAlbum => {
+                self.show_album(track.album);
+                Ok(())
+            }
+            // Podcast publishers stand in as artists without an id
+            TrackMenuItem::GoToArtist => match track
+                .artists
+                .into_iter()
+                .find(|artist| !artist.id.is_empty())
+            {
+                Some(artist) => {
+                    self.show_artist(artist);
+                    Ok(())
+                }
+                None => Ok(()),
+            },
+            TrackMenuItem::CopyLink => self.copy_track_links(false),
+        };
+
+        if let Err(e) = result {
+            self.notifications.error(e);
+        }
+    }
+
+    /// Copies the open.spotify.com links of the marked tracks, or the selected one, to the
+    /// clipboard, or their spotify: URIs with `as_uri`
+    fn copy_track_links(&mut self, as_uri: bool) -> Result<()> {
+        let links: Vec<String> = self
+            .action_tracks()
+            .iter()
+            .map(|track| {
+                if as_uri {
+                    track.uri.clone()
+                } else {
+                    web_link(&track.uri)
+                }
+            })
+            .collect();
+        if links.is_empty() {
+            return Ok(());
+        }
+
+        let copied = clipboard::copy(&links.join("\n"))?;
+        let noun = match (as_uri, links.len()) {
+            (false, 1) => "link",
+            (false, _) => "links",
+            (true, 1) => "URI",
+            (true, _) => "URIs",
+        };
+        let message = match copied {
+            Copied::Clipboard => format!("Copied {} {} to clipboard", links.len(), noun),
+            // Whether the terminal copied it can't be seen from here
+            Copied::Terminal => format!("Sent {} {} to the terminal to copy", links.len(), noun),
+        };
+        self.notifications.info(message);
+        Ok(())
+    }
+
+    /// The spotify: URI of the entry under the cursor in the focused pane
+    fn selected_uri(&self) -> Option<String> {
+        if matches!(self.focused_pane, FocusedPane::Tracks) {
+            return self.selected_track().map(|track| track.uri.clone());
+        }
+
+        let index = self.selected_library_index()?;
+        match self.library_tab {
+            LibraryTab::Playlists => self
+                .playlists
+                .get(index)
+                .map(|playlist| format!("spotify:playlist:{}", playlist.id)),
+            LibraryTab::ForYou => self
+                .playlists
+                .get(self.for_you_playlist(index)?)
+                .map(|playlist| format!("spotify:playlist:{}", playlist.id)),
+            LibraryTab::Albums => self
+                .saved_albums
+                .as_ref()?
+                .get(index)
+                .map(|album| format!("spotify:album:{}", album.id)),
+            LibraryTab::Artists => self
+                .followed_artists
+                .as_ref()?
+                .get(index)
+                .map(|artist| format!("spotify:artist:{}", artist.id)),
+            LibraryTab::Podcasts => self
+                .saved_shows
+                .as_ref()?
+                .get(index)
+                .map(|show| format!("spotify:show:{}", show.id)),
+            // Recently Added only exists in here
+            LibraryTab::Liked => {
+                Some("spotify:collection:tracks".to_string()).filter(|_| index == 0)
+            }
+        }
+    }
+
+    /// Opens the selected track or library entry in the browser, or in the Spotify app with
+    /// `in_app`, falling back to the browser if the app isn't installed
+    fn open_selected(&mut self, in_app: bool) -> Result<()> {
+        let Some(uri) = self.selected_uri() else {
+            return Ok(());
+        };
+
+        if in_app {
+            match launcher::open_in_app(&uri) {
+                Ok(_) => {
+                    self.notifications.info("Opened in Spotify");
+                    return Ok(());
+                }
+                Err(e) => self.notifications.record_error(e),
+            }
+        }
+        launcher::open_in_browser(&web_link(&uri))?;
+        self.notifications.info("Opened in your browser");
+        Ok(())
+    }
+
+    /// Plays the selected track followed by the rest of the tracks pane in display order
+    fn play_from_selected_track(&mut self) {
+        let Some(selected) = self.selected_track_position() else {
+            return;
+        };
+
+        let tracks = self.get_display_tracks();
+        let uris: Vec<String> = self
+            .track_order
+            .iter()
+            .skip(selected)
+            .take(PLAY_FROM_HERE_LIMIT)
+            .filter_map(|&index| tracks.get(index))
+            .map(|track| track.uri.clone())
+            .collect();
+        if !uris.is_empty() {
+            self.send(Command::Play(uris));
+        }
+    }
+
+    /// Leaves search mode and shows the album's tracks in the tracks pane
+    fn show_album(&mut self, album: Album) {
+        self.push_nav_history();
+        self.leave_search();
+        self.focused_pane = FocusedPane::Tracks;
+        self.load_tracks(TrackSource::Album(album));
+    }
+
+    /// Leaves search mode and shows the artist's top tracks in the tracks pane
+    fn show_artist(&mut self, artist: Artist) {
+        self.push_nav_history();
+        self.leave_search();
+        self.focused_pane = FocusedPane::Tracks;
+        self.load_tracks(TrackSource::Artist(artist));
+    }
+
+    /// Remembers the view in the tracks pane before leaving it for another
+    fn push_nav_history(&mut self) {
+        if let Some(ref source) = self.track_source {
+            self.nav_history.push(NavEntry {
+                source: source.clone(),
+                selected: self.tracks_state.selected(),
+            });
+        }
+    }
+
+    /// Returns the tracks pane to the view before the current one, with the same row selected
+    fn go_back(&mut self) {
+        let Some(entry) = self.nav_history.pop() else {
+            return;
+        };
+        self.leave_search();
+        self.focused_pane = FocusedPane::Tracks;
+        self.load_tracks(entry.source);
+        self.restore_selection = entry.selected;
+    }
+
+    fn leave_search(&mut self) {
+        self.clear_marks();
+        self.show_search = false;
+        self.search_input.clear();
+        self.search_results.clear();
+        self.cancel_search();
+        self.last_search_time = None;
+    }
+
+    fn open_playlist_picker(&mut self, purpose: PickerPurpose) {
+        self.playlist_picker_purpose = purpose;
+        self.show_playlist_picker = true;
+        self.playlist_picker_state.select(Some(0));
+    }
+
+    /// Playlists tracks can be added to, Liked Songs has its own action
+    pub fn playlist_picker_entries(&self) -> Vec<&Playlist> {
+        self.playlists.iter().collect()
+    }
+
+    fn handle_playlist_picker_key(&mut self, key: KeyEvent) -> Result<()> {
+        let len = self.playlist_picker_entries().len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.show_playlist_picker = false;
+            }
+            KeyCode::Enter => {
+                if let Some(selected) = self.playlist_picker_state.selected() {
+                    self.pick_playlist(selected);
+                }
+            }
+            KeyCode::Tab => {
+                if let PickerPurpose::Merge {
+                    skip_duplicates, ..
+                } = &mut self.playlist_picker_purpose
+                {
+                    *skip_duplicates = !*skip_duplicates;
+                }
+            }
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    let selected = self.playlist_picker_state.selected();
+                    let viewport = self.pane_areas.playlist_picker.height.saturating_sub(2);
+                    self.playlist_picker_state.select(motion.apply(
+                        selected,
+                        len,
+                        viewport as usize,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Does what the playlist picker was opened for with the playlist at `position`
+    fn pick_playlist(&mut self, position: usize) {
+        self.show_playlist_picker = false;
+        let Some((playlist_id, playlist_name)) = self
+            .playlist_picker_entries()
+            .get(position)
+            .map(|playlist| (playlist.id.clone(), playlist.name.clone()))
+        else {
+            return;
+        };
+
+        let purpose = std::mem::replace(
+            &mut self.playlist_picker_purpose,
+            PickerPurpose::AddTracks(Vec::new()),
+        );
+        match purpose {
+            PickerPurpose::AddTracks(uris) => {
+                self.add_to_playlist(playlist_id, playlist_name, uris, AfterAdding::Nothing)
+            }
+            PickerPurpose::SplitView => self.open_split(playlist_id, playlist_name),
+            PickerPurpose::Merge {
+                source_id,
+                source_name,
+                skip_duplicates,
+            } => {
+                if source_id == playlist_id {
+                    self.notifications
+                        .error("Pick a different playlist to merge into");
+                    return;
+                }
+                self.notifications
+                    .info(format!("Merging {} into {}", source_name, playlist_name));
+                let client = self.spotify_client.clone();
+                self.spawn_load(async move {
+                    let result =
+                        merge_uris(&client, &source_id, &playlist_id, skip_duplicates).await;
+                    Loaded::MergeTracks {
+                        playlist_id,
+                        playlist_name,
+                        source_name,
+                        result,
+                    }
+                });
+            }
+            PickerPurpose::Compare {
+                source_id,
+                source_name,
+            } => {
+                if source_id == playlist_id {
+                    self.notifications
+                        .error("Pick a different playlist to compare with");
+                    return;
+                }
+                self.notifications
+                    .info(format!("Comparing {} with {}", source_name, playlist_name));
+                let client = self.spotify_client.clone();
+                self.spawn_load(async move {
+                    let compare = async {
+                        let left = client.get_playlist_tracks(&source_id).await?;
+                        let right = client.get_playlist_tracks(&playlist_id).await?;
+                        Ok(Comparison::new(
+                            (source_id, source_name, left),
+                            (playlist_id, playlist_name, right),
+                        ))
+                    };
+                    Loaded::Comparison(compare.await)
+                });
+            }
+        }
+    }
+
+    /// Picks the playlist to compare the selected one with
+    fn start_comparison(&mut self) {
+        if self.library_tab != LibraryTab::Playlists {
+            return;
+        }
+        let Some(playlist) = self
+            .selected_library_index()
+            .and_then(|index| self.playlists.get(index))
+        else {
+            return;
+        };
+        self.open_playlist_picker(PickerPurpose::Compare {
+            source_id: playlist.id.clone(),
+            source_name: playlist.name.clone(),
+        });
+    }
+
+    fn handle_comparison_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.comparison = None,
+            KeyCode::Char('>') => self.sync_comparison(true),
+            KeyCode::Char('<') => self.sync_comparison(false),
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    self.scroll_comparison(motion);
+                }
+            }
+        }
+    }
+
+    fn scroll_comparison(&mut self, motion: Motion) {
+        if let Some(comparison) = &mut self.comparison {
+            let positions = comparison.len().saturating_sub(comparison.viewport) + 1;
+            comparison.scroll = motion
+                .apply(Some(comparison.scroll), positions, comparison.viewport)
+                .unwrap_or(0);
+        }
+    }
+
+    /// Fetches the selected playlist's tracks and their audio features for the stats popup
+    fn start_stats(&mut self) {
+        let selected = self
+            .selected_library_index()
+            .and_then(|index| self.library_source(index));
+        let (playlist_id, name) = match selected {
+            Some(TrackSource::Playlist(id)) => match self.playlist(&id) {
+                Some(playlist) => (id, playlist.name.clone()),
+                None => return,
+            },
+            Some(TrackSource::Liked) => ("liked".to_string(), "Liked Songs".to_string()),
+            _ => return,
+        };
+        self.notifications
+            .info(format!("Working out stats for {}…", name));
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let stats = async {
+                let tracks = client.get_playlist_tracks(&playlist_id).await?;
+                let ids: Vec<String> = tracks
+                    .iter()
+                    .filter(|track| track.uri.starts_with("spotify:track:"))
+                    .map(|track| track.id.clone())
+                    .collect();
+                // Spotify doesn't hand out audio features to every app, the rest of the stats
+                // are still worth showing without the tempo
+                let features = client.get_audio_features(&ids).await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to load audio features: {}", e);
+                    Vec::new()
+                });
+                Ok(PlaylistStats::new(name, &tracks, &features))
+            };
+            Loaded::Stats(stats.await)
+        });
+    }
+
+    fn handle_stats_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('i') => self.stats = None,
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    self.scroll_stats(motion);
+                }
+            }
+        }
+    }
+
+    fn scroll_stats(&mut self, motion: Motion) {
+        if let Some(stats) = &mut self.stats {
+            let positions = stats.len().saturating_sub(stats.viewport) + 1;
+            stats.scroll = motion
+                .apply(Some(stats.scroll), positions, stats.viewport)
+                .unwrap_or(0);
+        }
+    }
+
+    /// Copies the tracks only the left playlist has to the right one, or the other way round,
+    /// after which both have them
+    fn sync_comparison(&mut self, left_to_right: bool) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        let (tracks, playlist_id, playlist_name) = if left_to_right {
+            (
+                &comparison.only_left,
+                &comparison.right_id,
+                &comparison.right_name,
+            )
+        } else {
+            (
+                &comparison.only_right,
+                &comparison.left_id,
+                &comparison.left_name,
+            )
+        };
+        if tracks.is_empty() {
+            return;
+        }
+        let uris = tracks.iter().map(|track| track.uri.clone()).collect();
+        let (playlist_id, playlist_name) = (playlist_id.clone(), playlist_name.clone());
+
+        self.add_to_playlist(
+            playlist_id,
+            playlist_name,
+            uris,
+            AfterAdding::Comparison { left_to_right },
+        );
+    }
+
+    /// Writes the whole library to a JSON file in the background, which takes a request per
+    /// hundred tracks or so
+    fn start_backup(&mut self) {
+        if self.backing_up {
+            self.notifications.info("Already backing up the library");
+            return;
+        }
+        let Some(dir) = state::backup_dir() else {
+            self.notifications
+                .error("No HOME to keep backups in, use spotitui backup --output DIR");
+            return;
+        };
+        self.backing_up = true;
+        self.notifications.info("Backing up the library…");
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move { Loaded::Backup(export::backup(&client, &dir).await) });
+    }
+
+    /// Reads a file to import and searches for the tracks it names in the background
+    pub fn start_import(&mut self, path: &Path) {
+        let file = match import::read(path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.notifications.error(format!("{:#}", e));
+                return;
+            }
+        };
+        self.notifications.info(format!(
+            "Looking up {} to import into {}",
+            describe_count(file.entries.len()),
+            file.name
+        ));
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let search = async {
+                let mut matches = Vec::new();
+                for entry in file.entries {
+                    let candidates = match entry.query() {
+                        Some(query) => {
+                            let mut page = client.search_tracks(&query, 0).await?;
+                            page.tracks.truncate(IMPORT_CANDIDATES);
+                            page.tracks
+                        }
+                        None => Vec::new(),
+                    };
+                    let uri = match &entry {
+                        Entry::Uri(uri) => Some(uri.clone()),
+                        entry => entry
+                            .best_match(&candidates)
+                            .map(|index| candidates[index].uri.clone()),
+                    };
+                    matches.push(ImportMatch {
+                        entry,
+                        candidates,
+                        uri,
+                    });
+                }
+                Ok(matches)
+            };
+            Loaded::ImportMatches {
+                name: file.name,
+                result: search.await,
+            }
+        });
+    }
+
+    /// Asks about the entries search turned up tracks for but wasn't sure of, importing
+    /// straight away if there are none
+    fn review_import(&mut self, name: String, matches: Vec<ImportMatch>) {
+        let pending = (0..matches.len())
+            .filter(|&index| matches[index].uri.is_none() && !matches[index].candidates.is_empty())
+            .collect();
+        let mut review = ImportReview {
+            name,
+            matches,
+            pending,
+            state: ListState::default(),
+        };
+        if review.pending.is_empty() {
+            self.finish_import(review);
+            return;
+        }
+        review.state.select(Some(0));
+        self.import_review = Some(review);
+    }
+
+    fn handle_import_review_key(&mut self, key: KeyEvent) {
+        let Some(review) = self.import_review.as_mut() else {
+            return;
+        };
+        // The candidates and skipping
+        let len = review.current().map_or(0, |m| m.candidates.len()) + 1;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                let name = review.name.clone();
+                self.import_review = None;
+                self.notifications
+                    .info(format!("Stopped importing into {}", name));
+            }
+            KeyCode::Enter => self.pick_import_candidate(),
+            KeyCode::Char('s') => {
+                review.state.select(Some(len - 1));
+                self.pick_import_candidate();
+            }
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    let viewport = self.pane_areas.import_review.height.saturating_sub(4);
+                    review.state.select(motion.apply(
+                        review.state.selected(),
+                        len,
+                        viewport as usize,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn handle_import_review_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.import_review;
+        let Some(review) = self.import_review.as_mut() else {
+            return;
+        };
+        let len = review.current().map_or(0, |m| m.candidates.len()) + 1;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                // The entry being asked about takes the first two lines
+                let offset = review.state.offset();
+                if let Some(row) = row_at(area, position, 2, offset).filter(|&row| row < len) {
+                    review.state.select(Some(row));
+                    self.pick_import_candidate();
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let selected = review.state.selected();
+                review.state.select(Motion::Up(1).apply(selected, len, len));
+            }
+            MouseEventKind::ScrollDown => {
+                let selected = review.state.selected();
+                review
+                    .state
+                    .select(Motion::Down(1).apply(selected, len, len));
+            }
+            _ => {}
+        }
+    }
+
+    /// Imports the entry in the review popup as the candidate under the cursor, or skips it,
+    /// and moves on to the next
+    fn pick_import_candidate(&mut self) {
+        let Some(review) = self.import_review.as_mut() else {
+            return;
+        };
+        let Some(index) = review.pending.pop_front() else {
+            return;
+        };
+        let entry = &mut review.matches[index];
+        let picked = review
+            .state
+            .selected()
+            .and_then(|selected| entry.candidates.get(selected));
+        entry.uri = picked.map(|track| track.uri.clone());
+
+        review.state.select(Some(0));
+        *review.state.offset_mut() = 0;
+        if review.pending.is_empty() {
+            if let Some(review) = self.import_review.take() {
+                self.finish_import(review);
+            }
+        }
+    }
+
+    /// Adds the tracks picked for an import to the playlist of its name, making it if there's
+    /// none, skipping the tracks it already has
+    fn finish_import(&mut self, review: ImportReview) {
+        let mut seen = HashSet::new();
+        let uris: Vec<String> = review
+            .matches
+            .iter()
+            .filter_map(|m| m.uri.clone())
+            .filter(|uri| seen.insert(uri.clone()))
+            .collect();
+        let not_found = review.matches.iter().filter(|m| m.uri.is_none()).count();
+        if uris.is_empty() {
+            self.notifications.error(format!(
+                "Found none of the tracks to import into {}",
+                review.name
+            ));
+            return;
+        }
+        if self.merge_batch.is_some() {
+            self.notifications
+                .error("Wait for the running merge to finish first");
+            return;
+        }
+
+        let name = review.name;
+        let existing = self
+            .playlists
+            .iter()
+            .find(|playlist| playlist.name == name)
+            .map(|playlist| playlist.id.clone());
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let target = async {
+                match existing {
+                    Some(playlist_id) => {
+                        let have: HashSet<String> = client
+                            .get_playlist_tracks(&playlist_id)
+                            .await?
+                            .into_iter()
+                            .map(|track| track.uri)
+                            .collect();
+                        let uris = uris.into_iter().filter(|uri| !have.contains(uri)).collect();
+                        Ok((None, playlist_id, uris))
+                    }
+                    None => {
+                        let playlist = client
+                            .create_playlist(&name, "Imported with spotitui")
+                            .await?;
+                        let playlist_id = playlist.id.clone();
+                        Ok((Some(playlist), playlist_id, uris))
+                    }
+                }
+            };
+            Loaded::ImportTarget {
+                result: target.await,
+                name,
+                not_found,
+            }
+        });
+    }
+
+    /// Picks the playlist to merge the selected one into
+    fn start_merge(&mut self) {
+        if self.library_tab != LibraryTab::Playlists {
+            return;
+        }
+        if self.merge_batch.is_some() {
+            self.notifications
+                .error("Wait for the running merge to finish first");
+            return;
+        }
+        let Some(playlist) = self
+            .selected_library_index()
+            .and_then(|index| self.playlists.get(index))
+        else {
+            return;
+        };
+        self.open_playlist_picker(PickerPurpose::Merge {
+            source_id: playlist.id.clone(),
+            source_name: playlist.name.clone(),
+            skip_duplicates: true,
+        });
+    }
+
+    /// Sends the next request of a running merge, once the last one is answered
+    fn process_merge_batch(&mut self) {
+        let Some(batch) = self.merge_batch.as_mut() else {
+            return;
+        };
+        let Some(chunk) = batch.next_chunk(MERGE_CHUNK) else {
+            return;
+        };
+        let playlist_id = batch.playlist_id.clone();
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client
+                .add_tracks_to_playlist(&playlist_id, &chunk, None)
+                .await;
+            Loaded::Merged { chunk, result }
+        });
+    }
+
+    /// Carries on with the running merge once a chunk has been added
+    fn chunk_merged(&mut self, chunk: Vec<String>, result: Result<()>) {
+        let Some(batch) = self.merge_batch.as_mut() else {
+            return;
+        };
+        match result {
+            Ok(_) => batch.answered(chunk, true),
+            Err(e) => {
+                batch.answered(chunk, false);
+                // Later requests would most likely fail the same way
+                self.notifications.error(format!(
+                    "Stopped merging into {} after {} of {}: {}",
+                    batch.playlist_name,
+                    batch.done(),
+                    batch.total,
+                    e
+                ));
+                self.finish_merge();
+                return;
+            }
+        }
+        if batch.is_finished() {
+            self.notifications.success(format!(
+                "Merged {} into {}",
+                describe_count(batch.total),
+                batch.playlist_name
+            ));
+            self.finish_merge();
+        }
+    }
+
+    /// Ends the running merge, keeping what it added undoable
+    fn finish_merge(&mut self) {
+        let Some(batch) = self.merge_batch.take() else {
+            return;
+        };
+        if batch.added.is_empty() {
+            return;
+        }
+        if let Some(playlist) = self
+            .playlists
+            .iter_mut()
+            .find(|playlist| playlist.id == batch.playlist_id)
+        {
+            playlist.tracks.total += batch.added.len() as u32;
+        }
+        self.reload_playlist(&batch.playlist_id);
+        self.push_undo(Undoable::AddedToPlaylist {
+            playlist_id: batch.playlist_id,
+            playlist_name: batch.playlist_name,
+            uris: batch.added,
+        });
+    }
+
+    /// Adds tracks to the end of a playlist in the background, `then` bringing the panes up
+    /// to date once they're in
+    fn add_to_playlist(
+        &mut self,
+        playlist_id: String,
+        playlist_name: String,
+        uris: Vec<String>,
+        then: AfterAdding,
+    ) {
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client
+                .add_tracks_to_playlist(&playlist_id, &uris, None)
+                .await;
+            Loaded::AddedToPlaylist {
+                playlist_id,
+                playlist_name,
+                uris,
+                then,
+                result,
+            }
+        });
+    }
+
+    fn added_to_playlist(
+        &mut self,
+        playlist_id: String,
+        playlist_name: String,
+        uris: Vec<String>,
+        then: AfterAdding,
+    ) {
+        self.clear_marks();
+        self.notifications.success(format!(
+            "Added {} to {}",
+            describe_count(uris.len()),
+            playlist_name
+        ));
+        // The panes may have moved on to other playlists meanwhile
+        match then {
+            AfterAdding::Nothing => {}
+            AfterAdding::SplitView(tracks) => {
+                if let Some(split_tracks) = self
+                    .split
+                    .as_mut()
+                    .filter(|split| split.playlist_id == playlist_id)
+                    .and_then(|split| split.tracks.as_mut())
+                {
+                    split_tracks.extend(tracks);
+                }
+            }
+            AfterAdding::TracksPane(tracks) => {
+                if let Some(playlist) = self.playlist_mut(&playlist_id) {
+                    playlist.tracks.total += tracks.len() as u32;
+                }
+                let open = &self.track_source;
+                if matches!(open, Some(TrackSource::Playlist(open)) if *open == playlist_id) {
+                    self.current_tracks.extend(tracks);
+                    self.update_track_order();
+                }
+            }
+            AfterAdding::Comparison { left_to_right } => {
+                if let Some(comparison) = &mut self.comparison {
+                    let (copied, target) = if left_to_right {
+                        (&mut comparison.only_left, &comparison.right_id)
+                    } else {
+                        (&mut comparison.only_right, &comparison.left_id)
+                    };
+                    if *target == playlist_id {
+                        comparison.shared.append(copied);
+                    }
+                }
+                self.reload_playlist(&playlist_id);
+            }
+        }
+        self.push_undo(Undoable::AddedToPlaylist {
+            playlist_id,
+            playlist_name,
+            uris,
+        });
+    }
+
+    /// Shows a playlist in the split view, fetching its tracks in the background
+    fn open_split(&mut self, playlist_id: String, playlist_name: String) {
+        self.split = Some(SplitView {
+            playlist_id: playlist_id.clone(),
+            playlist_name,
+            tracks: None,
+            state: TableState::default().with_selected(Some(0)),
+            viewport: 0,
+        });
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client.get_playlist_tracks(&playlist_id).await;
+            Loaded::SplitTracks {
+                playlist_id,
+                result,
+            }
+        });
+    }
+
+    /// Opens the split view after picking its playlist, or closes it
+    fn toggle_split(&mut self) {
+        if self.split.take().is_some() {
+            if matches!(self.focused_pane, FocusedPane::Split) {
+                self.focused_pane = FocusedPane::Tracks;
+            }
+        } else {
+            self.open_playlist_picker(PickerPurpose::SplitView);
+        }
+    }
+
+    /// Copies the marked tracks, or the selected one, from the tracks pane to the split view's
+    /// playlist
+    fn copy_to_split(&mut self) {
+        let Some((playlist_id, playlist_name)) = self
+            .split
+            .as_ref()
+            .map(|split| (split.playlist_id.clone(), split.playlist_name.clone()))
+        else {
+            return;
+        };
+        let tracks = self.action_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+
+        let uris = tracks.iter().map(|track| track.uri.clone()).collect();
+        self.add_to_playlist(
+            playlist_id,
+            playlist_name,
+            uris,
+            AfterAdding::SplitView(tracks),
+        );
+    }
+
+    /// Copies the selected track of the split view to the playlist open in the tracks pane
+    fn copy_from_split(&mut self) {
+        let Some(track) = self
+            .split
+            .as_ref()
+            .and_then(|split| split.selected_track())
+            .cloned()
+        else {
+            return;
+        };
+        let Some(playlist) = (match &self.track_source {
+            Some(TrackSource::Playlist(id)) if !self.show_search => self.playlist(id),
+            _ => None,
+        }) else {
+            self.notifications
+                .error("Open a playlist in the tracks pane to copy tracks to it");
+            return;
+        };
+        let (playlist_id, playlist_name) = (playlist.id.clone(), playlist.name.clone());
+
+        let uris = vec![track.uri.clone()];
+        self.add_to_playlist(
+            playlist_id,
+            playlist_name,
+            uris,
+            AfterAdding::TracksPane(vec![track]),
+        );
+    }
+
+    /// Asks to remove the marked tracks, or the selected one, from the open playlist
+    fn remove_tracks_from_playlist(&mut self) {
+        let playlist_id = match &self.track_source {
+            Some(TrackSource::Playlist(id)) if !self.show_search => id,
+            _ => {
+                self.notifications
+                    .error("Only tracks in a playlist can be removed");
+                return;
+            }
+        };
+        let Some(playlist) = self.playlist(playlist_id) else {
+            return;
+        };
+        let tracks = self.action_tracks();
+        let what = match tracks.as_slice() {
+            [] => return,
+            [track] => format!("\"{}\"", track.name),
+            _ => describe_count(tracks.len()),
+        };
+
+        let message = format!("Remove {} from {}?", what, playlist.name);
+        let playlist_id = playlist.id.clone();
+        self.confirm(
+            message,
+            Destructive::RemoveTracks {
+                playlist_id,
+                tracks,
+            },
+        );
+    }
+
+    /// Asks to delete the playlist under the cursor in the library pane
+    fn delete_selected_playlist(&mut self) {
+        if self.library_tab != LibraryTab::Playlists {
+            return;
+        }
+        let Some(index) = self.selected_library_index() else {
+            return;
+        };
+        let Some(playlist) = self.playlists.get(index) else {
+            return;
+        };
+
+        let message = format!("Delete the playlist {}?", playlist.name);
+        let playlist_id = playlist.id.clone();
+        self.confirm(message, Destructive::DeletePlaylist(playlist_id));
+    }
+
+    /// Shows the confirmation popup for an action, or runs it straight away if
+    /// confirmations are turned off
+    fn confirm(&mut self, message: String, action: Destructive) {
+        if self.config.behavior.confirm_destructive {
+            self.confirmation = Some(Confirmation { message, action });
+        } else {
+            self.run_destructive(action);
+        }
+    }
+
+    fn handle_confirmation_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                if let Some(confirmation) = self.confirmation.take() {
+                    self.run_destructive(confirmation.action);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc | KeyCode::Char('q') => {
+                self.confirmation = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn run_destructive(&mut self, action: Destructive) {
+        match action {
+            Destructive::RemoveTracks {
+                playlist_id,
+                tracks,
+            } => {
+                let Some(playlist) = self.playlist(&playlist_id) else {
+                    self.notifications
+                        .error("The playlist is no longer in the library, nothing was removed");
+                    return;
+                };
+                let playlist_name = playlist.name.clone();
+                let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).collect();
+                let client = self.spotify_client.clone();
+                self.spawn_load(async move {
+                    let result = client
+                        .remove_tracks_from_playlist(&playlist_id, &uris)
+                        .await;
+                    Loaded::TracksRemoved {
+                        playlist_id,
+                        playlist_name,
+                        uris,
+                        result,
+                    }
+                });
+            }
+            Destructive::DeletePlaylist(playlist_id) => {
+                let Some(playlist) = self.playlists.iter().find(|p| p.id == playlist_id) else {
+                    self.notifications
+                        .error("The playlist is no longer in the library, nothing was deleted");
+                    return;
+                };
+                let playlist_name = playlist.name.clone();
+                let client = self.spotify_client.clone();
+                self.spawn_load(async move {
+                    let result = client.unfollow_playlist(&playlist_id).await;
+                    Loaded::PlaylistDeleted {
+                        playlist_id,
+                        playlist_name,
+                        result,
+                    }
+                });
+            }
+        }
+    }
+
+    /// Drops removed tracks from the tracks pane without fetching the playlist again, returning
+    /// the positions they were at. Spotify removes every occurrence of a track, so this does too
+    fn tracks_removed(&mut self, playlist_id: &str, uris: &[String]) -> Vec<(usize, String)> {
+        self.clear_marks();
+        if !matches!(&self.track_source, Some(TrackSource::Playlist(open)) if open == playlist_id) {
+            return Vec::new();
+        }
+
+        let removed: Vec<(usize, String)> = self
+            .current_tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| uris.contains(&track.uri))
+            .map(|(position, track)| (position, track.uri.clone()))
+            .collect();
+        self.current_tracks
+            .retain(|track| !uris.contains(&track.uri));
+        if let Some(playlist) = self.playlist_mut(playlist_id) {
+            playlist.tracks.total = playlist.tracks.total.saturating_sub(removed.len() as u32);
+        }
+
+        self.update_track_order();
+        let last = self.track_order.len().checked_sub(1);
+        let selected = self.tracks_state.selected();
+        self.tracks_state.select(
+            selected
+                .zip(last)
+                .map(|(selected, last)| selected.min(last)),
+        );
+        removed
+    }
+
+    /// Takes a deleted playlist out of the library, moving the tracks pane on to the
+    /// playlist that takes its place if it was open
+    fn playlist_deleted(&mut self, index: usize) -> Playlist {
+        let playlist = self.playlists.remove(index);
+        self.playlist_followers.remove(&playlist.id);
+
+        let was_open =
+            matches!(&self.track_source, Some(TrackSource::Playlist(open)) if *open == playlist.id);
+        self.nav_history.retain(
+            |entry| !matches!(&entry.source, TrackSource::Playlist(open) if *open == playlist.id),
+        );
+
+        self.update_library_order();
+        let last = self.library_order.len().saturating_sub(1);
+        let selected = self.library_state().selected();
+        self.library_state_mut()
+            .select(selected.map(|selected| selected.min(last)));
+
+        if was_open {
+            match self.selected_library_index() {
+                Some(next) => self.load_library_item(next),
+                None => {
+                    self.current_tracks.clear();
+                    self.track_source = None;
+                    self.update_track_order();
+                }
+            }
+        }
+        playlist
+    }
+
+    fn push_undo(&mut self, action: Undoable) {
+        self.undo_stack.push(action);
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Takes back the most recent change to the library in the background
+    fn undo(&mut self) {
+        let Some(action) = self.undo_stack.pop() else {
+            self.notifications.info("Nothing to undo");
+            return;
+        };
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = library::undo(&client, &action).await;
+            Loaded::Undone { action, result }
+        });
+    }
+
+    /// Records the outcome of an undo
+    fn action_undone(&mut self, action: Undoable, result: Result<String>) {
+        match result {
+            Ok(message) => {
+                self.notifications.success(format!("Undone: {}", message));
+                self.undone(action);
+            }
+            Err(e) => {
+                self.notifications.error(e);
+                // Keep it around so it can be retried
+                self.undo_stack.push(action);
+            }
+        }
+    }
+
+    /// Brings the library and tracks pane in line with an action that was just undone
+    fn undone(&mut self, action: Undoable) {
+        match action {
+            Undoable::Liked(_) => {
+                self.library_cache.forget(&cache::tracks_key("liked"));
+                if matches!(self.track_source, Some(TrackSource::Liked)) {
+                    self.load_tracks(TrackSource::Liked);
+                }
+            }
+            Undoable::AddedToPlaylist { playlist_id, .. } => self.reload_playlist(&playlist_id),
+            Undoable::RemovedFromPlaylist {
+                playlist_id,
+                removed,
+                ..
+            } => {
+                if let Some(playlist) = self.playlists.iter_mut().find(|p| p.id == playlist_id) {
+                    playlist.tracks.total += removed.len() as u32;
+                }
+                self.reload_playlist(&playlist_id);
+            }
+            Undoable::DeletedPlaylist { index, playlist } => {
+                let index = index.min(self.playlists.len());
+                self.playlists.insert(index, playlist);
+                self.update_library_order();
+            }
+        }
+    }
+
+    /// Fetches a playlist's tracks again if it's open in the tracks pane or the split view
+    fn reload_playlist(&mut self, playlist_id: &str) {
+        // The copy on disk is from before the change
+        self.library_cache.forget(&cache::tracks_key(playlist_id));
+        if matches!(&self.track_source, Some(TrackSource::Playlist(open)) if open == playlist_id) {
+            self.load_tracks(TrackSource::Playlist(playlist_id.to_string()));
+        }
+        let split = self.split.as_ref();
+        if let Some(split) = split.filter(|split| split.playlist_id == playlist_id) {
+            self.open_split(split.playlist_id.clone(), split.playlist_name.clone());
+        }
+    }
+
+    /// Lists the available devices, starting on the active one, once the worker has fetched
+    /// them
+    fn open_device_picker(&mut self) {
+        self.send(Command::ListDevices);
+    }
+
+    async fn handle_device_picker_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.show_device_picker = false;
+            }
+            KeyCode::Enter => {
+                if let Some(selected) = self.device_picker_state.selected() {
+                    self.select_device(selected);
+                }
+            }
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    let selected = self.device_picker_state.selected();
+                    let viewport = self.pane_areas.device_picker.height.saturating_sub(2);
+                    self.device_picker_state.select(motion.apply(
+                        selected,
+                        self.devices.len(),
+                        viewport as usize,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the genres to start a radio from, fetching them the first time
+    fn open_genre_picker(&mut self) {
+        if self.genres.is_none() {
+            let client = self.spotify_client.clone();
+            self.spawn_load(async move { Loaded::Genres(client.get_genre_seeds().await) });
+            return;
+        }
+        self.picked_genres.clear();
+        self.genre_picker_state.select(Some(0));
+        self.show_genre_picker = true;
+    }
+
+    fn handle_genre_picker_key(&mut self, key: KeyEvent) {
+        let len = self.genres.as_ref().map_or(0, Vec::len);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.show_genre_picker = false,
+            KeyCode::Char(' ') => {
+                if let Some(selected) = self.genre_picker_state.selected() {
+                    self.toggle_genre(selected);
+                    self.genre_picker_state
+                        .select(Motion::Down(1).apply(Some(selected), len, len));
+                }
+            }
+            KeyCode::Enter => self.play_genres(),
+            _ => {
+                if let Some(motion) = popup_motion(key) {
+                    let selected = self.genre_picker_state.selected();
+                    let viewport = self.pane_areas.genre_picker.height.saturating_sub(2);
+                    self.genre_picker_state
+                        .select(motion.apply(selected, len, viewport as usize));
+                }
+            }
+        }
+    }
+
+    /// Picks or unpicks the genre at `position`, up to MAX_GENRES of them
+    fn toggle_genre(&mut self, position: usize) {
+        if !self.picked_genres.remove(&position) {
+            if self.picked_genres.len() < MAX_GENRES {
+                self.picked_genres.insert(position);
+            } else {
+                self.notifications
+                    .info(format!("A radio takes up to {} genres", MAX_GENRES));
+            }
+        }
+    }
+
+    /// Starts a radio from the picked genres, or the one under the cursor if none are picked
+    fn play_genres(&mut self) {
+        let Some(genres) = &self.genres else {
+            return;
+        };
+        let picked: Vec<String> = if self.picked_genres.is_empty() {
+            self.genre_picker_state
+                .selected()
+                .and_then(|selected| genres.get(selected))
+                .cloned()
+                .into_iter()
+                .collect()
+        } else {
+            self.picked_genres
+                .iter()
+                .filter_map(|&index| genres.get(index).cloned())
+                .collect()
+        };
+        if picked.is_empty() {
+            return;
+        }
+        self.show_genre_picker = false;
+        let name = picked.join(", ");
+        let seeds = Seeds {
+            genres: picked,
+            ..Seeds::default()
+        };
+        self.start_radio(name, seeds, None);
+    }
+
+    /// Makes the device at `position` in the picker the target for playback
+    fn select_device(&mut self, position: usize) {
+        self.show_device_picker = false;
+        let Some(device) = self.devices.get(position).cloned() else {
+            return;
+        };
+        // Restricted devices are listed without an id and can't be controlled
+        let Some(ref device_id) = device.id else {
+            self.notifications
+                .error(format!("{} can't be controlled from here", device.name));
+            return;
+        };
+
+        self.send(Command::TransferPlayback {
+            device_id: device_id.clone(),
+            name: device.name,
+        });
+    }
+
+    async fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<()> {
+        let is_click = matches!(mouse.kind, MouseEventKind::Down(MouseButton::Left));
+
+        // Like keys, a click dismisses errors and the help popup
+        if matches!(self.state, AppState::Error(_)) {
+            if is_click {
+                self.set_state(AppState::Ready);
+            }
+            return Ok(());
+        }
+        if self.show_help {
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => self.show_help = false,
+                MouseEventKind::ScrollUp => self.scroll_help(Motion::Up(SCROLL_STEP)),
+                MouseEventKind::ScrollDown => self.scroll_help(Motion::Down(SCROLL_STEP)),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        let position = Position::new(mouse.column, mouse.row);
+        // Clicking outside the confirmation popup counts as a no
+        if self.confirmation.is_some() {
+            if is_click && !self.pane_areas.confirmation.contains(position) {
+                self.confirmation = None;
+            }
+            return Ok(());
+        }
+        if self.import_review.is_some() {
+            self.handle_import_review_mouse(mouse.kind, position);
+            return Ok(());
+        }
+        if self.comparison.is_some() {
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                    if !self.pane_areas.comparison.contains(position) =>
+                {
+                    self.comparison = None;
+                }
+                MouseEventKind::ScrollUp => self.scroll_comparison(Motion::Up(SCROLL_STEP)),
+                MouseEventKind::ScrollDown => self.scroll_comparison(Motion::Down(SCROLL_STEP)),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.stats.is_some() {
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                    if !self.pane_areas.stats.contains(position) =>
+                {
+                    self.stats = None;
+                }
+                MouseEventKind::ScrollUp => self.scroll_stats(Motion::Up(SCROLL_STEP)),
+                MouseEventKind::ScrollDown => self.scroll_stats(Motion::Down(SCROLL_STEP)),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.show_playback_controls {
+            self.handle_playback_controls_mouse(mouse.kind, position)
+                .await;
+            return Ok(());
+        } else if self.show_playlist_picker {
+            self.handle_playlist_picker_mouse(mouse.kind, position);
+            return Ok(());
+        } else if self.show_device_picker {
+            self.handle_device_picker_mouse(mouse.kind, position).await;
+            return Ok(());
+        } else if self.show_genre_picker {
+            self.handle_genre_picker_mouse(mouse.kind, position);
+            return Ok(());
+        } else if self.show_track_menu {
+            self.handle_track_menu_mouse(mouse.kind, position).await;
+            return Ok(());
+        }
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.click_at(position).await?,
+            MouseEventKind::Down(MouseButton::Right) => self.context_click_at(position),
+            MouseEventKind::ScrollUp => self.scroll_at(position, Motion::Up(SCROLL_STEP)).await?,
+            MouseEventKind::ScrollDown => {
+                self.scroll_at(position, Motion::Down(SCROLL_STEP)).await?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_playback_controls_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.playback_controls;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.show_playback_controls = false;
+                    return;
+                }
+                if let Some(row) = row_at(area, position, 0, 0) {
+                    if row < PLAYBACK_CONTROLS_COUNT {
+                        self.playback_controls_state.select(Some(row));
+                        self.execute_playback_control(row);
+                    }
+                }
+            }
+            MouseEventKind::ScrollUp => self.move_playback_controls_selection(Motion::Up(1)),
+            MouseEventKind::ScrollDown => self.move_playback_controls_selection(Motion::Down(1)),
+            _ => {}
+        }
+    }
+
+    async fn handle_track_menu_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.track_menu;
+        let len = TrackMenuItem::ALL.len();
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.show_track_menu = false;
+                    return;
+                }
+                if let Some(row) = row_at(area, position, 0, 0).filter(|&row| row < len) {
+                    self.track_menu_state.select(Some(row));
+                    self.execute_track_menu_item(TrackMenuItem::ALL[row]).await;
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let selected = self.track_menu_state.selected();
+                self.track_menu_state
+                    .select(Motion::Up(1).apply(selected, len, len));
+            }
+            MouseEventKind::ScrollDown => {
+                let selected = self.track_menu_state.selected();
+                self.track_menu_state
+                    .select(Motion::Down(1).apply(selected, len, len));
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_playlist_picker_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.playlist_picker;
+        let len = self.playlist_picker_entries().len();
+        let viewport = area.height.saturating_sub(2) as usize;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.show_playlist_picker = false;
+                    return;
+                }
+                let offset = self.playlist_picker_state.offset();
+                if let Some(row) = row_at(area, position, 0, offset).filter(|&row| row < len) {
+                    self.playlist_picker_state.select(Some(row));
+                    self.pick_playlist(row);
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let selected = self.playlist_picker_state.selected();
+                self.playlist_picker_state
+                    .select(Motion::Up(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            MouseEventKind::ScrollDown => {
+                let selected = self.playlist_picker_state.selected();
+                self.playlist_picker_state
+                    .select(Motion::Down(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            _ => {}
+        }
+    }
+
+    async fn handle_device_picker_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.device_picker;
+        let len = self.devices.len();
+        let viewport = area.height.saturating_sub(2) as usize;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.show_device_picker = false;
+                    return;
+                }
+                let offset = self.device_picker_state.offset();
+                if let Some(row) = row_at(area, position, 0, offset).filter(|&row| row < len) {
+                    self.device_picker_state.select(Some(row));
+                    self.select_device(row);
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let selected = self.device_picker_state.selected();
+                self.device_picker_state
+                    .select(Motion::Up(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            MouseEventKind::ScrollDown => {
+                let selected = self.device_picker_state.selected();
+                self.device_picker_state
+                    .select(Motion::Down(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            _ => {}
+        }
+    }
+
+    /// Clicking a genre picks or unpicks it
+    fn handle_genre_picker_mouse(&mut self, kind: MouseEventKind, position: Position) {
+        let area = self.pane_areas.genre_picker;
+        let len = self.genres.as_ref().map_or(0, Vec::len);
+        let viewport = area.height.saturating_sub(2) as usize;
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !area.contains(position) {
+                    self.show_genre_picker = false;
+                    return;
+                }
+                let offset = self.genre_picker_state.offset();
+                if let Some(row) = row_at(area, position, 0, offset).filter(|&row| row < len) {
+                    self.genre_picker_state.select(Some(row));
+                    self.toggle_genre(row);
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let selected = self.genre_picker_state.selected();
+                self.genre_picker_state
+                    .select(Motion::Up(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            MouseEventKind::ScrollDown => {
+                let selected = self.genre_picker_state.selected();
+                self.genre_picker_state
+                    .select(Motion::Down(SCROLL_STEP).apply(selected, len, viewport));
+            }
+            _ => {}
+        }
+    }
+
+    /// Right-clicking a track selects it and opens its context menu
+    fn context_click_at(&mut self, position: Position) {
+        if !matches!(self.pane_at(position), Some(FocusedPane::Tracks)) {
+            return;
+        }
+        self.editing_filter = None;
+        self.focused_pane = FocusedPane::Tracks;
+        if let Some(row) = self.track_row_at(position) {
+            self.tracks_state_mut().select(Some(row));
+            self.open_track_menu();
+        }
+    }
+
+    /// The row of the tracks pane under the cursor, if it holds a track
+    fn track_row_at(&self, position: Position) -> Option<usize> {
+        let offset = if self.show_search {
+            self.search_state.offset()
+        } else {
+            self.tracks_state.offset()
+        };
+        // Skip the header row of the table
+        let row = row_at(self.pane_areas.tracks, position, 1, offset)?;
+        if !self.group_by_album {
+            return Some(row).filter(|&row| row < self.track_order.len());
+        }
+
+        // Rows that start an album are two lines tall, walk down to the clicked one
+        let mut lines = row - offset;
+        for row in offset..self.track_order.len() {
+            let height = if self.starts_album_group(row) { 2 } else { 1 };
+            if lines < height {
+                return Some(row);
+            }
+            lines -= height;
+        }
+        None
+    }
+
+    /// The row of the library pane under the cursor
+    fn library_row_at(&self, position: Position) -> Option<usize> {
+        let offset = self.library_state().offset();
+        let row = row_at(self.pane_areas.library, position, 0, offset)?;
+
+        // Rows that start an open folder are two lines tall, walk down to the clicked one
+        let mut lines = row - offset;
+        for row in offset..self.library_order.len() {
+            let height = if self.starts_open_folder(row) { 2 } else { 1 };
+            if lines < height {
+                return Some(row);
+            }
+            lines -= height;
+        }
+        None
+    }
+
+    /// Whether the track at `position` in the tracks pane gets an album header above it, being
+    /// the first of a run from the same album while grouping by album
+    pub fn starts_album_group(&self, position: usize) -> bool {
+        if !self.group_by_album {
+            return false;
+        }
+        let tracks = self.get_display_tracks();
+        let album_id = |position: usize| {
+            self.track_order
+                .get(position)
+                .and_then(|&index| tracks.get(index))
+                .map(|track| track.album.id.as_str())
+        };
+        position == 0 || album_id(position) != album_id(position - 1)
+    }
+
+    /// Position of the cursor in the tracks pane
+    fn selected_track_position(&self) -> Option<usize> {
+        if self.show_search {
+            self.search_state.selected()
+        } else {
+            self.tracks_state.selected()
+        }
+    }
+
+    fn tracks_state_mut(&mut self) -> &mut TableState {
+        if self.show_search {
+            &mut self.search_state
+        } else {
+            &mut self.tracks_state
+        }
+    }
+
+    fn pane_at(&self, position: Position) -> Option<FocusedPane> {
+        if self.pane_areas.library.contains(position) {
+            Some(FocusedPane::Library)
+        } else if self.pane_areas.tracks.contains(position) {
+            Some(FocusedPane::Tracks)
+        } else if self.split.is_some() && self.pane_areas.split.contains(position) {
+            Some(FocusedPane::Split)
+        } else if self.show_search && self.pane_areas.search_bar.contains(position) {
+            Some(FocusedPane::SearchInput)
+        } else {
+            None
+        }
+    }
+
+    /// Focuses the pane under the cursor and selects the clicked row, double-click plays a track
+    async fn click_at(&mut self, position: Position) -> Result<()> {
+        let clicked_tab = self
+            .pane_areas
+            .library_tabs
+            .iter()
+            .position(|area| area.contains(position));
+        if let Some(index) = clicked_tab {
+            self.switch_library_tab(LibraryTab::ALL[index]);
+            return Ok(());
+        }
+
+        let Some(pane) = self.pane_at(position) else {
+            return Ok(());
+        };
+        self.editing_filter = None;
+        self.focused_pane = pane;
+
+        match pane {
+            FocusedPane::Library => {
+                if let Some(row) = self.library_row_at(position) {
+                    if self.library_state().selected() != Some(row) {
+                        self.library_state_mut().select(Some(row));
+                        self.load_library_item(self.library_order[row]);
+                    }
+                    if self.on_collapsed_folder() {
+                        self.set_folder_open(true);
+                    }
+                }
+            }
+            FocusedPane::Tracks => {
+                if let Some(row) = self.track_row_at(position) {
+                    self.tracks_state_mut().select(Some(row));
+
+                    let now = Instant::now();
+                    let is_double_click = self.last_track_click.is_some_and(|(time, last_row)| {
+                        last_row == row && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+                    });
+                    if is_double_click {
+                        self.last_track_click = None;
+                        self.play_selected_track();
+                    } else {
+                        self.last_track_click = Some((now, row));
+                    }
+                }
+            }
+            FocusedPane::Split => {
+                let area = self.pane_areas.split;
+                if let Some(split) = &mut self.split {
+                    let len = split.tracks.as_ref().map_or(0, Vec::len);
+                    let row = row_at(area, position, 1, split.state.offset());
+                    if let Some(row) = row.filter(|&row| row < len) {
+                        split.state.select(Some(row));
+                    }
+                }
+            }
+            FocusedPane::SearchInput => {}
+        }
+        Ok(())
+    }
+
+    /// Scrolls the list under the cursor, focusing it
+    async fn scroll_at(&mut self, position: Position, motion: Motion) -> Result<()> {
+        match self.pane_at(position) {
+            Some(pane @ (FocusedPane::Library | FocusedPane::Tracks | FocusedPane::Split)) => {
+                self.focused_pane = pane;
+                self.move_selection(motion).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn move_playback_controls_selection(&mut self, motion: Motion) {
+        // 0: Play/Pause, 1: Previous, 2: Next, 3: Close
+        let selected = self.playback_controls_state.selected();
+        if let Some(position) =
+            motion.apply(selected, PLAYBACK_CONTROLS_COUNT, PLAYBACK_CONTROLS_COUNT)
+        {
+            self.playback_controls_state.select(Some(position));
+        }
+    }
+
+    /// Queues the marked tracks, or the selected track if none are marked. Spotify takes one
+    /// track per request, so even one track goes through process_queue_batch, which keeps
+    /// tracks queued in quick succession in order
+    fn add_tracks_to_queue(&mut self) {
+        let tracks = self.action_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+        if self.connection.is_offline() {
+            self.defer_offline(OfflineAction::Queue(tracks));
+            return;
+        }
+        self.clear_marks();
+        self.enqueue_batch(tracks);
+    }
+
+    /// Runs a script command on the marked or selected tracks, then carries out what it asked
+    fn run_script_command(&mut self, command: &str) {
+        let tracks = self.action_tracks();
+        let context = CommandContext {
+            tracks: &tracks,
+            playing: self
+                .currently_playing
+                .as_ref()
+                .and_then(|p| p.item.as_ref()),
+        };
+        match self.scripts.run(command, context) {
+            Ok(actions) => {
+                // Queued one after another in one go, in the order the script gave them
+                let mut queue = Vec::new();
+                for action in actions {
+                    match action {
+                        ScriptAction::Queue(uri) => queue.push(uri),
+                        action => self.run_script_action(action),
+                    }
+                }
+                self.queue_uris(queue);
+            }
+            Err(e) => self.notifications.error(format!("{:#}", e)),
+        }
+    }
+
+    fn run_script_action(&mut self, action: ScriptAction) {
+        match action {
+            ScriptAction::Play(uri) if cli::is_context(&uri) => {
+                self.send(Command::PlayContext(uri))
+            }
+            ScriptAction::Play(uri) => self.send(Command::Play(vec![uri])),
+            ScriptAction::Queue(uri) => self.queue_uris(vec![uri]),
+            ScriptAction::Pause => self.send(Command::Pause),
+            ScriptAction::Resume => self.send(Command::Resume),
+            ScriptAction::Next => self.send(Command::Next),
+            ScriptAction::Previous => self.send(Command::Previous),
+            ScriptAction::Notify(message) => self.notifications.info(message),
+        }
+    }
+
+    /// Adds URIs to the queue one after another in the background, then shows the queue
+    fn queue_uris(&mut self, uris: Vec<String>) {
+        if uris.is_empty() {
+            return;
+        }
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let queued = async {
+                for uri in &uris {
+                    client.add_to_queue(uri).await?;
+                }
+                Ok(())
+            };
+            Loaded::UrisQueued(queued.await)
+        });
+    }
+
+    /// Adds tracks to the running batch queue addition, starting one if there's none
+    fn enqueue_batch(&mut self, tracks: Vec<Track>) {
+        self.queue_batch
+            .get_or_insert_with(QueueBatch::default)
+            .add(tracks);
+    }
+
+    /// Sends the next request of a batch queue addition, if one is running, the last request
+    /// has been answered and it isn't waiting out the rate limit
+    fn process_queue_batch(&mut self) {
+        if self.connection.is_offline() {
+            if let Some(batch) = self.queue_batch.take().filter(|b| !b.pending().is_empty()) {
+                self.defer_offline(OfflineAction::Queue(batch.into_pending()));
+            }
+            return;
+        }
+        let Some(track) = self.queue_batch.as_mut().and_then(QueueBatch::take_next) else {
+            return;
+        };
+
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move {
+            let result = client.add_to_queue(&track.uri).await;
+            Loaded::Queued { track, result }
+        });
+    }
+
+    /// Carries on with the batch queue addition once a track has been added
+    fn track_queued(&mut self, track: Track, result: Result<()>) {
+        if let Some(batch) = self.queue_batch.as_mut() {
+            batch.answered();
+        }
+        if let Err(e) = result {
+            if self.connection.is_offline() && is_network_error(&e) {
+                self.defer_offline(OfflineAction::Queue(vec![track]));
+                return;
+            }
+            if let Some(SpotifyError::RateLimited { retry_after }) = e.downcast_ref() {
+                // Try the same track again once Spotify lets us
+                if let Some(batch) = self.queue_batch.as_mut() {
+                    batch.retry(track, *retry_after);
+                }
+                return;
+            }
+            // Later requests would most likely fail the same way
+            match self.queue_batch.take() {
+                Some(batch) if batch.total > 1 => self.notifications.error(format!(
+                    "Stopped adding to queue after {} of {}: {}",
+                    batch.done() - 1,
+                    batch.total,
+                    e
+                )),
+                _ => self.notifications.error(e),
+            }
+            return;
+        }
+        self.hooks.run(hooks::Event::QueueAdded, &track);
+
+        if self
+            .queue_batch
+            .as_ref()
+            .is_some_and(|batch| batch.pending().is_empty())
+        {
+            let total = self.queue_batch.take().map_or(0, |batch| batch.total);
+            let added = match total {
+                1 => track.name,
+                _ => describe_count(total),
+            };
+            self.notifications
+                .success(format!("Added {} to queue", added));
+            // Immediately update the queue to show the new addition
+            self.refresh_queue();
+        }
+    }
+
+    /// The tracks batch actions apply to: the marked tracks in display order, then any marked
+    /// tracks hidden by the filter, or just the selected track when nothing is marked
+    fn action_tracks(&self) -> Vec<Track> {
+        if self.marked_tracks.is_empty() {
+            return self.selected_track().cloned().into_iter().collect();
+        }
+
+        let tracks = self.get_display_tracks();
+        let visible = self
+            .track_order
+            .iter()
+            .filter(|index| self.marked_tracks.contains(index));
+        let hidden = self
+            .marked_tracks
+            .iter()
+            .filter(|index| !self.track_order.contains(index));
+        visible
+            .chain(hidden)
+            .filter_map(|&index| tracks.get(index))
+            .cloned()
+            .collect()
+    }
+
+    pub fn has_marks(&self) -> bool {
+        !self.marked_tracks.is_empty() || self.visual_anchor.is_some()
+    }
+
+    pub fn visual_mode(&self) -> bool {
+        self.visual_anchor.is_some()
+    }
+
+    fn clear_marks(&mut self) {
+        self.marked_tracks.clear();
+        self.visual_anchor = None;
+    }
+
+    /// Marks or unmarks the selected track and moves to the next one
+    fn toggle_mark(&mut self) {
+        let Some(position) = self.selected_track_position() else {
+            return;
+        };
+        let Some(&index) = self.track_order.get(position) else {
+            return;
+        };
+        // Marking by hand finishes a visual selection, keeping what it covered
+        self.visual_anchor = None;
+        if !self.marked_tracks.remove(&index) {
+            self.marked_tracks.insert(index);
+        }
+        let next = Motion::Down(1).apply(Some(position), self.track_order.len(), 1);
+        self.tracks_state_mut().select(next);
+    }
+
+    /// Starts marking everything between the selected track and the cursor, or finishes doing so
+    fn toggle_visual_mode(&mut self) {
+        if self.visual_anchor.take().is_some() {
+            return;
+        }
+        let anchor = self
+            .selected_track_position()
+            .and_then(|position| self.track_order.get(position).copied());
+        if let Some(anchor) = anchor {
+            self.visual_anchor = Some((anchor, self.marked_tracks.clone()));
+            self.update_visual_marks();
+        }
+    }
+
+    /// Marks the range between the visual anchor and the cursor, on top of the earlier marks
+    fn update_visual_marks(&mut self) {
+        let Some((anchor, ref base)) = self.visual_anchor else {
+            return;
+        };
+        let anchor_position = self.track_order.iter().position(|&index| index == anchor);
+        let (Some(anchor_position), Some(cursor)) =
+            (anchor_position, self.selected_track_position())
+        else {
+            return;
+        };
+
+        let range = anchor_position.min(cursor)..=anchor_position.max(cursor);
+        let mut marks = base.clone();
+        marks.extend(self.track_order[range].iter().copied());
+        self.marked_tracks = marks;
+    }
+
+    fn play_selected_track(&mut self) {
+        if let Some(track) = self.selected_track() {
+            self.send(Command::Play(vec![track.uri.clone()]));
+        }
+    }
+
+    /// Id of the track playing on the active device, if any
+    pub fn playing_track_id(&self) -> Option<&str> {
+        self.currently_playing
+            .as_ref()?
+            .item
+            .as_ref()
+            .map(|track| track.id.as_str())
+    }
+
+    /// The track under the cursor in the tracks pane, accounting for the current sort order
+    pub fn selected_track(&self) -> Option<&Track> {
+        let selected = self.selected_track_position()?;
+        let index = *self.track_order.get(selected)?;
+        self.get_display_tracks().get(index)
+    }
+
+    /// Recomputes the display order of the tracks pane from the active filter and sort
+    pub fn update_track_order(&mut self) {
+        let tracks = self.get_display_tracks();
+        // The filter only narrows playlists, search results come straight from the API
+        let filter = if self.show_search {
+            ""
+        } else {
+            self.track_filter.text()
+        };
+        let hide_explicit = self.config.display.explicit == ExplicitMode::Hide;
+        let mut scored: Vec<(usize, i64)> = tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, track)| {
+                if hide_explicit && track.explicit {
+                    None
+                } else if filter.is_empty() {
+                    Some((index, 0))
+                } else {
+                    fuzzy::track_score(track, filter).map(|score| (index, score))
+                }
+            })
+            .collect();
+
+        // Stable sorts, so ties keep their playlist order
+        if let Some(sort) = self.track_sort {
+            scored.sort_by(|&(a, _), &(b, _)| {
+                let ordering = sort.column.compare(&tracks[a], &tracks[b], &self.tempos);
+                if sort.ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+        } else if !filter.is_empty() {
+            scored.sort_by(|(_, a), (_, b)| b.cmp(a));
+        }
+        self.track_order = scored.into_iter().map(|(index, _)| index).collect();
+        if self
+            .track_sort
+            .is_some_and(|sort| sort.column == SortColumn::Tempo)
+        {
+            self.fetch_tempos();
+        }
+    }
+
+    /// Fetches the tempo of the shown tracks that haven't been asked about yet, to sort by
+    fn fetch_tempos(&mut self) {
+        let mut ids: Vec<String> = self
+            .get_display_tracks()
+            .iter()
+            .filter(|track| track.uri.starts_with("spotify:track:"))
+            .map(|track| track.id.clone())
+            .collect();
+        // Tracks Spotify hasn't analysed come back without features, they aren't asked again
+        ids.retain(|id| self.tempos_requested.insert(id.clone()));
+        if ids.is_empty() {
+            return;
+        }
+        let client = self.spotify_client.clone();
+        self.spawn_load(async move { Loaded::Tempos(client.get_audio_features(&ids).await) });
+    }
+
+    /// Indices into playlists of the ones Spotify made for the listener, as the For You tab
+    /// lists them
+    pub fn for_you_playlists(&self) -> Vec<usize> {
+        let mut for_you: Vec<usize> = (0..self.playlists.len())
+            .filter(|&index| is_for_you(&self.playlists[index]))
+            .collect();
+        for_you.sort_by_key(|&index| {
+            let playlist = &self.playlists[index];
+            let kind = FOR_YOU_PLAYLISTS
+                .iter()
+                .position(|name| playlist.name.starts_with(name))
+                .unwrap_or(FOR_YOU_PLAYLISTS.len());
+            (kind, playlist.name.clone())
+        });
+        for_you
+    }
+
+    /// The playlist in the library with this id
+    pub fn playlist(&self, id: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|playlist| playlist.id == id)
+    }
+
+    fn playlist_mut(&mut self, id: &str) -> Option<&mut Playlist> {
+        self.playlists.iter_mut().find(|playlist| playlist.id == id)
+    }
+
+    /// The playlist at `index` in the For You tab
+    fn for_you_playlist(&self, index: usize) -> Option<usize> {
+        self.for_you_playlists().get(index).copied()
+    }
+
+    /// Whether the entry at `index` in the active library tab is a For You playlist
+    pub fn in_for_you(&self, index: usize) -> bool {
+        self.config.features.for_you
+            && self.library_tab == LibraryTab::Playlists
+            && self.playlists.get(index).is_some_and(is_for_you)
+    }
+
+    /// Whether the entry at `index` in the active library tab is a pinned playlist
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.library_tab == LibraryTab::Playlists
+            && self
+                .playlists
+                .get(index)
+                .is_some_and(|playlist| self.local_state.is_pinned(&playlist.id))
+    }
+
+    /// Pins the playlist under the cursor to the top of the Playlists tab, or unpins it
+    fn toggle_pin(&mut self) {
+        if self.library_tab != LibraryTab::Playlists {
+            return;
+        }
+        let Some(index) = self.selected_library_index() else {
+            return;
+        };
+        let Some(playlist) = self.playlists.get(index) else {
+            return;
+        };
+        let (playlist_id, playlist_name) = (playlist.id.clone(), playlist.name.clone());
+
+        let pinned = self.local_state.toggle_pin(&playlist_id);
+        if let Err(e) = self.local_state.save() {
+            self.notifications.error(e);
+        }
+        self.update_library_order();
+        // Keep the cursor on the playlist as it moves
+        let position = self.library_order.iter().position(|&i| i == index);
+        self.library_state_mut().select(position);
+
+        if pinned {
+            self.notifications.info(format!("Pinned {}", playlist_name));
+        } else {
+            self.notifications
+                .info(format!("Unpinned {}", playlist_name));
+        }
+    }
+
+    /// Recomputes the display order of the library pane from the active filter, with pinned
+    /// playlists first and the rest of the Playlists tab in the order picked with o
+    pub fn update_library_order(&mut self) {
+        let sort = match self.library_tab {
+            LibraryTab::Playlists => self.local_state.playlist_sort,
+            _ => PlaylistSort::Spotify,
+        };
+        if sort == PlaylistSort::RecentlyChanged && self.last_added.is_none() {
+            self.last_added = Some(self.library_cache.last_added());
+        }
+
+        let mut scored: Vec<(usize, i64)> = self
+            .library_names()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| {
+                if self.library_filter.is_empty() {
+                    Some((index, 0))
+                } else {
+                    fuzzy::fuzzy_score(name, self.library_filter.text()).map(|score| (index, score))
+                }
+            })
+            .collect();
+        scored.sort_by(|(a_index, a), (b_index, b)| {
+            self.in_for_you(*b_index)
+                .cmp(&self.in_for_you(*a_index))
+                .then(self.is_pinned(*b_index).cmp(&self.is_pinned(*a_index)))
+                .then(b.cmp(a))
+                .then_with(|| self.compare_playlists(sort, *a_index, *b_index))
+        });
+        let order = scored.into_iter().map(|(index, _)| index).collect();
+        self.library_order = self.group_into_folders(order);
+    }
+
+    /// Gathers the playlists of each folder where its first one is, leaving only that one to
+    /// stand in for a collapsed folder
+    fn group_into_folders(&self, order: Vec<usize>) -> Vec<usize> {
+        let mut groups: Vec<(Option<&str>, Vec<usize>)> = Vec::new();
+        for index in order {
+            let folder = self.library_folder(index).map(|(folder, _)| folder);
+            match groups
+                .iter_mut()
+                .find(|(f, _)| folder.is_some() && *f == folder)
+            {
+                Some((_, members)) => members.push(index),
+                None => groups.push((folder, vec![index])),
+            }
+        }
+        groups
+            .into_iter()
+            .flat_map(|(folder, mut members)| {
+                if folder.is_some_and(|folder| !self.local_state.is_folder_open(folder)) {
+                    members.truncate(1);
+                }
+                members
+            })
+            .collect()
+    }
+
+    /// The folder the playlist at `index` is in and its name within it, from the config's
+    /// [folders] or else a name like "Folder / Name". For You and pinned playlists stay out
+    /// of folders, as does everything while filtering
+    pub fn library_folder(&self, index: usize) -> Option<(&str, &str)> {
+        if self.library_tab != LibraryTab::Playlists
+            || !self.library_filter.is_empty()
+            || self.in_for_you(index)
+            || self.is_pinned(index)
+        {
+            return None;
+        }
+        let playlist = self.playlists.get(index)?;
+        let listed = self.config.folders.iter().find(|(_, playlists)| {
+            playlists
+                .iter()
+                .any(|entry| *entry == playlist.name || *entry == playlist.id)
+        });
+        if let Some((folder, _)) = listed {
+            return Some((folder, &playlist.name));
+        }
+
+        let separator = self.config.display.folder_separator.as_str();
+        if separator.is_empty() {
+            return None;
+        }
+        let (folder, name) = playlist.name.split_once(separator)?;
+        let (folder, name) = (folder.trim(), name.trim());
+        (!folder.is_empty() && !name.is_empty()).then_some((folder, name))
+    }
+
+    /// Whether the playlist at `index` is in a collapsed folder, where the first of them
+    /// stands in for the folder
+    pub fn in_collapsed_folder(&self, index: usize) -> bool {
+        self.library_folder(index)
+            .is_some_and(|(folder, _)| !self.local_state.is_folder_open(folder))
+    }
+
+    /// Whether the cursor in the library pane is on a collapsed folder
+    fn on_collapsed_folder(&self) -> bool {
+        self.library_state()
+            .selected()
+            .and_then(|row| self.library_order.get(row))
+            .is_some_and(|&index| self.in_collapsed_folder(index))
+    }
+
+    /// How many playlists are in the folder
+    pub fn folder_size(&self, folder: &str) -> usize {
+        (0..self.playlists.len())
+            .filter(|&index| self.library_folder(index).is_some_and(|(f, _)| f == folder))
+            .count()
+    }
+
+    /// Whether the row at `position` in the library pane is the first of an open folder,
+    /// which carries the folder's header line above it
+    pub fn starts_open_folder(&self, position: usize) -> bool {
+        let folder = |position: usize| {
+            self.library_order
+                .get(position)
+                .and_then(|&index| self.library_folder(index))
+                .map(|(folder, _)| folder)
+        };
+        match folder(position) {
+            Some(name) => {
+                self.local_state.is_folder_open(name)
+                    && (position == 0 || folder(position - 1) != Some(name))
+            }
+            None => false,
+        }
+    }
+
+    /// Expands or collapses the folder of the row under the cursor, which stays on the folder
+    fn set_folder_open(&mut self, open: bool) {
+        let Some(index) = self
+            .library_state()
+            .selected()
+            .and_then(|row| self.library_order.get(row).copied())
+        else {
+            return;
+        };
+        let Some((folder, _)) = self.library_folder(index) else {
+            return;
+        };
+        let folder = folder.to_string();
+        if self.local_state.is_folder_open(&folder) == open {
+            return;
+        }
+        self.local_state.set_folder_open(&folder, open);
+        if let Err(e) = self.local_state.save() {
+            self.notifications.error(e);
+        }
+        self.update_library_order();
+        let position = self.library_order.iter().position(|&index| {
+            self.library_folder(index)
+                .is_some_and(|(name, _)| name == folder)
+        });
+        self.library_state_mut().select(position);
+    }
+
+    /// Orders two playlists by `sort`, leaving them as Spotify has them if it can't tell
+    fn compare_playlists(&self, sort: PlaylistSort, a: usize, b: usize) -> Ordering {
+        let (Some(a), Some(b)) = (self.playlists.get(a), self.playlists.get(b)) else {
+            return Ordering::Equal;
+        };
+        match sort {
+            PlaylistSort::Spotify => Ordering::Equal,
+            PlaylistSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            PlaylistSort::Owner => {
+                let owner = |playlist: &Playlist| {
+                    playlist
+                        .owner
+                        .as_ref()
+                        .map(|owner| owner.display_name.as_ref().unwrap_or(&owner.id))
+                        .map(|name| name.to_lowercase())
+                };
+                owner(a)
+                    .cmp(&owner(b))
+                    .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            }
+            // Newest first, with playlists that haven't been fetched yet last
+            PlaylistSort::RecentlyChanged => self.last_change(&b.id).cmp(&self.last_change(&a.id)),
+        }
+    }
+
+    /// When a track was last added to the playlist, going by this session's fetches and then
+    /// the library cache
+    fn last_change(&self, playlist_id: &str) -> Option<&str> {
+        match self.track_cache.get(playlist_id) {
+            Some(tracks) => tracks.iter().filter_map(|t| t.added_at.as_deref()).max(),
+            None => self
+                .last_added
+                .as_ref()?
+                .get(playlist_id)
+                .map(String::as_str),
+        }
+    }
+
+    /// Moves the Playlists tab on to the next order, keeping the cursor on the same playlist
+    fn cycle_playlist_sort(&mut self) {
+        if self.library_tab != LibraryTab::Playlists {
+            return;
+        }
+        let sort = self.local_state.playlist_sort.next();
+        self.local_state.playlist_sort = sort;
+        if let Err(e) = self.local_state.save() {
+            self.notifications.error(e);
+        }
+        let selected = self.selected_library_index();
+        self.update_library_order();
+        let position =
+            selected.and_then(|index| self.library_order.iter().position(|&i| i == index));
+        self.library_state_mut().select(position.or(Some(0)));
+        self.notifications
+            .info(format!("Playlists {}", sort.label()));
+    }
+
+    /// Index into the active tab's items of the entry under the cursor in the library pane
+    /// None on a collapsed folder, whose row isn't the playlist standing in for it
+    pub fn selected_library_index(&self) -> Option<usize> {
+        let selected = self.library_state().selected()?;
+        let index = self.library_order.get(selected).copied()?;
+        (!self.in_collapsed_folder(index)).then_some(index)
+    }
+
+    fn cycle_sort_column(&mut self) {
+        self.track_sort = match self.track_sort {
+            None => Some(TrackSort {
+                column: SortColumn::Title,
+                ascending: true,
+            }),
+            Some(sort) => sort
+                .column
+                .next()
+                .map(|column| TrackSort { column, ..sort }),
+        };
+        self.resort_keeping_selection();
+    }
+
+    fn toggle_sort_direction(&mut self) {
+        if let Some(ref mut sort) = self.track_sort {
+            sort.ascending = !sort.ascending;
+            self.resort_keeping_selection();
+        }
+    }
+
+    fn resort_keeping_selection(&mut self) {
+        let selected_index = {
+            let state = if self.show_search {
+                &self.search_state
+            } else {
+                &self.tracks_state
+            };
+            state
+                .selected()
+                .and_then(|selected| self.track_order.get(selected).copied())
+        };
+
+        self.update_track_order();
+
+        if let Some(index) = selected_index {
+            let position = self.track_order.iter().position(|&i| i == index);
+            if self.show_search {
+                self.search_state.select(position);
+            } else {
+                self.tracks_state.select(position);
+            }
+        }
+    }
+}
+
+/// Fetches the tracks a source lists
+async fn fetch_tracks(
+    client: &impl SpotifyApi,
+    source: &TrackSource,
+    cached: Vec<Track>,
+) -> Result<Vec<Track>> {
+    match source {
+        TrackSource::Playlist(id) => client.get_playlist_tracks(id).await,
+        TrackSource::Album(album) => client.get_album_tracks(album).await,
+        TrackSource::Artist(artist) => client.get_artist_top_tracks(&artist.id).await,
+        TrackSource::Show(show) => client.get_show_episodes(show).await,
+        TrackSource::Liked => client.get_playlist_tracks("liked").await,
+        TrackSource::RecentlyAdded => {
+            let liked = client.get_playlist_tracks("liked").await?;
+            Ok(recently_added(liked.into_iter().chain(cached)))
+        }
+    }
+}
+
+/// The first page of tracks for the tracks pane. Playlists and Liked Songs come a page at a
+/// time, the rest all at once
+async fn fetch_first_page(
+    client: &impl SpotifyApi,
+    source: &TrackSource,
+    cached: Vec<Track>,
+) -> Result<TrackPage> {
+    match source {
+        TrackSource::Playlist(id) => client.get_playlist_tracks_page(id, 0).await,
+        TrackSource::Liked => client.get_playlist_tracks_page("liked", 0).await,
+        source => fetch_tracks(client, source, cached)
+            .await
+            .map(TrackPage::from),
+    }
+}
+
+/// Whether a request failed for want of a connection to Spotify
+fn is_network_error(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(SpotifyError::Network(_)))
+}
+
+/// Whether two lists of playlists would look the same in the library
+fn same_playlists(a: &[Playlist], b: &[Playlist]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.id == b.id && a.name == b.name && a.tracks.total == b.tracks.total)
+}
+
+/// Whether Spotify made the playlist for the listener, like Discover Weekly or a Blend, which
+/// is named after the listeners in it like "Alex + Sam"
+pub fn is_for_you(playlist: &Playlist) -> bool {
+    playlist
+        .owner
+        .as_ref()
+        .is_some_and(|owner| owner.id == "spotify")
+        && (FOR_YOU_PLAYLISTS
+            .iter()
+            .any(|name| playlist.name.starts_with(name))
+            || playlist.name.contains(" + "))
+}
+
+/// This week's Monday, when Discover Weekly refreshes, as YYYY-MM-DD in UTC
+fn week_start() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86_400;
+    // 1970-01-01 was a Thursday
+    let monday = days - (days + 3) % 7;
+    civil_date(monday as i64)
+}
+
+/// Name of a playlist saved from the queue, like "Queue 2024-06-03 18:30" in UTC
+fn queue_playlist_name() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let minutes = seconds % 86_400 / 60;
+    format!(
+        "Queue {} {:02}:{:02}",
+        civil_date((seconds / 86_400) as i64),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// The YYYY-MM-DD date a number of days after 1970-01-01, using Howard Hinnant's
+/// civil_from_days
+pub fn civil_date(days: i64) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The tracks that have a date added, newest first, each listed once at its latest addition
+fn recently_added(tracks: impl Iterator<Item = Track>) -> Vec<Track> {
+    let mut tracks: Vec<Track> = tracks.filter(|track| track.added_at.is_some()).collect();
+    tracks.sort_by(|a, b| b.added_at.cmp(&a.added_at));
+    let mut seen = HashSet::new();
+    tracks.retain(|track| seen.insert(track.uri.clone()));
+    tracks.truncate(RECENTLY_ADDED_LIMIT);
+    tracks
+}
+
+/// The open.spotify.com page for a spotify: URI, like spotify:track:<id>
+pub fn web_link(uri: &str) -> String {
+    match uri
+        .strip_prefix("spotify:")
+        .and_then(|rest| rest.split_once(':'))
+    {
+        Some((kind, id)) => format!("https://open.spotify.com/{}/{}", kind, id),
+        None => uri.to_string(),
+    }
+}
+
+/// The cursor movement a key makes in a popup menu, if any
+fn popup_motion(key: KeyEvent) -> Option<Motion> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Some(Motion::Up(1)),
+        KeyCode::Down | KeyCode::Char('j') => Some(Motion::Down(1)),
+        KeyCode::Char('p') if ctrl => Some(Motion::Up(1)),
+        KeyCode::Char('n') if ctrl => Some(Motion::Down(1)),
+        KeyCode::PageUp => Some(Motion::PageUp),
+        KeyCode::PageDown => Some(Motion::PageDown),
+        KeyCode::Home | KeyCode::Char('g') => Some(Motion::Top),
+        KeyCode::End | KeyCode::Char('G') => Some(Motion::Bottom),
+        _ => None,
+    }
+}
+
+/// Row index under `position` in a bordered list drawn at `area`, below `header_rows` header rows
+fn row_at(area: Rect, position: Position, header_rows: u16, offset: usize) -> Option<usize> {
+    let first_row = area.y + 1 + header_rows;
+    let last_row = area.bottom().saturating_sub(1);
+    if !area.contains(position) || position.y < first_row || position.y >= last_row {
+        return None;
+    }
+    Some(offset + (position.y - first_row) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{playlist, track, MockSpotify};
+    use spotitui_core::spotify::PlaylistOwner;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    async fn press(app: &mut App<MockSpotify>, keys: &str) {
+        for c in keys.chars() {
+            app.handle_key_event(key(KeyCode::Char(c))).await.unwrap();
+        }
+    }
+
+    /// Applies background results until `done` holds, failing the test if it never does
+    async fn settle(app: &mut App<MockSpotify>, done: impl Fn(&App<MockSpotify>) -> bool) {
+        for _ in 0..200 {
+            app.receive_loaded();
+            app.receive_worker_events();
+            if done(app) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        panic!("background work never finished");
+    }
+
+    /// Sends the batch queue addition's next track and waits for it to be answered
+    async fn queue_next(app: &mut App<MockSpotify>) {
+        app.process_queue_batch();
+        settle(app, |app| {
+            app.queue_batch
+                .as_ref()
+                .is_none_or(|batch| !batch.is_sending())
+        })
+        .await;
+    }
+
+    /// An app with one playlist of ten tracks, open in the tracks pane
+    async fn loaded_app() -> (App<MockSpotify>, MockSpotify) {
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            state.playlists = vec![playlist("mix", "Mix", 10)];
+            let tracks = (0..10)
+                .map(|i| track(&format!("t{}", i), &format!("Track {}", i)))
+                .collect();
+            state.playlist_tracks.insert("mix".to_string(), tracks);
+        }
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 10).await;
+        (app, mock)
+    }
+
+    #[tokio::test]
+    async fn loading_playlists_opens_the_first_one() {
+        let (app, mock) = loaded_app().await;
+
+        assert!(matches!(&app.track_source, Some(TrackSource::Playlist(id)) if id == "mix"));
+        assert_eq!(app.track_order.len(), 10);
+        assert_eq!(mock.calls_to("get_playlist_tracks_page"), vec!["mix 0"]);
+    }
+
+    #[tokio::test]
+    async fn confirmations_follow_the_playlist_when_the_library_is_reordered() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().playlists = vec![playlist("mix", "Mix", 10), playlist("road", "Road", 5)];
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists.len() == 2).await;
+        app.focused_pane = FocusedPane::Library;
+        app.handle_key_event(key(KeyCode::Delete)).await.unwrap();
+        assert!(app.confirmation.is_some());
+
+        // Fetched again while the popup is open, in another order
+        mock.state().playlists.reverse();
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists[0].id == "road").await;
+
+        press(&mut app, "y").await;
+        settle(&mut app, |_| !mock.calls_to("unfollow_playlist").is_empty()).await;
+        assert_eq!(mock.calls_to("unfollow_playlist"), ["mix"]);
+    }
+
+    #[tokio::test]
+    async fn the_open_playlist_is_followed_when_the_library_is_reordered() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().playlists = vec![playlist("road", "Road", 5), playlist("mix", "Mix", 10)];
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists[0].id == "road").await;
+
+        app.focused_pane = FocusedPane::Tracks;
+        app.handle_key_event(key(KeyCode::Delete)).await.unwrap();
+        press(&mut app, "y").await;
+        settle(&mut app, |_| {
+            !mock.calls_to("remove_tracks_from_playlist").is_empty()
+        })
+        .await;
+        assert_eq!(
+            mock.calls_to("remove_tracks_from_playlist"),
+            ["mix spotify:track:t0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn removals_and_their_undo_run_in_the_background() {
+        let (mut app, mock) = loaded_app().await;
+        app.focused_pane = FocusedPane::Tracks;
+        app.handle_key_event(key(KeyCode::Delete)).await.unwrap();
+        press(&mut app, "y").await;
+        assert_eq!(app.current_tracks.len(), 10);
+        settle(&mut app, |app| app.current_tracks.len() == 9).await;
+
+        press(&mut app, "u").await;
+        settle(&mut app, |app| app.current_tracks.len() == 10).await;
+        assert_eq!(
+            mock.calls_to("add_tracks_to_playlist"),
+            ["mix Some(0) spotify:track:t0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_token_refresh_is_reported_from_the_background() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().offline = true;
+
+        app.refresh_access_token();
+        assert!(!app.connection.is_offline());
+        settle(&mut app, |app| app.connection.is_offline()).await;
+        assert_eq!(mock.calls_to("refresh_access_token").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn confirmations_for_a_playlist_that_is_gone_do_nothing() {
+        let (mut app, mock) = loaded_app().await;
+        app.focused_pane = FocusedPane::Tracks;
+        app.handle_key_event(key(KeyCode::Delete)).await.unwrap();
+        assert!(app.confirmation.is_some());
+
+        mock.state().playlists = vec![playlist("road", "Road", 5)];
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists[0].id == "road").await;
+
+        press(&mut app, "y").await;
+        assert!(mock.calls_to("remove_tracks_from_playlist").is_empty());
+        assert!(app.notifications.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn for_you_gathers_spotifys_playlists_in_order() {
+        let mock = MockSpotify::default();
+        let by_spotify = |id: &str, name: &str| Playlist {
+            owner: Some(PlaylistOwner {
+                id: "spotify".to_string(),
+                display_name: None,
+            }),
+            ..playlist(id, name, 30)
+        };
+        mock.state().playlists = vec![
+            playlist("road", "Road Trip", 10),
+            by_spotify("mix2", "Daily Mix 2"),
+            by_spotify("blend", "Alex + Sam"),
+            by_spotify("dw", "Discover Weekly"),
+            by_spotify("mix1", "Daily Mix 1"),
+            by_spotify("top", "Top 50 - Global"),
+        ];
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists.len() == 6).await;
+
+        press(&mut app, "]").await;
+        assert_eq!(app.library_tab, LibraryTab::ForYou);
+        assert_eq!(
+            app.library_names(),
+            [
+                "Discover Weekly",
+                "Daily Mix 1",
+                "Daily Mix 2",
+                "Alex + Sam"
+            ]
+        );
+        // Release Radar and Your Time Capsule are missing, so they're looked up
+        settle(&mut app, |_| mock.calls_to("search_playlists").len() == 2).await;
+        assert!(mock
+            .calls_to("get_playlist_tracks_page")
+            .contains(&"dw 0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn playlists_can_be_ordered_by_name_below_the_pinned_ones() {
+        let mock = MockSpotify::default();
+        mock.state().playlists = vec![
+            playlist("zed", "Zed", 1),
+            playlist("apple", "apple", 1),
+            playlist("pinned", "Pinned", 1),
+            playlist("mango", "Mango", 1),
+        ];
+        let mut local_state = LocalState::default();
+        local_state.toggle_pin("pinned");
+        let mut app = App::with_client(Config::default(), mock.clone(), local_state);
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists.len() == 4).await;
+        app.focused_pane = FocusedPane::Library;
+        let order = |app: &App<MockSpotify>| {
+            app.library_order
+                .iter()
+                .map(|&i| app.playlists[i].name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&app), ["Pinned", "Zed", "apple", "Mango"]);
+
+        press(&mut app, "o").await;
+        assert_eq!(app.local_state.playlist_sort, PlaylistSort::Name);
+        assert_eq!(order(&app), ["Pinned", "apple", "Mango", "Zed"]);
+    }
+
+    #[tokio::test]
+    async fn playlists_named_into_a_folder_collapse_under_it() {
+        let mock = MockSpotify::default();
+        mock.state().playlists = vec![
+            playlist("run", "Gym / Run", 1),
+            playlist("road", "Road Trip", 1),
+            playlist("lift", "Gym / Lift", 1),
+        ];
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.playlists.len() == 3).await;
+        app.focused_pane = FocusedPane::Library;
+        app.library_state_mut().select(Some(0));
+
+        // Collapsed, the folder's first playlist stands in for it
+        assert_eq!(app.library_order, [0, 1]);
+        assert_eq!(app.library_folder(2), Some(("Gym", "Lift")));
+        assert_eq!(app.selected_library_index(), None);
+
+        app.handle_key_event(key(KeyCode::Enter)).await.unwrap();
+        assert_eq!(app.library_order, [0, 2, 1]);
+        assert!(app.local_state.is_folder_open("Gym"));
+        press(&mut app, "j").await;
+        assert_eq!(app.selected_library_index(), Some(2));
+
+        app.handle_key_event(key(KeyCode::Left)).await.unwrap();
+        assert_eq!(app.library_order, [0, 1]);
+        assert_eq!(app.library_state().selected(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn imports_ask_about_unsure_matches_then_fill_a_new_playlist() {
+        let (mut app, mock) = loaded_app().await;
+        app.review_import(
+            "Trip".to_string(),
+            vec![
+                ImportMatch {
+                    entry: Entry::Uri("spotify:track:t1".to_string()),
+                    candidates: Vec::new(),
+                    uri: Some("spotify:track:t1".to_string()),
+                },
+                ImportMatch {
+                    entry: Entry::parse("Wonderwall").unwrap(),
+                    candidates: vec![track("live", "Wonderwall (Live)"), track("w", "Wonderwall")],
+                    uri: None,
+                },
+            ],
+        );
+        assert_eq!(
+            app.import_review
+                .as_ref()
+                .map(|review| review.pending.len()),
+            Some(1)
+        );
+
+        press(&mut app, "j").await;
+        app.handle_key_event(key(KeyCode::Enter)).await.unwrap();
+        assert!(app.import_review.is_none());
+        settle(&mut app, |app| app.merge_batch.is_some()).await;
+
+        assert_eq!(mock.calls_to("create_playlist"), ["Trip"]);
+        assert!(app.playlists.iter().any(|p| p.id == "created"));
+        let batch = app.merge_batch.as_ref().unwrap();
+        assert_eq!(batch.playlist_name, "Trip");
+        assert_eq!(*batch.pending(), ["spotify:track:t1", "spotify:track:w"]);
+    }
+
+    #[tokio::test]
+    async fn shuffled_playlists_are_queued_in_the_seeds_order() {
+        let (mut app, _mock) = loaded_app().await;
+        app.config.behavior.shuffle_seed = Some(7);
+
+        press(&mut app, "hS").await;
+        settle(&mut app, |app| app.queue_batch.is_some()).await;
+
+        let mut expected: Vec<String> = (0..10).map(|i| format!("t{}", i)).collect();
+        shuffle::shuffle(&mut expected, 7);
+        let queued: Vec<&str> = app
+            .queue_batch
+            .as_ref()
+            .unwrap()
+            .pending()
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(queued, expected);
+        assert_ne!(queued[0], "t0");
+    }
+
+    #[tokio::test]
+    async fn the_queue_is_saved_as_a_new_playlist() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().queue = Some(Queue {
+            currently_playing: Some(track("t0", "Track 0")),
+            queue: vec![track("t3", "Track 3"), track("t5", "Track 5")],
+        });
+
+        press(&mut app, "Q").await;
+        settle(&mut app, |app| app.playlists.len() == 2).await;
+
+        assert!(mock.calls_to("create_playlist")[0].starts_with("Queue "));
+        assert_eq!(
+            mock.calls_to("add_tracks_to_playlist"),
+            ["created None spotify:track:t0 spotify:track:t3 spotify:track:t5"]
+        );
+        assert_eq!(app.playlists[1].tracks.total, 3);
+    }
+
+    #[tokio::test]
+    async fn stats_show_the_selected_playlist_with_its_tempo() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().audio_features = vec![AudioFeatures {
+            id: "t0".to_string(),
+            tempo: 124.0,
+            energy: 0.8,
+            danceability: 0.7,
+            valence: 0.6,
+        }];
+
+        press(&mut app, "hi").await;
+        settle(&mut app, |app| app.stats.is_some()).await;
+
+        let stats = app.stats.as_ref().unwrap();
+        assert_eq!((stats.name.as_str(), stats.tracks), ("Mix", 10));
+        assert_eq!(stats.tempo, Some(124.0));
+        assert_eq!(
+            mock.calls_to("get_audio_features"),
+            ["t0,t1,t2,t3,t4,t5,t6,t7,t8,t9"]
+        );
+        app.handle_key_event(key(KeyCode::Esc)).await.unwrap();
+        assert!(app.stats.is_none());
+    }
+
+    #[tokio::test]
+    async fn neighbouring_playlists_are_prefetched() {
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            for id in ["a", "b", "c"] {
+                state.playlists.push(playlist(id, id, 1));
+                let tracks = vec![track(&format!("{}0", id), "Track")];
+                state.playlist_tracks.insert(id.to_string(), tracks);
+            }
+        }
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 1).await;
+
+        app.move_selection(Motion::Down(1)).await.unwrap();
+        settle(&mut app, |app| app.track_cache.contains_key("c")).await;
+
+        // Shown before the fetch that brings it up to date has had a chance to run
+        app.move_selection(Motion::Down(1)).await.unwrap();
+        app.receive_loaded();
+        assert_eq!(app.current_tracks[0].id, "c0");
+
+        // Opened playlists load their first page, prefetched ones all of it
+        settle(&mut app, |_| {
+            mock.calls_to("get_playlist_tracks_page").len() == 3
+        })
+        .await;
+        let mut opened = mock.calls_to("get_playlist_tracks_page");
+        opened.sort();
+        assert_eq!(opened, ["a 0", "b 0", "c 0"]);
+        assert_eq!(mock.calls_to("get_playlist_tracks"), ["c"]);
+    }
+
+    #[tokio::test]
+    async fn cached_tracks_are_replaced_by_fresh_ones() {
+        let library_cache = LibraryCache::in_memory();
+        library_cache.save_playlists(&[playlist("mix", "Mix", 2)]);
+        library_cache.save_tracks("mix", &[track("old", "Old")]);
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            state.playlists = vec![playlist("mix", "Mix", 2)];
+            let tracks = vec![track("t0", "Track 0"), track("t1", "Track 1")];
+            state.playlist_tracks.insert("mix".to_string(), tracks);
+        }
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.library_cache = library_cache.clone();
+
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 2).await;
+
+        // The unchanged playlist list didn't open the playlist a second time
+        assert_eq!(mock.calls_to("get_playlist_tracks_page"), vec!["mix 0"]);
+        let cached = library_cache
+            .tracks("mix", Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(cached.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn long_playlists_load_a_page_at_a_time() {
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            state.playlists = vec![playlist("mix", "Mix", 50)];
+            let tracks = (0..50)
+                .map(|i| track(&format!("t{}", i), &format!("Track {}", i)))
+                .collect();
+            state.playlist_tracks.insert("mix".to_string(), tracks);
+            state.page_size = Some(25);
+        }
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 25).await;
+
+        // Nowhere near the end yet
+        app.load_more_near_end();
+        assert!(app.has_more_tracks());
+        assert_eq!(mock.calls_to("get_playlist_tracks_page"), ["mix 0"]);
+
+        app.tracks_state.select(Some(10));
+        app.load_more_near_end();
+        settle(&mut app, |app| app.current_tracks.len() == 50).await;
+
+        assert_eq!(
+            mock.calls_to("get_playlist_tracks_page"),
+            ["mix 0", "mix 25"]
+        );
+        assert_eq!(app.tracks_state.selected(), Some(10));
+        assert!(!app.has_more_tracks());
+        assert_eq!(app.track_cache["mix"].len(), 50);
+    }
+
+    #[tokio::test]
+    async fn sorting_liked_songs_loads_all_of_them() {
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            let tracks = (0..60)
+                .map(|i| Track {
+                    added_at: Some(format!("2024-06-03T08:00:{:02}Z", i)),
+                    ..track(&format!("t{}", i), &format!("Track {}", i))
+                })
+                .collect();
+            state.playlist_tracks.insert("liked".to_string(), tracks);
+            state.page_size = Some(25);
+        }
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.fetch_library_tab(LibraryTab::Playlists);
+        press(&mut app, "[").await;
+        settle(&mut app, |app| app.current_tracks.len() == 25).await;
+
+        // Date added, newest first
+        press(&mut app, "looooo").await;
+        press(&mut app, "O").await;
+        for _ in 0..2 {
+            app.load_more_near_end();
+            settle(&mut app, |app| !app.loading_more).await;
+        }
+
+        assert_eq!(app.current_tracks.len(), 60);
+        assert!(!app.has_more_tracks());
+        assert_eq!(app.current_tracks[app.track_order[0]].id, "t59");
+    }
+
+    #[tokio::test]
+    async fn sorting_by_tempo_fetches_it_once_per_track() {
+        let (mut app, mock) = loaded_app().await;
+        let features = |id: &str, tempo: f32| AudioFeatures {
+            id: id.to_string(),
+            tempo,
+            energy: 0.5,
+            danceability: 0.5,
+            valence: 0.5,
+        };
+        mock.state().audio_features = vec![features("t3", 128.0), features("t1", 92.0)];
+
+        press(&mut app, "loooooo").await;
+        settle(&mut app, |app| app.tempos.len() == 2).await;
+
+        assert_eq!(&app.track_order[..3], [1, 3, 0]);
+        // Another pass over the sort asks nothing more, the rest have no tempo to fetch
+        press(&mut app, "ooooooo").await;
+        assert_eq!(mock.calls_to("get_audio_features").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn offline_starts_from_the_cache_and_likes_once_back() {
+        let library_cache = LibraryCache::in_memory();
+        library_cache.save_playlists(&[playlist("mix", "Mix", 1)]);
+        library_cache.save_tracks("mix", &[track("t0", "Track 0")]);
+        let mock = MockSpotify::default();
+        mock.state().offline = true;
+        let mut app = App::with_client(Config::default(), mock.clone(), LocalState::default());
+        app.library_cache = library_cache;
+
+        app.authenticate().await.unwrap();
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 1).await;
+        assert!(app.connection.is_offline());
+        assert!(matches!(app.state, AppState::Ready));
+
+        app.execute_track_menu_item(TrackMenuItem::Like).await;
+        assert_eq!(app.offline_actions.len(), 1);
+        assert!(mock.calls_to("save_tracks").is_empty());
+
+        mock.state().offline = false;
+        app.reconnect();
+        settle(&mut app, |app| !app.connection.is_offline()).await;
+        app.replay_offline_actions();
+        settle(&mut app, |_| !mock.calls_to("save_tracks").is_empty()).await;
+        assert_eq!(mock.calls_to("save_tracks"), ["t0"]);
+        assert!(app.offline_actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn offline_shows_the_cache_however_old() {
+        let library_cache = LibraryCache::in_memory();
+        library_cache.save_playlists(&[playlist("mix", "Mix", 1)]);
+        library_cache.save_tracks("mix", &[track("t0", "Track 0")]);
+        let mock = MockSpotify::default();
+        mock.state().offline = true;
+        let mut config = Config::default();
+        // Expired as soon as it's written
+        config.cache.playlists_ttl_hours = 0;
+        config.cache.tracks_ttl_hours = 0;
+        let mut app = App::with_client(config, mock, LocalState::default());
+        app.library_cache = library_cache;
+
+        app.authenticate().await.unwrap();
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 1).await;
+
+        assert!(app.connection.is_offline());
+        assert_eq!(app.playlists.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failing_polls_go_offline_and_retries_back_off() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().offline = true;
+
+        // One failed poll could be a blip
+        app.refresh_player(false);
+        settle(&mut app, |app| !app.player_refresh_pending).await;
+        assert!(!app.connection.is_offline());
+        app.refresh_player(false);
+        settle(&mut app, |app| app.connection.is_offline()).await;
+
+        let first_wait = app.connection.reconnect_in().unwrap();
+        app.reconnect();
+        settle(&mut app, |app| !app.connection.is_reconnecting()).await;
+        assert!(app.connection.is_offline());
+        assert!(app.connection.reconnect_in().unwrap() > first_wait);
+
+        mock.state().offline = false;
+        app.reconnect();
+        settle(&mut app, |app| !app.connection.is_offline()).await;
+        assert_eq!(app.connection.reconnect_in(), None);
+    }
+
+    #[tokio::test]
+    async fn the_last_session_is_picked_up() {
+        let mock = MockSpotify::default();
+        {
+            let mut state = mock.state();
+            state.playlists = vec![playlist("a", "A", 1), playlist("b", "B", 10)];
+            let tracks = (0..10)
+                .map(|i| track(&format!("t{}", i), &format!("Track {}", i)))
+                .collect();
+            state.playlist_tracks.insert("b".to_string(), tracks);
+        }
+        let mut local_state = LocalState::default();
+        local_state.session = Some(Session {
+            library_tab: "Playlists".to_string(),
+            library_item: Some("b".to_string()),
+            open: Some(OpenView::Playlist {
+                id: "b".to_string(),
+            }),
+            track_row: Some(4),
+            tracks_focused: true,
+            ..Session::default()
+        });
+        let mut app = App::with_client(Config::default(), mock.clone(), local_state);
+
+        app.fetch_library_tab(LibraryTab::Playlists);
+        settle(&mut app, |app| app.current_tracks.len() == 10).await;
+
+        assert!(matches!(&app.track_source, Some(TrackSource::Playlist(id)) if id == "b"));
+        assert_eq!(app.tracks_state.selected(), Some(4));
+        assert!(matches!(app.focused_pane, FocusedPane::Tracks));
+        assert_eq!(mock.calls_to("get_playlist_tracks_page"), ["b 0"]);
+
+        // Albums are kept whole, to open again without the library
+        app.load_tracks(TrackSource::Album(track("t0", "Track 0").album));
+        settle(&mut app, |app| {
+            matches!(app.track_source, Some(TrackSource::Album(_)))
+        })
+        .await;
+        app.save_session();
+        let saved = toml::to_string(&app.local_state).unwrap();
+        let session = toml::from_str::<LocalState>(&saved)
+            .unwrap()
+            .session
+            .unwrap();
+        assert!(matches!(session.open, Some(OpenView::Album(album)) if album.id == "album"));
+        assert_eq!(session.library_item.as_deref(), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn tab_cycles_through_the_panes() {
+        let (mut app, _) = loaded_app().await;
+
+        app.handle_key_event(key(KeyCode::Tab)).await.unwrap();
+        assert!(matches!(app.focused_pane, FocusedPane::Tracks));
+        app.handle_key_event(key(KeyCode::Tab)).await.unwrap();
+        assert!(matches!(app.focused_pane, FocusedPane::Library));
+    }
+
+    #[tokio::test]
+    async fn counts_repeat_motions() {
+        let (mut app, _) = loaded_app().await;
+        press(&mut app, "l3j").await;
+        assert_eq!(app.tracks_state.selected(), Some(3));
+
+        press(&mut app, "2k").await;
+        assert_eq!(app.tracks_state.selected(), Some(1));
+
+        press(&mut app, "7G").await;
+        assert_eq!(app.tracks_state.selected(), Some(6));
+        assert_eq!(app.pending_count, None);
+    }
+
+    #[tokio::test]
+    async fn remapped_keys_are_left_alone_while_typing() {
+        let (mut app, _) = loaded_app().await;
+        let keys = [("n".to_string(), "j".to_string())].into_iter().collect();
+        app.remaps = Remaps::parse(&keys).unwrap();
+
+        press(&mut app, "lnn").await;
+        assert_eq!(app.tracks_state.selected(), Some(2));
+
+        press(&mut app, "sn").await;
+        assert_eq!(app.search_input.text(), "n");
+    }
+
+    #[tokio::test]
+    async fn search_waits_for_typing_to_pause() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().search_results = vec![track("found", "Found")];
+
+        press(&mut app, "sabba").await;
+        app.check_pending_search();
+        assert!(mock.calls_to("search_tracks").is_empty());
+
+        // As if the debounce delay had passed
+        app.search_debounce_ms = 0;
+        app.check_pending_search();
+        settle(&mut app, |app| !app.search_results.is_empty()).await;
+        assert_eq!(mock.calls_to("search_tracks"), vec!["abba 0"]);
+        assert_eq!(app.search_results[0].id, "found");
+    }
+
+    #[tokio::test]
+    async fn stale_search_results_are_dropped() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().search_results = vec![track("found", "Found")];
+        app.search_debounce_ms = 0;
+
+        press(&mut app, "sab").await;
+        app.check_pending_search();
+        // The query changes before the results come back
+        press(&mut app, "c").await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        app.receive_loaded();
+        assert!(app.search_results.is_empty());
+
+        mock.state().search_results = vec![track("newer", "Newer")];
+        app.check_pending_search();
+        settle(&mut app, |app| !app.search_results.is_empty()).await;
+        assert_eq!(app.search_results[0].id, "newer");
+        assert_eq!(mock.calls_to("search_tracks").last().unwrap(), "abc 0");
+    }
+
+    #[tokio::test]
+    async fn the_queue_is_fetched_again_for_a_new_track() {
+        let (mut app, mock) = loaded_app().await;
+        let paused = |id: &str| CurrentlyPlaying {
+            item: Some(track(id, id)),
+            is_playing: false,
+            progress_ms: Some(0),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        };
+
+        mock.state().player = Some(paused("t0"));
+        for _ in 0..3 {
+            app.refresh_player(true);
+            settle(&mut app, |app| !app.player_refresh_pending).await;
+        }
+        mock.state().player = Some(paused("t1"));
+        app.refresh_player(true);
+        settle(&mut app, |app| app.playing_track_id() == Some("t1")).await;
+
+        let calls = mock.state().calls.clone();
+        let fetched = |method: &str| calls.iter().filter(|call| *call == method).count();
+        assert_eq!(fetched("get_currently_playing"), 4);
+        assert_eq!(fetched("get_queue"), 2);
+    }
+
+    #[tokio::test]
+    async fn arrows_seek_outside_the_library_and_move_the_progress_at_once() {
+        let (mut app, mock) = loaded_app().await;
+        let paused_at = |progress_ms| CurrentlyPlaying {
+            item: Some(track("t0", "Track 0")),
+            is_playing: false,
+            progress_ms: Some(progress_ms),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        };
+        mock.state().player = Some(paused_at(30_000));
+        app.currently_playing = Some(paused_at(30_000));
+
+        press(&mut app, "l").await;
+        app.handle_key_event(key(KeyCode::Right)).await.unwrap();
+        assert_eq!(app.playback_progress_ms(), Some(40_000));
+        app.handle_key_event(KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT))
+            .await
+            .unwrap();
+        assert_eq!(app.playback_progress_ms(), Some(0));
+
+        settle(&mut app, |_| mock.calls_to("seek").len() == 2).await;
+        assert_eq!(mock.calls_to("seek"), vec!["40000", "0"]);
+    }
+
+    #[tokio::test]
+    async fn volume_presses_in_quick_succession_are_sent_once() {
+        let (mut app, mock) = loaded_app().await;
+        let playing = CurrentlyPlaying {
+            item: Some(track("t0", "Track 0")),
+            is_playing: true,
+            progress_ms: Some(0),
+            device: Some(Device {
+                id: Some("laptop".to_string()),
+                name: "Laptop".to_string(),
+                device_type: "Computer".to_string(),
+                is_active: true,
+                volume_percent: Some(50),
+            }),
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        };
+        mock.state().player = Some(playing.clone());
+        app.currently_playing = Some(playing);
+        let volume = |app: &App<MockSpotify>| {
+            app.currently_playing
+                .as_ref()
+                .and_then(|player| player.device.as_ref()?.volume_percent)
+        };
+        let alt = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+
+        let leave_alone = |app: &mut App<MockSpotify>| {
+            app.pending_volume = app
+                .pending_volume
+                .map(|(volume, _)| (volume, Instant::now() - VOLUME_DEBOUNCE));
+        };
+
+        // Steps grow 5, 10, 15 and no further, turning back starts over
+        for c in ['+', '+', '=', '+', '-'] {
+            app.handle_key_event(alt(c)).await.unwrap();
+        }
+        app.check_pending_volume();
+        assert_eq!(volume(&app), Some(90));
+        assert!(mock.calls_to("set_volume").is_empty());
+
+        // A press after a pause goes the one step again
+        leave_alone(&mut app);
+        app.handle_key_event(alt('-')).await.unwrap();
+        assert_eq!(volume(&app), Some(85));
+
+        leave_alone(&mut app);
+        app.check_pending_volume();
+        settle(&mut app, |_| !mock.calls_to("set_volume").is_empty()).await;
+        assert_eq!(mock.calls_to("set_volume"), vec!["85"]);
+
+        // Digits set it outright in the playback controls
+        press(&mut app, " 3").await;
+        assert_eq!(volume(&app), Some(30));
+    }
+
+    #[tokio::test]
+    async fn radio_is_topped_up_as_it_plays_through() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().recommendations = VecDeque::from([
+            vec![track("r1", "R1"), track("r2", "R2")],
+            vec![track("r2", "R2"), track("r3", "R3")],
+        ]);
+        let playing = |id: &str| CurrentlyPlaying {
+            item: Some(track(id, id)),
+            is_playing: true,
+            progress_ms: Some(0),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        };
+
+        press(&mut app, "lR").await;
+        settle(&mut app, |app| app.radio.is_some()).await;
+        settle(&mut app, |_| !mock.calls_to("play_tracks").is_empty()).await;
+        assert_eq!(
+            mock.calls_to("play_tracks"),
+            vec!["spotify:track:t0 spotify:track:r1 spotify:track:r2"]
+        );
+        assert_eq!(mock.calls_to("get_recommendations"), vec!["t0   20"]);
+
+        // Two tracks left is few enough to fetch more, the one heard already is left out
+        mock.state().player = Some(playing("r1"));
+        app.refresh_player(false);
+        settle(&mut app, |app| app.queue_batch.is_some()).await;
+        assert_eq!(app.queue_batch.as_ref().map(|batch| batch.total), Some(1));
+
+        mock.state().player = Some(playing("elsewhere"));
+        app.refresh_player(false);
+        settle(&mut app, |app| app.radio.is_none()).await;
+    }
+
+    #[tokio::test]
+    async fn artist_radio_is_seeded_by_the_tracks_artist() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().recommendations = VecDeque::from([vec![track("r1", "R1")]]);
+
+        press(&mut app, "l").await;
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))
+            .await
+            .unwrap();
+        settle(&mut app, |app| app.radio.is_some()).await;
+        settle(&mut app, |_| !mock.calls_to("play_tracks").is_empty()).await;
+
+        assert_eq!(
+            app.radio.as_ref().map(|radio| radio.name.as_str()),
+            Some("Artist")
+        );
+        assert_eq!(mock.calls_to("get_recommendations"), vec![" artist  20"]);
+        assert_eq!(mock.calls_to("play_tracks"), vec!["spotify:track:r1"]);
+    }
+
+    #[tokio::test]
+    async fn picked_genres_seed_a_radio() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().recommendations = VecDeque::from([vec![track("r1", "R1")]]);
+
+        press(&mut app, "e").await;
+        settle(&mut app, |app| app.show_genre_picker).await;
+        // Picking moves down to the next genre
+        press(&mut app, "  ").await;
+        app.handle_key_event(key(KeyCode::Enter)).await.unwrap();
+        settle(&mut app, |app| app.radio.is_some()).await;
+
+        assert!(!app.show_genre_picker);
+        assert_eq!(
+            app.radio.as_ref().map(|radio| radio.name.as_str()),
+            Some("blues, jazz")
+        );
+        assert_eq!(
+            mock.calls_to("get_recommendations"),
+            vec!["  blues,jazz 20"]
+        );
+    }
+
+    #[tokio::test]
+    async fn marked_tracks_are_queued_in_order() {
+        let (mut app, mock) = loaded_app().await;
+        // Marking moves the cursor down a row
+        press(&mut app, "lvjjvjv").await;
+        press(&mut app, "+").await;
+        assert_eq!(app.queue_batch.as_ref().map(|batch| batch.total), Some(3));
+
+        for _ in 0..3 {
+            queue_next(&mut app).await;
+        }
+
+        assert!(app.queue_batch.is_none());
+        assert!(!app.has_marks());
+        assert_eq!(
+            mock.calls_to("add_to_queue"),
+            vec!["spotify:track:t0", "spotify:track:t3", "spotify:track:t5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn playing_without_a_device_offers_the_devices() {
+        let (mut app, mock) = loaded_app().await;
+        {
+            let mut state = mock.state();
+            state.devices = vec![Device {
+                id: Some("phone".to_string()),
+                name: "Phone".to_string(),
+                device_type: "Smartphone".to_string(),
+                is_active: false,
+                volume_percent: None,
+            }];
+            state
+                .playback_errors
+                .push_back(SpotifyError::NoDevice.into());
+        }
+
+        press(&mut app, "l").await;
+        app.handle_key_event(key(KeyCode::Enter)).await.unwrap();
+        settle(&mut app, |app| app.show_device_picker).await;
+
+        assert_eq!(mock.calls_to("play_tracks"), vec!["spotify:track:t0"]);
+        assert_eq!(app.devices.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_queue_batch_waits_before_retrying() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state().queue_errors.push_back(
+            SpotifyError::RateLimited {
+                retry_after: Duration::from_secs(30),
+            }
+            .into(),
+        );
+        app.enqueue_batch(app.current_tracks[..2].to_vec());
+
+        queue_next(&mut app).await;
+        queue_next(&mut app).await;
+
+        let batch = app.queue_batch.as_ref().unwrap();
+        assert!(batch.paused_for().is_some());
+        assert_eq!(batch.done(), 0);
+        assert_eq!(mock.calls_to("add_to_queue"), vec!["spotify:track:t0"]);
+    }
+
+    #[tokio::test]
+    async fn failed_queue_batch_stops() {
+        let (mut app, mock) = loaded_app().await;
+        mock.state()
+            .queue_errors
+            .push_back(anyhow::anyhow!("No active device"));
+        app.enqueue_batch(app.current_tracks[..3].to_vec());
+
+        queue_next(&mut app).await;
+        queue_next(&mut app).await;
+
+        assert!(app.queue_batch.is_none());
+        assert_eq!(mock.calls_to("add_to_queue").len(), 1);
+        assert!(app.notifications.last_error.is_some());
     }
 }
@@ -0,0 +1,520 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use spotitui_core::spotify::{Album, Artist, Playlist, PlaylistOwner, PlaylistTracks, Track};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+/// Bumped whenever the tables change, throwing away a cache made by an older version
+const SCHEMA_VERSION: i32 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE lists (
+        key TEXT PRIMARY KEY,
+        fetched_at INTEGER NOT NULL
+    );
+    CREATE TABLE playlists (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        description TEXT,
+        owner_id TEXT,
+        owner_name TEXT,
+        total INTEGER NOT NULL
+    );
+    CREATE TABLE albums (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        images TEXT NOT NULL,
+        artists TEXT NOT NULL,
+        release_date TEXT,
+        saved_position INTEGER
+    );
+    CREATE TABLE artists (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        followed_position INTEGER
+    );
+    CREATE TABLE tracks (
+        uri TEXT PRIMARY KEY,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        album_id TEXT NOT NULL REFERENCES albums (id),
+        artists TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        explicit INTEGER NOT NULL,
+        popularity INTEGER
+    );
+    CREATE TABLE list_tracks (
+        list_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        track_uri TEXT NOT NULL REFERENCES tracks (uri),
+        added_at TEXT,
+        PRIMARY KEY (list_id, position)
+    );
+    CREATE INDEX list_tracks_added_at ON list_tracks (added_at);
+";
+
+/// Key of the cached playlist list in `lists`
+pub const PLAYLISTS: &str = "playlists";
+pub const ALBUMS: &str = "albums";
+pub const ARTISTS: &str = "artists";
+
+/// Key of a playlist's cached tracks, "liked" being Liked Songs
+pub fn tracks_key(playlist_id: &str) -> String {
+    format!("tracks:{}", playlist_id)
+}
+
+/// The library kept in SQLite between runs, so lists show up at once while fresh copies load
+/// and the whole of it can be queried locally. Without a database nothing is kept, which is how
+/// tests and `enabled = false` run
+#[derive(Clone, Default)]
+pub struct LibraryCache {
+    // Connections can't be shared between threads, background fetches take turns instead
+    db: Option<Arc<Mutex<Connection>>>,
+}
+
+impl LibraryCache {
+    /// Opens the database at `path`, starting it afresh if it's from another version
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(db: Connection) -> Result<Self> {
+        db.pragma_update(None, "journal_mode", "WAL")?;
+        let version: i32 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            db.execute_batch(
+                "DROP TABLE IF EXISTS list_tracks;
+                 DROP TABLE IF EXISTS tracks;
+                 DROP TABLE IF EXISTS artists;
+                 DROP TABLE IF EXISTS albums;
+                 DROP TABLE IF EXISTS playlists;
+                 DROP TABLE IF EXISTS lists;",
+            )?;
+            db.execute_batch(SCHEMA)?;
+            db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(Self {
+            db: Some(Arc::new(Mutex::new(db))),
+        })
+    }
+
+    /// An empty database in memory, for tests
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    pub fn playlists(&self, ttl: Duration) -> Option<Vec<Playlist>> {
+        self.read(PLAYLISTS, ttl, |db| {
+            let mut query = db.prepare(
+                "SELECT id, name, description, owner_id, owner_name, total
+                 FROM playlists ORDER BY position",
+            )?;
+            let playlists = query.query_map([], |row| {
+                let owner_id: Option<String> = row.get(3)?;
+                let owner_name: Option<String> = row.get(4)?;
+                Ok(Playlist {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    owner: owner_id.map(|id| PlaylistOwner {
+                        id,
+                        display_name: owner_name,
+                    }),
+                    tracks: PlaylistTracks { total: row.get(5)? },
+                })
+            })?;
+            playlists.collect()
+        })
+    }
+
+    pub fn save_playlists(&self, playlists: &[Playlist]) {
+        self.write(PLAYLISTS, |db| {
+            db.execute("DELETE FROM playlists", [])?;
+            let mut insert = db.prepare(
+                "INSERT OR REPLACE INTO playlists
+                 (id, position, name, description, owner_id, owner_name, total)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for (position, playlist) in playlists.iter().enumerate() {
+                let owner = playlist.owner.as_ref();
+                insert.execute(params![
+                    playlist.id,
+                    position as i64,
+                    playlist.name,
+                    playlist.description,
+                    owner.map(|owner| &owner.id),
+                    owner.and_then(|owner| owner.display_name.as_ref()),
+                    playlist.tracks.total,
+                ])?;
+            }
+            Ok(())
+        });
+    }
+
+    pub fn albums(&self, ttl: Duration) -> Option<Vec<Album>> {
+        self.read(ALBUMS, ttl, |db| {
+            let mut query = db.prepare(
+                "SELECT id, name, images, artists, release_date FROM albums
+                 WHERE saved_position IS NOT NULL ORDER BY saved_position",
+            )?;
+            let albums = query.query_map([], album_from_row)?;
+            albums.collect()
+        })
+    }
+
+    pub fn save_albums(&self, albums: &[Album]) {
+        self.write(ALBUMS, |db| {
+            db.execute("UPDATE albums SET saved_position = NULL", [])?;
+            for (position, album) in albums.iter().enumerate() {
+                upsert_album(db, album)?;
+                db.execute(
+                    "UPDATE albums SET saved_position = ?1 WHERE id = ?2",
+                    params![position as i64, album.id],
+                )?;
+            }
+            Ok(())
+        });
+    }
+
+    pub fn artists(&self, ttl: Duration) -> Option<Vec<Artist>> {
+        self.read(ARTISTS, ttl, |db| {
+            let mut query = db.prepare(
+                "SELECT id, name FROM artists
+                 WHERE followed_position IS NOT NULL ORDER BY followed_position",
+            )?;
+            let artists = query.query_map([], |row| {
+                Ok(Artist {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                })
+            })?;
+            artists.collect()
+        })
+    }
+
+    pub fn save_artists(&self, artists: &[Artist]) {
+        self.write(ARTISTS, |db| {
+            db.execute("UPDATE artists SET followed_position = NULL", [])?;
+            for (position, artist) in artists.iter().enumerate() {
+                db.execute(
+                    "INSERT INTO artists (id, name, followed_position) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (id) DO UPDATE
+                     SET name = excluded.name, followed_position = excluded.followed_position",
+                    params![artist.id, artist.name, position as i64],
+                )?;
+            }
+            Ok(())
+        });
+    }
+
+    /// The tracks of a playlist, or Liked Songs under "liked"
+    pub fn tracks(&self, list_id: &str, ttl: Duration) -> Option<Vec<Track>> {
+        self.read(&tracks_key(list_id), ttl, |db| {
+            let mut query = db.prepare(&format!(
+                "{} WHERE list_tracks.list_id = ?1 ORDER BY list_tracks.position",
+                SELECT_TRACKS
+            ))?;
+            let tracks = query.query_map([list_id], track_from_row)?;
+            tracks.collect()
+        })
+    }
+
+    pub fn save_tracks(&self, list_id: &str, tracks: &[Track]) {
+        self.write(&tracks_key(list_id), |db| {
+            db.execute("DELETE FROM list_tracks WHERE list_id = ?1", [list_id])?;
+            for (position, track) in tracks.iter().enumerate() {
+                upsert_album(db, &track.album)?;
+                for artist in &track.artists {
+                    db.execute(
+                        "INSERT INTO artists (id, name) VALUES (?1, ?2)
+                         ON CONFLICT (id) DO UPDATE SET name = excluded.name",
+                        params![artist.id, artist.name],
+                    )?;
+                }
+                db.execute(
+                    "INSERT OR REPLACE INTO tracks
+                     (uri, id, name, album_id, artists, duration_ms, explicit, popularity)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        track.uri,
+                        track.id,
+                        track.name,
+                        track.album.id,
+                        serde_json::to_string(&track.artists).unwrap_or_default(),
+                        track.duration_ms,
+                        track.explicit,
+                        track.popularity,
+                    ],
+                )?;
+                db.execute(
+                    "INSERT INTO list_tracks (list_id, position, track_uri, added_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![list_id, position as i64, track.uri, track.added_at],
+                )?;
+            }
+            Ok(())
+        });
+    }
+
+    /// Tracks from every cached playlist and Liked Songs, newest additions first, each listed
+    /// once at its latest addition
+    pub fn recently_added(&self, limit: usize) -> Vec<Track> {
+        let Some(db) = &self.db else {
+            return Vec::new();
+        };
+        let db = db.lock().unwrap();
+        let result: rusqlite::Result<Vec<Track>> = (|| {
+            let mut query = db.prepare(&format!(
+                "{} WHERE list_tracks.added_at = (
+                     SELECT MAX(latest.added_at) FROM list_tracks AS latest
+                     WHERE latest.track_uri = tracks.uri
+                 )
+                 GROUP BY tracks.uri ORDER BY list_tracks.added_at DESC LIMIT ?1",
+                SELECT_TRACKS
+            ))?;
+            let tracks = query.query_map([limit as i64], track_from_row)?;
+            tracks.collect()
+        })();
+        result.unwrap_or_else(|e| {
+            tracing::warn!("Failed to query the library cache: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// The newest addition to each cached list, by list id
+    pub fn last_added(&self) -> HashMap<String, String> {
+        let Some(db) = &self.db else {
+            return HashMap::new();
+        };
+        let db = db.lock().unwrap();
+        let result: rusqlite::Result<HashMap<String, String>> = (|| {
+            let mut query = db.prepare(
+                "SELECT list_id, MAX(added_at) FROM list_tracks
+                 WHERE added_at IS NOT NULL GROUP BY list_id",
+            )?;
+            let rows = query.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })();
+        result.unwrap_or_else(|e| {
+            tracing::warn!("Failed to query the library cache: {}", e);
+            HashMap::new()
+        })
+    }
+
+    /// Forgets when a list was fetched, for when a change made here has made it out of date
+    pub fn forget(&self, key: &str) {
+        self.write_raw(|db| {
+            db.execute("DELETE FROM lists WHERE key = ?1", [key])?;
+            Ok(())
+        });
+    }
+
+    /// Runs `query` if the list under `key` was fetched within `ttl`
+    fn read<T>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        query: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> Option<T> {
+        let db = self.db.as_ref()?.lock().unwrap();
+        let result = db
+            .query_row(
+                "SELECT fetched_at FROM lists WHERE key = ?1",
+                [key],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .and_then(|fetched_at| match fetched_at {
+                Some(fetched_at)
+                    if now()
+                        < fetched_at
+                            .saturating_add(i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX)) =>
+                {
+                    query(&db).map(Some)
+                }
+                _ => Ok(None),
+            });
+        result.unwrap_or_else(|e| {
+            tracing::warn!("Failed to read {} from the library cache: {}", key, e);
+            None
+        })
+    }
+
+    /// Replaces the list under `key` in one transaction, marking it fetched now. Failing to
+    /// is only a missed shortcut, so it's logged rather than reported
+    fn write(&self, key: &str, update: impl FnOnce(&Connection) -> rusqlite::Result<()>) {
+        self.write_raw(|db| {
+            update(db)?;
+            db.execute(
+                "INSERT OR REPLACE INTO lists (key, fetched_at) VALUES (?1, ?2)",
+                params![key, now()],
+            )?;
+            Ok(())
+        });
+    }
+
+    fn write_raw(&self, update: impl FnOnce(&Connection) -> rusqlite::Result<()>) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        let mut db = db.lock().unwrap();
+        let result = db.transaction().and_then(|transaction| {
+            update(&transaction)?;
+            transaction.commit()
+        });
+        if let Err(e) = result {
+            tracing::warn!("Failed to update the library cache: {}", e);
+        }
+    }
+}
+
+const SELECT_TRACKS: &str = "
+    SELECT tracks.id, tracks.name, tracks.artists, tracks.duration_ms, tracks.uri,
+           tracks.explicit, list_tracks.added_at, tracks.popularity,
+           albums.id, albums.name, albums.images, albums.artists, albums.release_date
+    FROM list_tracks
+    JOIN tracks ON tracks.uri = list_tracks.track_uri
+    JOIN albums ON albums.id = tracks.album_id";
+
+fn track_from_row(row: &Row) -> rusqlite::Result<Track> {
+    Ok(Track {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        artists: json_column(row, 2)?,
+        duration_ms: row.get(3)?,
+        uri: row.get(4)?,
+        explicit: row.get(5)?,
+        added_at: row.get(6)?,
+        popularity: row.get(7)?,
+        album: Album {
+            id: row.get(8)?,
+            name: row.get(9)?,
+            images: json_column(row, 10)?,
+            artists: json_column(row, 11)?,
+            release_date: row.get(12)?,
+        },
+    })
+}
+
+fn album_from_row(row: &Row) -> rusqlite::Result<Album> {
+    Ok(Album {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        images: json_column(row, 2)?,
+        artists: json_column(row, 3)?,
+        release_date: row.get(4)?,
+    })
+}
+
+fn upsert_album(db: &Connection, album: &Album) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO albums (id, name, images, artists, release_date) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (id) DO UPDATE
+         SET name = excluded.name, images = excluded.images, artists = excluded.artists,
+             release_date = excluded.release_date",
+        params![
+            album.id,
+            album.name,
+            serde_json::to_string(&album.images).unwrap_or_default(),
+            serde_json::to_string(&album.artists).unwrap_or_default(),
+            album.release_date,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Lists that are only ever read back whole, like a track's artists, are kept as JSON
+fn json_column<T: serde::de::DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// library.db in the cache directory
+pub fn path(profile: Option<&str>) -> Option<PathBuf> {
+    Some(dir(profile)?.join("library.db"))
+}
+
+/// The platform's cache directory, like ~/.cache/spotitui on Linux, with a directory of its own
+/// under profiles/ for each profile
+pub fn dir(profile: Option<&str>) -> Option<PathBuf> {
+    let dir = config::project_dirs()?.cache_dir().to_path_buf();
+    Some(match profile {
+        Some(profile) => dir.join("profiles").join(profile),
+        None => dir,
+    })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{playlist, track};
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn lists_read_back_as_saved_until_they_expire() {
+        let cache = LibraryCache::in_memory();
+        assert!(cache.playlists(HOUR).is_none());
+
+        cache.save_playlists(&[playlist("b", "B", 2), playlist("a", "A", 1)]);
+        let mut first = track("t1", "First");
+        first.added_at = Some("2024-06-01T00:00:00Z".to_string());
+        cache.save_tracks("b", &[first, track("t2", "Second")]);
+
+        let ids: Vec<String> = cache
+            .playlists(HOUR)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(ids, ["b", "a"]);
+        let tracks = cache.tracks("b", HOUR).unwrap();
+        assert_eq!(tracks[1].name, "Second");
+        assert_eq!(tracks[0].artists[0].name, "Artist");
+        assert!(cache.tracks("b", Duration::ZERO).is_none());
+
+        cache.forget(&tracks_key("b"));
+        assert!(cache.tracks("b", HOUR).is_none());
+    }
+
+    #[test]
+    fn recently_added_spans_every_list() {
+        let cache = LibraryCache::in_memory();
+        let added = |id: &str, date: &str| {
+            let mut track = track(id, id);
+            track.added_at = Some(date.to_string());
+            track
+        };
+        cache.save_tracks(
+            "liked",
+            &[added("t1", "2024-01-01"), added("t2", "2024-03-01")],
+        );
+        cache.save_tracks("mix", &[added("t1", "2024-05-01"), track("t3", "t3")]);
+
+        let uris: Vec<String> = cache
+            .recently_added(10)
+            .into_iter()
+            .map(|t| t.uri)
+            .collect();
+        assert_eq!(uris, ["spotify:track:t1", "spotify:track:t2"]);
+    }
+}
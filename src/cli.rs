@@ -0,0 +1,201 @@
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::export::Format;
+use crate::theme::THEMES;
+
+/// A terminal client for Spotify
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Reads the config from PATH instead of ~/.config/spotitui/config.toml
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Layers the config's [profiles.NAME] table over the rest of it, and keeps pins and marks
+    /// apart from other profiles
+    #[arg(long, value_name = "NAME", value_parser = profile_name, global = true)]
+    pub profile: Option<String>,
+
+    /// Draws with a built-in theme instead of the config's
+    #[arg(long, value_name = "NAME", value_parser = PossibleValuesParser::new(THEMES.map(|(name, _)| name)))]
+    pub theme: Option<String>,
+
+    /// Leaves the mouse to the terminal
+    #[arg(long)]
+    pub no_mouse: bool,
+
+    /// Shows errors with everything that led up to them, and writes a log to
+    /// ~/.local/state/spotitui/logs
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Prints what subcommands have to say, and their errors, as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Starts playing a track, album, playlist, artist or show once signed in, given as a
+    /// spotify: URI or an open.spotify.com link
+    #[arg(long, value_name = "URI", value_parser = spotify_uri)]
+    pub play: Option<String>,
+
+    /// Imports a CSV, JSON, M3U or text file of tracks into a playlist named after it once
+    /// signed in, asking which track is meant where search isn't sure
+    #[arg(long, value_name = "FILE")]
+    pub import: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<HeadlessCommand>,
+}
+
+/// Playback controls and exports that run without the interface, signed in with the token saved
+/// by it, for binding to hotkeys and scripts
+#[derive(Debug, Clone, Subcommand)]
+pub enum HeadlessCommand {
+    /// Resumes playback, or starts playing URI
+    Play {
+        #[arg(value_parser = spotify_uri)]
+        uri: Option<String>,
+    },
+    /// Pauses playback
+    Pause,
+    /// Skips to the next track
+    Next,
+    /// Goes back to the previous track
+    Prev,
+    /// Shows what's playing and where
+    Status,
+    /// Adds a track or episode to the queue
+    Queue {
+        #[arg(value_parser = spotify_uri)]
+        uri: String,
+    },
+    /// Writes a playlist's tracks to a file, or those of every playlist and Liked Songs to a
+    /// folder of files
+    Export {
+        /// A playlist's URI or link, or "liked" for Liked Songs. Leave out for the whole library
+        #[arg(value_parser = playlist_id)]
+        playlist: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Where to write, a folder for the whole library. Named after the playlist in the
+        /// current folder by default
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Writes every playlist, Liked Songs and the saved albums to a JSON file named after when
+    /// it was made
+    Backup {
+        /// The folder to write to, ~/.local/state/spotitui/backups by default
+        #[arg(long, short, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    /// Applies the flags that stand in for config settings
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(theme) = &self.theme {
+            config.theme.name = Some(theme.clone());
+        }
+        if self.no_mouse {
+            config.features.mouse = false;
+        }
+    }
+}
+
+/// Profile names end up in file names, so they're kept to letters, digits, - and _
+fn profile_name(arg: &str) -> Result<String, String> {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(arg.to_string())
+    } else {
+        Err("profile names can only use letters, digits, - and _".to_string())
+    }
+}
+
+/// Kinds of thing Spotify can start playing from a URI
+const PLAYABLE: [&str; 6] = ["track", "episode", "album", "playlist", "artist", "show"];
+
+/// Turns a spotify: URI or an open.spotify.com link into a spotify: URI
+pub fn spotify_uri(arg: &str) -> Result<String, String> {
+    let url;
+    let (kind, id) = if let Some(uri) = arg.strip_prefix("spotify:") {
+        uri.split_once(':').unwrap_or((uri, ""))
+    } else {
+        url = url::Url::parse(arg).map_err(|_| format!("\"{}\" isn't a Spotify URI", arg))?;
+        if url.host_str() != Some("open.spotify.com") {
+            return Err(format!("\"{}\" isn't an open.spotify.com link", arg));
+        }
+        let mut segments = url.path_segments().into_iter().flatten();
+        let mut kind = segments.next().unwrap_or("");
+        // Links shared from some regions start with their language, like /intl-de/track/...
+        if kind.starts_with("intl-") {
+            kind = segments.next().unwrap_or("");
+        }
+        (kind, segments.next().unwrap_or(""))
+    };
+
+    if !PLAYABLE.contains(&kind) {
+        return Err(format!(
+            "Can't play \"{}\", expected a {}",
+            arg,
+            PLAYABLE.join(", ")
+        ));
+    }
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("\"{}\" has no valid Spotify id", arg));
+    }
+    Ok(format!("spotify:{}:{}", kind, id))
+}
+
+/// Turns a playlist's URI or link into its id, leaving "liked" for Liked Songs
+fn playlist_id(arg: &str) -> Result<String, String> {
+    if arg == "liked" {
+        return Ok(arg.to_string());
+    }
+    match spotify_uri(arg)?.strip_prefix("spotify:playlist:") {
+        Some(id) => Ok(id.to_string()),
+        None => Err(format!("\"{}\" isn't a playlist", arg)),
+    }
+}
+
+/// Whether a spotify: URI is something to play within, like an album, rather than on its own
+pub fn is_context(uri: &str) -> bool {
+    !uri.starts_with("spotify:track:") && !uri.starts_with("spotify:episode:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_and_uris_are_accepted() {
+        assert_eq!(
+            spotify_uri("https://open.spotify.com/intl-de/album/4aawyAB9vmqN3uQ7FjRGTy?si=x"),
+            Ok("spotify:album:4aawyAB9vmqN3uQ7FjRGTy".to_string())
+        );
+        assert_eq!(
+            spotify_uri("spotify:track:6rqhFgbbKwnb9MLmUQDhG6"),
+            Ok("spotify:track:6rqhFgbbKwnb9MLmUQDhG6".to_string())
+        );
+        assert!(spotify_uri("spotify:user:someone").is_err());
+        assert!(spotify_uri("https://example.com/track/6rqhFgbbKwnb9MLmUQDhG6").is_err());
+        assert!(spotify_uri("spotify:track:").is_err());
+    }
+
+    #[test]
+    fn exports_take_a_playlist_or_liked() {
+        assert_eq!(
+            playlist_id("https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M"),
+            Ok("37i9dQZF1DXcBWIGoYBM5M".to_string())
+        );
+        assert_eq!(playlist_id("liked"), Ok("liked".to_string()));
+        assert!(playlist_id("spotify:album:4aawyAB9vmqN3uQ7FjRGTy").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+
+/// Where copied text went
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "clipboard"), allow(dead_code))]
+pub enum Copied {
+    /// Onto the system clipboard
+    Clipboard,
+    /// To the terminal as an OSC 52 escape sequence, which it may or may not pass on to the
+    /// clipboard, so there's no telling whether the copy worked
+    Terminal,
+}
+
+/// Copies text to the system clipboard. Without a display server to reach, like over SSH, it
+/// goes through the terminal with the OSC 52 escape sequence instead
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<Copied> {
+    use std::sync::Mutex;
+
+    // On X11 and Wayland the copied text is served by whoever copied it, so the clipboard is
+    // kept for as long as the app runs rather than dropped after each copy
+    static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    if clipboard.is_none() {
+        match arboard::Clipboard::new() {
+            Ok(opened) => *clipboard = Some(opened),
+            Err(e) => {
+                tracing::debug!("No system clipboard, copying through the terminal: {}", e);
+                return copy_osc52(text);
+            }
+        }
+    }
+    if let Some(clipboard) = clipboard.as_mut() {
+        clipboard.set_text(text)?;
+    }
+    Ok(Copied::Clipboard)
+}
+
+/// Writes the OSC 52 escape sequence asking the terminal to put `text` on the clipboard
+#[cfg(feature = "clipboard")]
+fn copy_osc52(text: &str) -> Result<Copied> {
+    use base64::{engine::general_purpose, Engine as _};
+    use std::io::{self, Write};
+
+    let mut stdout = io::stdout();
+    write!(
+        stdout,
+        "\x1b]52;c;{}\x07",
+        general_purpose::STANDARD.encode(text)
+    )?;
+    stdout.flush()?;
+    Ok(Copied::Terminal)
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_text: &str) -> Result<Copied> {
+    Err(anyhow::anyhow!(
+        "spotitui was built without the clipboard feature"
+    ))
+}
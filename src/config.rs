@@ -0,0 +1,728 @@
+use anyhow::Context;
+use directories::ProjectDirs;
+use serde::Deserialize;
+use spotitui_core::spotify;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::keymap::Remaps;
+use crate::logging;
+use crate::theme::ThemeConfig;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub credentials: CredentialsConfig,
+    pub layout: LayoutConfig,
+    pub display: DisplayConfig,
+    pub theme: ThemeConfig,
+    pub polling: PollingConfig,
+    pub network: NetworkConfig,
+    pub behavior: BehaviorConfig,
+    pub features: FeaturesConfig,
+    pub logging: LoggingConfig,
+    pub cache: CacheConfig,
+    pub listenbrainz: ListenBrainzConfig,
+    pub device: DeviceConfig,
+    pub hooks: HooksConfig,
+    /// Keys that act like other keys, as in `J = "down"`
+    pub keys: BTreeMap<String, String>,
+    /// Folders of the Playlists tab, each listing the names or ids of the playlists in it
+    pub folders: BTreeMap<String, Vec<String>>,
+    /// The profile picked with --profile, whose [profiles.NAME] table was applied
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+/// The Spotify app to sign in through, for when the environment variables aren't set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CredentialsConfig {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Where listens are submitted, nothing is without a token
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenBrainzConfig {
+    /// The user token from https://listenbrainz.org/settings/
+    pub token: Option<String>,
+    /// A self-hosted server's API, instead of https://api.listenbrainz.org
+    pub api_url: Option<String>,
+}
+
+/// Shell commands run on events, told about the track through SPOTITUI_* environment variables
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// A different track started playing
+    pub track_change: Option<String>,
+    /// Playback started or resumed
+    pub playback_started: Option<String>,
+    pub playback_paused: Option<String>,
+    /// A track was added to the queue from spotitui, once for each track of a batch
+    pub queue_added: Option<String>,
+}
+
+/// librespot run as a Spotify Connect device, for playing without another Spotify app open
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub enabled: bool,
+    /// The librespot executable, found on PATH unless it's a path
+    pub command: String,
+    /// What the device is called in Spotify's device lists
+    pub name: String,
+    /// 96, 160 or 320 kbps
+    pub bitrate: u32,
+    /// Passed on to librespot as they are, like ["--backend", "pulseaudio"]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Width of the left (library) column as a percentage of the terminal
+    pub library_width: u16,
+    /// Relative heights of the sections stacked in the left column
+    pub playlists_height: u16,
+    pub queue_height: u16,
+    /// Shows the full-width Now Playing bar along the bottom
+    pub show_now_playing: bool,
+    pub show_queue: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub explicit: ExplicitMode,
+    /// Marks focus and the playing track with borders, symbols and text styles as well as color
+    pub high_contrast: bool,
+    /// Starts with the tracks pane grouped under album headers
+    pub group_by_album: bool,
+    /// Puts playlists named like "Folder / Name" into folders, empty for none
+    pub folder_separator: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollingConfig {
+    /// How often the player is polled, the queue is only fetched again when the track changes
+    pub player_interval_ms: u64,
+    /// How long to wait for input before redrawing, which also sets how smoothly progress moves
+    pub event_poll_ms: u64,
+    /// Polls less often, skips the queue while it's hidden and doesn't prefetch playlists, to
+    /// save battery and API quota
+    pub low_power: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Most requests sent to Spotify each second, 0 for no limit
+    pub requests_per_second: f64,
+    /// A proxy for every request, like "http://proxy.example.com:8080". Without one,
+    /// HTTPS_PROXY, HTTP_PROXY and NO_PROXY are followed
+    pub proxy: Option<String>,
+    /// A PEM file of root certificates to trust besides the system's, for a proxy that
+    /// inspects traffic with its own
+    pub ca_certificate: Option<PathBuf>,
+    /// How long connecting to Spotify can take, in milliseconds, 0 for no limit
+    pub connect_timeout_ms: u64,
+    /// How long a response can go quiet before it's given up on, in milliseconds, 0 for no
+    /// limit
+    pub read_timeout_ms: u64,
+    /// How long a playback change like pausing or skipping can take in all, in milliseconds,
+    /// 0 for no limit
+    pub playback_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BehaviorConfig {
+    /// Asks before removing tracks from a playlist or deleting a playlist
+    pub confirm_destructive: bool,
+    /// Copies each week's Discover Weekly into a dated playlist of its own
+    pub archive_discover_weekly: bool,
+    /// Seed of the shuffle S queues, for the same order every time, a new one each time if unset
+    pub shuffle_seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Clicking and scrolling in the terminal, off leaves the mouse to the terminal itself
+    pub mouse: bool,
+    /// Surfaces Discover Weekly and the other playlists Spotify makes at the top of the list
+    pub for_you: bool,
+    /// Lets playerctl, desktop media applets and media keys control playback over D-Bus, on
+    /// Linux only
+    pub mpris: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Writes a log to the state directory's logs folder, as --debug does
+    pub enabled: bool,
+    /// One of error, warn, info, debug or trace
+    pub level: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Keeps the library on disk, so it shows up at once while being refreshed
+    pub enabled: bool,
+    /// How old a cached copy can be and still be shown, the playlists one covering albums and
+    /// artists too
+    pub playlists_ttl_hours: u64,
+    pub tracks_ttl_hours: u64,
+}
+
+/// How tracks with explicit content are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplicitMode {
+    /// Shown like any other track, with an E badge
+    #[default]
+    Show,
+    /// Greyed out
+    Dim,
+    /// Left out of track lists
+    Hide,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            library_width: 30,
+            playlists_height: 65,
+            queue_height: 35,
+            show_now_playing: true,
+            show_queue: true,
+        }
+    }
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            player_interval_ms: 2000,
+            event_poll_ms: 50,
+            low_power: false,
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 10.0,
+            proxy: None,
+            ca_certificate: None,
+            connect_timeout_ms: 10_000,
+            read_timeout_ms: 30_000,
+            playback_timeout_ms: 10_000,
+        }
+    }
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            mouse: true,
+            for_you: true,
+            mpris: true,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: "debug".to_string(),
+        }
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: "librespot".to_string(),
+            name: "spotitui".to_string(),
+            bitrate: 320,
+            args: Vec::new(),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            playlists_ttl_hours: 24,
+            tracks_ttl_hours: 24 * 7,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn playlists_ttl(&self) -> Duration {
+        Duration::from_secs(self.playlists_ttl_hours.saturating_mul(3600))
+    }
+
+    pub fn tracks_ttl(&self) -> Duration {
+        Duration::from_secs(self.tracks_ttl_hours.saturating_mul(3600))
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            explicit: ExplicitMode::default(),
+            high_contrast: false,
+            group_by_album: false,
+            folder_separator: " / ".to_string(),
+        }
+    }
+}
+
+impl Default for BehaviorConfig {
+    fn default() -> Self {
+        Self {
+            confirm_destructive: true,
+            archive_discover_weekly: false,
+            shuffle_seed: None,
+        }
+    }
+}
+
+/// Everything wrong with the config, shown on the startup screen
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: Option<PathBuf>,
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            writeln!(f, "Problems with {}:", path.display())?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config from `path`, or the default location when there's none, falling back
+    /// to defaults if nothing is there. A `profile` has its table layered over the rest
+    pub fn load(path: Option<PathBuf>, profile: Option<&str>) -> Result<Self, ConfigError> {
+        // A path given on the command line has to exist, the default one doesn't
+        let required = path.is_some();
+        let path = path.or_else(Self::path);
+        let contents = match &path {
+            Some(path) if required || path.exists() => {
+                fs::read_to_string(path).map_err(|e| ConfigError {
+                    path: Some(path.clone()),
+                    problems: vec![format!("Can't read it: {}", e)],
+                })?
+            }
+            _ => String::new(),
+        };
+
+        let mut config = Self::parse(&contents, profile).map_err(|problem| ConfigError {
+            path: path.clone(),
+            problems: vec![problem],
+        })?;
+        config.profile = profile.map(str::to_string);
+
+        let problems = config.validate();
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { path, problems })
+        }
+    }
+
+    fn parse(contents: &str, profile: Option<&str>) -> Result<Self, String> {
+        let mut table: toml::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let profiles = table.remove("profiles");
+        if let Some(name) = profile {
+            let Some(toml::Value::Table(overrides)) =
+                profiles.as_ref().and_then(|profiles| profiles.get(name))
+            else {
+                return Err(format!("There's no [profiles.{}] table", name));
+            };
+            merge(&mut table, overrides.clone());
+        }
+        table.try_into().map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    /// Every problem with the settings, so they can all be fixed in one go
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.credentials.client_id().is_none() {
+            problems.push(
+                "No Spotify client id: set SPOTIFY_CLIENT_ID or credentials.client_id".to_string(),
+            );
+        }
+        self.layout.validate(&mut problems);
+        self.polling.validate(&mut problems);
+        self.network.validate(&mut problems);
+        if !logging::LEVELS.contains(&self.logging.level.as_str()) {
+            problems.push(format!(
+                "logging.level must be one of {}, got \"{}\"",
+                logging::LEVELS.join(", "),
+                self.logging.level
+            ));
+        }
+        if ![96, 160, 320].contains(&self.device.bitrate) {
+            problems.push(format!(
+                "device.bitrate must be 96, 160 or 320, got {}",
+                self.device.bitrate
+            ));
+        }
+        if let Err(theme_problems) = self.theme.resolve() {
+            problems.extend(theme_problems);
+        }
+        if let Err(key_problems) = Remaps::parse(&self.keys) {
+            problems.extend(key_problems);
+        }
+        problems
+    }
+
+    /// config.toml in the platform's config directory, like ~/.config/spotitui on Linux
+    pub fn path() -> Option<PathBuf> {
+        Some(project_dirs()?.config_dir().join("config.toml"))
+    }
+}
+
+/// Where the platform keeps spotitui's config, cache and state, none without a home directory
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "spotitui")
+}
+
+impl ListenBrainzConfig {
+    /// LISTENBRAINZ_TOKEN if it's set, the config's token otherwise
+    #[cfg(feature = "scrobble")]
+    pub fn token(&self) -> Option<String> {
+        env::var("LISTENBRAINZ_TOKEN")
+            .ok()
+            .or_else(|| self.token.clone())
+            .filter(|token| !token.is_empty())
+    }
+}
+
+impl CredentialsConfig {
+    /// SPOTIFY_CLIENT_ID if it's set, the config's client id otherwise
+    pub fn client_id(&self) -> Option<String> {
+        env::var("SPOTIFY_CLIENT_ID")
+            .ok()
+            .or_else(|| self.client_id.clone())
+            .filter(|id| !id.is_empty())
+    }
+
+    pub fn client_secret(&self) -> String {
+        env::var("SPOTIFY_CLIENT_SECRET")
+            .ok()
+            .or_else(|| self.client_secret.clone())
+            .unwrap_or_default()
+    }
+}
+
+const MIN_LIBRARY_WIDTH: u16 = 10;
+const MAX_LIBRARY_WIDTH: u16 = 90;
+const MIN_SECTION_HEIGHT: u16 = 5;
+
+impl LayoutConfig {
+    pub fn resize_library(&mut self, grow: bool, step: u16) {
+        self.library_width = if grow {
+            (self.library_width + step).min(MAX_LIBRARY_WIDTH)
+        } else {
+            self.library_width
+                .saturating_sub(step)
+                .max(MIN_LIBRARY_WIDTH)
+        };
+    }
+
+    /// Moves the boundary between the playlists and the queue below them
+    pub fn resize_playlists(&mut self, grow: bool, step: u16) {
+        if !self.show_queue {
+            return;
+        }
+
+        if grow {
+            transfer_height(&mut self.queue_height, &mut self.playlists_height, step);
+        } else {
+            transfer_height(&mut self.playlists_height, &mut self.queue_height, step);
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(MIN_LIBRARY_WIDTH..=MAX_LIBRARY_WIDTH).contains(&self.library_width) {
+            problems.push(format!(
+                "layout.library_width must be between {} and {}, got {}",
+                MIN_LIBRARY_WIDTH, MAX_LIBRARY_WIDTH, self.library_width
+            ));
+        }
+        if self.playlists_height == 0 {
+            problems.push("layout.playlists_height must be greater than 0".to_string());
+        }
+        if self.show_queue && self.queue_height == 0 {
+            problems.push("layout.queue_height must be greater than 0".to_string());
+        }
+    }
+}
+
+/// Slowest rates used in low power mode, unless the config asks for slower ones
+const LOW_POWER_PLAYER_INTERVAL: Duration = Duration::from_secs(10);
+const LOW_POWER_EVENT_POLL: Duration = Duration::from_millis(250);
+
+impl PollingConfig {
+    pub fn player_interval(&self) -> Duration {
+        let interval = Duration::from_millis(self.player_interval_ms);
+        if self.low_power {
+            interval.max(LOW_POWER_PLAYER_INTERVAL)
+        } else {
+            interval
+        }
+    }
+
+    pub fn event_poll(&self) -> Duration {
+        let poll = Duration::from_millis(self.event_poll_ms);
+        if self.low_power {
+            poll.max(LOW_POWER_EVENT_POLL)
+        } else {
+            poll
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.player_interval_ms < 500 {
+            problems.push(format!(
+                "polling.player_interval_ms must be at least 500, got {}",
+                self.player_interval_ms
+            ));
+        }
+        if self.event_poll_ms == 0 {
+            problems.push("polling.event_poll_ms must be greater than 0".to_string());
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// An HTTP client going through the proxy, trusting the extra certificates and giving up
+    /// on requests that hang
+    pub fn http_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut builder = spotify::client_builder();
+        if let Some(timeout) = limit(self.connect_timeout_ms) {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = limit(self.read_timeout_ms) {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(path) = &self.ca_certificate {
+            let pem = fs::read(path).with_context(|| {
+                format!("Couldn't read network.ca_certificate {}", path.display())
+            })?;
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder.build()?)
+    }
+
+    pub fn playback_timeout(&self) -> Option<Duration> {
+        limit(self.playback_timeout_ms)
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        let per_second = self.requests_per_second;
+        if per_second.is_nan() || per_second < 0.0 {
+            problems.push(format!(
+                "network.requests_per_second can't be negative, got {}",
+                per_second
+            ));
+        }
+        if let Some(proxy) = &self.proxy {
+            if let Err(e) = url::Url::parse(proxy) {
+                problems.push(format!("network.proxy \"{}\" isn't a URL: {}", proxy, e));
+            }
+        }
+        if let Some(path) = &self.ca_certificate {
+            if !path.is_file() {
+                problems.push(format!(
+                    "network.ca_certificate {} doesn't exist",
+                    path.display()
+                ));
+            }
+        }
+    }
+}
+
+/// A timeout in milliseconds, 0 meaning none
+fn limit(ms: u64) -> Option<Duration> {
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+/// Copies `overrides` into `table`, keeping whatever in nested tables it doesn't mention
+fn merge(table: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => {
+                merge(existing, value)
+            }
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+fn transfer_height(from: &mut u16, to: &mut u16, step: u16) {
+    let amount = step.min(from.saturating_sub(MIN_SECTION_HEIGHT));
+    *from -= amount;
+    *to += amount;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn parse(toml: &str) -> Config {
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.credentials.client_id = Some("client-id".to_string());
+        config
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let config = parse(
+            r#"
+            [layout]
+            library_width = 95
+            [polling]
+            player_interval_ms = 100
+            [network]
+            proxy = "proxy.example.com"
+            ca_certificate = "/nonexistent/ca.pem"
+            [theme]
+            name = "neon"
+            accent = "not-a-color"
+            [keys]
+            "ctrl-" = "j"
+            "#,
+        );
+
+        let problems = config.validate();
+
+        assert_eq!(problems.len(), 7, "{:?}", problems);
+        assert!(problems[0].starts_with("layout.library_width"));
+        assert!(problems[2].starts_with("network.proxy"));
+        assert!(problems[3].starts_with("network.ca_certificate"));
+        assert!(problems[4].contains("default, light, mono"));
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let error = Config::parse(
+            r#"
+            [layout]
+            library_widht = 40
+            "#,
+            None,
+        )
+        .unwrap_err();
+
+        assert!(error.contains("library_widht"), "{}", error);
+    }
+
+    #[test]
+    fn huge_cache_lifetimes_saturate() {
+        let config = parse(
+            r#"
+            [cache]
+            playlists_ttl_hours = 9223372036854775807
+            "#,
+        );
+
+        assert_eq!(config.cache.playlists_ttl(), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn theme_colors_can_be_overridden() {
+        let config = parse(
+            r##"
+            [theme]
+            name = "light"
+            accent = "#89b4fa"
+            "##,
+        );
+
+        let theme = config.theme.resolve().unwrap();
+
+        assert_eq!(theme.accent, ratatui::style::Color::Rgb(0x89, 0xb4, 0xfa));
+        assert_eq!(theme.text, ratatui::style::Color::Black);
+    }
+
+    #[test]
+    fn keys_act_like_the_keys_they_are_mapped_to() {
+        let config = parse(
+            r#"
+            [keys]
+            J = "pagedown"
+            "ctrl-j" = "j"
+            "#,
+        );
+        let remaps = Remaps::parse(&config.keys).unwrap();
+
+        let shifted = KeyEvent::new(KeyCode::Char('J'), KeyModifiers::SHIFT);
+        assert_eq!(remaps.apply(shifted).code, KeyCode::PageDown);
+        let ctrl_j = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        assert_eq!(
+            remaps.apply(ctrl_j),
+            KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)
+        );
+        let other = KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE);
+        assert_eq!(remaps.apply(other), other);
+    }
+
+    #[test]
+    fn profiles_are_layered_over_the_rest() {
+        let contents = r#"
+            [layout]
+            library_width = 40
+            show_queue = false
+            [profiles.work.layout]
+            library_width = 25
+            [profiles.work.features]
+            mouse = false
+            "#;
+
+        let config = Config::parse(contents, Some("work")).unwrap();
+        assert_eq!(config.layout.library_width, 25);
+        assert!(!config.layout.show_queue);
+        assert!(!config.features.mouse);
+
+        let config = Config::parse(contents, None).unwrap();
+        assert_eq!(config.layout.library_width, 40);
+        assert!(Config::parse(contents, Some("home")).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use spotitui_core::api::SpotifyApi;
+use spotitui_core::spotify::{Album, Playlist, Track};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::app::civil_date;
+
+/// File formats a list of tracks can be written out as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One row per track, for spreadsheets and migration services
+    Csv,
+    /// Every field spotitui knows about each track
+    Json,
+    /// A playlist of spotify: URIs that media players and importers understand
+    M3u,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::M3u => "m3u",
+        }
+    }
+}
+
+/// A list of tracks as it's written to a file
+pub fn render(format: Format, name: &str, tracks: &[Track]) -> String {
+    match format {
+        Format::Csv => {
+            let mut out = String::from("name,artists,album,duration_ms,added_at,uri\n");
+            for track in tracks {
+                let artists = artist_names(track);
+                let row = [
+                    csv_field(&track.name),
+                    csv_field(&artists),
+                    csv_field(&track.album.name),
+                    Cow::Owned(track.duration_ms.to_string()),
+                    csv_field(track.added_at.as_deref().unwrap_or("")),
+                    csv_field(&track.uri),
+                ];
+                out.push_str(&row.join(","));
+                out.push('\n');
+            }
+            out
+        }
+        Format::Json => {
+            let tracks: Vec<Value> = tracks.iter().map(track_json).collect();
+            let list = json!({ "name": name, "tracks": tracks });
+            format!("{:#}\n", list)
+        }
+        Format::M3u => {
+            let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", name);
+            for track in tracks {
+                out.push_str(&format!(
+                    "#EXTINF:{},{} - {}\n{}\n",
+                    track.duration_ms / 1000,
+                    artist_names(track),
+                    track.name,
+                    track.uri
+                ));
+            }
+            out
+        }
+    }
+}
+
+/// Writes a list to `path`
+pub fn write(path: &Path, format: Format, name: &str, tracks: &[Track]) -> Result<()> {
+    fs::write(path, render(format, name, tracks))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Writes each list to a file of its own in `dir`, named after it, returning the files
+pub fn write_all(
+    dir: &Path,
+    format: Format,
+    lists: &[(String, Vec<Track>)],
+) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut taken = HashSet::new();
+    let mut paths = Vec::new();
+    for (name, tracks) in lists {
+        // Playlists can share a name, the later ones get a number
+        let stem = file_stem(name);
+        let mut file = format!("{}.{}", stem, format.extension());
+        let mut copy = 1;
+        while !taken.insert(file.to_lowercase()) {
+            copy += 1;
+            file = format!("{} ({}).{}", stem, copy, format.extension());
+        }
+        let path = dir.join(file);
+        write(&path, format, name, tracks)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// What went into a backup
+#[derive(Debug)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub playlists: usize,
+    pub tracks: usize,
+    pub albums: usize,
+}
+
+impl Backup {
+    pub fn describe(&self) -> String {
+        format!(
+            "Backed up {} playlists, {} tracks and {} albums to {}",
+            self.playlists,
+            self.tracks,
+            self.albums,
+            self.path.display()
+        )
+    }
+}
+
+/// Fetches every playlist with all its tracks, Liked Songs and the saved albums, and writes
+/// them to a JSON file in `dir` named after when it was made
+pub async fn backup(client: &impl SpotifyApi, dir: &Path) -> Result<Backup> {
+    let created_at = timestamp(SystemTime::now());
+    let (archive, mut backup) = library_json(client, &created_at).await?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    // Colons aren't allowed in file names everywhere
+    let file = format!("spotitui-backup-{}.json", created_at.replace(':', "-"));
+    backup.path = dir.join(file);
+    fs::write(&backup.path, format!("{:#}\n", archive))
+        .with_context(|| format!("Failed to write {}", backup.path.display()))?;
+    Ok(backup)
+}
+
+/// The whole library as one JSON document, with what's in it
+async fn library_json(client: &impl SpotifyApi, created_at: &str) -> Result<(Value, Backup)> {
+    let playlists = client.get_playlists().await?;
+    let mut tracks = 0;
+    let mut lists = Vec::new();
+    for playlist in &playlists {
+        let list = client.get_playlist_tracks(&playlist.id).await?;
+        tracks += list.len();
+        lists.push(playlist_json(playlist, &list));
+    }
+    let liked = client.get_playlist_tracks("liked").await?;
+    tracks += liked.len();
+    let albums = client.get_saved_albums().await?;
+
+    let archive = json!({
+        "created_at": created_at,
+        "playlists": lists,
+        "liked_songs": liked.iter().map(track_json).collect::<Vec<_>>(),
+        "saved_albums": albums.iter().map(album_json).collect::<Vec<_>>(),
+    });
+    let backup = Backup {
+        path: PathBuf::new(),
+        playlists: playlists.len(),
+        tracks,
+        albums: albums.len(),
+    };
+    Ok((archive, backup))
+}
+
+fn playlist_json(playlist: &Playlist, tracks: &[Track]) -> Value {
+    json!({
+        "id": playlist.id,
+        "uri": format!("spotify:playlist:{}", playlist.id),
+        "name": playlist.name,
+        "description": playlist.description,
+        "owner": playlist.owner.as_ref().map(|owner| &owner.id),
+        "tracks": tracks.iter().map(track_json).collect::<Vec<_>>(),
+    })
+}
+
+fn track_json(track: &Track) -> Value {
+    json!({
+        "id": track.id,
+        "uri": track.uri,
+        "name": track.name,
+        "artists": track.artists.iter().map(|a| &a.name).collect::<Vec<_>>(),
+        "album": track.album.name,
+        "duration_ms": track.duration_ms,
+        "added_at": track.added_at,
+        "explicit": track.explicit,
+    })
+}
+
+fn album_json(album: &Album) -> Value {
+    json!({
+        "id": album.id,
+        "uri": format!("spotify:album:{}", album.id),
+        "name": album.name,
+        "artists": album.artists.iter().map(|a| &a.name).collect::<Vec<_>>(),
+    })
+}
+
+/// A time as YYYY-MM-DDTHH:MM:SSZ
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let of_day = seconds % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        civil_date((seconds / 86_400) as i64),
+        of_day / 3600,
+        of_day % 3600 / 60,
+        of_day % 60
+    )
+}
+
+/// A list's name as a file name, without the characters file systems object to
+pub fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let stem = stem.trim().trim_matches('.');
+    if stem.is_empty() {
+        "playlist".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn artist_names(track: &Track) -> String {
+    let names: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    names.join(", ")
+}
+
+/// Quotes a field that holds a comma, quote or line break
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn tracks_are_written_as_csv_and_m3u() {
+        let mut track = mock::track("t1", "Hello, \"World\"");
+        track.duration_ms = 61_500;
+        let tracks = [track];
+
+        assert_eq!(
+            render(Format::Csv, "Mix", &tracks),
+            "name,artists,album,duration_ms,added_at,uri\n\
+             \"Hello, \"\"World\"\"\",Artist,Album,61500,,spotify:track:t1\n"
+        );
+        assert_eq!(
+            render(Format::M3u, "Mix", &tracks),
+            "#EXTM3U\n#PLAYLIST:Mix\n#EXTINF:61,Artist - Hello, \"World\"\nspotify:track:t1\n"
+        );
+        assert_eq!(file_stem("AC/DC: Live?"), "AC_DC_ Live_");
+    }
+
+    #[tokio::test]
+    async fn backups_hold_every_playlist_and_liked_songs() {
+        let mock = mock::MockSpotify::default();
+        {
+            let mut state = mock.state();
+            state.playlists = vec![mock::playlist("mix", "Mix", 2)];
+            state.playlist_tracks.insert(
+                "mix".to_string(),
+                vec![mock::track("t1", "First"), mock::track("t2", "Second")],
+            );
+            state
+                .playlist_tracks
+                .insert("liked".to_string(), vec![mock::track("t3", "Third")]);
+        }
+
+        let (archive, backup) = library_json(&mock, "2024-06-03T10:00:00Z").await.unwrap();
+
+        assert_eq!((backup.playlists, backup.tracks), (1, 3));
+        assert_eq!(archive["playlists"][0]["name"], "Mix");
+        assert_eq!(
+            archive["playlists"][0]["tracks"][1]["uri"],
+            "spotify:track:t2"
+        );
+        assert_eq!(archive["liked_songs"][0]["name"], "Third");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + std::time::Duration::from_secs(90_061)),
+            "1970-01-02T01:01:01Z"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use spotitui_core::spotify::Track;
+use std::sync::OnceLock;
+
+fn matcher() -> &'static SkimMatcherV2 {
+    static MATCHER: OnceLock<SkimMatcherV2> = OnceLock::new();
+    MATCHER.get_or_init(|| SkimMatcherV2::default().ignore_case())
+}
+
+/// Skim-style fuzzy match, returning the score and the char indices of the matched characters
+pub fn fuzzy_indices(text: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    matcher().fuzzy_indices(text, pattern)
+}
+
+pub fn fuzzy_score(text: &str, pattern: &str) -> Option<i64> {
+    matcher().fuzzy_match(text, pattern)
+}
+
+/// Best score across a track's title, artists and album
+pub fn track_score(track: &Track, pattern: &str) -> Option<i64> {
+    let artists = track
+        .artists
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    [
+        fuzzy_score(&track.name, pattern),
+        fuzzy_score(&artists, pattern),
+        fuzzy_score(&track.album.name, pattern),
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+}
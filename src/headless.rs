@@ -0,0 +1,278 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use spotitui_core::api::SpotifyApi;
+use spotitui_core::spotify::{CurrentlyPlaying, Device, Queue, SpotifyError, Track};
+use std::path::PathBuf;
+
+use crate::cli::{self, HeadlessCommand};
+use crate::config::ConfigError;
+use crate::export::{self, Format};
+use crate::state;
+use crate::ui::format_duration;
+
+/// Runs a command against Spotify and returns what to print, if anything. Signs in with the
+/// saved refresh token since there's no browser flow to fall back on
+pub async fn run(
+    client: &impl SpotifyApi,
+    command: HeadlessCommand,
+    json: bool,
+) -> Result<Option<String>> {
+    client.refresh_access_token().await.map_err(|e| {
+        // Failures to reach Spotify say so themselves
+        if let Some(SpotifyError::Auth(_)) = e.downcast_ref() {
+            SpotifyError::Auth(
+                "Not signed in, start spotitui once to sign in to Spotify".to_string(),
+            )
+            .into()
+        } else {
+            e
+        }
+    })?;
+
+    match command {
+        HeadlessCommand::Play { uri: None } => client.resume_playback().await?,
+        HeadlessCommand::Play { uri: Some(uri) } if cli::is_context(&uri) => {
+            client.play_context(&uri).await?
+        }
+        HeadlessCommand::Play { uri: Some(uri) } => client.play_tracks(&[&uri]).await?,
+        HeadlessCommand::Pause => client.pause_playback().await?,
+        HeadlessCommand::Next => client.next_track().await?,
+        HeadlessCommand::Prev => client.previous_track().await?,
+        HeadlessCommand::Status => {
+            let playing = client.get_currently_playing().await?;
+            if !json {
+                return Ok(Some(describe(playing.as_ref())));
+            }
+            // The one-line status has no room for the queue
+            let queue = client.get_queue().await?;
+            return Ok(Some(
+                status_json(playing.as_ref(), queue.as_ref()).to_string(),
+            ));
+        }
+        HeadlessCommand::Queue { uri } => {
+            if cli::is_context(&uri) {
+                return Err(anyhow!("Only tracks and episodes can be queued"));
+            }
+            client.add_to_queue(&uri).await?
+        }
+        HeadlessCommand::Export {
+            playlist,
+            format,
+            output,
+        } => {
+            let (path, tracks) = export_library(client, playlist, format, output).await?;
+            if json {
+                return Ok(Some(
+                    json!({ "ok": true, "path": path, "tracks": tracks }).to_string(),
+                ));
+            }
+            return Ok(Some(format!(
+                "Wrote {} tracks to {}",
+                tracks,
+                path.display()
+            )));
+        }
+        HeadlessCommand::Backup { output } => {
+            let dir = output.or_else(state::backup_dir).ok_or_else(|| {
+                anyhow!("No HOME to keep backups in, pick a folder with --output")
+            })?;
+            let backup = export::backup(client, &dir).await?;
+            if json {
+                return Ok(Some(
+                    json!({
+                        "ok": true,
+                        "path": backup.path,
+                        "playlists": backup.playlists,
+                        "tracks": backup.tracks,
+                        "albums": backup.albums,
+                    })
+                    .to_string(),
+                ));
+            }
+            return Ok(Some(backup.describe()));
+        }
+    }
+    Ok(json.then(|| json!({ "ok": true }).to_string()))
+}
+
+/// Writes one playlist to a file, or all of them and Liked Songs to a folder, returning where
+/// and how many tracks
+async fn export_library(
+    client: &impl SpotifyApi,
+    playlist: Option<String>,
+    format: Format,
+    output: Option<PathBuf>,
+) -> Result<(PathBuf, usize)> {
+    let playlists = client.get_playlists().await?;
+    let Some(id) = playlist else {
+        let mut lists = vec![(
+            "Liked Songs".to_string(),
+            client.get_playlist_tracks("liked").await?,
+        )];
+        for playlist in playlists {
+            let tracks = client.get_playlist_tracks(&playlist.id).await?;
+            lists.push((playlist.name, tracks));
+        }
+        let dir = output.unwrap_or_else(|| PathBuf::from("spotitui-export"));
+        export::write_all(&dir, format, &lists)?;
+        let tracks = lists.iter().map(|(_, tracks)| tracks.len()).sum();
+        return Ok((dir, tracks));
+    };
+
+    // Playlists that aren't followed have no name to go by
+    let name = match id.as_str() {
+        "liked" => "Liked Songs".to_string(),
+        _ => playlists
+            .into_iter()
+            .find(|playlist| playlist.id == id)
+            .map_or_else(|| id.clone(), |playlist| playlist.name),
+    };
+    let tracks = client.get_playlist_tracks(&id).await?;
+    let path = output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "{}.{}",
+            export::file_stem(&name),
+            format.extension()
+        ))
+    });
+    export::write(&path, format, &name, &tracks)?;
+    Ok((path, tracks.len()))
+}
+
+/// An error in the same shape as other --json output, for scripts reading stdout, with its
+/// kind for telling failures apart
+pub fn error_json(error: anyhow::Error) -> String {
+    let kind = match error.downcast_ref::<SpotifyError>() {
+        Some(e) => e.kind(),
+        None if error.is::<ConfigError>() => "config",
+        None => "other",
+    };
+    json!({ "error": error.to_string().trim_end(), "kind": kind }).to_string()
+}
+
+/// One line on what's playing, short enough for a status bar
+fn describe(playing: Option<&CurrentlyPlaying>) -> String {
+    let Some((playing, track)) = playing.and_then(|p| p.item.as_ref().map(|track| (p, track)))
+    else {
+        return "Nothing playing".to_string();
+    };
+
+    let mut line = format!(
+        "{} {} - {} ({} / {})",
+        if playing.is_playing { "▶" } else { "⏸" },
+        artist_names(track).join(", "),
+        track.name,
+        format_duration(playing.progress_ms.unwrap_or(0)),
+        format_duration(track.duration_ms as u64)
+    );
+    if let Some(device) = &playing.device {
+        line.push_str(&format!(" on {}", device.name));
+    }
+    line
+}
+
+/// The player state with only the fields worth scripting against, so the output doesn't change
+/// shape whenever Spotify's responses do
+fn status_json(playing: Option<&CurrentlyPlaying>, queue: Option<&Queue>) -> Value {
+    let queue: Vec<Value> = queue
+        .map(|queue| queue.queue.iter().map(track_json).collect())
+        .unwrap_or_default();
+    match playing {
+        Some(playing) => json!({
+            "is_playing": playing.is_playing,
+            "progress_ms": playing.progress_ms,
+            "shuffle": playing.shuffle_state,
+            "repeat": playing.repeat_state,
+            "track": playing.item.as_ref().map(track_json),
+            "device": playing.device.as_ref().map(device_json),
+            "queue": queue,
+        }),
+        None => json!({
+            "is_playing": false,
+            "progress_ms": null,
+            "shuffle": false,
+            "repeat": "off",
+            "track": null,
+            "device": null,
+            "queue": queue,
+        }),
+    }
+}
+
+fn track_json(track: &Track) -> Value {
+    json!({
+        "id": track.id,
+        "uri": track.uri,
+        "name": track.name,
+        "artists": artist_names(track),
+        "album": track.album.name,
+        "duration_ms": track.duration_ms,
+    })
+}
+
+fn device_json(device: &Device) -> Value {
+    json!({
+        "id": device.id,
+        "name": device.name,
+        "type": device.device_type,
+        "volume_percent": device.volume_percent,
+    })
+}
+
+fn artist_names(track: &Track) -> Vec<&str> {
+    track.artists.iter().map(|a| a.name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockSpotify};
+
+    #[tokio::test]
+    async fn play_starts_albums_as_a_context() {
+        let mock = MockSpotify::default();
+
+        let album = "spotify:album:4aawyAB9vmqN3uQ7FjRGTy".to_string();
+        run(&mock, HeadlessCommand::Play { uri: Some(album) }, false)
+            .await
+            .unwrap();
+        let track = "spotify:track:t1".to_string();
+        run(&mock, HeadlessCommand::Play { uri: Some(track) }, false)
+            .await
+            .unwrap();
+        run(&mock, HeadlessCommand::Play { uri: None }, false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.calls_to("play_context"),
+            ["spotify:album:4aawyAB9vmqN3uQ7FjRGTy"]
+        );
+        assert_eq!(mock.calls_to("play_tracks"), ["spotify:track:t1"]);
+        assert_eq!(mock.calls_to("resume_playback").len(), 1);
+    }
+
+    #[test]
+    fn status_json_has_the_track_and_queue() {
+        let playing = CurrentlyPlaying {
+            item: Some(mock::track("t1", "First")),
+            is_playing: true,
+            progress_ms: Some(61_000),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "context".to_string(),
+        };
+        let queue = Queue {
+            currently_playing: None,
+            queue: vec![mock::track("t2", "Second")],
+        };
+
+        let status = status_json(Some(&playing), Some(&queue));
+
+        assert_eq!(status["track"]["name"], "First");
+        assert_eq!(status["track"]["artists"], json!(["Artist"]));
+        assert_eq!(status["progress_ms"], 61_000);
+        assert_eq!(status["device"], Value::Null);
+        assert_eq!(status["queue"][0]["uri"], "spotify:track:t2");
+    }
+}
@@ -0,0 +1,199 @@
+use spotitui_core::spotify::{CurrentlyPlaying, Track};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+use crate::config::HooksConfig;
+use crate::shutdown::Shutdown;
+
+/// Something a hook can be run on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    TrackChange,
+    PlaybackStarted,
+    PlaybackPaused,
+    QueueAdded,
+}
+
+impl Event {
+    /// As it's written in the config and SPOTITUI_EVENT
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::TrackChange => "track_change",
+            Event::PlaybackStarted => "playback_started",
+            Event::PlaybackPaused => "playback_paused",
+            Event::QueueAdded => "queue_added",
+        }
+    }
+}
+
+/// Runs the shell commands configured under [hooks]
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    config: HooksConfig,
+}
+
+impl Hooks {
+    pub fn new(config: HooksConfig) -> Self {
+        Self { config }
+    }
+
+    fn command(&self, event: Event) -> Option<&str> {
+        match event {
+            Event::TrackChange => self.config.track_change.as_deref(),
+            Event::PlaybackStarted => self.config.playback_started.as_deref(),
+            Event::PlaybackPaused => self.config.playback_paused.as_deref(),
+            Event::QueueAdded => self.config.queue_added.as_deref(),
+        }
+    }
+
+    /// Whether any of the events that come from the player have a hook
+    pub fn follows_player(&self) -> bool {
+        [
+            Event::TrackChange,
+            Event::PlaybackStarted,
+            Event::PlaybackPaused,
+        ]
+        .into_iter()
+        .any(|event| self.command(event).is_some())
+    }
+
+    /// Starts the event's hook, if it has one, without waiting for it to finish
+    pub fn run(&self, event: Event, track: &Track) {
+        let Some(command) = self.command(event) else {
+            return;
+        };
+        let mut child = shell(command);
+        child
+            .envs(env(event, track))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        match child.spawn() {
+            Ok(mut child) => {
+                // Waited on so it doesn't linger as a zombie
+                tokio::spawn(async move {
+                    match child.wait().await {
+                        Ok(status) if !status.success() => {
+                            tracing::warn!(event = event.name(), %status, "Hook failed")
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!(event = event.name(), "Hook failed: {}", e),
+                    }
+                });
+            }
+            Err(e) => tracing::warn!(event = event.name(), "Couldn't run hook: {}", e),
+        }
+    }
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// What a hook is told about the track
+fn env(event: Event, track: &Track) -> Vec<(&'static str, String)> {
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    vec![
+        ("SPOTITUI_EVENT", event.name().to_string()),
+        ("SPOTITUI_TRACK_ID", track.id.clone()),
+        ("SPOTITUI_TRACK_URI", track.uri.clone()),
+        ("SPOTITUI_TRACK_NAME", track.name.clone()),
+        ("SPOTITUI_ARTISTS", artists.join(", ")),
+        ("SPOTITUI_ALBUM", track.album.name.clone()),
+        ("SPOTITUI_DURATION_MS", track.duration_ms.to_string()),
+    ]
+}
+
+/// The events between two polls of the player
+pub fn player_events<'a>(
+    previous: Option<&CurrentlyPlaying>,
+    playing: Option<&'a CurrentlyPlaying>,
+) -> Vec<(Event, &'a Track)> {
+    let Some((playing, track)) = playing.and_then(|p| p.item.as_ref().map(|t| (p, t))) else {
+        return Vec::new();
+    };
+    let previous_track = previous.and_then(|p| p.item.as_ref());
+    let was_playing = previous.is_some_and(|p| p.is_playing);
+
+    let mut events = Vec::new();
+    if previous_track.map(|t| &t.uri) != Some(&track.uri) {
+        events.push((Event::TrackChange, track));
+    }
+    if playing.is_playing && !was_playing {
+        events.push((Event::PlaybackStarted, track));
+    } else if !playing.is_playing && was_playing {
+        events.push((Event::PlaybackPaused, track));
+    }
+    events
+}
+
+/// Runs the player's hooks as the polled player changes, until the app quits
+pub fn spawn(
+    hooks: Hooks,
+    mut player: watch::Receiver<Option<CurrentlyPlaying>>,
+    shutdown: &Shutdown,
+) {
+    shutdown.spawn(async move {
+        // What was playing at startup isn't a change
+        let mut previous = player.borrow_and_update().clone();
+        while player.changed().await.is_ok() {
+            let playing = player.borrow_and_update().clone();
+            for (event, track) in player_events(previous.as_ref(), playing.as_ref()) {
+                hooks.run(event, track);
+            }
+            previous = playing;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn player_changes_become_events() {
+        let playing = |id: &str, is_playing: bool| CurrentlyPlaying {
+            is_playing,
+            item: Some(mock::track(id, id)),
+            progress_ms: None,
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        };
+        let events = |previous: &CurrentlyPlaying, playing: &CurrentlyPlaying| {
+            player_events(Some(previous), Some(playing))
+                .into_iter()
+                .map(|(event, track)| (event, track.id.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            events(&playing("a", true), &playing("b", true)),
+            [(Event::TrackChange, "b".to_string())]
+        );
+        assert_eq!(
+            events(&playing("a", true), &playing("a", false)),
+            [(Event::PlaybackPaused, "a".to_string())]
+        );
+        assert_eq!(
+            events(&playing("a", false), &playing("b", true)),
+            [
+                (Event::TrackChange, "b".to_string()),
+                (Event::PlaybackStarted, "b".to_string())
+            ]
+        );
+        assert!(events(&playing("a", true), &playing("a", true)).is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use spotitui_core::spotify::Track;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::cli;
+
+/// A track named by an imported file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    /// A track's spotify: URI
+    Uri(String),
+    /// A track to search for
+    Search {
+        artist: Option<String>,
+        title: String,
+    },
+}
+
+impl Entry {
+    /// Reads a track's URI or link, "Artist - Title" or just a title
+    pub fn parse(line: &str) -> Option<Entry> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        if line.starts_with("spotify:") || line.starts_with("https://") {
+            return track_uri(line).map(Entry::Uri);
+        }
+        match line.split_once(" - ") {
+            Some((artist, title)) => Some(Entry::search(Some(artist), title)),
+            None => Some(Entry::search(None, line)),
+        }
+    }
+
+    fn search(artist: Option<&str>, title: &str) -> Entry {
+        Entry::Search {
+            artist: artist
+                .map(str::trim)
+                .filter(|artist| !artist.is_empty())
+                .map(str::to_string),
+            title: title.trim().to_string(),
+        }
+    }
+
+    /// What to search Spotify for, None for a URI
+    pub fn query(&self) -> Option<String> {
+        match self {
+            Entry::Uri(_) => None,
+            Entry::Search {
+                artist: Some(artist),
+                title,
+            } => Some(format!("track:{} artist:{}", title, artist)),
+            Entry::Search {
+                artist: None,
+                title,
+            } => Some(title.clone()),
+        }
+    }
+
+    /// The search result that's plainly this track, by its title and artist. Entries without
+    /// an artist are never sure enough to go without a review
+    pub fn best_match(&self, tracks: &[Track]) -> Option<usize> {
+        let Entry::Search {
+            artist: Some(artist),
+            title,
+        } = self
+        else {
+            return None;
+        };
+        let (artist, title) = (artist.to_lowercase(), title.to_lowercase());
+        tracks.iter().position(|track| {
+            let name = track.name.to_lowercase();
+            // Like "Title - Remastered 2011" or "Title (Live)"
+            let same_title = name == title
+                || name.starts_with(&format!("{} - ", title))
+                || name.starts_with(&format!("{} (", title));
+            same_title
+                && track
+                    .artists
+                    .iter()
+                    .any(|a| a.name.to_lowercase() == artist)
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::Uri(uri) => write!(f, "{}", uri),
+            Entry::Search {
+                artist: Some(artist),
+                title,
+            } => write!(f, "{} - {}", artist, title),
+            Entry::Search {
+                artist: None,
+                title,
+            } => write!(f, "{}", title),
+        }
+    }
+}
+
+/// What's read from a file to import, with the name of the playlist it goes into
+#[derive(Debug)]
+pub struct ImportFile {
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+
+/// Reads a CSV or JSON file, like the ones `spotitui export` writes, or any other file as
+/// lines of URIs and "Artist - Title", which covers M3U playlists too
+pub fn read(path: &Path) -> Result<ImportFile> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported".to_string());
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let file = match extension.as_deref() {
+        Some("json") => from_json(&contents, name),
+        Some("csv") => from_csv(&contents, name),
+        _ => Ok(from_lines(&contents, name)),
+    }
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+    if file.entries.is_empty() {
+        return Err(anyhow!("{} has no tracks in it", path.display()));
+    }
+    Ok(file)
+}
+
+/// An object with a list of tracks under "tracks" and maybe a "name", or just the list. Each
+/// track is a line as in a text file, or an object with a "uri" or a "name" and "artists"
+fn from_json(contents: &str, name: String) -> Result<ImportFile> {
+    let value: Value = serde_json::from_str(contents)?;
+    let name = value["name"].as_str().map_or(name, str::to_string);
+    let tracks = match &value {
+        Value::Array(tracks) => tracks,
+        _ => value["tracks"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Expected a list of tracks"))?,
+    };
+
+    let entries = tracks
+        .iter()
+        .filter_map(|track| {
+            if let Some(line) = track.as_str() {
+                return Entry::parse(line);
+            }
+            if let Some(uri) = track["uri"].as_str().and_then(track_uri) {
+                return Some(Entry::Uri(uri));
+            }
+            let title = track["name"].as_str().or(track["title"].as_str())?;
+            let artist = match &track["artists"] {
+                Value::Array(artists) => artists.first().and_then(Value::as_str),
+                artists => artists.as_str().or(track["artist"].as_str()),
+            };
+            Some(Entry::search(artist, title))
+        })
+        .collect();
+    Ok(ImportFile { name, entries })
+}
+
+/// A header row naming the columns, a URI column or a title column with an artist one beside
+/// it, like "uri", "name" and "artists" or Exportify's "Track URI", "Track Name" and
+/// "Artist Name(s)"
+fn from_csv(contents: &str, name: String) -> Result<ImportFile> {
+    let mut rows = csv_rows(contents).into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    let column = |matches: fn(&str) -> bool| header.iter().position(|column| matches(column));
+    let uri = column(|column| column.contains("uri"));
+    let title = column(|column| ["name", "title", "track", "track name"].contains(&column));
+    let artist = column(|column| column.starts_with("artist"));
+    if uri.is_none() && title.is_none() {
+        return Err(anyhow!("No URI, name or title column in the header row"));
+    }
+
+    let field = |row: &[String], column: Option<usize>| {
+        column
+            .and_then(|column| row.get(column))
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+    };
+    let entries = rows
+        .filter_map(|row| {
+            if let Some(uri) = field(&row, uri).as_deref().and_then(track_uri) {
+                return Some(Entry::Uri(uri));
+            }
+            // Several artists are written as one field, the first is enough to search by
+            let artists = field(&row, artist);
+            let artist = artists
+                .as_deref()
+                .map(|artists| artists.split(',').next().unwrap_or(artists));
+            Some(Entry::search(artist, &field(&row, title)?))
+        })
+        .collect();
+    Ok(ImportFile { name, entries })
+}
+
+/// A line per track, skipping comments, with an M3U's #EXTINF line standing in for the file
+/// path below it and its #PLAYLIST line naming it
+fn from_lines(contents: &str, mut name: String) -> ImportFile {
+    let mut entries = Vec::new();
+    let mut described = None;
+    for line in contents.lines().map(str::trim) {
+        if let Some(playlist) = line.strip_prefix("#PLAYLIST:") {
+            name = playlist.trim().to_string();
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // Like "#EXTINF:215,Artist - Title"
+            described = info.split_once(',').map(|(_, title)| title.to_string());
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            let entry = match described.take() {
+                Some(title) if track_uri(line).is_none() => Entry::parse(&title),
+                _ => Entry::parse(line),
+            };
+            entries.extend(entry);
+        }
+    }
+    ImportFile { name, entries }
+}
+
+/// The spotify: URI of a track's URI or link
+fn track_uri(arg: &str) -> Option<String> {
+    cli::spotify_uri(arg)
+        .ok()
+        .filter(|uri| uri.starts_with("spotify:track:"))
+}
+
+/// Splits CSV into rows of fields, with quoted fields holding commas, quotes and line breaks
+fn csv_rows(contents: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.is_empty()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{self, Format};
+    use crate::mock;
+
+    #[test]
+    fn exported_files_read_back_in() {
+        let tracks = [mock::track("t1", "First"), mock::track("t2", "Second")];
+        let uris = vec![
+            Entry::Uri("spotify:track:t1".to_string()),
+            Entry::Uri("spotify:track:t2".to_string()),
+        ];
+
+        let csv = from_csv(&export::render(Format::Csv, "Mix", &tracks), "mix".into()).unwrap();
+        assert_eq!(csv.entries, uris);
+        let json = from_json(&export::render(Format::Json, "Mix", &tracks), "mix".into()).unwrap();
+        assert_eq!((json.name.as_str(), json.entries), ("Mix", uris.clone()));
+        let m3u = from_lines(&export::render(Format::M3u, "Mix", &tracks), "mix".into());
+        assert_eq!((m3u.name.as_str(), m3u.entries), ("Mix", uris));
+    }
+
+    #[test]
+    fn lines_are_searched_for_by_artist_and_title() {
+        let file = from_lines(
+            "# Road trip\nQueen - Bohemian Rhapsody\n\nWonderwall\n#EXTINF:200,Oasis - Live Forever\n/music/live-forever.mp3\n",
+            "trip".into(),
+        );
+        assert_eq!(
+            file.entries,
+            [
+                Entry::search(Some("Queen"), "Bohemian Rhapsody"),
+                Entry::search(None, "Wonderwall"),
+                Entry::search(Some("Oasis"), "Live Forever"),
+            ]
+        );
+
+        let mut remaster = mock::track("t2", "Bohemian Rhapsody - Remastered 2011");
+        remaster.artists[0].name = "Queen".to_string();
+        let results = [mock::track("t1", "Bohemian Rhapsody"), remaster];
+        assert_eq!(file.entries[0].best_match(&results), Some(1));
+        assert_eq!(file.entries[1].best_match(&results), None);
+    }
+}
@@ -0,0 +1,626 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::fuzzy;
+
+/// Groups of key bindings, in the order the help popup lists them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    Navigation,
+    Library,
+    Tracks,
+    SearchAndFilter,
+    Playback,
+    Layout,
+    General,
+}
+
+impl Section {
+    pub const ALL: [Section; 7] = [
+        Section::Navigation,
+        Section::Library,
+        Section::Tracks,
+        Section::SearchAndFilter,
+        Section::Playback,
+        Section::Layout,
+        Section::General,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Section::Navigation => "Navigation",
+            Section::Library => "Library",
+            Section::Tracks => "Tracks",
+            Section::SearchAndFilter => "Search & Filter",
+            Section::Playback => "Playback",
+            Section::Layout => "Layout",
+            Section::General => "General",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Binding {
+    pub section: Section,
+    pub keys: &'static str,
+    pub description: &'static str,
+    /// The actions of KEYMAP the keys stand for, none for keys only popups and inputs read
+    pub actions: &'static [Action],
+}
+
+const fn bind(
+    section: Section,
+    keys: &'static str,
+    description: &'static str,
+    actions: &'static [Action],
+) -> Binding {
+    Binding {
+        section,
+        keys,
+        description,
+        actions,
+    }
+}
+
+impl Binding {
+    /// The keys as the help popup shows them, followed by keys the config remaps onto them
+    pub fn keys(&self, remaps: &Remaps) -> String {
+        let remapped = remaps.keys_for(self.actions);
+        if remapped.is_empty() {
+            self.keys.to_string()
+        } else {
+            format!("{}, {}", self.keys, remapped.join(", "))
+        }
+    }
+}
+
+/// Every key the app responds to, as the help popup lists them
+#[rustfmt::skip]
+pub const BINDINGS: &[Binding] = &[
+    bind(Section::Navigation, "Tab", "Switch between the library and tracks panes", &[Action::NextPane]),
+    bind(Section::Navigation, "h / l", "Focus the library / tracks pane", &[Action::FocusLibrary, Action::FocusTracks]),
+    bind(Section::Navigation, "↑/↓, j/k, Ctrl+P/N", "Move up/down in the focused pane", &[Action::Up, Action::Down]),
+    bind(Section::Navigation, "g/G, Home/End", "Jump to the top/bottom", &[Action::Top, Action::Bottom]),
+    bind(Section::Navigation, "<count> j/k, <count>G", "Move that many rows / go to that row, like 15j", &[]),
+    bind(Section::Navigation, "Ctrl+D/U", "Half a page down/up", &[Action::HalfPageDown, Action::HalfPageUp]),
+    bind(Section::Navigation, "PgUp/PgDn", "A full page up/down", &[Action::PageUp, Action::PageDown]),
+    bind(Section::Library, "[ / ]", "Previous/next library tab", &[Action::PreviousTab, Action::NextTab]),
+    bind(Section::Library, "Enter", "Show the selected entry's tracks", &[Action::Select]),
+    bind(Section::Library, "→/←", "Expand/collapse the playlist's folder", &[Action::Right, Action::Left]),
+    bind(Section::Library, "+", "Add every track of the entry to the queue", &[Action::Queue]),
+    bind(Section::Library, "S", "Shuffle the entry's tracks and add them to the queue", &[Action::ShuffleQueue]),
+    bind(Section::Library, "R, Ctrl+R", "Start a radio from the selected artist", &[Action::Radio, Action::ArtistRadio]),
+    bind(Section::Library, "p", "Pin/unpin the playlist to the top of the list", &[Action::TogglePin]),
+    bind(Section::Library, "o", "Order playlists by name, owner or latest change", &[Action::CycleSort]),
+    bind(Section::Library, "M", "Merge the playlist into another one", &[Action::Merge]),
+    bind(Section::Library, "=", "Compare the playlist with another one to sync them", &[Action::Compare]),
+    bind(Section::Library, "i", "Show the playlist's stats: artists, decades, tempo...", &[Action::Stats]),
+    bind(Section::Library, "m<letter>", "Mark the playlist with a letter", &[Action::SetMark]),
+    bind(Section::Library, "'<letter>", "Jump to the marked playlist, on the row you left it", &[Action::JumpToMark]),
+    bind(Section::Library, "Delete", "Delete the selected playlist", &[Action::Delete]),
+    bind(Section::Library, "B", "Back up the whole library to a JSON file", &[Action::Backup]),
+    bind(Section::Tracks, "Enter", "Play the selected track", &[Action::Select]),
+    bind(Section::Tracks, "+", "Add the track (or marked tracks) to the queue", &[Action::Queue]),
+    bind(Section::Tracks, "a", "Track actions: play from here, like, go to album...", &[Action::TrackMenu]),
+    bind(Section::Tracks, "A", "Add the rest of the track's album to the queue", &[Action::QueueRestOfAlbum]),
+    bind(Section::Tracks, "R", "Start a radio of tracks like the selected one", &[Action::Radio]),
+    bind(Section::Tracks, "Ctrl+R", "Start a radio from the track's artist", &[Action::ArtistRadio]),
+    bind(Section::Tracks, "Delete", "Remove the track (or marked tracks) from the playlist", &[Action::Delete]),
+    bind(Section::Tracks, "y / Y", "Copy the track's link / spotify: URI", &[Action::CopyLink, Action::CopyUri]),
+    bind(Section::Tracks, "v", "Mark/unmark the track for batch actions", &[Action::ToggleMark]),
+    bind(Section::Tracks, "V", "Start/finish marking a range (visual mode)", &[Action::VisualMode]),
+    bind(Section::Tracks, "o / O", "Cycle the sort column / flip the sort direction", &[Action::CycleSort, Action::FlipSort]),
+    bind(Section::Tracks, "b", "Group the tracks under album headers", &[Action::GroupByAlbum]),
+    bind(Section::Tracks, "Backspace", "Go back to the view before the album or artist", &[Action::Back]),
+    bind(Section::Tracks, "Esc", "Clear the marks", &[Action::Escape]),
+    bind(Section::SearchAndFilter, "s", "Search Spotify for tracks", &[Action::Search]),
+    bind(Section::SearchAndFilter, "/", "Fuzzy filter the focused list", &[Action::Filter]),
+    bind(Section::SearchAndFilter, "f", "Type the start of a name to jump to it", &[Action::TypeAhead]),
+    bind(Section::SearchAndFilter, "←/→, Ctrl+A/E", "Move the cursor in the input", &[]),
+    bind(Section::SearchAndFilter, "Ctrl+W / Ctrl+U", "Delete a word / clear the input", &[]),
+    bind(Section::SearchAndFilter, "Esc", "Leave search or clear the filter", &[Action::Escape]),
+    bind(Section::Playback, "Space", "Open the playback controls", &[Action::PlaybackControls]),
+    bind(Section::Playback, "d", "Pick the device to play on", &[Action::DevicePicker]),
+    bind(Section::Playback, "e", "Pick genres to play a radio of", &[Action::GenrePicker]),
+    bind(Section::Playback, "Q", "Save the queue as a new playlist", &[Action::SaveQueue]),
+    bind(Section::Playback, "Enter", "Run the selected control: play/pause, previous, next", &[]),
+    bind(Section::Playback, "←/→, Alt+h/l", "Seek back/forward 10 seconds, ←/→ outside the library", &[Action::Seek(-SEEK_STEP_MS), Action::Seek(SEEK_STEP_MS)]),
+    bind(Section::Playback, "Shift+←/→, Alt+H/L", "Seek back/forward a minute", &[Action::Seek(-LONG_SEEK_STEP_MS), Action::Seek(LONG_SEEK_STEP_MS)]),
+    bind(Section::Playback, "Alt+ +/-, Alt+0-9", "Turn the volume up/down 5%, or set it to 0-90%", &[Action::Volume(VOLUME_STEP), Action::Volume(-VOLUME_STEP), Action::SetVolume(0), Action::SetVolume(10), Action::SetVolume(20), Action::SetVolume(30), Action::SetVolume(40), Action::SetVolume(50), Action::SetVolume(60), Action::SetVolume(70), Action::SetVolume(80), Action::SetVolume(90)]),
+    bind(Section::Playback, "+/-, 0-9", "The same in the playback controls, without Alt", &[]),
+    bind(Section::Layout, "|", "Open/close a second playlist in a split view", &[Action::ToggleSplit]),
+    bind(Section::Layout, ">", "Copy the track (or marked tracks) to the split view's playlist", &[Action::CopyToSplit]),
+    bind(Section::Layout, "<", "Copy the split view's track to the open playlist", &[Action::CopyFromSplit]),
+    bind(Section::Layout, "Ctrl+←/→", "Shrink/grow the library column", &[]),
+    bind(Section::Layout, "Ctrl+↑/↓", "Shrink/grow the library section", &[]),
+    bind(Section::General, "w / W", "Open the selected track or entry in the browser / Spotify app", &[Action::OpenInBrowser, Action::OpenInApp]),
+    bind(Section::General, "u", "Undo the last like, playlist change or deletion", &[Action::Undo]),
+    bind(Section::General, "D", "Show/hide recent requests to Spotify and how long they took", &[Action::ToggleDebug]),
+    bind(Section::General, "?", "Show/close this help", &[Action::Help]),
+    bind(Section::General, "q", "Quit", &[Action::Quit]),
+];
+
+/// Bindings whose keys or description match the filter, all of them if it's empty
+pub fn matching(filter: &str) -> impl Iterator<Item = &'static Binding> + '_ {
+    BINDINGS.iter().filter(move |binding| {
+        filter.is_empty()
+            || fuzzy::fuzzy_score(binding.description, filter).is_some()
+            || fuzzy::fuzzy_score(binding.keys, filter).is_some()
+    })
+}
+
+/// A key and the modifiers that matter for telling keys apart
+pub type Key = (KeyCode, KeyModifiers);
+
+/// Keys the config's [keys] table makes act like other keys
+#[derive(Debug, Clone, Default)]
+pub struct Remaps(HashMap<Key, Key>);
+
+impl Remaps {
+    /// Parses a table like `J = "down"`, or lists the keys it can't make sense of
+    pub fn parse(table: &BTreeMap<String, String>) -> Result<Self, Vec<String>> {
+        let mut remaps = HashMap::new();
+        let mut problems = Vec::new();
+        for (from, to) in table {
+            match (parse_key(from), parse_key(to)) {
+                (Some(from), Some(to)) => {
+                    remaps.insert(from, to);
+                }
+                (from_key, _) => {
+                    let bad = if from_key.is_none() { from } else { to };
+                    problems.push(format!(
+                        "keys: \"{}\" isn't a key, use a character or a name like \"ctrl-n\", \"enter\" or \"pagedown\"",
+                        bad
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(Self(remaps))
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// The key a pressed key stands for, itself unless the config remaps it
+    pub fn apply(&self, key: KeyEvent) -> KeyEvent {
+        match self.0.get(&normalize(key.code, key.modifiers)) {
+            Some(&(code, modifiers)) => KeyEvent::new(code, modifiers),
+            None => key,
+        }
+    }
+
+    /// Keys remapped onto a key with one of `actions` in KEYMAP
+    pub fn keys_for(&self, actions: &[Action]) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .0
+            .iter()
+            .filter(|(_, to)| {
+                KEYMAP.iter().any(|(key, action)| {
+                    actions.contains(action) && parse_key(key).as_ref() == Some(*to)
+                })
+            })
+            .map(|(from, _)| key_name(*from))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Remapped keys and what they stand for, for the help popup
+    pub fn describe(&self) -> Vec<(String, String)> {
+        let mut remaps: Vec<(String, String)> = self
+            .0
+            .iter()
+            .map(|(from, to)| (key_name(*from), key_name(*to)))
+            .collect();
+        remaps.sort();
+        remaps
+    }
+}
+
+/// Something a key asks the app to do, whichever key the keymap ties to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Search,
+    PlaybackControls,
+    DevicePicker,
+    GenrePicker,
+    ToggleDebug,
+    Help,
+    Filter,
+    /// Clears marks, a filter or the search, whichever is there
+    Escape,
+    Up,
+    Down,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    Top,
+    /// The bottom row, or the row a count names
+    Bottom,
+    NextPane,
+    FocusLibrary,
+    FocusTracks,
+    PreviousTab,
+    NextTab,
+    /// Expands the playlist's folder in the library, seeks forward elsewhere
+    Right,
+    /// Collapses the playlist's folder in the library, seeks back elsewhere
+    Left,
+    /// Moves the playing track this many milliseconds forward, or back when negative
+    Seek(i64),
+    /// Turns the volume up this many percent, or down when negative
+    Volume(i32),
+    /// Sets the volume to this percent
+    SetVolume(u32),
+    /// Plays the selected track or opens the selected entry
+    Select,
+    Queue,
+    /// Queues the library entry's tracks in a seeded shuffle of our own
+    ShuffleQueue,
+    TrackMenu,
+    QueueRestOfAlbum,
+    Radio,
+    ArtistRadio,
+    ToggleMark,
+    VisualMode,
+    CopyLink,
+    CopyUri,
+    OpenInBrowser,
+    OpenInApp,
+    CycleSort,
+    FlipSort,
+    GroupByAlbum,
+    TogglePin,
+    Merge,
+    Compare,
+    Stats,
+    TypeAhead,
+    SetMark,
+    JumpToMark,
+    Delete,
+    Back,
+    ToggleSplit,
+    CopyToSplit,
+    CopyFromSplit,
+    Undo,
+    Backup,
+    SaveQueue,
+}
+
+impl Action {
+    /// Whether the action works on search results too. The rest act on the library, which
+    /// search covers up
+    fn works_in_search(self) -> bool {
+        !matches!(
+            self,
+            Action::Quit
+                | Action::Search
+                | Action::PlaybackControls
+                | Action::DevicePicker
+                | Action::GenrePicker
+                | Action::ToggleDebug
+                | Action::Help
+                | Action::Filter
+                | Action::NextPane
+                | Action::FocusLibrary
+                | Action::FocusTracks
+                | Action::PreviousTab
+                | Action::NextTab
+                | Action::ShuffleQueue
+                | Action::TogglePin
+                | Action::Merge
+                | Action::Compare
+                | Action::Stats
+                | Action::SetMark
+                | Action::JumpToMark
+                | Action::Delete
+                | Action::Back
+                | Action::ToggleSplit
+                | Action::CopyToSplit
+                | Action::CopyFromSplit
+                | Action::Backup
+        )
+    }
+}
+
+/// Where a key is pressed, which decides the actions it can stand for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Browse,
+    Search,
+}
+
+/// How far ←/→ seek, and with Shift
+pub const SEEK_STEP_MS: i64 = 10_000;
+const LONG_SEEK_STEP_MS: i64 = 60_000;
+/// How far the volume keys turn the volume, in percent
+pub const VOLUME_STEP: i32 = 5;
+
+/// The keys for each action. Which pane has the focus is left to the app, as some actions do
+/// something different in each
+#[rustfmt::skip]
+const KEYMAP: &[(&str, Action)] = &[
+    ("q", Action::Quit),
+    ("s", Action::Search),
+    ("space", Action::PlaybackControls),
+    ("d", Action::DevicePicker),
+    ("e", Action::GenrePicker),
+    ("D", Action::ToggleDebug),
+    ("?", Action::Help),
+    ("/", Action::Filter),
+    ("esc", Action::Escape),
+    ("up", Action::Up),
+    ("k", Action::Up),
+    ("ctrl-p", Action::Up),
+    ("down", Action::Down),
+    ("j", Action::Down),
+    ("ctrl-n", Action::Down),
+    ("ctrl-u", Action::HalfPageUp),
+    ("ctrl-d", Action::HalfPageDown),
+    ("pageup", Action::PageUp),
+    ("pagedown", Action::PageDown),
+    ("g", Action::Top),
+    ("home", Action::Top),
+    ("G", Action::Bottom),
+    ("end", Action::Bottom),
+    ("tab", Action::NextPane),
+    ("h", Action::FocusLibrary),
+    ("l", Action::FocusTracks),
+    ("[", Action::PreviousTab),
+    ("]", Action::NextTab),
+    ("right", Action::Right),
+    ("left", Action::Left),
+    ("alt-l", Action::Seek(SEEK_STEP_MS)),
+    ("alt-h", Action::Seek(-SEEK_STEP_MS)),
+    ("shift-right", Action::Seek(LONG_SEEK_STEP_MS)),
+    ("shift-left", Action::Seek(-LONG_SEEK_STEP_MS)),
+    ("alt-L", Action::Seek(LONG_SEEK_STEP_MS)),
+    ("alt-H", Action::Seek(-LONG_SEEK_STEP_MS)),
+    ("alt-+", Action::Volume(VOLUME_STEP)),
+    ("alt-=", Action::Volume(VOLUME_STEP)),
+    ("alt--", Action::Volume(-VOLUME_STEP)),
+    ("alt-0", Action::SetVolume(0)),
+    ("alt-1", Action::SetVolume(10)),
+    ("alt-2", Action::SetVolume(20)),
+    ("alt-3", Action::SetVolume(30)),
+    ("alt-4", Action::SetVolume(40)),
+    ("alt-5", Action::SetVolume(50)),
+    ("alt-6", Action::SetVolume(60)),
+    ("alt-7", Action::SetVolume(70)),
+    ("alt-8", Action::SetVolume(80)),
+    ("alt-9", Action::SetVolume(90)),
+    ("enter", Action::Select),
+    ("+", Action::Queue),
+    ("S", Action::ShuffleQueue),
+    ("a", Action::TrackMenu),
+    ("A", Action::QueueRestOfAlbum),
+    ("R", Action::Radio),
+    ("ctrl-r", Action::ArtistRadio),
+    ("v", Action::ToggleMark),
+    ("V", Action::VisualMode),
+    ("y", Action::CopyLink),
+    ("Y", Action::CopyUri),
+    ("w", Action::OpenInBrowser),
+    ("W", Action::OpenInApp),
+    ("o", Action::CycleSort),
+    ("O", Action::FlipSort),
+    ("b", Action::GroupByAlbum),
+    ("p", Action::TogglePin),
+    ("M", Action::Merge),
+    ("=", Action::Compare),
+    ("i", Action::Stats),
+    ("f", Action::TypeAhead),
+    ("m", Action::SetMark),
+    ("'", Action::JumpToMark),
+    ("delete", Action::Delete),
+    ("backspace", Action::Back),
+    ("|", Action::ToggleSplit),
+    (">", Action::CopyToSplit),
+    ("<", Action::CopyFromSplit),
+    ("u", Action::Undo),
+    ("B", Action::Backup),
+    ("Q", Action::SaveQueue),
+];
+
+/// Turns keys into actions
+#[derive(Debug, Clone)]
+pub struct Keymap(HashMap<Key, Action>);
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self(
+            KEYMAP
+                .iter()
+                .map(|(key, action)| (parse_key(key).expect("KEYMAP holds valid keys"), *action))
+                .collect(),
+        )
+    }
+}
+
+impl Keymap {
+    /// The action a key stands for. A key held with Ctrl or Alt that has nothing of its own
+    /// acts like the key alone
+    pub fn resolve(&self, context: Context, key: KeyEvent) -> Option<Action> {
+        let (code, modifiers) = normalize(key.code, key.modifiers);
+        let action = self
+            .0
+            .get(&(code, modifiers))
+            .or_else(|| self.0.get(&(code, KeyModifiers::NONE)))
+            .copied()?;
+        match context {
+            Context::Search if !action.works_in_search() => None,
+            _ => Some(action),
+        }
+    }
+}
+
+/// Terminals differ on whether capitals come with Shift, so characters go without it. Shift
+/// only counts on ←/→, where it takes a longer step
+pub fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    let mut kept = KeyModifiers::CONTROL | KeyModifiers::ALT;
+    if matches!(code, KeyCode::Left | KeyCode::Right) {
+        kept |= KeyModifiers::SHIFT;
+    }
+    (code, modifiers & kept)
+}
+
+/// Names for keys that aren't a single character
+const KEY_NAMES: [(&str, KeyCode); 15] = [
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("space", KeyCode::Char(' ')),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// A key as written in the config, like "J", "ctrl-n", "alt-enter" or "pagedown"
+pub fn parse_key(name: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    loop {
+        if let Some(after) = rest.strip_prefix("ctrl-") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("alt-") {
+            modifiers |= KeyModifiers::ALT;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("shift-") {
+            modifiers |= KeyModifiers::SHIFT;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(key, _)| rest.eq_ignore_ascii_case(key))
+            .map(|(_, code)| *code)?,
+    };
+    Some(normalize(code, modifiers))
+}
+
+pub fn key_name((code, modifiers): Key) -> String {
+    let key = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(_, named)| *named == code)
+            .map_or_else(|| format!("{:?}", code), |(name, _)| name.to_string()),
+    };
+    let mut name = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        name.push_str("ctrl-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        name.push_str("alt-");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        name.push_str("shift-");
+    }
+    name + &key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_lists_every_action() {
+        for (key, action) in KEYMAP {
+            assert!(
+                BINDINGS
+                    .iter()
+                    .any(|binding| binding.actions.contains(action)),
+                "{} ({:?}) is missing from BINDINGS",
+                key,
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn help_shows_remapped_keys_beside_their_action() {
+        let table = BTreeMap::from([("J".to_string(), "down".to_string())]);
+        let remaps = Remaps::parse(&table).unwrap();
+        let keys = |description| {
+            let binding = BINDINGS.iter().find(|b| b.description == description);
+            binding.unwrap().keys(&remaps)
+        };
+
+        assert_eq!(
+            keys("Move up/down in the focused pane"),
+            "↑/↓, j/k, Ctrl+P/N, J"
+        );
+        assert_eq!(keys("Quit"), "q");
+    }
+
+    #[test]
+    fn keys_resolve_to_actions_for_where_they_are_pressed() {
+        let keymap = Keymap::default();
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+        assert_eq!(
+            keymap.resolve(Context::Browse, key(KeyCode::Char('d'), KeyModifiers::NONE)),
+            Some(Action::DevicePicker)
+        );
+        assert_eq!(
+            keymap.resolve(
+                Context::Browse,
+                key(KeyCode::Char('d'), KeyModifiers::CONTROL)
+            ),
+            Some(Action::HalfPageDown)
+        );
+        // Shift comes with capitals on some terminals and not others
+        assert_eq!(
+            keymap.resolve(
+                Context::Browse,
+                key(KeyCode::Char('G'), KeyModifiers::SHIFT)
+            ),
+            Some(Action::Bottom)
+        );
+        // Nothing is bound to Ctrl+Q, so it's q
+        assert_eq!(
+            keymap.resolve(
+                Context::Browse,
+                key(KeyCode::Char('q'), KeyModifiers::CONTROL)
+            ),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            keymap.resolve(Context::Search, key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(
+            keymap.resolve(Context::Search, key(KeyCode::Char('j'), KeyModifiers::NONE)),
+            Some(Action::Down)
+        );
+        // Shift is kept on arrows, it's the longer seek
+        assert_eq!(
+            keymap.resolve(Context::Browse, key(KeyCode::Right, KeyModifiers::SHIFT)),
+            Some(Action::Seek(60_000))
+        );
+        assert_eq!(
+            keymap.resolve(Context::Browse, key(KeyCode::Char('h'), KeyModifiers::ALT)),
+            Some(Action::Seek(-10_000))
+        );
+    }
+}
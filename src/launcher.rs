@@ -0,0 +1,35 @@
+use anyhow::{anyhow, Result};
+use std::process::{Command, Stdio};
+
+/// Opens a web page in the default browser
+pub fn open_in_browser(url: &str) -> Result<()> {
+    webbrowser::open(url)?;
+    Ok(())
+}
+
+/// Hands a spotify: URI to whatever the system has registered for it, normally the desktop
+/// client. Fails if nothing is registered, so the caller can fall back to the browser
+pub fn open_in_app(uri: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    // The opener's output would end up on top of the UI
+    let status = command
+        .arg(uri)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("No app is registered to open {}", uri))
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+
+use crate::config::DeviceConfig;
+
+/// librespot reads any of its options from `LIBRESPOT_` variables. The token goes through one
+/// of those, since the command line can be read by every user on the machine with ps
+const ACCESS_TOKEN_VAR: &str = "LIBRESPOT_ACCESS_TOKEN";
+
+/// A librespot process running as a Spotify Connect device, so music plays where spotitui runs
+/// without another Spotify app open. librespot is a program of its own, installed separately
+/// and found through device.command. It's stopped when this is dropped
+pub struct LocalDevice {
+    _child: Child,
+}
+
+impl LocalDevice {
+    /// Starts librespot, signing it in with `access_token` when it has no saved credentials in
+    /// `cache_dir` to sign in with, and connecting through `proxy` if there is one
+    pub fn start(
+        config: &DeviceConfig,
+        access_token: Option<&str>,
+        cache_dir: Option<PathBuf>,
+        proxy: Option<&str>,
+    ) -> Result<Self> {
+        let mut command = Command::new(&config.command);
+        command.args(args(config, cache_dir.as_deref(), proxy));
+        if let Some(token) = access_token {
+            command.env(ACCESS_TOKEN_VAR, token);
+        }
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "Couldn't start {}, is librespot installed? See device.command",
+                    config.command
+                )
+            })?;
+
+        // librespot logs to stderr, which would draw over the interface
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("librespot: {}", line);
+                }
+            });
+        }
+        tracing::info!(name = %config.name, "Started librespot");
+        Ok(Self { _child: child })
+    }
+
+    /// Where librespot keeps its credentials, so only its first run needs a token
+    pub fn has_credentials(cache_dir: &Path) -> bool {
+        cache_dir.join("credentials.json").exists()
+    }
+}
+
+fn args(config: &DeviceConfig, cache_dir: Option<&Path>, proxy: Option<&str>) -> Vec<String> {
+    let mut args = vec![
+        "--name".to_string(),
+        config.name.clone(),
+        "--bitrate".to_string(),
+        config.bitrate.to_string(),
+        "--device-type".to_string(),
+        "computer".to_string(),
+        // Only this account should find it, not everyone on the network
+        "--disable-discovery".to_string(),
+    ];
+    if let Some(dir) = cache_dir {
+        args.extend(["--cache".to_string(), dir.display().to_string()]);
+    }
+    if let Some(proxy) = proxy {
+        args.extend(["--proxy".to_string(), proxy.to_string()]);
+    }
+    args.extend(config.args.iter().cloned());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_configured_args_come_last() {
+        let config = DeviceConfig {
+            args: vec!["--backend".to_string(), "pulseaudio".to_string()],
+            ..DeviceConfig::default()
+        };
+
+        let args = args(&config, Some(Path::new("/cache")), None);
+
+        assert_eq!(
+            args.join(" "),
+            "--name spotitui --bitrate 320 --device-type computer --disable-discovery \
+             --cache /cache --backend pulseaudio"
+        );
+    }
+}
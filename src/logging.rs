@@ -0,0 +1,27 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::EnvFilter;
+
+/// Levels the config can ask for, from least to most detailed
+pub const LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+/// Sends logs to a file in `dir` that starts afresh each day, since anything printed would end
+/// up on top of the interface. SPOTITUI_LOG overrides `level` with a full filter, like
+/// "spotitui=trace,reqwest=debug". Logs are written until the returned guard is dropped
+pub fn init(dir: &Path, level: &str) -> Result<WorkerGuard> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let appender = tracing_appender::rolling::daily(dir, "spotitui.log");
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter = EnvFilter::try_from_env("SPOTITUI_LOG")
+        .unwrap_or_else(|_| EnvFilter::new(format!("spotitui={}", level)));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(false)
+        .init();
+
+    Ok(guard)
+}
@@ -1,68 +1,155 @@
 use anyhow::Result;
-use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use crossterm::event::{self, Event, KeyEventKind};
 use ratatui::{backend::CrosstermBackend, Terminal};
+use spotitui_core::spotify::{self, SpotifyClient};
 use std::io;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::time::Duration;
 
 mod app;
-mod spotify;
+mod cache;
+mod cli;
+mod clipboard;
+mod config;
+mod export;
+mod fuzzy;
+mod headless;
+mod hooks;
+mod import;
+mod input;
+mod keymap;
+mod launcher;
+#[cfg(feature = "librespot")]
+mod librespot;
+mod logging;
+#[cfg(test)]
+mod mock;
+#[cfg(all(target_os = "linux", feature = "mpris"))]
+mod mpris;
+mod notifications;
+mod scripting;
+#[cfg(feature = "scrobble")]
+mod scrobble;
+mod shuffle;
+mod shutdown;
+mod state;
+mod stats;
+mod terminal;
+mod theme;
 mod ui;
 
 use app::App;
+use cli::Cli;
+use config::{Config, ConfigError};
+use hooks::Hooks;
+use scripting::Scripts;
+#[cfg(feature = "scrobble")]
+use scrobble::{ListenBrainz, RetryQueue};
+use shutdown::Shutdown;
+use terminal::TerminalGuard;
 
-static TERMINAL_INITIALIZED: AtomicBool = AtomicBool::new(false);
-
-fn restore_terminal() {
-    if TERMINAL_INITIALIZED.load(Ordering::SeqCst) {
-        let _ = disable_raw_mode();
-        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
-        TERMINAL_INITIALIZED.store(false, Ordering::SeqCst);
-    }
-}
+/// How long quitting waits on cache writes and listen submissions still going
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Set up signal handlers and panic hook
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-
-    ctrlc::set_handler(move || {
-        restore_terminal();
-        r.store(false, Ordering::SeqCst);
-        std::process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
+    // Parsed before touching the terminal so --help and mistakes print normally
+    let cli = Cli::parse();
+    if cli.json && cli.command.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--json only applies to subcommands",
+            )
+            .exit();
+    }
 
-    // Set up panic hook
+    // The terminal guard puts the terminal back before this runs
     std::panic::set_hook(Box::new(|panic_info| {
-        restore_terminal();
         eprintln!("Application panicked: {}", panic_info);
         std::process::exit(1);
     }));
 
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    TERMINAL_INITIALIZED.store(true, Ordering::SeqCst);
+    let mut config = match Config::load(cli.config.clone(), cli.profile.as_deref()) {
+        Ok(config) => config,
+        // Headless commands are likely run from a hotkey, where a screen would get in the way
+        Err(e) if cli.json => {
+            println!("{}", headless::error_json(e.into()));
+            std::process::exit(1);
+        }
+        Err(e) if cli.command.is_some() => {
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            show_config_error(&e)?;
+            // Left on the terminal too, for when the screen couldn't be shown
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    cli.apply(&mut config);
+
+    // Buffered log lines are written out when this is dropped, which exiting skips
+    let log_guard = match state::log_dir() {
+        Some(dir) if cli.debug || config.logging.enabled => {
+            Some(logging::init(&dir, &config.logging.level)?)
+        }
+        _ => None,
+    };
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        profile = ?config.profile,
+        tls = spotify::TLS_BACKEND,
+        "starting"
+    );
+
+    if let Some(command) = cli.command.clone() {
+        tracing::info!(?command, "running headless");
+        let result = headless::run(&spotify_client(&config)?, command, cli.json).await;
+        if let Err(e) = &result {
+            tracing::error!("Headless command failed: {:#}", e);
+        }
+        drop(log_guard);
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+        match result {
+            Ok(None) => {}
+            Ok(Some(output)) => println!("{}", output),
+            Err(e) if cli.json => {
+                println!("{}", headless::error_json(e));
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("spotitui: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-    // Run the application with proper error handling
-    let app_result = run_app(&mut terminal).await;
+    let shutdown = Shutdown::default();
+    shutdown.on_signals();
+    let terminal_guard = TerminalGuard::enter(config.features.mouse)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
 
-    // Restore terminal
-    restore_terminal();
+    let app_result = run_app(&mut terminal, config, &cli, &shutdown).await;
+
+    // The shell comes back first, then what's left to write out gets a moment
+    drop(terminal_guard);
+    shutdown.finish(SHUTDOWN_TIMEOUT).await;
 
     // Handle the result
+    if let Err(e) = &app_result {
+        tracing::error!("Application error: {:#}", e);
+    }
+    drop(log_guard);
     match app_result {
         Ok(_) => Ok(()),
+        Err(e) if cli.debug => {
+            eprintln!("Application error: {:?}", e);
+            std::process::exit(1);
+        }
         Err(e) => {
             eprintln!("Application error: {}", e);
             std::process::exit(1);
@@ -70,16 +157,81 @@ async fn main() -> Result<()> {
     }
 }
 
-async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
-    let mut app = match App::new().await {
-        Ok(app) => app,
-        Err(e) => {
-            restore_terminal();
-            eprintln!("Failed to initialize application: {}", e);
-            eprintln!("Make sure you have set the SPOTIFY_CLIENT_ID and SPOTIFY_CLIENT_SECRET environment variables.");
-            std::process::exit(1);
+/// Shows what's wrong with the config until a key is pressed
+fn show_config_error(error: &ConfigError) -> Result<()> {
+    let _terminal_guard = TerminalGuard::enter(false)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    loop {
+        terminal.draw(|f| ui::draw_config_error(f, error))?;
+        // Anything else, like a resize, just redraws
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                break;
+            }
         }
+    }
+    Ok(())
+}
+
+/// A client for the config's Spotify app, keeping its token in the profile's token file
+fn spotify_client(config: &Config) -> Result<SpotifyClient> {
+    // Config::load has already checked there's a client id
+    let client_id = config
+        .credentials
+        .client_id()
+        .ok_or_else(|| anyhow::anyhow!("No Spotify client id"))?;
+    let client = SpotifyClient::new(client_id, config.credentials.client_secret())
+        .with_http_client(config.network.http_client()?)
+        .with_rate_limit(config.network.requests_per_second)
+        .with_playback_timeout(config.network.playback_timeout());
+    Ok(match state::token_path(config.profile.as_deref()) {
+        Some(path) => client.with_token_file(path),
+        None => client,
+    })
+}
+
+async fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    config: Config,
+    cli: &Cli,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    let spotify_client = spotify_client(&config)?;
+    let mut app = App::new(config, spotify_client);
+    app.shutdown = shutdown.clone();
+    app.notifications.detailed = cli.debug;
+    app.play_on_start = cli.play.clone();
+    app.import_on_start = cli.import.clone();
+    if let Some(path) = scripting::path() {
+        match Scripts::load(&path) {
+            Ok(scripts) => app.scripts = scripts,
+            Err(e) => app.notifications.error(format!("{:#}", e)),
+        }
+    }
+
+    // Stays on the bus until the app quits, a missing session bus only costs the media keys
+    #[cfg(all(target_os = "linux", feature = "mpris"))]
+    let _mpris = if app.config.features.mpris {
+        mpris::start(app.command_sender(), app.watch_player())
+            .await
+            .map_err(|e| tracing::warn!("Failed to register with MPRIS: {:#}", e))
+            .ok()
+    } else {
+        None
     };
 
+    #[cfg(feature = "scrobble")]
+    if let Some(token) = app.config.listenbrainz.token() {
+        let listenbrainz = ListenBrainz::new(token, app.config.listenbrainz.api_url.clone())
+            .with_client(app.config.network.http_client()?);
+        let queue = RetryQueue::load(state::listens_path(app.config.profile.as_deref()));
+        scrobble::spawn(listenbrainz, app.watch_player(), queue, shutdown);
+    }
+
+    let hooks = Hooks::new(app.config.hooks.clone());
+    if hooks.follows_player() {
+        hooks::spawn(hooks, app.watch_player(), shutdown);
+    }
+
     app.run(terminal).await
 }
@@ -0,0 +1,381 @@
+use anyhow::{anyhow, Result};
+use spotitui_core::api::SpotifyApi;
+use spotitui_core::spotify::{
+    Album, Artist, AudioFeatures, CurrentlyPlaying, Device, Playlist, PlaylistTracks, Queue,
+    RequestRecord, Seeds, Show, SpotifyError, Track, TrackPage,
+};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// What the mock answers with and the calls it has seen, shared by its clones so a test can
+/// check on requests made from background tasks
+#[derive(Default)]
+pub struct MockState {
+    pub playlists: Vec<Playlist>,
+    /// Tracks by playlist id, "liked" being Liked Songs
+    pub playlist_tracks: HashMap<String, Vec<Track>>,
+    pub search_results: Vec<Track>,
+    /// Answers to the next get_recommendations calls, one per call, none once they run out
+    pub recommendations: VecDeque<Vec<Track>>,
+    pub audio_features: Vec<AudioFeatures>,
+    pub player: Option<CurrentlyPlaying>,
+    pub queue: Option<Queue>,
+    /// Splits track listings and search results into pages this long when set
+    pub page_size: Option<usize>,
+    pub devices: Vec<Device>,
+    /// Errors returned by the next play_tracks calls, one per call
+    pub playback_errors: VecDeque<anyhow::Error>,
+    /// Errors returned by the next add_to_queue calls, one per call
+    pub queue_errors: VecDeque<anyhow::Error>,
+    /// Fails every call with a network error while set
+    pub offline: bool,
+    /// Every call made so far, as the method name followed by its arguments
+    pub calls: Vec<String>,
+}
+
+/// A SpotifyApi answering from memory, for testing the app without the network
+#[derive(Clone, Default)]
+pub struct MockSpotify {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockSpotify {
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Calls whose method name is `method`, with their arguments
+    pub fn calls_to(&self, method: &str) -> Vec<String> {
+        let prefix = format!("{} ", method);
+        self.state()
+            .calls
+            .iter()
+            .filter(|call| *call == method || call.starts_with(&prefix))
+            .map(|call| call.trim_start_matches(&prefix).to_string())
+            .collect()
+    }
+
+    fn record(&self, call: String) -> MutexGuard<'_, MockState> {
+        let mut state = self.state();
+        state.calls.push(call);
+        state
+    }
+
+    fn reply<T: Send + 'static>(
+        &self,
+        call: String,
+        answer: impl FnOnce(&mut MockState) -> Result<T>,
+    ) -> impl Future<Output = Result<T>> + Send {
+        let mut state = self.record(call);
+        let result = if state.offline {
+            Err(SpotifyError::Network("offline".to_string()).into())
+        } else {
+            answer(&mut state)
+        };
+        drop(state);
+        async move { result }
+    }
+}
+
+/// The page of `tracks` starting at `offset`, all of them when `size` isn't set
+fn page(tracks: &[Track], offset: usize, size: Option<usize>) -> TrackPage {
+    let end = size.map_or(tracks.len(), |size| (offset + size).min(tracks.len()));
+    let offset = offset.min(end);
+    TrackPage {
+        tracks: tracks[offset..end].to_vec(),
+        next: (end < tracks.len()).then_some(end),
+        total: tracks.len(),
+    }
+}
+
+pub fn track(id: &str, name: &str) -> Track {
+    Track {
+        id: id.to_string(),
+        name: name.to_string(),
+        artists: vec![Artist {
+            id: "artist".to_string(),
+            name: "Artist".to_string(),
+        }],
+        album: Album {
+            id: "album".to_string(),
+            name: "Album".to_string(),
+            images: Vec::new(),
+            artists: Vec::new(),
+            release_date: None,
+        },
+        duration_ms: 180_000,
+        uri: format!("spotify:track:{}", id),
+        added_at: None,
+        explicit: false,
+        popularity: None,
+    }
+}
+
+pub fn playlist(id: &str, name: &str, total: u32) -> Playlist {
+    Playlist {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: None,
+        owner: None,
+        tracks: PlaylistTracks { total },
+    }
+}
+
+impl SpotifyApi for MockSpotify {
+    fn refresh_access_token(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("refresh_access_token".to_string(), |_| Ok(()))
+    }
+
+    fn authenticate(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("authenticate".to_string(), |_| Ok(()))
+    }
+
+    async fn access_token(&self) -> Option<String> {
+        Some("token".to_string())
+    }
+
+    fn recent_requests(&self) -> Vec<RequestRecord> {
+        Vec::new()
+    }
+
+    fn get_playlists(&self) -> impl Future<Output = Result<Vec<Playlist>>> + Send {
+        self.reply("get_playlists".to_string(), |state| {
+            Ok(state.playlists.clone())
+        })
+    }
+
+    fn get_saved_albums(&self) -> impl Future<Output = Result<Vec<Album>>> + Send {
+        self.reply("get_saved_albums".to_string(), |_| Ok(Vec::new()))
+    }
+
+    fn get_followed_artists(&self) -> impl Future<Output = Result<Vec<Artist>>> + Send {
+        self.reply("get_followed_artists".to_string(), |_| Ok(Vec::new()))
+    }
+
+    fn get_saved_shows(&self) -> impl Future<Output = Result<Vec<Show>>> + Send {
+        self.reply("get_saved_shows".to_string(), |_| Ok(Vec::new()))
+    }
+
+    fn get_show_episodes(&self, show: &Show) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        self.reply(format!("get_show_episodes {}", show.id), |_| Ok(Vec::new()))
+    }
+
+    fn get_playlist_followers(
+        &self,
+        playlist_id: &str,
+    ) -> impl Future<Output = Result<u32>> + Send {
+        self.reply(format!("get_playlist_followers {}", playlist_id), |_| Ok(0))
+    }
+
+    fn get_playlist_tracks(
+        &self,
+        playlist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        let id = playlist_id.to_string();
+        self.reply(format!("get_playlist_tracks {}", id), move |state| {
+            state
+                .playlist_tracks
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| anyhow!("No playlist {}", id))
+        })
+    }
+
+    fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send {
+        let id = playlist_id.to_string();
+        self.reply(
+            format!("get_playlist_tracks_page {} {}", id, offset),
+            move |state| {
+                let tracks = state
+                    .playlist_tracks
+                    .get(&id)
+                    .ok_or_else(|| anyhow!("No playlist {}", id))?;
+                Ok(page(tracks, offset, state.page_size))
+            },
+        )
+    }
+
+    fn get_album_tracks(&self, album: &Album) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        self.reply(format!("get_album_tracks {}", album.id), |_| Ok(Vec::new()))
+    }
+
+    fn get_artist_top_tracks(
+        &self,
+        artist_id: &str,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        self.reply(format!("get_artist_top_tracks {}", artist_id), |_| {
+            Ok(Vec::new())
+        })
+    }
+
+    fn get_recommendations(
+        &self,
+        seeds: &Seeds,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<Track>>> + Send {
+        let seeds = [&seeds.track_ids, &seeds.artist_ids, &seeds.genres].map(|ids| ids.join(","));
+        self.reply(
+            format!("get_recommendations {} {}", seeds.join(" "), limit),
+            |state| Ok(state.recommendations.pop_front().unwrap_or_default()),
+        )
+    }
+
+    fn get_genre_seeds(&self) -> impl Future<Output = Result<Vec<String>>> + Send {
+        self.reply("get_genre_seeds".to_string(), |_| {
+            Ok(["blues", "jazz", "rock"].map(String::from).to_vec())
+        })
+    }
+
+    fn get_audio_features(
+        &self,
+        track_ids: &[String],
+    ) -> impl Future<Output = Result<Vec<AudioFeatures>>> + Send {
+        self.reply(
+            format!("get_audio_features {}", track_ids.join(",")),
+            |state| {
+                Ok(state
+                    .audio_features
+                    .iter()
+                    .filter(|features| track_ids.contains(&features.id))
+                    .cloned()
+                    .collect())
+            },
+        )
+    }
+
+    fn search_tracks(
+        &self,
+        query: &str,
+        offset: usize,
+    ) -> impl Future<Output = Result<TrackPage>> + Send {
+        self.reply(
+            format!("search_tracks {} {}", query, offset),
+            move |state| Ok(page(&state.search_results, offset, state.page_size)),
+        )
+    }
+
+    fn search_playlists(&self, query: &str) -> impl Future<Output = Result<Vec<Playlist>>> + Send {
+        self.reply(format!("search_playlists {}", query), |_| Ok(Vec::new()))
+    }
+
+    fn play_tracks(&self, track_uris: &[&str]) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("play_tracks {}", track_uris.join(" ")), |state| {
+            state.playback_errors.pop_front().map_or(Ok(()), Err)
+        })
+    }
+
+    fn play_context(&self, context_uri: &str) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("play_context {}", context_uri), |_| Ok(()))
+    }
+
+    fn pause_playback(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("pause_playback".to_string(), |_| Ok(()))
+    }
+
+    fn resume_playback(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("resume_playback".to_string(), |_| Ok(()))
+    }
+
+    fn next_track(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("next_track".to_string(), |_| Ok(()))
+    }
+
+    fn previous_track(&self) -> impl Future<Output = Result<()>> + Send {
+        self.reply("previous_track".to_string(), |_| Ok(()))
+    }
+
+    fn seek(&self, position_ms: u64) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("seek {}", position_ms), |_| Ok(()))
+    }
+
+    fn set_volume(&self, volume_percent: u32) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("set_volume {}", volume_percent), |_| Ok(()))
+    }
+
+    fn get_devices(&self) -> impl Future<Output = Result<Vec<Device>>> + Send {
+        self.reply("get_devices".to_string(), |state| Ok(state.devices.clone()))
+    }
+
+    fn set_target_device(&self, device_id: &str) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("set_target_device {}", device_id), |_| Ok(()))
+    }
+
+    fn get_currently_playing(
+        &self,
+    ) -> impl Future<Output = Result<Option<CurrentlyPlaying>>> + Send {
+        self.reply("get_currently_playing".to_string(), |state| {
+            Ok(state.player.clone())
+        })
+    }
+
+    fn get_queue(&self) -> impl Future<Output = Result<Option<Queue>>> + Send {
+        self.reply("get_queue".to_string(), |state| Ok(state.queue.clone()))
+    }
+
+    fn add_to_queue(&self, track_uri: &str) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("add_to_queue {}", track_uri), |state| {
+            state.queue_errors.pop_front().map_or(Ok(()), Err)
+        })
+    }
+
+    fn add_tracks_to_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+        position: Option<usize>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let call = format!(
+            "add_tracks_to_playlist {} {:?} {}",
+            playlist_id,
+            position,
+            track_uris.join(" ")
+        );
+        self.reply(call, |_| Ok(()))
+    }
+
+    fn remove_tracks_from_playlist(
+        &self,
+        playlist_id: &str,
+        track_uris: &[String],
+    ) -> impl Future<Output = Result<()>> + Send {
+        let call = format!(
+            "remove_tracks_from_playlist {} {}",
+            playlist_id,
+            track_uris.join(" ")
+        );
+        self.reply(call, |_| Ok(()))
+    }
+
+    fn create_playlist(
+        &self,
+        name: &str,
+        _description: &str,
+    ) -> impl Future<Output = Result<Playlist>> + Send {
+        let created = playlist("created", name, 0);
+        self.reply(format!("create_playlist {}", name), |_| Ok(created))
+    }
+
+    fn unfollow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("unfollow_playlist {}", playlist_id), |_| Ok(()))
+    }
+
+    fn follow_playlist(&self, playlist_id: &str) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("follow_playlist {}", playlist_id), |_| Ok(()))
+    }
+
+    fn save_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send {
+        self.reply(format!("save_tracks {}", track_ids.join(" ")), |_| Ok(()))
+    }
+
+    fn remove_saved_tracks(&self, track_ids: &[String]) -> impl Future<Output = Result<()>> + Send {
+        self.reply(
+            format!("remove_saved_tracks {}", track_ids.join(" ")),
+            |_| Ok(()),
+        )
+    }
+}
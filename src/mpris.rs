@@ -0,0 +1,449 @@
+use anyhow::Result;
+use spotitui_core::spotify::{CurrentlyPlaying, Track};
+use spotitui_core::worker::Command;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use zbus::fdo::{RequestNameFlags, RequestNameReply};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{connection, interface, Connection};
+
+use crate::app::web_link;
+use crate::cli;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const NAME: &str = "org.mpris.MediaPlayer2.spotitui";
+/// How far the position can drift from where it should be before it counts as a seek
+const SEEK_THRESHOLD_MS: u64 = 2000;
+
+/// The player as last polled and when, so the position moves on between polls
+type Snapshot = Arc<Mutex<(Option<CurrentlyPlaying>, Instant)>>;
+
+/// spotitui on the session bus as an MPRIS player, so playerctl, desktop media applets and
+/// media keys can control it. It stays on the bus for as long as this is held
+pub struct Mpris {
+    _connection: Connection,
+}
+
+/// Registers the player, sending its controls to the worker through `commands` and following
+/// the polled player state through `player`
+pub async fn start(
+    commands: UnboundedSender<Command>,
+    mut player: watch::Receiver<Option<CurrentlyPlaying>>,
+) -> Result<Mpris> {
+    let snapshot: Snapshot = Arc::new(Mutex::new((player.borrow().clone(), Instant::now())));
+    let connection = connection::Builder::session()?
+        .serve_at(PATH, Root)?
+        .serve_at(
+            PATH,
+            Player {
+                commands,
+                snapshot: snapshot.clone(),
+            },
+        )?
+        .build()
+        .await?;
+
+    // Another instance already has the name, the spec has later ones add their own suffix
+    let flags = RequestNameFlags::DoNotQueue.into();
+    let reply = connection.request_name_with_flags(NAME, flags).await?;
+    if reply == RequestNameReply::Exists {
+        let name = format!("{}.instance{}", NAME, std::process::id());
+        connection.request_name_with_flags(name, flags).await?;
+    }
+
+    let server = connection.object_server().clone();
+    tokio::spawn(async move {
+        let Ok(interface) = server.interface::<_, Player>(PATH).await else {
+            return;
+        };
+        while player.changed().await.is_ok() {
+            let playing = player.borrow_and_update().clone();
+            let (previous, expected) = {
+                let mut snapshot = snapshot.lock().unwrap();
+                let expected = position_ms(&snapshot.0, snapshot.1);
+                let (previous, _) =
+                    std::mem::replace(&mut *snapshot, (playing.clone(), Instant::now()));
+                (previous, expected)
+            };
+
+            // Applets only redraw what they're told has changed, and the position is left to
+            // them to move on except when it jumps
+            let emitter = interface.signal_emitter();
+            let player = interface.get().await;
+            let track = |playing: &Option<CurrentlyPlaying>| {
+                playing
+                    .as_ref()
+                    .and_then(|p| p.item.as_ref())
+                    .map(|t| t.uri.clone())
+            };
+            let position = position_ms(&playing, Instant::now());
+            if track(&previous) != track(&playing) {
+                let _ = player.metadata_changed(emitter).await;
+            } else if expected.abs_diff(position) > SEEK_THRESHOLD_MS {
+                let _ = Player::seeked(emitter, position as i64 * 1000).await;
+            }
+            if status(&previous) != status(&playing) {
+                let _ = player.playback_status_changed(emitter).await;
+            }
+            let shuffle = |playing: &Option<CurrentlyPlaying>| {
+                playing
+                    .as_ref()
+                    .map(|p| (p.shuffle_state, p.repeat_state.clone()))
+            };
+            if shuffle(&previous) != shuffle(&playing) {
+                let _ = player.shuffle_changed(emitter).await;
+                let _ = player.loop_status_changed(emitter).await;
+            }
+        }
+    });
+
+    Ok(Mpris {
+        _connection: connection,
+    })
+}
+
+/// org.mpris.MediaPlayer2, describing the player itself
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    /// The interface lives in a terminal, which can't be brought forward from here
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "spotitui"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["spotify".to_string(), "https".to_string()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// org.mpris.MediaPlayer2.Player, playback controls and what's playing
+struct Player {
+    commands: UnboundedSender<Command>,
+    snapshot: Snapshot,
+}
+
+impl Player {
+    fn send(&self, command: Command) {
+        // The worker only stops when the app is shutting down
+        let _ = self.commands.send(command);
+    }
+
+    fn playing(&self) -> Option<CurrentlyPlaying> {
+        self.snapshot.lock().unwrap().0.clone()
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        self.send(Command::Resume);
+    }
+
+    fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    fn play_pause(&self) {
+        let playing = self.playing().is_some_and(|p| p.is_playing);
+        self.send(if playing {
+            Command::Pause
+        } else {
+            Command::Resume
+        });
+    }
+
+    /// Spotify has no stop, pausing is the closest
+    fn stop(&self) {
+        self.send(Command::Pause);
+    }
+
+    fn next(&self) {
+        self.send(Command::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Command::Previous);
+    }
+
+    /// Moves `offset` microseconds from where playback is
+    fn seek(&self, offset: i64) {
+        let (playing, fetched_at) = self.snapshot.lock().unwrap().clone();
+        let position = position_ms(&playing, fetched_at) as i64 * 1000 + offset;
+        if let Some(command) = seek_command(&playing, position) {
+            self.send(command);
+        }
+    }
+
+    /// Jumps to `position` microseconds into the track, if it's still the one playing
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let playing = self.playing();
+        let track = playing.as_ref().and_then(|p| p.item.as_ref());
+        let same_track = track
+            .and_then(track_path)
+            .is_some_and(|path| path.as_str() == track_id.as_str());
+        // Past the end is ignored here, unlike Seek
+        let in_track = track.is_some_and(|t| position <= t.duration_ms as i64 * 1000);
+        if same_track && in_track {
+            if let Some(command) = seek_command(&playing, position) {
+                self.send(command);
+            }
+        }
+    }
+
+    /// Plays a spotify: URI or open.spotify.com link
+    fn open_uri(&self, uri: &str) -> zbus::fdo::Result<()> {
+        let uri = cli::spotify_uri(uri).map_err(zbus::fdo::Error::InvalidArgs)?;
+        self.send(if cli::is_context(&uri) {
+            Command::PlayContext(uri)
+        } else {
+            Command::Play(vec![uri])
+        });
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &'static str {
+        status(&self.playing())
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &'static str {
+        match self.playing().as_ref().map(|p| p.repeat_state.as_str()) {
+            Some("track") => "Track",
+            Some("context") => "Playlist",
+            _ => "None",
+        }
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.playing().is_some_and(|p| p.shuffle_state)
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        metadata(self.playing().as_ref().and_then(|p| p.item.as_ref()))
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.playing()
+            .and_then(|p| p.device)
+            .and_then(|device| device.volume_percent)
+            .map_or(1.0, |volume| volume as f64 / 100.0)
+    }
+
+    /// In microseconds, players leave it out of PropertiesChanged and send Seeked instead
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        let (playing, fetched_at) = &*self.snapshot.lock().unwrap();
+        position_ms(playing, *fetched_at) as i64 * 1000
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+fn status(playing: &Option<CurrentlyPlaying>) -> &'static str {
+    match playing {
+        Some(playing) if playing.item.is_some() && playing.is_playing => "Playing",
+        Some(playing) if playing.item.is_some() => "Paused",
+        _ => "Stopped",
+    }
+}
+
+/// Where playback should be now, going by where it was when polled
+fn position_ms(playing: &Option<CurrentlyPlaying>, fetched_at: Instant) -> u64 {
+    let Some(playing) = playing else {
+        return 0;
+    };
+    let mut position = playing.progress_ms.unwrap_or(0);
+    if playing.is_playing {
+        position += fetched_at.elapsed().as_millis() as u64;
+    }
+    match &playing.item {
+        Some(track) => position.min(track.duration_ms as u64),
+        None => position,
+    }
+}
+
+/// What seeking to `position` microseconds into the playing track comes to. The spec has a
+/// position before the start go to the start, and one past the end skip to the next track
+fn seek_command(playing: &Option<CurrentlyPlaying>, position: i64) -> Option<Command> {
+    let track = playing.as_ref()?.item.as_ref()?;
+    let position_ms = position.max(0) / 1000;
+    Some(if position_ms >= track.duration_ms as i64 {
+        Command::Next
+    } else {
+        Command::Seek(position_ms as u64)
+    })
+}
+
+/// The track's mpris:trackid
+fn track_path(track: &Track) -> Option<ObjectPath<'static>> {
+    // Object paths only allow letters, digits and _, which Spotify ids keep to anyway
+    let id: String = track
+        .id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    ObjectPath::try_from(format!("/org/spotitui/track/{}", id)).ok()
+}
+
+/// The xesam and mpris fields applets show, see
+/// https://www.freedesktop.org/wiki/Specifications/mpris-spec/metadata/
+fn metadata(track: Option<&Track>) -> HashMap<String, OwnedValue> {
+    let mut metadata = HashMap::new();
+    let mut insert = |key: &str, value: Value| {
+        // Only file descriptors can fail to be owned
+        if let Ok(value) = OwnedValue::try_from(value) {
+            metadata.insert(key.to_string(), value);
+        }
+    };
+    let Some(track) = track else {
+        let no_track =
+            ObjectPath::from_static_str_unchecked("/org/mpris/MediaPlayer2/TrackList/NoTrack");
+        insert("mpris:trackid", no_track.into());
+        return metadata;
+    };
+
+    if let Some(path) = track_path(track) {
+        insert("mpris:trackid", path.into());
+    }
+    insert("mpris:length", (track.duration_ms as i64 * 1000).into());
+    insert("xesam:title", track.name.as_str().into());
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    insert("xesam:artist", artists.into());
+    insert("xesam:album", track.album.name.as_str().into());
+    // Spotify lists the largest first
+    if let Some(image) = track.album.images.first() {
+        insert("mpris:artUrl", image.url.as_str().into());
+    }
+    insert("xesam:url", web_link(&track.uri).into());
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn metadata_has_what_applets_show() {
+        let mut track = mock::track("6rqhFgbbKwnb9MLmUQDhG6", "First");
+        track.duration_ms = 61_000;
+
+        let metadata = metadata(Some(&track));
+
+        let text = |key: &str| String::try_from(metadata[key].try_clone().unwrap()).unwrap();
+        assert_eq!(text("xesam:title"), "First");
+        assert_eq!(text("xesam:album"), "Album");
+        assert_eq!(
+            text("xesam:url"),
+            "https://open.spotify.com/track/6rqhFgbbKwnb9MLmUQDhG6"
+        );
+        assert_eq!(
+            i64::try_from(&metadata["mpris:length"]).unwrap(),
+            61_000_000
+        );
+        let artists = Vec::<String>::try_from(metadata["xesam:artist"].try_clone().unwrap());
+        assert_eq!(artists.unwrap(), ["Artist"]);
+        assert!(!metadata.contains_key("mpris:artUrl"));
+    }
+
+    #[test]
+    fn seeking_stays_within_the_track() {
+        let mut track = mock::track("t1", "First");
+        track.duration_ms = 60_000;
+        let playing = Some(CurrentlyPlaying {
+            item: Some(track),
+            is_playing: true,
+            progress_ms: Some(0),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        });
+
+        let seek = |position| seek_command(&playing, position);
+        assert!(matches!(seek(30_000_000), Some(Command::Seek(30_000))));
+        assert!(matches!(seek(-5_000_000), Some(Command::Seek(0))));
+        assert!(matches!(seek(61_000_000), Some(Command::Next)));
+        assert!(seek_command(&None, 0).is_none());
+    }
+}
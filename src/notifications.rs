@@ -0,0 +1,94 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+/// Most toasts shown at once, older ones are dropped first
+const MAX_TOASTS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Success,
+    Error,
+}
+
+impl Severity {
+    /// How long a toast stays on screen, errors linger so they can be read
+    fn lifetime(&self) -> Duration {
+        match self {
+            Severity::Info | Severity::Success => Duration::from_secs(3),
+            Severity::Error => Duration::from_secs(6),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub severity: Severity,
+    pub message: String,
+    created_at: Instant,
+}
+
+/// Short-lived messages stacked in a corner of the screen that don't block input
+#[derive(Debug, Default)]
+pub struct Notifications {
+    toasts: Vec<Toast>,
+    /// The most recent error, kept for the status bar after its toast expires
+    pub last_error: Option<String>,
+    /// Shows errors with their causes, as asked for with --debug
+    pub detailed: bool,
+}
+
+impl Notifications {
+    pub fn push(&mut self, severity: Severity, message: impl Into<String>) {
+        self.toasts.push(Toast {
+            severity,
+            message: message.into(),
+            created_at: Instant::now(),
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Severity::Info, message);
+    }
+
+    pub fn success(&mut self, message: impl Into<String>) {
+        self.push(Severity::Success, message);
+    }
+
+    pub fn error(&mut self, error: impl Display) {
+        let message = self.describe(&error);
+        tracing::warn!("{}", message);
+        self.last_error = Some(message.clone());
+        self.push(Severity::Error, message);
+    }
+
+    /// Remembers an error for the status bar without showing a toast, for background polls
+    pub fn record_error(&mut self, error: impl Display) {
+        let message = self.describe(&error);
+        tracing::debug!("{}", message);
+        self.last_error = Some(message);
+    }
+
+    /// The alternate form of an anyhow error lists its causes after it
+    fn describe(&self, error: &impl Display) -> String {
+        if self.detailed {
+            format!("{:#}", error)
+        } else {
+            error.to_string()
+        }
+    }
+
+    /// Drops toasts that have been shown for their full lifetime
+    pub fn expire(&mut self) {
+        self.toasts
+            .retain(|toast| toast.created_at.elapsed() < toast.severity.lifetime());
+    }
+
+    /// Toasts from oldest to newest
+    pub fn toasts(&self) -> &[Toast] {
+        &self.toasts
+    }
+}
@@ -0,0 +1,396 @@
+#[cfg(feature = "scripting")]
+use anyhow::Context;
+use anyhow::{anyhow, Result};
+use crossterm::event::KeyEvent;
+#[cfg(feature = "scripting")]
+use mlua::{Function, Lua, RegistryKey, Table};
+use spotitui_core::spotify::Track;
+#[cfg(feature = "scripting")]
+use std::collections::HashMap;
+#[cfg(feature = "scripting")]
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "scripting")]
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+#[cfg(feature = "scripting")]
+use crate::keymap::{self, Key};
+
+/// Something a script asked for, carried out by the app once the script returns
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+pub enum ScriptAction {
+    /// Tracks by URI, or an album, playlist, artist or show
+    Play(String),
+    Queue(String),
+    Pause,
+    Resume,
+    Next,
+    Previous,
+    Notify(String),
+}
+
+/// A command defined by spotitui.command, run through the key bound to it
+#[cfg(feature = "scripting")]
+struct ScriptCommand {
+    name: String,
+    description: String,
+    function: RegistryKey,
+}
+
+/// What init.lua registered, shared with the functions it calls
+#[cfg(feature = "scripting")]
+#[derive(Default)]
+struct Registry {
+    commands: Vec<ScriptCommand>,
+    bindings: Vec<(String, String)>,
+    format_track: Option<RegistryKey>,
+    actions: Vec<ScriptAction>,
+}
+
+/// The user's init.lua, with the commands, key bindings and track formatting it sets up.
+/// Empty when there's no script
+#[cfg(feature = "scripting")]
+#[derive(Default)]
+pub struct Scripts {
+    lua: Option<Lua>,
+    registry: Arc<Mutex<Registry>>,
+    keys: HashMap<Key, String>,
+    /// Formatted titles by track id, since the tracks pane is drawn many times a second
+    titles: Mutex<HashMap<String, Option<String>>>,
+}
+
+/// Without the scripting feature there's no Lua to run init.lua with
+#[cfg(not(feature = "scripting"))]
+#[derive(Default)]
+pub struct Scripts {}
+
+/// What a command's function is handed
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+pub struct CommandContext<'a> {
+    /// The selected track, or the marked ones
+    pub tracks: &'a [Track],
+    pub playing: Option<&'a Track>,
+}
+
+#[cfg(feature = "scripting")]
+impl Scripts {
+    /// Runs the script at `path`, nothing if there's no file there
+    pub fn load(path: &Path) -> Result<Self> {
+        let Ok(source) = fs::read_to_string(path) else {
+            return Ok(Self::default());
+        };
+        Self::from_source(&source, &path.display().to_string())
+    }
+
+    fn from_source(source: &str, name: &str) -> Result<Self> {
+        let lua = Lua::new();
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        install_api(&lua, &registry).map_err(lua_error)?;
+        lua.load(source)
+            .set_name(name)
+            .exec()
+            .map_err(lua_error)
+            .with_context(|| format!("Couldn't run {}", name))?;
+
+        let mut keys = HashMap::new();
+        {
+            let mut registry = registry.lock().unwrap();
+            // Actions taken while loading have nothing to act on yet
+            registry.actions.clear();
+            for (key, command) in &registry.bindings {
+                let parsed = keymap::parse_key(key)
+                    .ok_or_else(|| anyhow!("{}: \"{}\" isn't a key", name, key))?;
+                if !registry.commands.iter().any(|c| &c.name == command) {
+                    return Err(anyhow!("{}: no command named \"{}\"", name, command));
+                }
+                keys.insert(parsed, command.clone());
+            }
+        }
+        Ok(Self {
+            lua: Some(lua),
+            registry,
+            keys,
+            titles: Mutex::default(),
+        })
+    }
+
+    /// The command bound to a key, if a script bound one
+    pub fn command_for(&self, key: KeyEvent) -> Option<&str> {
+        self.keys
+            .get(&keymap::normalize(key.code, key.modifiers))
+            .map(String::as_str)
+    }
+
+    /// Keys and the descriptions of the commands they run, for the help popup
+    pub fn describe(&self) -> Vec<(String, String)> {
+        let registry = self.registry.lock().unwrap();
+        let mut bindings: Vec<(String, String)> = self
+            .keys
+            .iter()
+            .map(|(key, command)| {
+                let description = registry
+                    .commands
+                    .iter()
+                    .find(|c| &c.name == command)
+                    .map_or_else(|| command.clone(), |c| c.description.clone());
+                (keymap::key_name(*key), description)
+            })
+            .collect();
+        bindings.sort();
+        bindings
+    }
+
+    /// Calls a command's function, returning what it asked the app to do
+    pub fn run(&self, command: &str, context: CommandContext) -> Result<Vec<ScriptAction>> {
+        let Some(lua) = &self.lua else {
+            return Ok(Vec::new());
+        };
+        let function: Function = {
+            let registry = self.registry.lock().unwrap();
+            let command = registry
+                .commands
+                .iter()
+                .find(|c| c.name == command)
+                .ok_or_else(|| anyhow!("No script command named {}", command))?;
+            lua.registry_value(&command.function).map_err(lua_error)?
+        };
+
+        let table = lua.create_table().map_err(lua_error)?;
+        let tracks = lua
+            .create_sequence_from(
+                context
+                    .tracks
+                    .iter()
+                    .map(|track| track_table(lua, track))
+                    .collect::<mlua::Result<Vec<_>>>()
+                    .map_err(lua_error)?,
+            )
+            .map_err(lua_error)?;
+        let track = tracks.get::<_, Option<Table>>(1).map_err(lua_error)?;
+        table.set("track", track).map_err(lua_error)?;
+        table.set("tracks", tracks).map_err(lua_error)?;
+        if let Some(playing) = context.playing {
+            let playing = track_table(lua, playing).map_err(lua_error)?;
+            table.set("playing", playing).map_err(lua_error)?;
+        }
+
+        let result = function
+            .call::<_, ()>(table)
+            .map_err(lua_error)
+            .with_context(|| format!("Script command {} failed", command));
+        let actions = std::mem::take(&mut self.registry.lock().unwrap().actions);
+        result.map(|_| actions)
+    }
+
+    /// The title spotitui.format_track gives the track, if a script set it
+    pub fn format_track(&self, track: &Track) -> Option<String> {
+        let lua = self.lua.as_ref()?;
+        let mut titles = self.titles.lock().unwrap();
+        if let Some(title) = titles.get(&track.id) {
+            return title.clone();
+        }
+        // Let go of before calling in, as the formatter can call back into the registry
+        let format = {
+            let registry = self.registry.lock().unwrap();
+            lua.registry_value::<Function>(registry.format_track.as_ref()?)
+        };
+        let title =
+            format.and_then(|format| format.call::<_, Option<String>>(track_table(lua, track)?));
+        // A broken formatter shows the plain title, and is only logged once per track
+        let title = title
+            .map_err(|e| tracing::warn!("format_track failed: {}", e))
+            .ok()
+            .flatten();
+        titles.insert(track.id.clone(), title.clone());
+        title
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+impl Scripts {
+    /// Nothing if there's no file at `path`, and an error saying why it's skipped if there is
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            return Err(anyhow!(
+                "{} needs spotitui built with the scripting feature",
+                path.display()
+            ));
+        }
+        Ok(Self {})
+    }
+
+    pub fn command_for(&self, _key: KeyEvent) -> Option<&str> {
+        None
+    }
+
+    pub fn describe(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    pub fn run(&self, command: &str, _context: CommandContext) -> Result<Vec<ScriptAction>> {
+        Err(anyhow!("No script command named {}", command))
+    }
+
+    pub fn format_track(&self, _track: &Track) -> Option<String> {
+        None
+    }
+}
+
+/// init.lua beside config.toml
+pub fn path() -> Option<PathBuf> {
+    Some(Config::path()?.with_file_name("init.lua"))
+}
+
+/// The spotitui table scripts call into
+#[cfg(feature = "scripting")]
+fn install_api(lua: &Lua, registry: &Arc<Mutex<Registry>>) -> mlua::Result<()> {
+    let api = lua.create_table()?;
+
+    let commands = registry.clone();
+    api.set(
+        "command",
+        lua.create_function(
+            move |lua, (name, description, function): (String, String, Function)| {
+                let function = lua.create_registry_value(function)?;
+                let mut registry = commands.lock().unwrap();
+                registry.commands.retain(|c| c.name != name);
+                registry.commands.push(ScriptCommand {
+                    name,
+                    description,
+                    function,
+                });
+                Ok(())
+            },
+        )?,
+    )?;
+
+    let bindings = registry.clone();
+    api.set(
+        "bind",
+        lua.create_function(move |_, (key, command): (String, String)| {
+            bindings.lock().unwrap().bindings.push((key, command));
+            Ok(())
+        })?,
+    )?;
+
+    let format = registry.clone();
+    api.set(
+        "format_track",
+        lua.create_function(move |lua, function: Function| {
+            format.lock().unwrap().format_track = Some(lua.create_registry_value(function)?);
+            Ok(())
+        })?,
+    )?;
+
+    let action = |make: fn(String) -> ScriptAction| {
+        let actions = registry.clone();
+        lua.create_function(move |_, argument: String| {
+            actions.lock().unwrap().actions.push(make(argument));
+            Ok(())
+        })
+    };
+    api.set("play", action(ScriptAction::Play)?)?;
+    api.set("queue", action(ScriptAction::Queue)?)?;
+    api.set("notify", action(ScriptAction::Notify)?)?;
+
+    for (name, done) in [
+        ("pause", ScriptAction::Pause),
+        ("resume", ScriptAction::Resume),
+        ("next", ScriptAction::Next),
+        ("previous", ScriptAction::Previous),
+    ] {
+        let actions = registry.clone();
+        api.set(
+            name,
+            lua.create_function(move |_, ()| {
+                actions.lock().unwrap().actions.push(done.clone());
+                Ok(())
+            })?,
+        )?;
+    }
+
+    lua.globals().set("spotitui", api)
+}
+
+/// A track as scripts see it
+#[cfg(feature = "scripting")]
+fn track_table<'lua>(lua: &'lua Lua, track: &Track) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("id", track.id.as_str())?;
+    table.set("uri", track.uri.as_str())?;
+    table.set("name", track.name.as_str())?;
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    table.set("artists", artists)?;
+    table.set("album", track.album.name.as_str())?;
+    table.set("duration_ms", track.duration_ms)?;
+    table.set("explicit", track.explicit)?;
+    Ok(table)
+}
+
+/// mlua's errors hold Lua values that anyhow can't carry across threads
+#[cfg(feature = "scripting")]
+fn lua_error(error: mlua::Error) -> anyhow::Error {
+    anyhow!("{}", error)
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    const SCRIPT: &str = r#"
+        spotitui.command("radio", "Queue the track twice", function(ctx)
+            spotitui.queue(ctx.track.uri)
+            spotitui.queue(ctx.track.uri)
+            spotitui.notify("Queued " .. ctx.track.name .. " by " .. ctx.track.artists[1])
+        end)
+        spotitui.bind("ctrl-r", "radio")
+        spotitui.format_track(function(track)
+            if track.explicit then return nil end
+            return track.name:upper()
+        end)
+    "#;
+
+    #[test]
+    fn bound_commands_run_with_the_selected_track() {
+        let scripts = Scripts::from_source(SCRIPT, "init.lua").unwrap();
+        let track = mock::track("1", "First");
+
+        let key = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        let command = scripts.command_for(key).unwrap();
+        let actions = scripts
+            .run(
+                command,
+                CommandContext {
+                    tracks: std::slice::from_ref(&track),
+                    playing: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            actions,
+            [
+                ScriptAction::Queue("spotify:track:1".to_string()),
+                ScriptAction::Queue("spotify:track:1".to_string()),
+                ScriptAction::Notify("Queued First by Artist".to_string()),
+            ]
+        );
+        assert_eq!(
+            scripts.describe(),
+            [("ctrl-r".to_string(), "Queue the track twice".to_string())]
+        );
+        assert_eq!(scripts.format_track(&track).as_deref(), Some("FIRST"));
+    }
+
+    #[test]
+    fn binding_an_unknown_command_is_an_error() {
+        let error = Scripts::from_source(r#"spotitui.bind("x", "nope")"#, "init.lua")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("no command named \"nope\""));
+    }
+}
@@ -0,0 +1,469 @@
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use spotitui_core::spotify::{CurrentlyPlaying, Track};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+use crate::app::web_link;
+use crate::shutdown::Shutdown;
+
+pub const LISTENBRAINZ_API: &str = "https://api.listenbrainz.org";
+/// A track counts as listened to after half of it or four minutes, whichever comes first,
+/// as ListenBrainz and Last.fm both have it
+const LISTEN_AFTER: Duration = Duration::from_secs(240);
+/// How often listens that failed to submit are tried again
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Most listens ListenBrainz takes in one request
+const BATCH_SIZE: usize = 100;
+
+/// A track played for long enough to count, with what a service needs to file it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    /// Unix time the track started
+    pub listened_at: u64,
+    pub track: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub duration_ms: u32,
+    pub uri: String,
+}
+
+impl Listen {
+    fn new(track: &Track, listened_at: u64) -> Self {
+        Self {
+            listened_at,
+            track: track.name.clone(),
+            artists: track.artists.iter().map(|a| a.name.clone()).collect(),
+            album: track.album.name.clone(),
+            duration_ms: track.duration_ms,
+            uri: track.uri.clone(),
+        }
+    }
+}
+
+/// What the player doing something means for scrobbling
+#[derive(Debug, PartialEq)]
+pub enum TrackEvent {
+    /// A track started, for "now playing"
+    Started(Listen),
+    /// The track playing has been listened to
+    Listened(Listen),
+}
+
+/// Works out from the polled player when tracks start and when they've been listened to.
+/// Only time spent playing counts, so pausing halfway doesn't submit a listen
+#[derive(Default)]
+pub struct ListenTracker {
+    current: Option<Current>,
+}
+
+struct Current {
+    listen: Listen,
+    played: Duration,
+    /// When it was last seen playing, and how far in
+    last_seen: Option<(Instant, u64)>,
+    counted: bool,
+}
+
+impl ListenTracker {
+    /// Takes in the player as polled at `now`, `unix_now` being the same moment in Unix time
+    pub fn update(
+        &mut self,
+        playing: Option<&CurrentlyPlaying>,
+        now: Instant,
+        unix_now: u64,
+    ) -> Vec<TrackEvent> {
+        let mut events = Vec::new();
+        let playing = playing.and_then(|p| p.item.as_ref().map(|t| (p, t)));
+        let progress = playing.and_then(|(p, _)| p.progress_ms).unwrap_or(0);
+        let same = self.current.as_ref().is_some_and(|current| {
+            // A track started over from the top on repeat is a new listen
+            let restarted = current.last_seen.is_some_and(|(_, last)| {
+                current.counted && progress + 10_000 < last && progress < 10_000
+            });
+            playing.is_some_and(|(_, track)| current.listen.uri == track.uri) && !restarted
+        });
+
+        if !same {
+            // The player is only polled now and then, and only sent on when it changes, so the
+            // last track may have played on since it was last seen
+            if let Some(listen) = self.current.as_mut().and_then(|last| last.advance(now)) {
+                events.push(TrackEvent::Listened(listen));
+            }
+            self.current = playing.map(|(playing, track)| {
+                let started_at = unix_now.saturating_sub(progress / 1000);
+                let listen = Listen::new(track, started_at);
+                if playing.is_playing {
+                    events.push(TrackEvent::Started(listen.clone()));
+                }
+                Current {
+                    listen,
+                    played: Duration::ZERO,
+                    last_seen: None,
+                    counted: false,
+                }
+            });
+        }
+        let (Some(current), Some((playing, _))) = (self.current.as_mut(), playing) else {
+            return events;
+        };
+
+        if let Some(listen) = current.advance(now) {
+            events.push(TrackEvent::Listened(listen));
+        }
+        current.last_seen = playing.is_playing.then_some((now, progress));
+        events
+    }
+}
+
+impl Current {
+    /// Adds the time played since it was last seen, no further than the end of the track, and
+    /// returns the listen once that's enough for it to count
+    fn advance(&mut self, now: Instant) -> Option<Listen> {
+        if let Some((seen_at, progress)) = self.last_seen {
+            let left = (self.listen.duration_ms as u64).saturating_sub(progress);
+            self.played += now
+                .saturating_duration_since(seen_at)
+                .min(Duration::from_millis(left));
+        }
+        let needed = (Duration::from_millis(self.listen.duration_ms as u64) / 2).min(LISTEN_AFTER);
+        if self.counted || self.played < needed {
+            return None;
+        }
+        self.counted = true;
+        Some(self.listen.clone())
+    }
+}
+
+/// Submits to ListenBrainz with a user token
+#[derive(Clone)]
+pub struct ListenBrainz {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+}
+
+impl ListenBrainz {
+    pub fn new(token: String, api_url: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: api_url.unwrap_or_else(|| LISTENBRAINZ_API.to_string()),
+            token,
+        }
+    }
+
+    /// Submits through `client`, one set up with a proxy or extra certificates
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Only a courtesy, so a failure isn't retried
+    async fn playing_now(&self, listen: &Listen) -> Result<()> {
+        self.submit("playing_now", vec![payload(listen, false)])
+            .await
+            .map_err(|e| e.error)
+    }
+
+    async fn listens(&self, listens: &[Listen]) -> Result<(), Rejected> {
+        let listen_type = if listens.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+        let payload = listens.iter().map(|listen| payload(listen, true)).collect();
+        self.submit(listen_type, payload).await
+    }
+
+    async fn submit(&self, listen_type: &str, payload: Vec<Value>) -> Result<(), Rejected> {
+        let body = json!({ "listen_type": listen_type, "payload": payload });
+        let response = self
+            .client
+            .post(format!("{}/1/submit-listens", self.api_url))
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Rejected::retry(e.into()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        let error = anyhow!("ListenBrainz answered {}: {}", status, text.trim());
+        // Anything else would be turned down again the same way
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            Err(Rejected::retry(error))
+        } else {
+            Err(Rejected {
+                error,
+                retry: false,
+            })
+        }
+    }
+}
+
+/// A failed submission, and whether it's worth trying again
+struct Rejected {
+    error: anyhow::Error,
+    retry: bool,
+}
+
+impl Rejected {
+    fn retry(error: anyhow::Error) -> Self {
+        Self { error, retry: true }
+    }
+}
+
+/// A listen as ListenBrainz takes it, see
+/// https://listenbrainz.readthedocs.io/en/latest/users/json.html
+fn payload(listen: &Listen, with_time: bool) -> Value {
+    let mut payload = json!({
+        "track_metadata": {
+            "artist_name": listen.artists.join(", "),
+            "track_name": listen.track,
+            "release_name": listen.album,
+            "additional_info": {
+                "duration_ms": listen.duration_ms,
+                "origin_url": web_link(&listen.uri),
+                "spotify_id": web_link(&listen.uri),
+                "artist_names": listen.artists,
+                "music_service": "spotify.com",
+                "media_player": "spotitui",
+                "submission_client": "spotitui",
+                "submission_client_version": env!("CARGO_PKG_VERSION"),
+            },
+        },
+    });
+    if with_time {
+        payload["listened_at"] = json!(listen.listened_at);
+    }
+    payload
+}
+
+/// Listens waiting to be submitted, kept on disk so they outlast failures and restarts
+pub struct RetryQueue {
+    pending: VecDeque<Listen>,
+    path: Option<PathBuf>,
+}
+
+impl RetryQueue {
+    /// The listens left over from the last run, if any
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let pending = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self { pending, path }
+    }
+
+    pub fn push(&mut self, listen: Listen) {
+        self.pending.push_back(listen);
+        self.save();
+    }
+
+    /// Submits what's waiting, oldest first, stopping at the first batch that's worth trying
+    /// again later
+    pub async fn flush(&mut self, listenbrainz: &ListenBrainz) {
+        if self.pending.is_empty() {
+            return;
+        }
+        while !self.pending.is_empty() {
+            let count = self.pending.len().min(BATCH_SIZE);
+            let batch: Vec<Listen> = self.pending.iter().take(count).cloned().collect();
+            match listenbrainz.listens(&batch).await {
+                Ok(_) => tracing::debug!(count, "Submitted listens"),
+                Err(rejected) if rejected.retry => {
+                    tracing::debug!(
+                        "Failed to submit listens, retrying later: {:#}",
+                        rejected.error
+                    );
+                    break;
+                }
+                Err(rejected) => {
+                    tracing::warn!(
+                        "ListenBrainz turned down {} listens: {:#}",
+                        count,
+                        rejected.error
+                    )
+                }
+            }
+            self.pending.drain(..count);
+        }
+        self.save();
+    }
+
+    /// Failing to save only risks losing listens on a restart, so it's logged
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string(&self.pending)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                Ok(fs::write(path, contents)?)
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to save listens to {}: {:#}", path.display(), e);
+        }
+    }
+}
+
+/// Follows the player in the background, submitting each listen to ListenBrainz and trying
+/// failed ones again until they go through
+pub fn spawn(
+    listenbrainz: ListenBrainz,
+    mut player: watch::Receiver<Option<CurrentlyPlaying>>,
+    mut queue: RetryQueue,
+    shutdown: &Shutdown,
+) {
+    let cancelled = shutdown.cancelled();
+    shutdown.track(async move {
+        tokio::pin!(cancelled);
+        let mut tracker = ListenTracker::default();
+        let mut retry = tokio::time::interval(RETRY_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = &mut cancelled => break,
+                changed = player.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let playing = player.borrow_and_update().clone();
+                    for event in tracker.update(playing.as_ref(), Instant::now(), unix_now()) {
+                        match event {
+                            TrackEvent::Started(listen) => {
+                                if let Err(e) = listenbrainz.playing_now(&listen).await {
+                                    tracing::debug!("Failed to submit now playing: {:#}", e);
+                                }
+                            }
+                            TrackEvent::Listened(listen) => {
+                                queue.push(listen);
+                                queue.flush(&listenbrainz).await;
+                            }
+                        }
+                    }
+                }
+                _ = retry.tick() => queue.flush(&listenbrainz).await,
+            }
+        }
+        // One last try for what's waiting, it's kept on disk for next time otherwise
+        queue.flush(&listenbrainz).await;
+    });
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn playing(track: &Track, is_playing: bool, progress_ms: u64) -> CurrentlyPlaying {
+        CurrentlyPlaying {
+            item: Some(track.clone()),
+            is_playing,
+            progress_ms: Some(progress_ms),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        }
+    }
+
+    #[test]
+    fn only_time_spent_playing_counts() {
+        let mut track = mock::track("t1", "First");
+        track.duration_ms = 180_000;
+        let mut tracker = ListenTracker::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let events = tracker.update(Some(&playing(&track, true, 0)), at(0), 1_000);
+        assert_eq!(events, [TrackEvent::Started(Listen::new(&track, 1_000))]);
+        assert!(tracker
+            .update(Some(&playing(&track, true, 60_000)), at(60), 1_060)
+            .is_empty());
+        // Paused for a long while, which doesn't count towards the 90 seconds needed
+        tracker.update(Some(&playing(&track, false, 60_000)), at(61), 1_061);
+        tracker.update(Some(&playing(&track, true, 60_000)), at(600), 1_600);
+        assert!(tracker
+            .update(Some(&playing(&track, true, 80_000)), at(620), 1_620)
+            .is_empty());
+
+        let events = tracker.update(Some(&playing(&track, true, 95_000)), at(635), 1_635);
+        assert_eq!(events, [TrackEvent::Listened(Listen::new(&track, 1_000))]);
+        assert!(tracker
+            .update(Some(&playing(&track, true, 120_000)), at(660), 1_660)
+            .is_empty());
+    }
+
+    #[test]
+    fn a_track_played_out_between_polls_still_counts() {
+        let mut first = mock::track("t1", "First");
+        first.duration_ms = 180_000;
+        let second = mock::track("t2", "Second");
+        let mut tracker = ListenTracker::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        tracker.update(Some(&playing(&first, true, 0)), at(0), 1_000);
+        let events = tracker.update(Some(&playing(&second, true, 5_000)), at(185), 1_185);
+
+        assert_eq!(
+            events,
+            [
+                TrackEvent::Listened(Listen::new(&first, 1_000)),
+                TrackEvent::Started(Listen::new(&second, 1_180)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_listens_are_kept_for_later() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/1/submit-listens"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/1/submit-listens"))
+            .and(header("Authorization", "Token secret"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let listenbrainz = ListenBrainz::new("secret".to_string(), Some(server.uri()));
+        let mut queue = RetryQueue::load(None);
+
+        queue.push(Listen::new(&mock::track("t1", "First"), 1_000));
+        queue.flush(&listenbrainz).await;
+        assert_eq!(queue.pending.len(), 1);
+        queue.flush(&listenbrainz).await;
+        assert_eq!(queue.pending.len(), 0);
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["listen_type"], "single");
+        assert_eq!(body["payload"][0]["listened_at"], 1_000);
+        assert_eq!(body["payload"][0]["track_metadata"]["track_name"], "First");
+    }
+}
@@ -0,0 +1,126 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Quitting, shared by the app and its background tasks. Tasks started with `spawn` are
+/// dropped when it begins, those started with `track` are left to finish what they're writing
+/// and waited on in `finish`
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Shutdown {
+    /// Asks everything to stop, the app's loop included
+    pub fn begin(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutting down begins
+    pub fn cancelled(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.token.clone().cancelled_owned()
+    }
+
+    /// Runs a task that's abandoned when shutting down begins, like a fetch nothing will see.
+    /// Its handle can abandon it sooner
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+        let cancelled = self.cancelled();
+        self.tasks.spawn(async move {
+            tokio::select! {
+                _ = cancelled => {}
+                _ = task => {}
+            }
+        })
+    }
+
+    /// Runs a task that `finish` waits for, which watches `cancelled` itself to wrap up
+    pub fn track(&self, task: impl Future<Output = ()> + Send + 'static) {
+        self.tasks.spawn(task);
+    }
+
+    /// Begins shutting down and waits up to `timeout` for tracked tasks to be done
+    pub async fn finish(&self, timeout: Duration) {
+        self.begin();
+        self.tasks.close();
+        if tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                remaining = self.tasks.len(),
+                "Gave up waiting for background tasks"
+            );
+        }
+    }
+
+    /// Begins shutting down on SIGTERM, SIGINT or SIGHUP, Ctrl+C on Windows. Ctrl+C in the
+    /// terminal is a key press in raw mode, so these only come from outside
+    pub fn on_signals(&self) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+                let (Ok(mut term), Ok(mut int), Ok(mut hup)) = (
+                    signal(SignalKind::terminate()),
+                    signal(SignalKind::interrupt()),
+                    signal(SignalKind::hangup()),
+                ) else {
+                    tracing::warn!("Couldn't listen for signals");
+                    return;
+                };
+                tokio::select! {
+                    _ = term.recv() => {}
+                    _ = int.recv() => {}
+                    _ = hup.recv() => {}
+                }
+            }
+            #[cfg(not(unix))]
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            tracing::info!("Shutting down on a signal");
+            shutdown.begin();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn finishing_waits_for_tracked_tasks_and_drops_the_rest() {
+        let shutdown = Shutdown::default();
+        let flushed = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicBool::new(true));
+
+        let cancelled = shutdown.cancelled();
+        let done = flushed.clone();
+        shutdown.track(async move {
+            cancelled.await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            done.store(true, Ordering::SeqCst);
+        });
+        let finished = dropped.clone();
+        shutdown.spawn(async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            finished.store(false, Ordering::SeqCst);
+        });
+
+        shutdown.finish(Duration::from_secs(5)).await;
+
+        assert!(flushed.load(Ordering::SeqCst));
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(shutdown.is_shutting_down());
+    }
+}
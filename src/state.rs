@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use spotitui_core::spotify::{Album, Artist, Show};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config;
+
+/// Things the app remembers between runs, unlike the config which is only ever read
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalState {
+    /// Ids of the playlists pinned to the top of the Playlists tab
+    pub pinned_playlists: Vec<String>,
+    /// Playlist ids by the letter they're marked with
+    pub marks: BTreeMap<String, String>,
+    /// How the Playlists tab is ordered
+    pub playlist_sort: PlaylistSort,
+    /// Folders of the Playlists tab that are expanded, the rest are collapsed
+    pub open_folders: Vec<String>,
+    /// Monday of the last week whose Discover Weekly was archived, as YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_archived_week: Option<String>,
+    /// Where the app was left on quitting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<Session>,
+    /// Where it's saved, None keeps it in memory only
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl LocalState {
+    /// Loads the state file, starting afresh if it's missing or unreadable since nothing in it
+    /// is worth refusing to start over. Each profile has a state file of its own
+    pub fn load(profile: Option<&str>) -> Self {
+        let path = Self::path(profile);
+        let mut state: Self = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        state.path = path;
+        state
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let contents = toml::to_string(self)?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// $XDG_STATE_HOME/spotitui/state.toml, or ~/.local/state/spotitui/state.toml, and
+    /// state-NAME.toml beside it for a profile
+    pub fn path(profile: Option<&str>) -> Option<PathBuf> {
+        state_file("state", "toml", profile)
+    }
+
+    pub fn is_pinned(&self, playlist_id: &str) -> bool {
+        self.pinned_playlists.iter().any(|id| id == playlist_id)
+    }
+
+    /// Pins the playlist, or unpins it if it already is, returning whether it's now pinned
+    pub fn toggle_pin(&mut self, playlist_id: &str) -> bool {
+        if self.is_pinned(playlist_id) {
+            self.pinned_playlists.retain(|id| id != playlist_id);
+            false
+        } else {
+            self.pinned_playlists.push(playlist_id.to_string());
+            true
+        }
+    }
+
+    pub fn is_folder_open(&self, folder: &str) -> bool {
+        self.open_folders.iter().any(|name| name == folder)
+    }
+
+    pub fn set_folder_open(&mut self, folder: &str, open: bool) {
+        self.open_folders.retain(|name| name != folder);
+        if open {
+            self.open_folders.push(folder.to_string());
+        }
+    }
+
+    /// Letters the playlist is marked with
+    pub fn marks_on<'a>(&'a self, playlist_id: &'a str) -> impl Iterator<Item = &'a str> {
+        self.marks
+            .iter()
+            .filter(move |(_, id)| *id == playlist_id)
+            .map(|(mark, _)| mark.as_str())
+    }
+}
+
+/// How the Playlists tab is ordered below For You and the pinned playlists, which stay on top
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaylistSort {
+    /// As Spotify lists them, most recently followed or made first
+    #[default]
+    Spotify,
+    Name,
+    Owner,
+    /// Newest track addition first, as far as the playlists fetched so far tell
+    RecentlyChanged,
+}
+
+impl PlaylistSort {
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaylistSort::Spotify => "in Spotify's order",
+            PlaylistSort::Name => "by name",
+            PlaylistSort::Owner => "by owner",
+            PlaylistSort::RecentlyChanged => "by latest change",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PlaylistSort::Spotify => PlaylistSort::Name,
+            PlaylistSort::Name => PlaylistSort::Owner,
+            PlaylistSort::Owner => PlaylistSort::RecentlyChanged,
+            PlaylistSort::RecentlyChanged => PlaylistSort::Spotify,
+        }
+    }
+}
+
+/// The views and positions to pick up from on the next run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Label of the library tab that was open
+    pub library_tab: String,
+    /// Id of the library entry under the cursor, "liked" or "recently-added" on the Liked tab
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library_item: Option<String>,
+    pub library_offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open: Option<OpenView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_row: Option<usize>,
+    pub track_offset: usize,
+    /// Whether the tracks pane had focus rather than the library
+    pub tracks_focused: bool,
+}
+
+/// What the tracks pane was showing, by id rather than position since playlists move around
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum OpenView {
+    Playlist { id: String },
+    Album(Album),
+    Artist(Artist),
+    Show(Show),
+    Liked,
+    RecentlyAdded,
+}
+
+/// Where the refresh token is kept between runs, tokens.txt or tokens-NAME.txt for a profile
+pub fn token_path(profile: Option<&str>) -> Option<PathBuf> {
+    state_file("tokens", "txt", profile)
+}
+
+/// Listens waiting to be submitted to ListenBrainz, listens.json or listens-NAME.json for a
+/// profile
+#[cfg(feature = "scrobble")]
+pub fn listens_path(profile: Option<&str>) -> Option<PathBuf> {
+    state_file("listens", "json", profile)
+}
+
+/// Where library backups are written by default, shared by every profile
+pub fn backup_dir() -> Option<PathBuf> {
+    Some(state_dir()?.join("backups"))
+}
+
+/// Where debug logs are written, shared by every profile
+pub fn log_dir() -> Option<PathBuf> {
+    Some(state_dir()?.join("logs"))
+}
+
+fn state_file(stem: &str, extension: &str, profile: Option<&str>) -> Option<PathBuf> {
+    let file = match profile {
+        Some(profile) => format!("{}-{}.{}", stem, profile, extension),
+        None => format!("{}.{}", stem, extension),
+    };
+    Some(state_dir()?.join(file))
+}
+
+/// The platform's state directory, like ~/.local/state/spotitui on Linux. Platforms without
+/// one keep it with the app's local data
+fn state_dir() -> Option<PathBuf> {
+    let dirs = config::project_dirs()?;
+    let dir = dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir());
+    Some(dir.to_path_buf())
+}
@@ -0,0 +1,176 @@
+use spotitui_core::spotify::{AudioFeatures, Track};
+use std::collections::HashMap;
+
+/// Artists listed by name, the rest are counted together under "Others"
+const TOP_ARTISTS: usize = 8;
+/// Width of each tempo range, in BPM
+const TEMPO_STEP: u32 = 20;
+
+/// What a playlist is made of, for the stats popup
+#[derive(Debug)]
+pub struct PlaylistStats {
+    pub name: String,
+    pub tracks: usize,
+    pub duration_ms: u64,
+    /// Tracks by their first artist, most first
+    pub artists: Vec<(String, usize)>,
+    /// Tracks by the decade they came out, oldest first, like ("1990s", 12)
+    pub decades: Vec<(String, usize)>,
+    /// Tracks by tempo, slowest first, like ("120-139 BPM", 4)
+    pub tempos: Vec<(String, usize)>,
+    /// Out of 100, averaged over the tracks Spotify rates
+    pub popularity: Option<f32>,
+    /// Beats per minute, averaged over the tracks Spotify has analysed
+    pub tempo: Option<f32>,
+    /// First line shown in the popup
+    pub scroll: usize,
+    /// Visible lines, updated on every draw
+    pub viewport: usize,
+}
+
+impl PlaylistStats {
+    pub fn new(name: String, tracks: &[Track], features: &[AudioFeatures]) -> Self {
+        let mut artists: HashMap<&str, usize> = HashMap::new();
+        for artist in tracks.iter().filter_map(|track| track.artists.first()) {
+            *artists.entry(artist.name.as_str()).or_default() += 1;
+        }
+        let mut artists: Vec<(String, usize)> = artists
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        artists.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        if artists.len() > TOP_ARTISTS {
+            let others = artists.split_off(TOP_ARTISTS - 1);
+            artists.push(("Others".to_string(), others.iter().map(|a| a.1).sum()));
+        }
+
+        let decades = tracks.iter().filter_map(|track| {
+            let year: u32 = track
+                .album
+                .release_date
+                .as_deref()?
+                .get(..4)?
+                .parse()
+                .ok()?;
+            // Spotify dates some local and very old tracks 0000
+            (year > 0).then_some(year / 10 * 10)
+        });
+        let decades = histogram(decades, |decade| format!("{}s", decade));
+
+        let features: Vec<&AudioFeatures> = features.iter().filter(|f| f.tempo > 0.0).collect();
+        let tempos = features
+            .iter()
+            .map(|features| features.tempo as u32 / TEMPO_STEP * TEMPO_STEP);
+        let tempos = histogram(tempos, |low| {
+            format!("{}-{} BPM", low, low + TEMPO_STEP - 1)
+        });
+
+        let popularity: Vec<u32> = tracks.iter().filter_map(|track| track.popularity).collect();
+        Self {
+            name,
+            tracks: tracks.len(),
+            duration_ms: tracks
+                .iter()
+                .map(|track| u64::from(track.duration_ms))
+                .sum(),
+            artists,
+            decades,
+            tempos,
+            popularity: average(popularity.iter().map(|&p| p as f32)),
+            tempo: average(features.iter().map(|features| features.tempo)),
+            scroll: 0,
+            viewport: 0,
+        }
+    }
+
+    /// The charts in the order the popup draws them, leaving out the empty ones
+    pub fn charts(&self) -> impl Iterator<Item = (&'static str, &[(String, usize)])> {
+        [
+            ("Artists", self.artists.as_slice()),
+            ("Decades", self.decades.as_slice()),
+            ("Tempo", self.tempos.as_slice()),
+        ]
+        .into_iter()
+        .filter(|(_, rows)| !rows.is_empty())
+    }
+
+    /// Lines in the popup: the summary, then a gap and a heading above each chart
+    pub fn len(&self) -> usize {
+        3 + self.charts().map(|(_, rows)| 2 + rows.len()).sum::<usize>()
+    }
+}
+
+/// Counts of each value, in order of the values, labelled by `label`
+fn histogram(
+    values: impl Iterator<Item = u32>,
+    label: impl Fn(u32) -> String,
+) -> Vec<(String, usize)> {
+    let mut counts: Vec<(u32, usize)> = Vec::new();
+    for value in values {
+        match counts.binary_search_by_key(&value, |&(v, _)| v) {
+            Ok(found) => counts[found].1 += 1,
+            Err(at) => counts.insert(at, (value, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(value, count)| (label(value), count))
+        .collect()
+}
+
+fn average(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn stats_count_artists_decades_and_tempos() {
+        let track = |id: &str, artist: &str, released: &str, popularity: u32| {
+            let mut track = mock::track(id, id);
+            track.artists[0].name = artist.to_string();
+            track.album.release_date = Some(released.to_string());
+            track.popularity = Some(popularity);
+            track
+        };
+        let tracks = [
+            track("t1", "Oasis", "1995-10-02", 80),
+            track("t2", "Blur", "1994", 60),
+            track("t3", "Oasis", "2008-10-06", 40),
+        ];
+        let features = |id: &str, tempo: f32| AudioFeatures {
+            id: id.to_string(),
+            tempo,
+            energy: 0.5,
+            danceability: 0.5,
+            valence: 0.5,
+        };
+
+        let stats = PlaylistStats::new(
+            "Britpop".to_string(),
+            &tracks,
+            &[features("t1", 128.4), features("t3", 91.6)],
+        );
+
+        assert_eq!((stats.tracks, stats.duration_ms), (3, 540_000));
+        assert_eq!(
+            stats.artists,
+            [("Oasis".to_string(), 2), ("Blur".to_string(), 1)]
+        );
+        assert_eq!(
+            stats.decades,
+            [("1990s".to_string(), 2), ("2000s".to_string(), 1)]
+        );
+        assert_eq!(
+            stats.tempos,
+            [("80-99 BPM".to_string(), 1), ("120-139 BPM".to_string(), 1)]
+        );
+        assert_eq!(stats.popularity, Some(60.0));
+        assert_eq!(stats.tempo, Some(110.0));
+        assert_eq!(stats.len(), 3 + 4 + 4 + 4);
+    }
+}
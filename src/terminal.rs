@@ -0,0 +1,59 @@
+use anyhow::Result;
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The terminal in raw mode on the alternate screen, put back the way it was when dropped.
+/// A panic exits from its hook before anything is dropped, so the hook puts it back too
+pub struct TerminalGuard {
+    mouse: bool,
+    active: Arc<AtomicBool>,
+}
+
+impl TerminalGuard {
+    /// Takes over the terminal, capturing the mouse too when `mouse` is set
+    pub fn enter(mouse: bool) -> Result<Self> {
+        enable_raw_mode()?;
+        // Made first so a failure below still puts the terminal back
+        let guard = Self {
+            mouse,
+            active: Arc::new(AtomicBool::new(true)),
+        };
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        if mouse {
+            execute!(io::stdout(), EnableMouseCapture)?;
+        }
+
+        let active = guard.active.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if active.swap(false, Ordering::SeqCst) {
+                restore(mouse);
+            }
+            previous(info);
+        }));
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.active.swap(false, Ordering::SeqCst) {
+            restore(self.mouse);
+        }
+    }
+}
+
+fn restore(mouse: bool) {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    if mouse {
+        let _ = execute!(io::stdout(), DisableMouseCapture);
+    }
+}
@@ -0,0 +1,146 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Colors the interface is drawn with, named after what they're used for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Prompts, breadcrumbs, For You entries and info toasts
+    pub accent: Color,
+    /// Popup borders, pins, headers and matched characters
+    pub highlight: Color,
+    /// The focused pane, the playing track and successes
+    pub positive: Color,
+    /// Errors and destructive actions
+    pub negative: Color,
+    /// Track titles and other primary text
+    pub text: Color,
+    /// Artists and other secondary text
+    pub secondary: Color,
+    /// Separators, hints and placeholders
+    pub muted: Color,
+    /// Tracks marked for batch actions
+    pub marked: Color,
+    /// Borders of the help and comparison popups
+    pub info: Color,
+}
+
+const DEFAULT: Theme = Theme {
+    accent: Color::Cyan,
+    highlight: Color::Yellow,
+    positive: Color::Green,
+    negative: Color::Red,
+    text: Color::White,
+    secondary: Color::Gray,
+    muted: Color::DarkGray,
+    marked: Color::Magenta,
+    info: Color::Blue,
+};
+
+/// For terminals with a light background
+const LIGHT: Theme = Theme {
+    accent: Color::Blue,
+    highlight: Color::Magenta,
+    positive: Color::Green,
+    negative: Color::Red,
+    text: Color::Black,
+    secondary: Color::DarkGray,
+    muted: Color::Gray,
+    marked: Color::Cyan,
+    info: Color::Blue,
+};
+
+/// The terminal's own colors only, pairs well with high contrast mode
+const MONO: Theme = Theme {
+    accent: Color::Reset,
+    highlight: Color::Reset,
+    positive: Color::Reset,
+    negative: Color::Reset,
+    text: Color::Reset,
+    secondary: Color::Reset,
+    muted: Color::Reset,
+    marked: Color::Reset,
+    info: Color::Reset,
+};
+
+/// Built-in themes by the name the config picks them with
+pub const THEMES: [(&str, Theme); 3] = [("default", DEFAULT), ("light", LIGHT), ("mono", MONO)];
+
+impl Default for Theme {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Self> {
+        THEMES
+            .iter()
+            .find(|(theme, _)| *theme == name)
+            .map(|(_, theme)| *theme)
+    }
+}
+
+/// The [theme] table: a built-in theme, with any of its colors swapped for others given by
+/// name ("red", "lightblue"), index ("208") or hex ("#89b4fa")
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub name: Option<String>,
+    pub accent: Option<String>,
+    pub highlight: Option<String>,
+    pub positive: Option<String>,
+    pub negative: Option<String>,
+    pub text: Option<String>,
+    pub secondary: Option<String>,
+    pub muted: Option<String>,
+    pub marked: Option<String>,
+    pub info: Option<String>,
+}
+
+impl ThemeConfig {
+    /// The theme to draw with, or what's wrong with the table
+    pub fn resolve(&self) -> Result<Theme, Vec<String>> {
+        let mut problems = Vec::new();
+        let name = self.name.as_deref().unwrap_or("default");
+        let mut theme = Theme::named(name).unwrap_or_else(|| {
+            let names: Vec<&str> = THEMES.iter().map(|(name, _)| *name).collect();
+            problems.push(format!(
+                "theme.name must be one of {}, got \"{}\"",
+                names.join(", "),
+                name
+            ));
+            DEFAULT
+        });
+
+        let overrides = [
+            ("accent", &self.accent, &mut theme.accent),
+            ("highlight", &self.highlight, &mut theme.highlight),
+            ("positive", &self.positive, &mut theme.positive),
+            ("negative", &self.negative, &mut theme.negative),
+            ("text", &self.text, &mut theme.text),
+            ("secondary", &self.secondary, &mut theme.secondary),
+            ("muted", &self.muted, &mut theme.muted),
+            ("marked", &self.marked, &mut theme.marked),
+            ("info", &self.info, &mut theme.info),
+        ];
+        for (role, value, color) in overrides {
+            let Some(value) = value else {
+                continue;
+            };
+            match Color::from_str(value) {
+                Ok(parsed) => *color = parsed,
+                Err(_) => problems.push(format!(
+                    "theme.{} isn't a color: \"{}\", use a name like \"cyan\", an index or #rrggbb",
+                    role, value
+                )),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(theme)
+        } else {
+            Err(problems)
+        }
+    }
+}
@@ -1,183 +1,647 @@
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Position, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style},
-    text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    text::{Line, Span, Text},
+    widgets::{
+        Block, BorderType, Borders, Cell, Clear, LineGauge, List, ListItem, Padding, Paragraph,
+        Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, Tabs, Wrap,
+    },
     Frame,
 };
+use spotitui_core::api::SpotifyApi;
+use spotitui_core::library::describe_count;
+use spotitui_core::spotify::Playlist;
+use spotitui_core::state::AppState;
 use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::app::{App, AppState, FocusedPane};
+use crate::app::{
+    App, FilterTarget, FocusedPane, LibraryTab, MarkAction, PaneAreas, PickerPurpose, SortColumn,
+    TrackMenuItem, TrackSource,
+};
+use crate::config::{ConfigError, ExplicitMode};
+use crate::fuzzy;
+use crate::input::TextInput;
+use crate::keymap;
+use crate::notifications::Severity;
+use crate::state::PlaylistSort;
+use crate::theme::Theme;
+
+#[derive(Debug, Clone, Copy)]
+enum LibrarySection {
+    Library,
+    Queue,
+}
 
-pub fn draw(f: &mut Frame, app: &mut App) {
+/// Terminals shorter than this get the compact layout: a one-line Now Playing bar, the queue
+/// and playlist header folded away, and popups sized to fit
+const COMPACT_HEIGHT: u16 = 30;
+
+/// Terminals narrower than this show the library or the tracks full width instead of both
+const NARROW_WIDTH: u16 = 80;
+
+/// Popups sized as a share of the width are kept at least this wide, so narrow terminals don't
+/// cut their text down to a few letters
+const MIN_POPUP_WIDTH: u16 = 36;
+/// Requests taking longer than this stand out in the debug overlay
+const SLOW_REQUEST_MS: u128 = 1000;
+
+pub fn draw(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    // Panes that aren't drawn this frame shouldn't respond to clicks
+    app.pane_areas = PaneAreas::default();
+
+    let compact = f.area().height < COMPACT_HEIGHT;
+    let layout = app.config.layout.clone();
+    let now_playing_height = match (layout.show_now_playing, compact) {
+        (false, _) => 0,
+        (true, true) => 1,
+        (true, false) => 4,
+    };
     let main_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
-        .split(f.area());
-
-    let content_area = main_layout[0];
-    let help_area = main_layout[1];
-
-    let main_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
-        .split(content_area);
-
-    // Split the left side into playlists (top), currently playing (middle), and queue (bottom)
-    let left_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
             [
-                Constraint::Percentage(50),
-                Constraint::Percentage(25),
-                Constraint::Percentage(25),
+                Constraint::Length(app.connection.is_offline() as u16),
+                Constraint::Min(0),
+                Constraint::Length(now_playing_height),
+                Constraint::Length(1),
             ]
             .as_ref(),
         )
-        .split(main_chunks[0]);
+        .split(f.area());
 
-    draw_playlists(f, app, left_chunks[0]);
-    draw_currently_playing(f, app, left_chunks[1]);
-    draw_queue(f, app, left_chunks[2]);
+    let banner_area = main_layout[0];
+    let content_area = main_layout[1];
+    let now_playing_area = main_layout[2];
+    let status_area = main_layout[3];
 
-    // Split the right side for search functionality
-    if app.show_search {
-        let right_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
-            .split(main_chunks[1]);
+    if app.connection.is_offline() {
+        draw_offline_banner(f, app, banner_area);
+    }
 
-        draw_search_bar(f, app, right_chunks[0]);
-        draw_tracks(f, app, right_chunks[1]);
+    // Narrow terminals show one column at a time, whichever holds the focus
+    app.narrow = f.area().width < NARROW_WIDTH;
+    if app.narrow {
+        if matches!(app.focused_pane, FocusedPane::Library) {
+            draw_left_column(f, app, content_area, compact);
+        } else {
+            draw_right_column(f, app, content_area, compact);
+        }
     } else {
-        draw_tracks(f, app, main_chunks[1]);
+        let main_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(layout.library_width),
+                    Constraint::Percentage(100 - layout.library_width),
+                ]
+                .as_ref(),
+            )
+            .split(content_area);
+        draw_left_column(f, app, main_chunks[0], compact);
+        draw_right_column(f, app, main_chunks[1], compact);
     }
 
-    draw_help_hint(f, help_area);
+    if layout.show_now_playing {
+        draw_now_playing_bar(f, app, now_playing_area);
+    }
+    draw_status_bar(f, app, status_area);
 
     if app.show_playback_controls {
         draw_playback_controls_popup(f, app);
     }
 
+    if app.show_track_menu {
+        draw_track_menu_popup(f, app);
+    }
+
+    if app.show_playlist_picker {
+        draw_playlist_picker_popup(f, app);
+    }
+
+    if app.show_device_picker {
+        draw_device_picker_popup(f, app);
+    }
+
+    if app.show_genre_picker {
+        draw_genre_picker_popup(f, app);
+    }
+
     if app.show_help {
         draw_help_popup(f, app);
     }
 
+    if app.comparison.is_some() {
+        draw_comparison_popup(f, app);
+    }
+
+    if app.stats.is_some() {
+        draw_stats_popup(f, app);
+    }
+
+    if app.import_review.is_some() {
+        draw_import_review_popup(f, app);
+    }
+
+    if app.confirmation.is_some() {
+        draw_confirmation_popup(f, app);
+    }
+
+    if app.show_debug {
+        draw_debug_overlay(f, app, content_area);
+    }
+
+    draw_toasts(f, app, content_area);
+
     // Show error messages or status
     if let AppState::Error(ref error) = app.state {
-        draw_error_popup(f, error);
-    } else if matches!(app.state, AppState::Loading) {
-        draw_status_popup(f, "Loading...");
+        draw_error_popup(f, app.theme, error);
     } else if matches!(app.state, AppState::Authenticating) {
-        draw_status_popup(f, "Authenticating...");
+        draw_status_popup(f, app.theme, "Authenticating...");
     }
 }
 
-fn draw_playlists(f: &mut Frame, app: &mut App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .playlists
+/// The library and, unless hidden or there's no room, the queue below it
+fn draw_left_column(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect, compact: bool) {
+    let layout = app.config.layout.clone();
+    let mut left_sections = vec![(LibrarySection::Library, layout.playlists_height)];
+    if layout.show_queue && !compact {
+        left_sections.push((LibrarySection::Queue, layout.queue_height));
+    }
+
+    let left_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            left_sections
+                .iter()
+                .map(|(_, height)| Constraint::Fill(*height)),
+        )
+        .split(area);
+
+    for ((section, _), area) in left_sections.iter().zip(left_chunks.iter()) {
+        match section {
+            LibrarySection::Library => draw_library(f, app, *area),
+            LibrarySection::Queue => draw_queue(f, app, *area),
+        }
+    }
+}
+
+/// The tracks pane, with the search or filter bar and the playlist header above it
+fn draw_right_column(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect, compact: bool) {
+    // Details of the open playlist go above everything else on the right
+    let header_height = if compact {
+        0
+    } else {
+        playlist_header_height(app)
+    };
+    let breadcrumbs_height = if app.nav_history.is_empty() { 0 } else { 1 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(breadcrumbs_height),
+            Constraint::Length(header_height),
+            Constraint::Min(0),
+        ])
+        .split(area);
+    if breadcrumbs_height > 0 {
+        draw_breadcrumbs(f, app, chunks[0]);
+    }
+    if header_height > 0 {
+        draw_playlist_header(f, app, chunks[1]);
+    }
+    let mut right_area = chunks[2];
+
+    // The split view sits beside the tracks, or under them when there's no room across
+    if app.split.is_some() {
+        let direction = if right_area.width >= 100 {
+            Direction::Horizontal
+        } else {
+            Direction::Vertical
+        };
+        let split_chunks = Layout::default()
+            .direction(direction)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(right_area);
+        right_area = split_chunks[0];
+        draw_split_pane(f, app, split_chunks[1]);
+    }
+
+    // Split the right side for search functionality
+    if app.show_search {
+        let right_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(right_area);
+
+        draw_search_bar(f, app, right_chunks[0]);
+        draw_tracks(f, app, right_chunks[1]);
+    } else if app.editing_filter == Some(FilterTarget::Tracks) || !app.track_filter.is_empty() {
+        let right_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(right_area);
+
+        draw_filter_bar(f, app, FilterTarget::Tracks, right_chunks[0]);
+        draw_tracks(f, app, right_chunks[1]);
+    } else {
+        draw_tracks(f, app, right_area);
+    }
+}
+
+/// The trail of views that led to the tracks pane, like "Playlists ▸ Road Trip ▸ Album: AM"
+fn draw_breadcrumbs(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let trail: Vec<&TrackSource> = app
+        .nav_history
         .iter()
-        .map(|playlist| {
-            let content = vec![Line::from(Span::raw(&playlist.name))];
-            ListItem::new(content)
-        })
+        .map(|entry| &entry.source)
+        .chain(app.track_source.iter())
+        .collect();
+    let Some(root) = trail.first() else {
+        return;
+    };
+
+    let separator = Span::styled(" ▸ ", Style::default().fg(theme.muted));
+    let mut crumbs: Vec<String> = trail
+        .iter()
+        .enumerate()
+        .map(|(i, source)| source.breadcrumb(&app.playlists, i == 0))
         .collect();
+    crumbs.insert(0, root.library_tab().label().to_string());
+    let last = crumbs.len() - 1;
+
+    let mut spans = vec![Span::raw(" ")];
+    for (i, crumb) in crumbs.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(separator.clone());
+        }
+        let style = if i == last {
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.secondary)
+        };
+        spans.push(Span::styled(crumb, style));
+    }
+    spans.push(Span::styled("  ⌫ back", Style::default().fg(theme.muted)));
+
+    f.render_widget(
+        Paragraph::new(truncate_line(Line::from(spans), area.width as usize)),
+        area,
+    );
+}
+
+/// Marker drawn before the selected row of lists and tables
+const HIGHLIGHT_SYMBOL: &str = ">> ";
 
-    let border_style = if matches!(app.focused_pane, FocusedPane::Playlists) {
-        Style::default().fg(Color::Green)
+fn draw_library(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(area);
+    draw_library_tabs(f, app, chunks[0]);
+    let area = chunks[1];
+
+    let area =
+        if app.editing_filter == Some(FilterTarget::Library) || !app.library_filter.is_empty() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+                .split(area);
+            draw_filter_bar(f, app, FilterTarget::Library, chunks[0]);
+            chunks[1]
+        } else {
+            area
+        };
+
+    app.library_viewport = area.height.saturating_sub(2) as usize;
+    app.pane_areas.library = area;
+
+    let names = app.library_names();
+    let item_width = list_item_width(area);
+    let loading = app.loading_tabs.contains(&app.library_tab);
+    let items: Vec<ListItem> = if loading {
+        vec![ListItem::new(loading_line(theme, "Loading…"))]
     } else {
-        Style::default()
+        app.library_order
+            .iter()
+            .enumerate()
+            .filter_map(|(position, &index)| names.get(index).map(|name| (position, index, name)))
+            .map(|(position, index, name)| {
+                let folder = app.library_folder(index);
+                if let Some((folder, _)) = folder.filter(|_| app.in_collapsed_folder(index)) {
+                    let line = Line::from(vec![
+                        Span::styled(
+                            format!("▸ {}", folder),
+                            Style::default()
+                                .fg(theme.highlight)
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(
+                            format!(" {}", app.folder_size(folder)),
+                            Style::default().fg(theme.muted),
+                        ),
+                    ]);
+                    return ListItem::new(vec![truncate_line(line, item_width)]);
+                }
+
+                let name = folder.map_or(name.as_str(), |(_, name)| name);
+                let mut line =
+                    highlight_matches(theme, name, app.library_filter.text(), Style::default());
+                if folder.is_some() {
+                    line.spans.insert(0, Span::raw("  "));
+                }
+                if app.in_for_you(index) {
+                    line.spans
+                        .insert(0, Span::styled("✦ ", Style::default().fg(theme.accent)));
+                }
+                if app.is_pinned(index) {
+                    line.spans
+                        .insert(0, Span::styled("📌 ", Style::default().fg(theme.highlight)));
+                }
+                if app.library_tab == LibraryTab::Playlists {
+                    if let Some(playlist) = app.playlists.get(index) {
+                        for mark in app.local_state.marks_on(&playlist.id) {
+                            line.spans.push(Span::styled(
+                                format!(" '{}", mark),
+                                Style::default().fg(theme.muted),
+                            ));
+                        }
+                    }
+                }
+                let line = truncate_line(line, item_width);
+                match folder.filter(|_| app.starts_open_folder(position)) {
+                    // The first playlist of an open folder carries the folder's header above it
+                    Some((folder, _)) => ListItem::new(vec![
+                        truncate_line(
+                            Line::styled(
+                                format!("▾ {}", folder),
+                                Style::default()
+                                    .fg(theme.highlight)
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                            item_width,
+                        ),
+                        line,
+                    ]),
+                    None => ListItem::new(vec![line]),
+                }
+            })
+            .collect()
     };
 
+    let focused = matches!(app.focused_pane, FocusedPane::Library);
+    let sort = app.local_state.playlist_sort;
+    let title = if app.library_tab == LibraryTab::Playlists && sort != PlaylistSort::Spotify {
+        format!("{} {}", app.library_tab.label(), sort.label())
+    } else {
+        app.library_tab.label().to_string()
+    };
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Playlists")
-                .border_style(border_style),
-        )
+        .block(pane_block(app, title, focused))
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-        .highlight_symbol(">> ");
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
 
-    f.render_stateful_widget(list, area, &mut app.playlists_state);
+    if loading {
+        f.render_widget(list, area);
+        return;
+    }
+    f.render_stateful_widget(list, area, app.library_state_mut());
+    draw_scrollbar(
+        f,
+        area,
+        app.library_order.len(),
+        app.library_state().selected().unwrap_or(0),
+    );
 }
 
-fn draw_currently_playing(f: &mut Frame, app: &App, area: Rect) {
-    let content = if let Some(ref currently_playing) = app.currently_playing {
-        if let Some(ref track) = currently_playing.item {
-            let artists = track
-                .artists
-                .iter()
-                .map(|a| a.name.clone())
-                .collect::<Vec<_>>()
-                .join(", ");
-            let device_name = currently_playing
-                .device
-                .as_ref()
-                .map(|d| d.name.clone())
-                .unwrap_or_else(|| "Unknown Device".to_string());
-            let status = if currently_playing.is_playing {
-                "▶"
+/// One-line tab bar above the library pane, falling back to short labels in narrow columns
+fn draw_library_tabs(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    const DIVIDER: &str = " ";
+    let full_width: usize = LibraryTab::ALL
+        .iter()
+        .map(|tab| tab.label().width())
+        .sum::<usize>()
+        + LibraryTab::ALL.len()
+        - 1;
+    let labels: Vec<&str> = LibraryTab::ALL
+        .iter()
+        .map(|tab| {
+            if full_width <= area.width as usize {
+                tab.label()
             } else {
-                "⏸"
-            };
+                tab.short_label()
+            }
+        })
+        .collect();
 
-            let progress = if let Some(progress_ms) = currently_playing.progress_ms {
-                let progress_sec = progress_ms / 1000;
-                let progress_min = progress_sec / 60;
-                let progress_sec = progress_sec % 60;
-                let duration_sec = track.duration_ms / 1000;
-                let duration_min = duration_sec / 60;
-                let duration_sec = duration_sec % 60;
-                format!(
-                    " {}:{:02} / {}:{:02}",
-                    progress_min, progress_sec, duration_min, duration_sec
-                )
-            } else {
-                String::new()
-            };
+    // Record where each tab lands so it can be clicked, mirroring how Tabs lays them out
+    let mut x = area.x;
+    app.pane_areas.library_tabs = labels
+        .iter()
+        .map(|label| {
+            let width = (label.width() as u16).min(area.right().saturating_sub(x));
+            let tab_area = Rect { x, width, ..area };
+            x = (x + width + 1).min(area.right());
+            tab_area
+        })
+        .collect();
 
-            vec![
-                Line::from(vec![
-                    Span::styled(
-                        status,
-                        Style::default().fg(if currently_playing.is_playing {
-                            Color::Green
-                        } else {
-                            Color::Yellow
-                        }),
-                    ),
-                    Span::raw(" "),
-                    Span::styled(&track.name, Style::default().fg(Color::White)),
-                ]),
-                Line::from(Span::styled(artists, Style::default().fg(Color::Gray))),
-                Line::from(Span::styled(device_name, Style::default().fg(Color::Cyan))),
-                Line::from(Span::styled(progress, Style::default().fg(Color::Gray))),
-            ]
+    let tabs = Tabs::new(labels)
+        .select(app.library_tab.index())
+        .padding("", "")
+        .divider(DIVIDER)
+        .style(Style::default().fg(theme.muted))
+        .highlight_style(if app.config.display.high_contrast {
+            Style::default()
+                .fg(theme.positive)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
         } else {
-            vec![Line::from(Span::raw("No track information available"))]
-        }
+            Style::default()
+                .fg(theme.positive)
+                .add_modifier(Modifier::BOLD)
+        });
+    f.render_widget(tabs, area);
+}
+
+/// Full-width bar along the bottom with the playing track, its progress and the player state.
+/// A single-line area gets a borderless version with the times in place of the progress bar
+fn draw_now_playing_bar(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let compact = area.height == 1;
+    let inner = if compact {
+        area
     } else {
-        vec![Line::from(Span::raw("Nothing currently playing"))]
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Now Playing")
+            .border_style(Style::default());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        inner
     };
 
-    let paragraph = Paragraph::new(content)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Now Playing")
-                .border_style(Style::default()),
-        )
-        .wrap(Wrap { trim: true });
+    let player = app.currently_playing.as_ref();
+    let Some((player, track)) = player.and_then(|p| p.item.as_ref().map(|track| (p, track))) else {
+        let message = if player.is_some() {
+            "No track information available"
+        } else {
+            "Nothing currently playing"
+        };
+        f.render_widget(
+            Paragraph::new(Span::styled(message, Style::default().fg(theme.muted))),
+            inner,
+        );
+        return;
+    };
 
-    f.render_widget(paragraph, area);
+    let progress = app.playback_progress_ms().unwrap_or(0);
+    let duration = track.duration_ms as u64;
+    let times = format!(
+        "{} / {}",
+        format_duration(progress),
+        format_duration(duration)
+    );
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .split(inner);
+
+    let details = Line::from(player_details(
+        app,
+        Span::styled(" · ", Style::default().fg(theme.muted)),
+    ));
+    let times_width = if compact { times.width() as u16 + 2 } else { 0 };
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(times_width),
+            Constraint::Length(details.width() as u16 + 1),
+        ])
+        .split(rows[0]);
+
+    let artists = track
+        .artists
+        .iter()
+        .map(|a| a.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (status, status_color) = if player.is_playing {
+        ("▶", theme.positive)
+    } else {
+        ("⏸", theme.highlight)
+    };
+    let title = Line::from(vec![
+        Span::styled(status, Style::default().fg(status_color)),
+        Span::raw(" "),
+        Span::styled(
+            track.name.clone(),
+            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" — "),
+        Span::styled(artists, Style::default().fg(theme.secondary)),
+    ]);
+    f.render_widget(
+        Paragraph::new(truncate_line(title, columns[0].width as usize)),
+        columns[0],
+    );
+    f.render_widget(
+        Paragraph::new(details)
+            .style(Style::default().fg(theme.secondary))
+            .alignment(Alignment::Right),
+        columns[2],
+    );
+
+    if compact {
+        f.render_widget(
+            Paragraph::new(Span::styled(times, Style::default().fg(status_color)))
+                .alignment(Alignment::Right),
+            columns[1],
+        );
+        return;
+    }
+
+    let ratio = if duration > 0 {
+        progress as f64 / duration as f64
+    } else {
+        0.0
+    };
+    let gauge = LineGauge::default()
+        .filled_style(Style::default().fg(status_color))
+        .unfilled_style(Style::default().fg(theme.muted))
+        .label(times)
+        .ratio(ratio.clamp(0.0, 1.0));
+    f.render_widget(gauge, rows[1]);
+}
+
+/// The active device and shuffle/repeat icons, lit up while on, joined by `separator`
+fn player_details(app: &App<impl SpotifyApi>, separator: Span<'static>) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    let mut details = Vec::new();
+
+    if let Some(radio) = &app.radio {
+        details.push(Span::styled(
+            format!("◎ {}", radio.name),
+            Style::default().fg(theme.accent),
+        ));
+        details.push(separator.clone());
+    }
+
+    let player = app.currently_playing.as_ref();
+    if let Some(device) = player.and_then(|p| p.device.as_ref()) {
+        details.push(Span::styled(
+            device.name.clone(),
+            Style::default().fg(theme.accent),
+        ));
+    } else {
+        details.push(Span::styled(
+            "No active device",
+            Style::default().fg(theme.muted),
+        ));
+    }
+
+    if let Some(player) = player {
+        let active = Style::default()
+            .fg(theme.positive)
+            .add_modifier(Modifier::BOLD);
+        let inactive = Style::default().fg(theme.muted);
+        let shuffle = Span::styled(
+            "⇄",
+            if player.shuffle_state {
+                active
+            } else {
+                inactive
+            },
+        );
+        let repeat = match player.repeat_state.as_str() {
+            "track" => Span::styled("↻1", active),
+            "context" => Span::styled("↻", active),
+            _ => Span::styled("↻", inactive),
+        };
+        // Dimmed icons only differ by color, so high contrast mode leaves them out instead
+        let keep = |icon: &Span| !app.config.display.high_contrast || icon.style == active;
+        for icon in [shuffle, repeat].into_iter().filter(keep) {
+            details.push(separator.clone());
+            details.push(icon);
+        }
+    }
+
+    details
 }
 
-fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
+fn draw_queue(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let item_width = area.width.saturating_sub(2) as usize;
     let items: Vec<ListItem> = if let Some(ref queue) = app.queue {
         // Filter out tracks that match the currently playing song and remove duplicates
         let currently_playing_id = queue.currently_playing.as_ref().map(|t| &t.id);
-        let mut actual_queue: Vec<&crate::spotify::Track> = Vec::new();
+        let mut actual_queue: Vec<&spotitui_core::spotify::Track> = Vec::new();
         let mut seen_ids = HashSet::new();
 
         for track in &queue.queue {
@@ -198,10 +662,11 @@ fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
         if actual_queue.is_empty() {
             vec![ListItem::new(vec![Line::from(Span::styled(
                 "Queue is empty",
-                Style::default().fg(Color::DarkGray),
+                Style::default().fg(theme.muted),
             ))])]
         } else {
-            actual_queue
+            let hidden = actual_queue.len().saturating_sub(10);
+            let mut items: Vec<ListItem> = actual_queue
                 .iter()
                 .take(10)
                 .enumerate()
@@ -212,20 +677,29 @@ fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
                         .map(|a| a.name.clone())
                         .collect::<Vec<_>>()
                         .join(", ");
-                    let content = vec![Line::from(vec![
-                        Span::styled(format!("{}. ", i + 1), Style::default().fg(Color::DarkGray)),
-                        Span::styled(&track.name, Style::default().fg(Color::White)),
+                    let line = Line::from(vec![
+                        Span::styled(format!("{}. ", i + 1), Style::default().fg(theme.muted)),
+                        Span::styled(track.name.clone(), Style::default().fg(theme.text)),
                         Span::raw(" - "),
-                        Span::styled(artists, Style::default().fg(Color::Gray)),
-                    ])];
+                        Span::styled(artists, Style::default().fg(theme.secondary)),
+                    ]);
+                    let content = vec![truncate_line(line, item_width)];
                     ListItem::new(content)
                 })
-                .collect()
+                .collect();
+            // The queue isn't scrollable, so say how much sits beyond the first 10 instead
+            if hidden > 0 {
+                items.push(ListItem::new(Line::from(Span::styled(
+                    format!("+{} more", hidden),
+                    Style::default().fg(theme.muted),
+                ))));
+            }
+            items
         }
     } else {
         vec![ListItem::new(vec![Line::from(Span::styled(
             "No queue data available",
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(theme.muted),
         ))])]
     };
 
@@ -252,8 +726,6 @@ fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
 
         if actual_queue_count == 0 {
             "Queue (0 songs)".to_string()
-        } else if actual_queue_count > 10 {
-            format!("Queue ({} songs, showing first 10)", actual_queue_count)
         } else {
             format!("Queue ({} songs)", actual_queue_count)
         }
@@ -271,53 +743,379 @@ fn draw_queue(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(list, area);
 }
 
-fn draw_tracks(f: &mut Frame, app: &mut App, area: Rect) {
+/// The playlist open in the tracks pane, unless search results or another load cover it
+fn open_playlist(app: &App<impl SpotifyApi>) -> Option<&Playlist> {
+    if app.show_search || app.loading_tracks.is_some() {
+        return None;
+    }
+    match &app.track_source {
+        Some(TrackSource::Playlist(id)) => app.playlist(id),
+        _ => None,
+    }
+}
+
+/// Rows taken by the playlist header, 0 when no playlist is open
+fn playlist_header_height(app: &App<impl SpotifyApi>) -> u16 {
+    match open_playlist(app) {
+        Some(playlist) if !plain_description(playlist).is_empty() => 4,
+        Some(_) => 3,
+        None => 0,
+    }
+}
+
+/// Owner, followers, track count and length of the open playlist, with its description below
+fn draw_playlist_header(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let Some(playlist) = open_playlist(app) else {
+        return;
+    };
+
+    let separator = || Span::styled(" · ", Style::default().fg(theme.muted));
+    let mut details = Vec::new();
+    if let Some(owner) = playlist.owner.as_ref() {
+        let name = owner
+            .display_name
+            .clone()
+            .unwrap_or_else(|| owner.id.clone());
+        details.push(Span::styled(
+            format!("by {}", name),
+            Style::default().fg(theme.accent),
+        ));
+    }
+    if let Some(followers) = app.playlist_followers.get(&playlist.id) {
+        if !details.is_empty() {
+            details.push(separator());
+        }
+        let noun = if *followers == 1 {
+            "follower"
+        } else {
+            "followers"
+        };
+        details.push(Span::raw(format!("{} {}", followers, noun)));
+    }
+    if !details.is_empty() {
+        details.push(separator());
+    }
+    details.push(Span::raw(describe_count(playlist.tracks.total as usize)));
+
+    // Only the tracks fetched so far count towards the length
+    let loaded = app.current_tracks.len();
+    let total_ms: u64 = app
+        .current_tracks
+        .iter()
+        .map(|track| track.duration_ms as u64)
+        .sum();
+    details.push(separator());
+    if loaded < playlist.tracks.total as usize {
+        details.push(Span::raw(format!(
+            "{} for the first {}",
+            format_total_duration(total_ms),
+            describe_count(loaded)
+        )));
+    } else {
+        details.push(Span::raw(format_total_duration(total_ms)));
+    }
+
+    let width = area.width.saturating_sub(2) as usize;
+    let mut lines = vec![truncate_line(Line::from(details), width)];
+    let description = plain_description(playlist);
+    if !description.is_empty() {
+        lines.push(truncate_line(
+            Line::from(Span::styled(
+                description,
+                Style::default()
+                    .fg(theme.secondary)
+                    .add_modifier(Modifier::ITALIC),
+            )),
+            width,
+        ));
+    }
+
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL));
+    f.render_widget(paragraph, area);
+}
+
+/// A playlist's description without the HTML links and entities Spotify includes in it
+fn plain_description(playlist: &Playlist) -> String {
+    let Some(description) = playlist.description.as_deref() else {
+        return String::new();
+    };
+
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in description.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .trim()
+        .to_string()
+}
+
+fn draw_tracks(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    // Borders plus the header row
+    app.tracks_viewport = area.height.saturating_sub(3) as usize;
+    app.pane_areas.tracks = area;
+
+    // Sorting by tempo shows it in a column of its own
+    let show_tempo = app
+        .track_sort
+        .is_some_and(|sort| sort.column == SortColumn::Tempo);
+    let mut widths = vec![
+        Constraint::Percentage(40),
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+        Constraint::Length(6),
+    ];
+    if show_tempo {
+        widths.insert(3, Constraint::Length(3));
+    }
+    // Lay the columns out the way the table will, so long names can be cut with an ellipsis
+    let column_widths: Vec<usize> = Layout::horizontal(&widths)
+        .spacing(1)
+        .split(Rect {
+            width: list_item_width(area) as u16,
+            ..area
+        })
+        .iter()
+        .map(|column| column.width as usize)
+        .collect();
+
     let tracks = app.get_display_tracks().clone();
-    let items: Vec<ListItem> = tracks
+    let explicit_mode = app.config.display.explicit;
+    let high_contrast = app.config.display.high_contrast;
+    let playing_id = app.playing_track_id();
+    let filter = if app.show_search {
+        ""
+    } else {
+        app.track_filter.text()
+    };
+    let rows: Vec<Row> = app
+        .track_order
         .iter()
-        .map(|track| {
+        .enumerate()
+        .filter_map(|(position, &index)| tracks.get(index).map(|track| (position, index, track)))
+        .map(|(position, index, track)| {
             let artists = track
                 .artists
                 .iter()
                 .map(|a| a.name.clone())
                 .collect::<Vec<_>>()
                 .join(", ");
-            let content = vec![Line::from(vec![
-                Span::styled(&track.name, Style::default().fg(Color::White)),
-                Span::raw(" - "),
-                Span::styled(artists, Style::default().fg(Color::Gray)),
-            ])];
-            ListItem::new(content)
+            let dim = track.explicit && explicit_mode == ExplicitMode::Dim;
+            let playing = playing_id == Some(track.id.as_str());
+            let (title_style, detail_style) = if playing && high_contrast {
+                (
+                    Style::default()
+                        .fg(theme.positive)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    Style::default()
+                        .fg(theme.positive)
+                        .add_modifier(Modifier::UNDERLINED),
+                )
+            } else if playing {
+                (
+                    Style::default()
+                        .fg(theme.positive)
+                        .add_modifier(Modifier::BOLD),
+                    Style::default().fg(theme.positive),
+                )
+            } else if dim {
+                (
+                    Style::default().fg(theme.muted),
+                    Style::default().fg(theme.muted),
+                )
+            } else {
+                (
+                    Style::default().fg(theme.text),
+                    Style::default().fg(theme.secondary),
+                )
+            };
+            let name = app
+                .scripts
+                .format_track(track)
+                .unwrap_or_else(|| track.name.clone());
+            let mut title = highlight_matches(theme, &name, filter, title_style);
+            if track.explicit {
+                title.spans.insert(0, Span::raw(" "));
+                title.spans.insert(
+                    0,
+                    Span::styled("E", Style::default().fg(Color::Black).bg(theme.secondary)),
+                );
+            }
+            if playing {
+                title
+                    .spans
+                    .insert(0, Span::styled("♪ ", Style::default().fg(theme.positive)));
+            }
+            if app.marked_tracks.contains(&index) {
+                title
+                    .spans
+                    .insert(0, Span::styled("● ", Style::default().fg(theme.marked)));
+            }
+            if app.group_by_album {
+                title.spans.insert(0, Span::raw("  "));
+            }
+            let mut cells = vec![
+                truncate_line(title, column_widths[0]),
+                truncate_line(
+                    highlight_matches(theme, &artists, filter, detail_style),
+                    column_widths[1],
+                ),
+                truncate_line(
+                    highlight_matches(theme, &track.album.name, filter, detail_style),
+                    column_widths[2],
+                ),
+                Line::styled(
+                    format_duration(track.duration_ms as u64),
+                    Style::default().fg(theme.muted),
+                )
+                .alignment(Alignment::Right),
+            ];
+            if show_tempo {
+                let tempo = app
+                    .tempos
+                    .get(&track.id)
+                    .map_or(String::new(), |tempo| format!("{:.0}", tempo));
+                cells.insert(
+                    3,
+                    Line::styled(tempo, Style::default().fg(theme.muted))
+                        .alignment(Alignment::Right),
+                );
+            }
+            if !app.starts_album_group(position) {
+                return Row::new(cells);
+            }
+
+            // The first track of each album carries the album's header line above it
+            let album_artists = track
+                .album
+                .artists
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut header = vec![
+                truncate_line(
+                    Line::styled(
+                        format!("▾ {}", track.album.name),
+                        Style::default()
+                            .fg(theme.highlight)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    column_widths[0],
+                ),
+                truncate_line(
+                    Line::styled(album_artists, Style::default().fg(theme.secondary)),
+                    column_widths[1],
+                ),
+            ];
+            header.resize(cells.len(), Line::default());
+            Row::new(
+                header
+                    .into_iter()
+                    .zip(cells)
+                    .map(|(header, line)| Cell::from(Text::from(vec![header, line]))),
+            )
+            .height(2)
         })
         .collect();
 
-    let border_style = if matches!(app.focused_pane, FocusedPane::Tracks) {
-        Style::default().fg(Color::Green)
+    let focused = matches!(app.focused_pane, FocusedPane::Tracks);
+
+    // Stand in for the rows while another source's tracks are fetched
+    let loading = !app.show_search && app.loading_tracks.is_some();
+    let rows = if loading {
+        vec![Row::new(vec![Cell::from(loading_line(theme, "Loading…"))])]
+    } else if app.has_more_tracks() {
+        // The next page is fetched as the cursor nears this row
+        let mut rows = rows;
+        rows.push(Row::new(vec![Cell::from(loading_line(
+            theme,
+            "Loading more…",
+        ))]));
+        rows
     } else {
-        Style::default()
+        rows
     };
 
     let title = if app.show_search {
         "Search Results".to_string()
-    } else if let Some(selected) = app.playlists_state.selected() {
-        if selected < app.playlists.len() {
-            app.playlists[selected].name.clone()
-        } else {
-            "Tracks".to_string()
-        }
     } else {
-        "Tracks".to_string()
+        let source = app.loading_tracks.as_ref().or(app.track_source.as_ref());
+        match source {
+            Some(TrackSource::Playlist(id)) => app
+                .playlist(id)
+                .map(|playlist| playlist.name.clone())
+                .unwrap_or_else(|| "Tracks".to_string()),
+            Some(TrackSource::Album(album)) => format!("Album: {}", album.name),
+            Some(TrackSource::Artist(artist)) => format!("Artist: {} — Top Tracks", artist.name),
+            Some(TrackSource::Show(show)) => format!("Podcast: {}", show.name),
+            Some(TrackSource::Liked) => "Liked Songs".to_string(),
+            Some(TrackSource::RecentlyAdded) => format!(
+                "Recently Added (Liked Songs and {} fetched playlists)",
+                app.track_cache.len()
+            ),
+            None => "Tracks".to_string(),
+        }
     };
+    let mut title = match app.track_sort {
+        Some(sort) => format!(
+            "{} (sorted by {} {})",
+            title,
+            sort.column.label(),
+            if sort.ascending { "▲" } else { "▼" }
+        ),
+        None => title,
+    };
+    if app.group_by_album {
+        title.push_str(" (grouped by album)");
+    }
+    if app.visual_mode() {
+        title.push_str(&format!(
+            " -- VISUAL, {} selected --",
+            app.marked_tracks.len()
+        ));
+    } else if !app.marked_tracks.is_empty() {
+        title.push_str(&format!(" ({} selected)", app.marked_tracks.len()));
+    }
 
-    let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(title.as_str())
-                .border_style(border_style),
-        )
-        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-        .highlight_symbol(">> ");
+    let mut header = vec![
+        Cell::from("Title"),
+        Cell::from("Artist"),
+        Cell::from("Album"),
+        Cell::from(Line::from("Time").alignment(Alignment::Right)),
+    ];
+    if show_tempo {
+        header.insert(3, Cell::from(Line::from("BPM").alignment(Alignment::Right)));
+    }
+    let header = Row::new(header).style(
+        Style::default()
+            .fg(theme.highlight)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(pane_block(app, title, focused))
+        .column_spacing(1)
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    if loading {
+        f.render_widget(table, area);
+        return;
+    }
 
     let state = if app.show_search {
         &mut app.search_state
@@ -325,36 +1123,230 @@ fn draw_tracks(f: &mut Frame, app: &mut App, area: Rect) {
         &mut app.tracks_state
     };
 
-    f.render_stateful_widget(list, area, state);
+    f.render_stateful_widget(table, area, state);
+    let position = state.selected().unwrap_or(0);
+    // Keep the scrollbar clear of the header row
+    let rows_area = Rect {
+        y: area.y + 1,
+        height: area.height.saturating_sub(1),
+        ..area
+    };
+    draw_scrollbar(f, rows_area, app.track_order.len(), position);
 }
 
-fn draw_search_bar(f: &mut Frame, app: &App, area: Rect) {
-    let border_style = if matches!(app.focused_pane, FocusedPane::SearchInput) {
-        Style::default().fg(Color::Green)
-    } else {
-        Style::default()
+/// The playlist open in the split view, which tracks are copied to and from
+fn draw_split_pane(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    app.pane_areas.split = area;
+    let focused = matches!(app.focused_pane, FocusedPane::Split);
+    let block = match &app.split {
+        Some(split) => {
+            let title = match &split.tracks {
+                Some(tracks) => format!("{} ({} tracks)", split.playlist_name, tracks.len()),
+                None => split.playlist_name.clone(),
+            };
+            pane_block(app, title, focused)
+        }
+        None => return,
     };
+    let Some(split) = &mut app.split else {
+        return;
+    };
+    // Borders plus the header row
+    split.viewport = area.height.saturating_sub(3) as usize;
 
-    let input = Paragraph::new(app.search_input.as_str())
-        .style(Style::default().fg(Color::Yellow))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Search")
-                .border_style(border_style),
-        );
+    let widths = [
+        Constraint::Percentage(50),
+        Constraint::Percentage(50),
+        Constraint::Length(6),
+    ];
+    let column_widths: Vec<usize> = Layout::horizontal(widths)
+        .spacing(1)
+        .split(Rect {
+            width: list_item_width(area) as u16,
+            ..area
+        })
+        .iter()
+        .map(|column| column.width as usize)
+        .collect();
+
+    let rows: Vec<Row> = match &split.tracks {
+        None => vec![Row::new(vec![Cell::from(loading_line(theme, "Loading…"))])],
+        Some(tracks) => tracks
+            .iter()
+            .map(|track| {
+                let artists = track
+                    .artists
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Row::new([
+                    truncate_line(
+                        Line::styled(track.name.clone(), Style::default().fg(theme.text)),
+                        column_widths[0],
+                    ),
+                    truncate_line(
+                        Line::styled(artists, Style::default().fg(theme.secondary)),
+                        column_widths[1],
+                    ),
+                    Line::styled(
+                        format_duration(track.duration_ms as u64),
+                        Style::default().fg(theme.muted),
+                    )
+                    .alignment(Alignment::Right),
+                ])
+            })
+            .collect(),
+    };
+
+    let header = Row::new(vec![
+        Cell::from("Title"),
+        Cell::from("Artist"),
+        Cell::from(Line::from("Time").alignment(Alignment::Right)),
+    ])
+    .style(
+        Style::default()
+            .fg(theme.highlight)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .column_spacing(1)
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    let Some(len) = split.tracks.as_ref().map(Vec::len) else {
+        f.render_widget(table, area);
+        return;
+    };
+    f.render_stateful_widget(table, area, &mut split.state);
+    let position = split.state.selected().unwrap_or(0);
+    let rows_area = Rect {
+        y: area.y + 1,
+        height: area.height.saturating_sub(1),
+        ..area
+    };
+    draw_scrollbar(f, rows_area, len, position);
+}
 
-    f.render_widget(input, area);
+fn draw_search_bar(f: &mut Frame, app: &mut App<impl SpotifyApi>, area: Rect) {
+    app.pane_areas.search_bar = area;
 
     // Only show cursor when search input is focused
-    if matches!(app.focused_pane, FocusedPane::SearchInput) {
-        let position = Position::new(area.x + app.search_input.len() as u16 + 1, area.y + 1);
+    let focused = matches!(app.focused_pane, FocusedPane::SearchInput);
+    draw_text_input(
+        f,
+        app,
+        &app.search_input,
+        "Search".to_string(),
+        focused,
+        area,
+    );
+}
+
+fn draw_filter_bar(f: &mut Frame, app: &App<impl SpotifyApi>, target: FilterTarget, area: Rect) {
+    let editing = app.editing_filter == Some(target);
+    let (input, matches) = match target {
+        FilterTarget::Library => (&app.library_filter, app.library_order.len()),
+        FilterTarget::Tracks => (&app.track_filter, app.track_order.len()),
+    };
+
+    let title = format!("Filter ({} matches)", matches);
+    draw_text_input(f, app, input, title, editing, area);
+}
+
+/// Draws a bordered one-line text box, scrolled to keep the cursor in view while focused
+fn draw_text_input(
+    f: &mut Frame,
+    app: &App<impl SpotifyApi>,
+    input: &TextInput,
+    title: String,
+    focused: bool,
+    area: Rect,
+) {
+    let theme = app.theme;
+    let inner_width = area.width.saturating_sub(2);
+    let cursor = input.cursor_width();
+    let scroll = cursor.saturating_sub(inner_width.saturating_sub(1));
+
+    let paragraph = Paragraph::new(input.text())
+        .style(Style::default().fg(theme.highlight))
+        .scroll((0, scroll))
+        .block(pane_block(app, title, focused));
+
+    f.render_widget(paragraph, area);
+
+    if focused {
+        let position = Position::new(area.x + 1 + cursor - scroll, area.y + 1);
         f.set_cursor_position(position);
     }
 }
 
-fn draw_playback_controls_popup(f: &mut Frame, app: &mut App) {
+/// Bordered block for a pane or input, with a green border while focused. High contrast mode
+/// also thickens the border and marks the title so focus doesn't depend on color alone
+fn pane_block(app: &App<impl SpotifyApi>, title: String, focused: bool) -> Block<'static> {
+    let theme = app.theme;
+    let block = Block::default().borders(Borders::ALL);
+    match (focused, app.config.display.high_contrast) {
+        (false, _) => block.title(title),
+        (true, false) => block
+            .title(title)
+            .border_style(Style::default().fg(theme.positive)),
+        (true, true) => block
+            .title(format!("▶ {} ◀", title))
+            .title_style(Style::default().add_modifier(Modifier::BOLD))
+            .border_type(BorderType::Thick)
+            .border_style(Style::default().fg(theme.positive)),
+    }
+}
+
+/// Renders text with the characters matched by a fuzzy filter highlighted
+fn highlight_matches(theme: Theme, text: &str, pattern: &str, style: Style) -> Line<'static> {
+    let indices = if pattern.is_empty() {
+        None
+    } else {
+        fuzzy::fuzzy_indices(text, pattern)
+    };
+    let Some((_, indices)) = indices else {
+        return Line::from(Span::styled(text.to_string(), style));
+    };
+
+    let highlight_style = style.fg(theme.highlight).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = indices.contains(&i);
+        if matched != current_matched && !current.is_empty() {
+            let span_style = if current_matched {
+                highlight_style
+            } else {
+                style
+            };
+            spans.push(Span::styled(std::mem::take(&mut current), span_style));
+        }
+        current_matched = matched;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        let span_style = if current_matched {
+            highlight_style
+        } else {
+            style
+        };
+        spans.push(Span::styled(current, span_style));
+    }
+
+    Line::from(spans)
+}
+
+fn draw_playback_controls_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
     let popup_area = centered_rect(40, 8, f.area());
+    app.pane_areas.playback_controls = popup_area;
 
     f.render_widget(Clear, popup_area);
 
@@ -380,126 +1372,952 @@ fn draw_playback_controls_popup(f: &mut Frame, app: &mut App) {
             Block::default()
                 .borders(Borders::ALL)
                 .title("Playback Controls")
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(Style::default().fg(theme.highlight)),
         )
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-        .highlight_symbol(">> ");
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
 
     f.render_stateful_widget(list, popup_area, &mut app.playback_controls_state);
 }
 
-fn draw_help_popup(f: &mut Frame, _app: &App) {
-    let popup_area = centered_rect(80, 22, f.area());
+fn draw_track_menu_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(40, TrackMenuItem::ALL.len() as u16 + 2, f.area());
+    app.pane_areas.track_menu = popup_area;
+
+    f.render_widget(Clear, popup_area);
+
+    let title = if app.marked_tracks.is_empty() {
+        app.selected_track()
+            .map(|track| track.name.clone())
+            .unwrap_or_else(|| "Track".to_string())
+    } else {
+        format!("{} marked tracks", app.marked_tracks.len())
+    };
+    let items: Vec<ListItem> = TrackMenuItem::ALL
+        .iter()
+        .map(|item| ListItem::new(Line::from(item.label())))
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(theme.highlight)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    f.render_stateful_widget(list, popup_area, &mut app.track_menu_state);
+}
+
+fn draw_playlist_picker_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(50, 20, f.area());
+    app.pane_areas.playlist_picker = popup_area;
+
+    f.render_widget(Clear, popup_area);
+
+    let entries = app.playlist_picker_entries();
+    let len = entries.len();
+    let items: Vec<ListItem> = entries
+        .into_iter()
+        .map(|playlist| ListItem::new(Line::from(playlist.name.clone())))
+        .collect();
+
+    let title = match &app.playlist_picker_purpose {
+        PickerPurpose::AddTracks(_) => "Add to playlist".to_string(),
+        PickerPurpose::SplitView => "Open in split view".to_string(),
+        PickerPurpose::Compare { source_name, .. } => format!("Compare {} with", source_name),
+        PickerPurpose::Merge {
+            source_name,
+            skip_duplicates,
+            ..
+        } => format!(
+            "Merge {} into ({}, Tab to change)",
+            source_name,
+            if *skip_duplicates {
+                "skipping duplicates"
+            } else {
+                "keeping duplicates"
+            }
+        ),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(theme.highlight)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    f.render_stateful_widget(list, popup_area, &mut app.playlist_picker_state);
+    draw_scrollbar(
+        f,
+        popup_area,
+        len,
+        app.playlist_picker_state.selected().unwrap_or(0),
+    );
+}
+
+/// Available devices with their type, volume and which one is playing
+fn draw_device_picker_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(50, app.devices.len() as u16 + 2, f.area());
+    app.pane_areas.device_picker = popup_area;
+
+    f.render_widget(Clear, popup_area);
+
+    let item_width = list_item_width(popup_area);
+    let items: Vec<ListItem> = app
+        .devices
+        .iter()
+        .map(|device| {
+            let volume = device
+                .volume_percent
+                .map(|volume| format!(" Vol {}%", volume))
+                .unwrap_or_default();
+            let mut spans = vec![
+                Span::raw(format!("{} ", device_icon(&device.device_type))),
+                Span::raw(device.name.clone()),
+                Span::styled(volume, Style::default().fg(theme.secondary)),
+            ];
+            if device.is_active {
+                spans.push(Span::styled(
+                    " ● playing",
+                    Style::default().fg(theme.positive),
+                ));
+            }
+            if device.id.is_none() {
+                spans.push(Span::styled(
+                    " (restricted)",
+                    Style::default().fg(theme.muted),
+                ));
+            }
+            ListItem::new(truncate_line(Line::from(spans), item_width))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Play on")
+                .border_style(Style::default().fg(theme.highlight)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    f.render_stateful_widget(list, popup_area, &mut app.device_picker_state);
+    draw_scrollbar(
+        f,
+        popup_area,
+        app.devices.len(),
+        app.device_picker_state.selected().unwrap_or(0),
+    );
+}
+
+/// The genres a radio can be started from, with the picked ones marked
+fn draw_genre_picker_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(40, 20, f.area());
+    app.pane_areas.genre_picker = popup_area;
+
+    f.render_widget(Clear, popup_area);
+
+    let genres = app.genres.as_deref().unwrap_or_default();
+    let items: Vec<ListItem> = genres
+        .iter()
+        .enumerate()
+        .map(|(index, genre)| {
+            if app.picked_genres.contains(&index) {
+                ListItem::new(Line::from(vec![
+                    Span::styled("● ", Style::default().fg(theme.marked)),
+                    Span::styled(genre.clone(), Style::default().fg(theme.marked)),
+                ]))
+            } else {
+                ListItem::new(Line::from(format!("  {}", genre)))
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Radio from genres")
+                .title_bottom(Line::from(" Space pick  Enter play ").alignment(Alignment::Right))
+                .border_style(Style::default().fg(theme.highlight)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+    f.render_stateful_widget(list, popup_area, &mut app.genre_picker_state);
+    draw_scrollbar(
+        f,
+        popup_area,
+        genres.len(),
+        app.genre_picker_state.selected().unwrap_or(0),
+    );
+}
+
+/// The latest requests in the top right corner, which doesn't take the focus
+fn draw_debug_overlay(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let requests = app.spotify_client.recent_requests();
+    let width = area.width.min(72);
+    let height = (requests.len() as u16 + 3)
+        .min(area.height / 2)
+        .max(4)
+        .min(area.height);
+    let overlay = Rect {
+        x: area.right() - width,
+        y: area.y,
+        width,
+        height,
+    };
+    f.render_widget(Clear, overlay);
+
+    let latencies: Vec<u128> = requests.iter().map(|r| r.latency.as_millis()).collect();
+    let summary = match latencies.iter().max() {
+        Some(slowest) => format!(
+            "{} requests, {}ms on average, slowest {}ms",
+            latencies.len(),
+            latencies.iter().sum::<u128>() / latencies.len() as u128,
+            slowest
+        ),
+        None => "No requests yet".to_string(),
+    };
+    let mut lines = vec![Line::styled(summary, Style::default().fg(theme.secondary))];
+    let item_width = list_item_width(overlay);
+    for request in &requests {
+        let failed = request.status.is_none_or(|status| status >= 400);
+        let status = request
+            .status
+            .map_or_else(|| "---".to_string(), |status| status.to_string());
+        let latency = request.latency.as_millis();
+        let mut spans = vec![
+            Span::styled(
+                format!("{:>4}s ", request.sent_at.elapsed().as_secs()),
+                Style::default().fg(theme.muted),
+            ),
+            Span::raw(format!("{:<6} ", request.method.as_str())),
+            Span::styled(
+                format!("{} ", status),
+                Style::default().fg(if failed {
+                    theme.negative
+                } else {
+                    theme.positive
+                }),
+            ),
+            Span::styled(
+                format!("{:>6}ms ", latency),
+                Style::default().fg(if latency >= SLOW_REQUEST_MS {
+                    theme.highlight
+                } else {
+                    theme.text
+                }),
+            ),
+        ];
+        if request.retry > 0 {
+            spans.push(Span::styled(
+                format!("retry {} ", request.retry),
+                Style::default().fg(theme.highlight),
+            ));
+        }
+        spans.push(Span::raw(request.path.clone()));
+        if let Some(error) = &request.error {
+            spans.push(Span::styled(
+                format!(" {}", error),
+                Style::default().fg(theme.negative),
+            ));
+        }
+        lines.push(truncate_line(Line::from(spans), item_width));
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Requests")
+        .title_bottom(Line::from(" D close ").alignment(Alignment::Right))
+        .border_style(Style::default().fg(theme.info));
+    f.render_widget(Paragraph::new(lines).block(block), overlay);
+}
+
+/// Symbol for a device type as reported by Spotify
+fn device_icon(device_type: &str) -> &'static str {
+    match device_type {
+        "Computer" => "⌨",
+        "Smartphone" | "Tablet" => "▯",
+        "Speaker" | "AVR" | "STB" | "AudioDongle" => "◉",
+        "TV" | "CastVideo" => "▭",
+        "CastAudio" => "◎",
+        "Automobile" => "⛟",
+        "GameConsole" => "◈",
+        _ => "♪",
+    }
+}
+
+/// Key bindings grouped by section, scrollable and narrowed down by the help filter
+fn draw_help_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(80, 34, f.area());
 
     f.render_widget(Clear, popup_area);
 
-    let help_text = vec![
-        Line::from(vec![Span::styled(
-            "Navigation",
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Help - SpotiTUI")
+        .title_bottom(Line::from(" / filter  ↑/↓ scroll  Esc close ").alignment(Alignment::Right))
+        .border_style(Style::default().fg(theme.info));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let area = if app.editing_help_filter || !app.help_filter.is_empty() {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(inner);
+        draw_text_input(
+            f,
+            app,
+            &app.help_filter,
+            "Filter".to_string(),
+            app.editing_help_filter,
+            chunks[0],
+        );
+        chunks[1]
+    } else {
+        inner
+    };
+
+    let filter = app.help_filter.text();
+    let keys_width = keymap::BINDINGS
+        .iter()
+        .map(|binding| binding.keys(&app.remaps).width())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let mut help_text = Vec::new();
+    for section in keymap::Section::ALL {
+        let bindings: Vec<&keymap::Binding> = keymap::matching(filter)
+            .filter(|binding| binding.section == section)
+            .collect();
+        if bindings.is_empty() {
+            continue;
+        }
+        if !help_text.is_empty() {
+            help_text.push(Line::from(""));
+        }
+        help_text.push(Line::from(Span::styled(
+            section.label(),
             Style::default()
-                .fg(Color::Yellow)
+                .fg(theme.highlight)
                 .add_modifier(Modifier::BOLD),
-        )]),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("Tab", Style::default().fg(Color::Green)),
-            Span::raw("           Switch between playlists and tracks panes"),
-        ]),
-        Line::from(vec![
-            Span::styled("↑/↓ or Ctrl+P/N", Style::default().fg(Color::Green)),
-            Span::raw(" Navigate up/down in current pane"),
-        ]),
-        Line::from(vec![
-            Span::styled("Enter", Style::default().fg(Color::Green)),
-            Span::raw("         Play track or load playlist"),
-        ]),
-        Line::from(""),
-        Line::from(vec![Span::styled(
-            "Features",
+        )));
+        for binding in bindings {
+            let keys = binding.keys(&app.remaps);
+            let padding = keys_width.saturating_sub(keys.width());
+            let mut line = highlight_matches(theme, binding.description, filter, Style::default());
+            line.spans.insert(
+                0,
+                Span::styled(
+                    format!("{}{}", keys, " ".repeat(padding)),
+                    Style::default().fg(theme.positive),
+                ),
+            );
+            help_text.push(line);
+        }
+    }
+    // Keys the config remaps, when the filter leaves them in
+    let remaps: Vec<(String, String)> = app
+        .remaps
+        .describe()
+        .into_iter()
+        .filter(|(from, to)| {
+            let description = format!("acts like {}", to);
+            filter.is_empty()
+                || fuzzy::fuzzy_score(&description, filter).is_some()
+                || fuzzy::fuzzy_score(from, filter).is_some()
+        })
+        .collect();
+    if !remaps.is_empty() {
+        if !help_text.is_empty() {
+            help_text.push(Line::from(""));
+        }
+        help_text.push(Line::from(Span::styled(
+            "Remapped in config",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(theme.highlight)
                 .add_modifier(Modifier::BOLD),
-        )]),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("s", Style::default().fg(Color::Green)),
-            Span::raw("             Search for tracks"),
-        ]),
-        Line::from(vec![
-            Span::styled("Space", Style::default().fg(Color::Green)),
-            Span::raw("         Open playback controls"),
-        ]),
-        Line::from(vec![
-            Span::styled("+", Style::default().fg(Color::Green)),
-            Span::raw("             Add track to queue"),
-        ]),
-        Line::from(vec![
-            Span::styled("q", Style::default().fg(Color::Green)),
-            Span::raw("             Quit application"),
-        ]),
-        Line::from(vec![
-            Span::styled("?", Style::default().fg(Color::Green)),
-            Span::raw("             Show this help"),
-        ]),
-        Line::from(""),
-        Line::from(vec![Span::styled(
-            "Playback Controls",
+        )));
+        for (from, to) in remaps {
+            let padding = keys_width.saturating_sub(from.width());
+            help_text.push(Line::from(vec![
+                Span::styled(
+                    format!("{}{}", from, " ".repeat(padding)),
+                    Style::default().fg(theme.positive),
+                ),
+                Span::raw(format!("acts like {}", to)),
+            ]));
+        }
+    }
+    // Keys bound by init.lua
+    let scripted: Vec<(String, String)> = app
+        .scripts
+        .describe()
+        .into_iter()
+        .filter(|(key, description)| {
+            filter.is_empty()
+                || fuzzy::fuzzy_score(description, filter).is_some()
+                || fuzzy::fuzzy_score(key, filter).is_some()
+        })
+        .collect();
+    if !scripted.is_empty() {
+        if !help_text.is_empty() {
+            help_text.push(Line::from(""));
+        }
+        help_text.push(Line::from(Span::styled(
+            "Bound in init.lua",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(theme.highlight)
                 .add_modifier(Modifier::BOLD),
-        )]),
-        Line::from(""),
-        Line::from("Press Space to open playback controls popup with:"),
-        Line::from("  • Play/Pause current track"),
-        Line::from("  • Skip to previous/next track"),
+        )));
+        for (key, description) in scripted {
+            let padding = keys_width.saturating_sub(key.width());
+            help_text.push(Line::from(vec![
+                Span::styled(
+                    format!("{}{}", key, " ".repeat(padding)),
+                    Style::default().fg(theme.positive),
+                ),
+                Span::raw(description),
+            ]));
+        }
+    }
+    if help_text.is_empty() {
+        help_text.push(Line::from(Span::styled(
+            "No matching keys",
+            Style::default().fg(theme.muted),
+        )));
+    }
+
+    app.help_len = help_text.len();
+    app.help_viewport = area.height as usize;
+    app.help_scroll = app
+        .help_scroll
+        .min(app.help_len.saturating_sub(app.help_viewport));
+
+    let paragraph = Paragraph::new(help_text).scroll((app.help_scroll as u16, 0));
+    f.render_widget(paragraph, area);
+    // The scrollbar sits on the popup's right border, alongside the lines
+    draw_scrollbar(
+        f,
+        Rect {
+            x: popup_area.x,
+            width: popup_area.width,
+            y: area.y.saturating_sub(1),
+            height: area.height + 2,
+        },
+        app.help_len,
+        app.help_scroll,
+    );
+}
+
+/// Stays across the top while Spotify can't be reached, counting down to the next try
+fn draw_offline_banner(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let text = match app.connection.reconnect_in() {
+        Some(wait) => format!(
+            " Offline — reconnecting in {}s, browsing the cached library ",
+            wait.as_secs() + 1
+        ),
+        None => " Offline — reconnecting… ".to_string(),
+    };
+    let banner = Paragraph::new(text).alignment(Alignment::Center).style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(theme.negative)
+            .add_modifier(Modifier::BOLD),
+    );
+    f.render_widget(banner, area);
+}
+
+/// Player and connection state on the left, the key hints on the right
+fn draw_status_bar(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let separator = || Span::styled("  |  ", Style::default().fg(theme.muted));
+    let mut status = Vec::new();
+
+    let (auth_text, auth_color) = match app.state {
+        AppState::Authenticating => ("● Authenticating", theme.highlight),
+        AppState::Error(_) => ("● Disconnected", theme.negative),
+        AppState::Ready if app.connection.is_offline() => ("● Offline, read-only", theme.negative),
+        AppState::Ready => ("● Connected", theme.positive),
+    };
+    status.push(Span::styled(
+        auth_text,
+        Style::default()
+            .fg(auth_color)
+            .add_modifier(if app.connection.is_offline() {
+                Modifier::BOLD
+            } else {
+                Modifier::empty()
+            }),
+    ));
+    if !app.offline_actions.is_empty() {
+        status.push(separator());
+        status.push(Span::styled(
+            format!("{} waiting to sync", app.offline_actions.len()),
+            Style::default().fg(theme.highlight),
+        ));
+    }
+
+    // The Now Playing bar already shows the player state when it's visible
+    if !app.config.layout.show_now_playing {
+        status.push(separator());
+        status.extend(player_details(app, separator()));
+    }
+
+    if let Some(action) = app.pending_mark {
+        let prompt = match action {
+            MarkAction::Set => "m… mark with letter",
+            MarkAction::Jump => "'… jump to mark",
+        };
+        status.push(separator());
+        status.push(Span::styled(prompt, Style::default().fg(theme.accent)));
+    }
+
+    if let Some(count) = app.pending_count {
+        status.push(separator());
+        status.push(Span::styled(
+            count.to_string(),
+            Style::default().fg(theme.accent),
+        ));
+    }
+
+    if let Some(type_ahead) = app.type_ahead.as_ref().filter(|t| !t.expired()) {
+        status.push(separator());
+        status.push(Span::styled(
+            format!("Find: {}▏", type_ahead.text),
+            Style::default().fg(theme.accent),
+        ));
+    }
+
+    if let Some(ref batch) = app.queue_batch {
+        status.push(separator());
+        let mut progress = format!("Adding to queue {}/{}", batch.done(), batch.total);
+        if let Some(wait) = batch.paused_for() {
+            progress.push_str(&format!(
+                " (rate limited, resuming in {}s)",
+                wait.as_secs() + 1
+            ));
+        }
+        status.push(Span::styled(progress, Style::default().fg(theme.highlight)));
+    }
+
+    if let Some(ref batch) = app.merge_batch {
+        status.push(separator());
+        status.push(Span::styled(
+            format!(
+                "Merging into {} {}/{}",
+                batch.playlist_name,
+                batch.done(),
+                batch.total
+            ),
+            Style::default().fg(theme.highlight),
+        ));
+    }
+
+    if let Some(ref error) = app.notifications.last_error {
+        status.push(separator());
+        status.push(Span::styled(
+            format!("Last error: {}", error),
+            Style::default().fg(theme.negative),
+        ));
+    }
+
+    let mut hint = Vec::new();
+    if app.narrow {
+        hint.push(Span::styled("Tab", Style::default().fg(theme.highlight)));
+        hint.push(Span::raw(" library/tracks  "));
+    }
+    hint.extend([
+        Span::styled("?", Style::default().fg(theme.highlight)),
+        Span::raw(" help  "),
+        Span::styled("q", Style::default().fg(theme.negative)),
+        Span::raw(" quit "),
+    ]);
+    let hint = Line::from(hint);
+    let volume = app
+        .currently_playing
+        .as_ref()
+        .and_then(|player| player.device.as_ref())
+        .and_then(|device| device.volume_percent);
+    let volume_width = if volume.is_some() {
+        VOLUME_GAUGE_WIDTH
+    } else {
+        0
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(volume_width),
+            Constraint::Length(hint.width() as u16),
+        ])
+        .split(area);
+
+    f.render_widget(
+        Paragraph::new(Line::from(status)).style(Style::default().fg(theme.secondary)),
+        chunks[0],
+    );
+    if let Some(volume) = volume {
+        draw_volume_gauge(f, theme, volume, chunks[1]);
+    }
+    f.render_widget(
+        Paragraph::new(hint)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Right),
+        chunks[2],
+    );
+}
+
+/// Columns taken by the volume gauge in the status bar, label and trailing gap included
+const VOLUME_GAUGE_WIDTH: u16 = 22;
+
+/// The active device's volume as a label and a bar, refreshed with every player poll
+fn draw_volume_gauge(f: &mut Frame, theme: Theme, volume: u32, area: Rect) {
+    let area = Rect {
+        width: area.width.saturating_sub(2),
+        ..area
+    };
+    let gauge = LineGauge::default()
+        .label(format!("Vol {:>3}%", volume))
+        .style(Style::default().fg(theme.secondary))
+        .filled_style(Style::default().fg(theme.accent))
+        .unfilled_style(Style::default().fg(theme.muted))
+        .ratio((volume.min(100) as f64) / 100.0);
+    f.render_widget(gauge, area);
+}
+
+/// Yes/no question before a destructive action
+fn draw_confirmation_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let Some(ref confirmation) = app.confirmation else {
+        return;
+    };
+    let popup_area = centered_rect(50, 6, f.area());
+    app.pane_areas.confirmation = popup_area;
+
+    f.render_widget(Clear, popup_area);
+
+    let text = vec![
+        Line::from(confirmation.message.as_str()),
         Line::from(""),
-        Line::from(vec![Span::styled(
-            "Press Esc or ? to close this help",
-            Style::default().fg(Color::Cyan),
-        )]),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(theme.negative)),
+            Span::raw(" yes   "),
+            Span::styled("n/Esc", Style::default().fg(theme.positive)),
+            Span::raw(" no"),
+        ]),
     ];
-
-    let paragraph = Paragraph::new(help_text)
+    let paragraph = Paragraph::new(text)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Help - SpotiTUI")
-                .border_style(Style::default().fg(Color::Blue)),
+                .title("Are you sure?")
+                .border_style(Style::default().fg(theme.negative)),
         )
         .wrap(Wrap { trim: true });
 
     f.render_widget(paragraph, popup_area);
 }
 
-fn draw_help_hint(f: &mut Frame, area: Rect) {
-    let help_text = vec![Line::from(vec![
-        Span::raw("Press "),
-        Span::styled("?", Style::default().fg(Color::Yellow)),
-        Span::raw(" for help  |  "),
-        Span::styled("Tab", Style::default().fg(Color::Cyan)),
-        Span::raw(" to switch panes  |  "),
-        Span::styled("q", Style::default().fg(Color::Red)),
-        Span::raw(" to quit  |  "),
-        Span::styled("Space", Style::default().fg(Color::Green)),
-        Span::raw(" for controls  |  "),
-        Span::styled("s", Style::default().fg(Color::LightBlue)),
-        Span::raw(" for search"),
-    ])];
-
-    let paragraph = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::DarkGray))
-        .alignment(Alignment::Center);
+/// An entry of an imported file search wasn't sure of, with the tracks it could be
+fn draw_import_review_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(60, 10, f.area());
+    app.pane_areas.import_review = popup_area;
+    let Some(ref mut review) = app.import_review else {
+        return;
+    };
+    let Some(current) = review.current() else {
+        return;
+    };
+
+    f.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "Importing into {} · {} to check",
+            review.name,
+            review.pending.len()
+        ))
+        .title_bottom(Line::from(" Enter pick  s skip  Esc stop ").alignment(Alignment::Right))
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+
+    let width = chunks[0].width as usize;
+    let question = vec![
+        Line::styled("Which track is", Style::default().fg(theme.muted)),
+        truncate_line(
+            Line::styled(
+                current.entry.to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            width,
+        ),
+    ];
+    f.render_widget(Paragraph::new(question), chunks[0]);
+
+    let mut items: Vec<ListItem> = current
+        .candidates
+        .iter()
+        .map(|track| {
+            let artists = track
+                .artists
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let line = Line::from(vec![
+                Span::styled(track.name.clone(), Style::default().fg(theme.text)),
+                Span::styled(
+                    format!(" · {} · {}", artists, track.album.name),
+                    Style::default().fg(theme.secondary),
+                ),
+            ]);
+            ListItem::new(truncate_line(
+                line,
+                width.saturating_sub(HIGHLIGHT_SYMBOL.len()),
+            ))
+        })
+        .collect();
+    items.push(ListItem::new(Line::styled(
+        "None of these, skip it",
+        Style::default().fg(theme.muted),
+    )));
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+    f.render_stateful_widget(list, chunks[1], &mut review.state);
+}
+
+/// Tracks only one of two playlists has, and the ones they share
+fn draw_comparison_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let popup_area = centered_rect(70, 30, f.area());
+    app.pane_areas.comparison = popup_area;
+    let Some(ref mut comparison) = app.comparison else {
+        return;
+    };
+    comparison.viewport = popup_area.height.saturating_sub(2) as usize;
+    comparison.scroll = comparison
+        .scroll
+        .min(comparison.len().saturating_sub(comparison.viewport));
+
+    f.render_widget(Clear, popup_area);
+
+    let groups = [
+        (
+            format!("Only in {}", comparison.left_name),
+            &comparison.only_left,
+            theme.accent,
+        ),
+        (
+            format!("Only in {}", comparison.right_name),
+            &comparison.only_right,
+            theme.marked,
+        ),
+        ("In both".to_string(), &comparison.shared, theme.positive),
+    ];
+    let mut lines = Vec::new();
+    for (heading, tracks, color) in groups {
+        lines.push(Line::from(Span::styled(
+            format!("{} ({})", heading, tracks.len()),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )));
+        for track in tracks {
+            let artists = track
+                .artists
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            lines.push(Line::from(vec![
+                Span::raw(format!("  {}", track.name)),
+                Span::styled(
+                    format!(" — {}", artists),
+                    Style::default().fg(theme.secondary),
+                ),
+            ]));
+        }
+    }
 
+    let hint = format!(
+        " > copy to {}  < copy to {}  Esc close ",
+        comparison.right_name, comparison.left_name
+    );
+    let paragraph = Paragraph::new(lines)
+        .scroll((comparison.scroll as u16, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "{} ↔ {}",
+                    comparison.left_name, comparison.right_name
+                ))
+                .title_bottom(Line::from(hint).alignment(Alignment::Right))
+                .border_style(Style::default().fg(theme.info)),
+        );
+
+    f.render_widget(paragraph, popup_area);
+    draw_scrollbar(f, popup_area, comparison.len(), comparison.scroll);
+}
+
+/// A playlist's length and averages, with bar charts of its artists, decades and tempos
+fn draw_stats_popup(f: &mut Frame, app: &mut App<impl SpotifyApi>) {
+    let theme = app.theme;
+    let Some(ref mut stats) = app.stats else {
+        return;
+    };
+    let popup_area = centered_rect(60, stats.len() as u16 + 2, f.area());
+    app.pane_areas.stats = popup_area;
+    stats.viewport = popup_area.height.saturating_sub(2) as usize;
+    stats.scroll = stats.scroll.min(stats.len().saturating_sub(stats.viewport));
+
+    f.render_widget(Clear, popup_area);
+
+    let dim = Style::default().fg(theme.secondary);
+    let heading = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::BOLD);
+    let average = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{:<12}", label), dim),
+            Span::raw(value),
+        ])
+    };
+    let mut lines = vec![
+        average(
+            "Length",
+            format!(
+                "{}, {}",
+                describe_count(stats.tracks),
+                format_total_duration(stats.duration_ms)
+            ),
+        ),
+        average(
+            "Popularity",
+            stats
+                .popularity
+                .map_or("unknown".to_string(), |p| format!("{:.0} out of 100", p)),
+        ),
+        average(
+            "Tempo",
+            stats.tempo.map_or("unknown".to_string(), |t| {
+                format!("{:.0} BPM on average", t)
+            }),
+        ),
+    ];
+
+    // Labels line up in one column, the bars fill what's left of the popup
+    let label_width = stats
+        .charts()
+        .flat_map(|(_, rows)| rows.iter().map(|(label, _)| label.width()))
+        .max()
+        .unwrap_or(0)
+        .min(24);
+    let bar_width = (popup_area.width as usize).saturating_sub(label_width + 16);
+    for (title, rows) in stats.charts() {
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled(title, heading)));
+        let most = rows.iter().map(|(_, count)| *count).max().unwrap_or(1);
+        for (label, count) in rows {
+            let percent = *count as f64 * 100.0 / stats.tracks.max(1) as f64;
+            let mut line = truncate_line(Line::raw(label.clone()), label_width);
+            let padding = label_width.saturating_sub(line.width()) + 1;
+            line.spans.push(Span::raw(" ".repeat(padding)));
+            line.spans.push(Span::styled(
+                bar(*count, most, bar_width),
+                Style::default().fg(theme.info),
+            ));
+            line.spans
+                .push(Span::styled(format!(" {} ({:.0}%)", count, percent), dim));
+            lines.push(line);
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .scroll((stats.scroll as u16, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Stats for {}", stats.name))
+                .title_bottom(Line::from(" Esc close ").alignment(Alignment::Right))
+                .border_style(Style::default().fg(theme.info)),
+        );
+
+    f.render_widget(paragraph, popup_area);
+    draw_scrollbar(f, popup_area, stats.len(), stats.scroll);
+}
+
+/// A bar `width` cells long for the largest value, in eighths of a cell so small differences
+/// still show
+fn bar(value: usize, largest: usize, width: usize) -> String {
+    const PARTS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    let eighths = value * width * 8 / largest.max(1);
+    let bar = "█".repeat(eighths / 8) + PARTS[eighths % 8];
+    format!("{:<width$}", bar, width = width)
+}
+
+/// Startup screen listing what's wrong with the config, drawn instead of the app
+pub fn draw_config_error(f: &mut Frame, error: &ConfigError) {
+    let theme = Theme::default();
+    let mut lines = vec![Line::from(match &error.path {
+        Some(path) => format!("Spotitui can't start because of {}:", path.display()),
+        None => "Spotitui can't start:".to_string(),
+    })];
+    lines.push(Line::from(""));
+    for problem in &error.problems {
+        lines.push(Line::from(vec![
+            Span::styled("• ", Style::default().fg(theme.negative)),
+            Span::raw(problem.clone()),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Fix these and start spotitui again. The README lists every setting.",
+        Style::default().fg(theme.secondary),
+    )));
+
+    let height = (lines.len() as u16 + 4).min(f.area().height);
+    let area = centered_rect(80, height, f.area());
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Config problems ")
+            .title_bottom(Line::from(" Press any key to quit ").alignment(Alignment::Right))
+            .border_style(Style::default().fg(theme.negative))
+            .padding(Padding::horizontal(1)),
+    );
     f.render_widget(paragraph, area);
 }
 
-fn draw_error_popup(f: &mut Frame, error: &str) {
+fn draw_error_popup(f: &mut Frame, theme: Theme, error: &str) {
     let popup_area = centered_rect(60, 5, f.area());
 
     f.render_widget(Clear, popup_area);
 
     let error_text = Paragraph::new(error)
-        .style(Style::default().fg(Color::Red))
+        .style(Style::default().fg(theme.negative))
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -509,34 +2327,267 @@ fn draw_error_popup(f: &mut Frame, error: &str) {
     f.render_widget(error_text, popup_area);
 }
 
-fn draw_status_popup(f: &mut Frame, status: &str) {
+/// Stacks the active toasts in the bottom-right corner of `area`, newest at the bottom
+fn draw_toasts(f: &mut Frame, app: &App<impl SpotifyApi>, area: Rect) {
+    let theme = app.theme;
+    let width = area.width.min(50);
+    let text_width = width.saturating_sub(2).max(1) as usize;
+    let mut bottom = area.bottom();
+
+    for toast in app.notifications.toasts().iter().rev() {
+        let (title, color) = match toast.severity {
+            Severity::Info => ("Info", theme.accent),
+            Severity::Success => ("Done", theme.positive),
+            Severity::Error => ("Error", theme.negative),
+        };
+        // Wrapped lines plus borders, capped so one long error can't fill the screen
+        let lines = toast.message.width().div_ceil(text_width).clamp(1, 3);
+        let height = lines as u16 + 2;
+        if bottom < area.y + height {
+            break;
+        }
+        bottom -= height;
+
+        let toast_area = Rect {
+            x: area.right() - width,
+            y: bottom,
+            width,
+            height,
+        };
+        f.render_widget(Clear, toast_area);
+        let paragraph = Paragraph::new(toast.message.as_str())
+            .style(Style::default().fg(color))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(color)),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(paragraph, toast_area);
+    }
+}
+
+fn draw_status_popup(f: &mut Frame, theme: Theme, status: &str) {
     let popup_area = centered_rect(40, 3, f.area());
 
     f.render_widget(Clear, popup_area);
 
     let status_text = Paragraph::new(status)
-        .style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(theme.highlight))
         .block(Block::default().borders(Borders::ALL).title("Status"));
 
     f.render_widget(status_text, popup_area);
 }
 
+/// Placeholder row for a pane whose contents, or next page, are still being fetched, with a
+/// spinner that advances on every redraw
+fn loading_line(theme: Theme, label: &str) -> Line<'static> {
+    const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let frame = FRAMES[(millis / 100) as usize % FRAMES.len()];
+    Line::from(Span::styled(
+        format!("{} {}", frame, label),
+        Style::default().fg(theme.muted),
+    ))
+}
+
+/// Draws a vertical scrollbar over the right border of a bordered list, if it overflows
+fn draw_scrollbar(f: &mut Frame, area: Rect, content_length: usize, position: usize) {
+    let viewport_length = area.height.saturating_sub(2) as usize;
+    if content_length <= viewport_length {
+        return;
+    }
+
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None);
+    let mut scrollbar_state = ScrollbarState::new(content_length)
+        .position(position)
+        .viewport_content_length(viewport_length);
+
+    f.render_stateful_widget(
+        scrollbar,
+        area.inner(Margin {
+            vertical: 1,
+            horizontal: 0,
+        }),
+        &mut scrollbar_state,
+    );
+}
+
+/// Columns available to the text of a bordered list's rows, after the highlight symbol
+fn list_item_width(area: Rect) -> usize {
+    (area.width as usize).saturating_sub(2 + HIGHLIGHT_SYMBOL.width())
+}
+
+/// Cuts a line down to `width` terminal columns by display width rather than bytes or chars,
+/// so CJK and emoji don't push later columns out of line. Ends with … if anything was cut
+fn truncate_line(line: Line<'static>, width: usize) -> Line<'static> {
+    if line.width() <= width {
+        return line;
+    }
+
+    // Leave room for the ellipsis
+    let mut remaining = width.saturating_sub(1);
+    let mut spans = Vec::new();
+    for span in line.spans {
+        if remaining == 0 {
+            break;
+        }
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if char_width > remaining {
+                remaining = 0;
+                break;
+            }
+            remaining -= char_width;
+            content.push(c);
+        }
+        spans.push(Span::styled(content, span.style));
+    }
+    if width > 0 {
+        spans.push(Span::raw("…"));
+    }
+
+    Line { spans, ..line }
+}
+
+/// Formats milliseconds as m:ss
+pub fn format_duration(ms: u64) -> String {
+    let seconds = ms / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Length of a whole playlist, like "1 hr 5 min" or "42 min"
+fn format_total_duration(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    if minutes >= 60 {
+        format!("{} hr {} min", minutes / 60, minutes % 60)
+    } else {
+        format!("{} min", minutes)
+    }
+}
+
+/// A popup `height` rows tall, shrunk on short terminals to leave a row clear above and below
+/// so it's still clear what's underneath. Lists in popups scroll when cut short. It's never
+/// narrower than MIN_POPUP_WIDTH unless the terminal is, and never outside `r`
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length((r.height - height) / 2),
-            Constraint::Length(height),
-            Constraint::Length((r.height - height) / 2),
-        ])
-        .split(r);
+    let height = height.min(r.height.saturating_sub(2)).max(r.height.min(3));
+    let width = (u32::from(r.width) * u32::from(percent_x.min(100)) / 100) as u16;
+    let width = width.max(MIN_POPUP_WIDTH).min(r.width);
+    Rect {
+        x: r.x + (r.width - width) / 2,
+        y: r.y + (r.height - height) / 2,
+        width,
+        height,
+    }
+}
 
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock::{self, MockSpotify};
+    use crate::state::LocalState;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use spotitui_core::spotify::CurrentlyPlaying;
+
+    /// A playlist open in the tracks pane with its second track paused, so nothing on screen
+    /// depends on the time
+    fn app() -> App<MockSpotify> {
+        let mut app = App::with_client(
+            Config::default(),
+            MockSpotify::default(),
+            LocalState::default(),
+        );
+        app.state = AppState::Ready;
+        app.playlists = vec![
+            mock::playlist("p1", "Road Trip", 3),
+            mock::playlist("p2", "Focus", 12),
+        ];
+        app.update_library_order();
+        app.library_state_mut().select(Some(0));
+
+        app.current_tracks = vec![
+            mock::track("1", "First"),
+            mock::track("2", "Second"),
+            mock::track("3", "Third"),
+        ];
+        app.track_source = Some(TrackSource::Playlist("p1".to_string()));
+        app.update_track_order();
+        app.tracks_state.select(Some(0));
+
+        app.currently_playing = Some(CurrentlyPlaying {
+            is_playing: false,
+            item: Some(mock::track("2", "Second")),
+            progress_ms: Some(60_000),
+            device: None,
+            shuffle_state: false,
+            repeat_state: "off".to_string(),
+        });
+        app
+    }
+
+    fn render(app: &mut App<MockSpotify>, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| draw(f, app)).unwrap();
+        terminal.backend().to_string()
+    }
+
+    #[tokio::test]
+    async fn main_layout() {
+        insta::assert_snapshot!(render(&mut app(), 100, 30));
+    }
+
+    #[tokio::test]
+    async fn help_popup() {
+        let mut app = app();
+        app.show_help = true;
+        insta::assert_snapshot!(render(&mut app, 100, 30));
+    }
+
+    #[tokio::test]
+    async fn popups_fit_in_any_terminal_size() {
+        let popups: [fn(&mut App<MockSpotify>); 8] = [
+            |_| {},
+            |app| app.show_help = true,
+            |app| app.show_playback_controls = true,
+            |app| app.show_track_menu = true,
+            |app| app.show_playlist_picker = true,
+            |app| app.show_device_picker = true,
+            |app| app.show_debug = true,
+            |app| app.state = AppState::Error("Something went wrong".to_string()),
+        ];
+        for (width, height) in [(0, 0), (1, 1), (8, 2), (15, 4), (30, 7), (45, 12)] {
+            for open in popups {
+                let mut app = app();
+                app.notifications.error("A toast over everything");
+                open(&mut app);
+                let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+                terminal.draw(|f| draw(f, &mut app)).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn narrow_terminal_shows_the_focused_column() {
+        let mut app = app();
+        app.focused_pane = FocusedPane::Tracks;
+        insta::assert_snapshot!(render(&mut app, 50, 14));
+    }
+
+    #[tokio::test]
+    async fn offline_banner_sits_above_the_panes() {
+        let mut app = app();
+        app.connection.go_offline();
+        let screen = render(&mut app, 100, 30);
+        let first_line = screen.lines().next().unwrap();
+        assert!(first_line.contains("Offline — reconnecting"));
+    }
 }